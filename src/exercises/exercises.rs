@@ -17,6 +17,9 @@ use bitcoin::hashes::Hash;
 use bitcoin::{Block, OutPoint, PubkeyHash, Sequence, Transaction, TxIn, TxOut, Witness};
 use bitcoin::transaction::Version;
 use bitcoin::locktime::absolute::LockTime;
+use crate::exercises_appendix::tx_exercises::{
+    get_commitment_transaction_number_obscure_factor, build_commitment_input, build_commitment_locktime,
+};
 
 //
 // Exercise 1
@@ -94,8 +97,12 @@ pub fn to_local(
 // Exercise 7
 //
 
+#[allow(clippy::too_many_arguments, reason = "one argument per commitment field, so the exercise reads like BOLT 3")]
 pub fn build_commitment_transaction(
-    funding_txin: TxIn,
+    funding_outpoint: OutPoint,
+    channel_open_payment_basepoint: &secp256k1PublicKey,
+    channel_accept_payment_basepoint: &secp256k1PublicKey,
+    commitment_number: u64,
     revocation_pubkey: &PublicKey,
     to_local_delayed_pubkey: &PublicKey,
     remote_pubkey: PublicKey,
@@ -111,8 +118,12 @@ pub fn build_commitment_transaction(
 // Exercise 8
 //
 
+#[allow(clippy::too_many_arguments, reason = "one argument per commitment field, so the exercise reads like BOLT 3")]
 pub fn build_htlc_commitment_transaction(
-    funding_txin: TxIn,
+    funding_outpoint: OutPoint,
+    channel_open_payment_basepoint: &secp256k1PublicKey,
+    channel_accept_payment_basepoint: &secp256k1PublicKey,
+    commitment_number: u64,
     revocation_pubkey: &PublicKey,
     remote_htlc_pubkey: &PublicKey,
     local_htlc_pubkey: &PublicKey,
@@ -17,6 +17,9 @@ use bitcoin::hashes::Hash;
 use bitcoin::{Block, OutPoint, PubkeyHash, Sequence, Transaction, TxIn, TxOut, Witness};
 use bitcoin::transaction::Version;
 use bitcoin::locktime::absolute::LockTime;
+use crate::exercises_appendix::solutions::{
+    get_commitment_transaction_number_obscure_factor, build_commitment_input, build_commitment_locktime,
+};
 
 //
 // Exercise 1
@@ -165,8 +168,12 @@ pub fn to_local(
 // Exercise 7
 //
 
+#[allow(clippy::too_many_arguments, reason = "one argument per commitment field, so the exercise reads like BOLT 3")]
 pub fn build_commitment_transaction(
-    funding_txin: TxIn,
+    funding_outpoint: OutPoint,
+    channel_open_payment_basepoint: &secp256k1PublicKey,
+    channel_accept_payment_basepoint: &secp256k1PublicKey,
+    commitment_number: u64,
     revocation_pubkey: &PublicKey,
     to_local_delayed_pubkey: &PublicKey,
     remote_pubkey: PublicKey,
@@ -175,6 +182,12 @@ pub fn build_commitment_transaction(
     remote_amount: u64,
 ) -> Transaction {
 
+    let obscure_factor = get_commitment_transaction_number_obscure_factor(
+        channel_open_payment_basepoint,
+        channel_accept_payment_basepoint);
+
+    let funding_txin = build_commitment_input(funding_outpoint, &obscure_factor, &commitment_number);
+
     let to_local_script =
         to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
 
@@ -185,7 +198,7 @@ pub fn build_commitment_transaction(
     let remote_output = build_output(remote_amount, to_remote_script);
 
     let version = Version::TWO;
-    let locktime = LockTime::ZERO;
+    let locktime = build_commitment_locktime(&obscure_factor, &commitment_number);
 
     let tx = build_transaction(version,
                       locktime,
@@ -198,8 +211,12 @@ pub fn build_commitment_transaction(
 // Exercise 8
 //
 
+#[allow(clippy::too_many_arguments, reason = "one argument per commitment field, so the exercise reads like BOLT 3")]
 pub fn build_htlc_commitment_transaction(
-    funding_txin: TxIn,
+    funding_outpoint: OutPoint,
+    channel_open_payment_basepoint: &secp256k1PublicKey,
+    channel_accept_payment_basepoint: &secp256k1PublicKey,
+    commitment_number: u64,
     revocation_pubkey: &PublicKey,
     remote_htlc_pubkey: &PublicKey,
     local_htlc_pubkey: &PublicKey,
@@ -211,6 +228,12 @@ pub fn build_htlc_commitment_transaction(
     local_amount: u64,
     remote_amount: u64,
 ) -> Transaction {
+    let obscure_factor = get_commitment_transaction_number_obscure_factor(
+        channel_open_payment_basepoint,
+        channel_accept_payment_basepoint);
+
+    let funding_txin = build_commitment_input(funding_outpoint, &obscure_factor, &commitment_number);
+
     let htlc_offerer_script = build_htlc_offerer_witness_script(
         revocation_pubkey,
         remote_htlc_pubkey,
@@ -230,7 +253,7 @@ pub fn build_htlc_commitment_transaction(
     let remote_output = build_output(remote_amount, to_remote_script);
 
     let version = Version::TWO;
-    let locktime = LockTime::ZERO;
+    let locktime = build_commitment_locktime(&obscure_factor, &commitment_number);
 
     let tx = build_transaction(version,
                                locktime,
@@ -175,12 +175,9 @@ fn test_07_build_commitment_transaction() {
         1,
     );
 
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
+    let open_payment_basepoint = secp256k1pubkey_from_private_key(&[0x04; 32]);
+    let accept_payment_basepoint = secp256k1pubkey_from_private_key(&[0x05; 32]);
+    let commitment_number: u64 = 42;
 
     let revocation_pubkey = pubkey_from_private_key(&[0x01; 32]);
     let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
@@ -191,7 +188,10 @@ fn test_07_build_commitment_transaction() {
     let bob_amount: u64 = 1_000_500;
 
     let transaction = build_commitment_transaction(
-        txin,
+        outpoint,
+        &open_payment_basepoint,
+        &accept_payment_basepoint,
+        commitment_number,
         &revocation_pubkey,
         &to_local_delayed_pubkey,
         remote_pubkey,
@@ -205,7 +205,7 @@ fn test_07_build_commitment_transaction() {
     println!("their solution: {}", their_solution);
 
     let acceptable_solutions =
-        ["83aef4d9008ac14c71967a7944f2f0b8bcb30ef58ae2946e0c550de6bb908cba".to_string()];
+        ["c3f72d3f509d71d4fdb801a976cc258a0739e3b2ced779291ae05b2028493ebc".to_string()];
 
     assert!(acceptable_solutions.contains(&their_solution));
 }
@@ -219,12 +219,9 @@ fn test_08_build_htlc_commitment_transaction() {
         1,
     );
 
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
+    let open_payment_basepoint = secp256k1pubkey_from_private_key(&[0x04; 32]);
+    let accept_payment_basepoint = secp256k1pubkey_from_private_key(&[0x05; 32]);
+    let commitment_number: u64 = 42;
 
     let revocation_pubkey = pubkey_from_private_key(&[0x01; 32]);
     let remote_htlc_pubkey = pubkey_from_private_key(&[0x02; 32]);
@@ -239,7 +236,10 @@ fn test_08_build_htlc_commitment_transaction() {
     let remote_amount: u64 = 1_000_500;
 
     let transaction = build_htlc_commitment_transaction(
-        txin,
+        outpoint,
+        &open_payment_basepoint,
+        &accept_payment_basepoint,
+        commitment_number,
         &revocation_pubkey,
         &remote_htlc_pubkey,
         &local_htlc_pubkey,
@@ -257,7 +257,7 @@ fn test_08_build_htlc_commitment_transaction() {
     println!("their solution: {}", their_solution);
 
     let acceptable_solutions =
-        ["cecd7f4c7bebfddbdd563fd96bab55a1d5d72b672518104aaa68e9bbf99a4acb".to_string()];
+        ["e436cf799fd36f171ba1a02bdb6fca966850199ea8a8f2b49ec92e8e95b07e96".to_string()];
 
     assert!(acceptable_solutions.contains(&their_solution));
 }
@@ -6,6 +6,7 @@ use bitcoin::hashes::Hash;
 use bitcoin::hashes::HashEngine;
 use bitcoin::script::{ScriptBuf};
 use bitcoin::{OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+use crate::internal::tx_utils::{COMMITMENT_LOCKTIME_MARKER, COMMITMENT_SEQUENCE_MARKER};
 
 fn extract_lower_48_bits(input: [u8; 32]) -> u64 {
   ((input[26] as u64) << 5 * 8)
@@ -41,7 +42,7 @@ pub fn build_commitment_input(
   TxIn {
     previous_output: funding_outpoint,
     script_sig: ScriptBuf::new(),
-    sequence: Sequence(((0x80 as u32) << 8 * 3)
+    sequence: Sequence((COMMITMENT_SEQUENCE_MARKER << 8 * 3)
       | ((obscured_commitment_transaction_number >> 3 * 8) as u32)),
     witness: Witness::new(),
   }
@@ -56,6 +57,6 @@ pub fn build_commitment_locktime(
   let obscured_commitment_transaction_number = 
     commitment_transaction_number_obscure_factor ^ commitment_number;
 
-  LockTime::from_consensus(((0x20 as u32) << 8 * 3) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
+  LockTime::from_consensus((COMMITMENT_LOCKTIME_MARKER << 8 * 3) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
 
 }
\ No newline at end of file
@@ -11,6 +11,8 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
 use internal::tx_utils::{build_output, build_transaction, get_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_funding_transaction};
+use internal::store::{Store, DEFAULT_STORE_PATH};
+use crate::interactive::funding::channel_peers;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -20,13 +22,14 @@ pub struct KeyManager{
     pub delayed_pubkey: PublicKey,
     pub commitment_pubkey: PublicKey,
     pub revocation_pubkey: PublicKey,
+    pub payment_basepoint: secp256k1PublicKey,
 }
 
 pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                         txid: String,
                                         our_key_manager: KeyManager,
                                         counterparty_key_manager: KeyManager,
-                                        funding_amount: u64,
+                                        commitment_number: u64,
                                         our_balance: u64,
                                         counterparty_balance: u64) {
 
@@ -35,7 +38,10 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
 
     let tx = build_commitment_transaction(
-        funding_txin,
+        funding_txin.previous_output,
+        &our_key_manager.payment_basepoint,
+        &counterparty_key_manager.payment_basepoint,
+        commitment_number,
         &our_key_manager.revocation_pubkey,
         &our_key_manager.delayed_pubkey,
         counterparty_key_manager.commitment_pubkey,
@@ -58,51 +64,63 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
 }
 
 
-pub async fn run(funding_txid: String) {
+pub async fn run(funding_txid: Option<String>, channel: Option<String>) {
 
-    // Parse the argument as txid
-    let txid = funding_txid;
+    let mut store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (alice, bob) = channel_peers(store.node_seed());
+
+    // a named channel remembers its funding txid and where its commitments
+    // are up to, so each run builds the next one. A bare txid only ever gets
+    // the first commitment
+    let (txid, commitment_number) = match (funding_txid, &channel) {
+        (Some(txid), _) => (txid, 0),
+        (None, Some(name)) => {
+            let channel = store.channel_mut(name).expect("Unknown channel");
+            let commitment_number = channel.advance_commitments(|revoked| bob.per_commitment_secret(revoked));
+            (channel.funding_txid.clone(), commitment_number)
+        }
+        (None, None) => panic!("Pass a funding txid or a channel name"),
+    };
 
     // get bitcoin client
     let bitcoind = get_bitcoind_client().await;
 
-    // Get our keys
-    let our_funding_private_key = secp256k1_private_key(&[0x01; 32]);
-    let our_funding_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let our_commitment_pubkey = pubkey_from_private_key(&[0x11; 32]);
-    let our_revocation_pubkey = pubkey_from_private_key(&[0x12; 32]);
-    let our_delayed_pubkey = pubkey_from_private_key(&[0x13; 32]);
+    // every key comes from the node seed, and the commitment number is
+    // obscured with the payment basepoints the channel was opened with
 
+    // Get our keys
+    let our_keys = alice.commitment_keys(&bob, commitment_number);
     let our_key_manager = KeyManager{
-            funding_private_key: our_funding_private_key,
-            funding_public_key: our_funding_public_key,
-            delayed_pubkey: our_delayed_pubkey,
-            commitment_pubkey: our_commitment_pubkey,
-            revocation_pubkey: our_revocation_pubkey
+            funding_private_key: alice.funding_key(),
+            funding_public_key: PublicKey::new(alice.pubkeys().funding_pubkey),
+            delayed_pubkey: PublicKey::new(our_keys.broadcaster_delayed_pubkey),
+            commitment_pubkey: PublicKey::new(alice.pubkeys().payment_basepoint),
+            revocation_pubkey: PublicKey::new(our_keys.revocation_pubkey),
+            payment_basepoint: alice.pubkeys().payment_basepoint,
         };
 
     // Get our Counterparty Pubkey
-    let counterparty_funding_private_key = secp256k1_private_key(&[0x02; 32]);
-    let counterparty_funding_public_key = pubkey_from_private_key(&[0x02; 32]);
-    let counterparty_commitment_pubkey = pubkey_from_private_key(&[0x21; 32]);
-    let counterparty_revocation_pubkey = pubkey_from_private_key(&[0x22; 32]);
-    let counterparty_delayed_pubkey = pubkey_from_private_key(&[0x23; 32]);
-
+    let counterparty_keys = bob.commitment_keys(&alice, commitment_number);
     let counterparty_key_manager = KeyManager{
-            funding_private_key: counterparty_funding_private_key,
-            funding_public_key: counterparty_funding_public_key,
-            delayed_pubkey: counterparty_delayed_pubkey,
-            commitment_pubkey: counterparty_commitment_pubkey,
-            revocation_pubkey: counterparty_revocation_pubkey
+            funding_private_key: bob.funding_key(),
+            funding_public_key: PublicKey::new(bob.pubkeys().funding_pubkey),
+            delayed_pubkey: PublicKey::new(counterparty_keys.broadcaster_delayed_pubkey),
+            commitment_pubkey: PublicKey::new(our_keys.countersignatory_payment_pubkey),
+            revocation_pubkey: PublicKey::new(counterparty_keys.revocation_pubkey),
+            payment_basepoint: bob.pubkeys().payment_basepoint,
         };
     
-    let funding_amount = 5_000_000;
     let our_balance = 3_998_500;
     let counterparty_balance = 1_000_500;
     
-    create_broadcast_funding_tx(bitcoind, txid.clone(), our_key_manager, counterparty_key_manager, funding_amount,
+    create_broadcast_funding_tx(bitcoind, txid.clone(), our_key_manager, counterparty_key_manager, commitment_number,
                                our_balance, counterparty_balance).await;
 
+    if let Some(name) = channel {
+        store.save().expect("Failed to save the store");
+        println!("Saved commitment {} on channel '{}'", commitment_number, name);
+    }
+
     // Add a delay to allow the spawned task to complete
     sleep(Duration::from_secs(2)).await;
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::consensus::encode;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::Transaction;
+use internal::bitcoind_client::get_bitcoind_client;
+use internal::cpfp::{bump_fee, feerate_per_kw, BumpOutput};
+use internal::hex_utils;
+use internal::key_utils::secp256k1_private_key;
+use internal::store::{Store, DEFAULT_STORE_PATH};
+use crate::interactive::funding::channel_peers;
+use lightning::chain::chaininterface::ConfirmationTarget;
+
+pub async fn run(tx_hex: String, vout: u32, anchor: bool, to_remote: bool, input_value: u64, feerate: Option<u32>) {
+    let parent: Transaction = encode::deserialize(&hex_utils::to_vec(&tx_hex).expect("Invalid hex")).expect("Invalid transaction");
+
+    // the parent's fee is whatever its inputs have that its outputs don't spend
+    let output_value: u64 = parent.output.iter().map(|output| output.value.to_sat()).sum();
+    let parent_fee = input_value.checked_sub(output_value).expect("Parent outputs spend more than its inputs");
+
+    // our anchor is the one for our funding key, and the to_remote of the
+    // commitments Commit and Htlc build pays Bob's payment key, both derived
+    // from the node seed
+    let bump = if anchor || to_remote {
+        let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+        let (alice, bob) = channel_peers(store.node_seed());
+        if anchor {
+            BumpOutput::Anchor { vout, funding_key: alice.funding_key() }
+        } else {
+            BumpOutput::ToRemote { vout, payment_key: bob.payment_key() }
+        }
+    } else {
+        BumpOutput::Wallet { vout }
+    };
+
+    let bitcoind = get_bitcoind_client().await;
+    let target_feerate = match feerate {
+        Some(feerate) => feerate,
+        None => bitcoind.get_feerate(ConfirmationTarget::UrgentOnChainSweep).await,
+    };
+
+    println!("\n");
+    println!("Parent feerate: {} sat/kW", feerate_per_kw(parent_fee, parent.weight()));
+    println!("Target package feerate: {} sat/kW", target_feerate);
+
+    let (signed_child, child) = bump_fee(&bitcoind, &parent, parent_fee, &bump, target_feerate)
+        .await
+        .unwrap_or_else(|e| panic!("Could not bump the parent: {:?}", e));
+
+    println!("Child fee: {} sats, spending {} wallet UTXOs", child.fee, child.spent_utxos.len());
+    println!("Package feerate: {} sat/kW", child.package_feerate_per_kw);
+    println!("\n");
+    println!("Child Tx ID: {}", signed_child.compute_txid());
+    println!("\n");
+    println!("Child Tx Hex: {}", serialize_hex(&signed_child));
+}
@@ -1,58 +1,387 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use crate::exercises_appendix;
+use bitcoin::bip32::ChildNumber;
 use bitcoin::consensus::encode::serialize_hex;
-use bitcoin::{TxIn};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::constants::ChainHash;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use bitcoin::{Amount, Network, OutPoint, Transaction, TxIn};
+use lightning::chain::chaininterface::ConfirmationTarget;
 use bitcoin::PublicKey;
 use exercises::exercises::build_funding_transaction;
+use exercises::solutions::generate_revocation_pubkey;
+use exercises_appendix::exercises::{ChannelKeysManager, NodeKeysManager};
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
+use internal::channel::{derive_private_key, derive_public_key, Channel, ChannelParameters, ChannelPublicKeys, CommitmentKeys,
+                        INITIAL_COMMITMENT_NUMBER};
+use internal::hex_utils::hex_str;
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
-use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input};
+use internal::tx_utils::{build_output, get_unspent_utxo, build_transaction, get_funding_input, fee_for_weight};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
+use internal::store::{Store, DEFAULT_STORE_PATH};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction};
+use internal::wire::{AcceptChannel, ChannelReady, CommitmentSigned, FundingCreated, FundingSigned, OpenChannel, WireMessage};
 use std::time::Duration;
 use tokio::time::sleep;
 
+const DUST_LIMIT_SATOSHIS: u64 = 546;
+const TO_SELF_DELAY: u16 = 144;
+// every interactive channel uses the first set of channel keys
+const CHANNEL_KEYS_INDEX: u32 = 1;
+
+/// One side of the channel open. Each peer only knows its own secrets and
+/// learns everything about the other side from the messages it receives.
+pub struct Peer {
+    pub name: String,
+    node_secret: SecretKey,
+    funding_key: SecretKey,
+    keys: ChannelKeysManager,
+}
+
+impl Peer {
+    /// Derives the peer's funding key and channel keys from its node seed.
+    pub fn new(name: &str, node_seed: [u8; 32]) -> Peer {
+        let node_keys = NodeKeysManager::new(node_seed);
+        let funding_key = node_keys
+            .channel_master_key
+            .derive_priv(
+                &node_keys.secp_ctx,
+                &[ChildNumber::from_hardened_idx(CHANNEL_KEYS_INDEX).unwrap(), ChildNumber::from_hardened_idx(0).unwrap()],
+            )
+            .expect("Your RNG is busted")
+            .private_key;
+
+        Peer {
+            name: name.to_string(),
+            node_secret: node_keys.node_secret,
+            funding_key,
+            keys: node_keys.derive_channel_keys(CHANNEL_KEYS_INDEX),
+        }
+    }
+
+    /// The key the peer signs invoices and gossip with.
+    pub fn node_secret(&self) -> SecretKey {
+        self.node_secret
+    }
+
+    pub fn funding_key(&self) -> SecretKey {
+        self.funding_key
+    }
+
+    /// The key to_remote pays in the counterparty's commitments.
+    pub fn payment_key(&self) -> SecretKey {
+        self.keys.payment_key
+    }
+
+    pub fn pubkeys(&self) -> ChannelPublicKeys {
+        ChannelPublicKeys::new(&self.funding_key, &self.keys)
+    }
+
+    pub fn per_commitment_secret(&self, commitment_number: u64) -> [u8; 32] {
+        self.keys.build_commitment_secret(INITIAL_COMMITMENT_NUMBER - commitment_number)
+    }
+
+    pub fn per_commitment_point(&self, commitment_number: u64) -> secp256k1PublicKey {
+        pubkey_from_secret(SecretKey::from_slice(&self.per_commitment_secret(commitment_number)).unwrap())
+    }
+
+    pub fn first_per_commitment_point(&self) -> secp256k1PublicKey {
+        self.per_commitment_point(0)
+    }
+
+    /// The keys in our commitment transaction `commitment_number`, the same
+    /// ones `Channel` puts in it.
+    pub fn commitment_keys(&self, counterparty: &Peer, commitment_number: u64) -> CommitmentKeys {
+        let per_commitment_point = self.per_commitment_point(commitment_number);
+        let (holder, countersignatory) = (self.pubkeys(), counterparty.pubkeys());
+
+        CommitmentKeys {
+            per_commitment_point,
+            revocation_pubkey: generate_revocation_pubkey(countersignatory.revocation_basepoint, per_commitment_point),
+            broadcaster_delayed_pubkey: derive_public_key(&holder.delayed_payment_basepoint, &per_commitment_point),
+            broadcaster_htlc_pubkey: derive_public_key(&holder.htlc_basepoint, &per_commitment_point),
+            countersignatory_htlc_pubkey: derive_public_key(&countersignatory.htlc_basepoint, &per_commitment_point),
+            countersignatory_payment_pubkey: countersignatory.payment_basepoint,
+            to_self_delay: TO_SELF_DELAY,
+        }
+    }
+
+    /// Our HTLC key in either side's commitment with `per_commitment_point`.
+    pub fn htlc_key(&self, per_commitment_point: &secp256k1PublicKey) -> SecretKey {
+        derive_private_key(&self.keys.htlc_base_key, per_commitment_point)
+    }
+
+    pub fn open_channel(&self, temporary_channel_id: [u8; 32], funding_satoshis: u64, push_msat: u64, feerate_per_kw: u32) -> OpenChannel {
+        let pubkeys = self.pubkeys();
+        OpenChannel {
+            chain_hash: ChainHash::using_genesis_block(Network::Regtest),
+            temporary_channel_id,
+            funding_satoshis,
+            push_msat,
+            dust_limit_satoshis: DUST_LIMIT_SATOSHIS,
+            max_htlc_value_in_flight_msat: funding_satoshis * 1000,
+            channel_reserve_satoshis: funding_satoshis / 100,
+            htlc_minimum_msat: 1,
+            feerate_per_kw,
+            to_self_delay: TO_SELF_DELAY,
+            max_accepted_htlcs: 483,
+            funding_pubkey: pubkeys.funding_pubkey,
+            revocation_basepoint: pubkeys.revocation_basepoint,
+            payment_basepoint: pubkeys.payment_basepoint,
+            delayed_payment_basepoint: pubkeys.delayed_payment_basepoint,
+            htlc_basepoint: pubkeys.htlc_basepoint,
+            first_per_commitment_point: self.first_per_commitment_point(),
+            channel_flags: 1,
+            shutdown_scriptpubkey: None,
+            channel_type: None,
+        }
+    }
+
+    pub fn accept_channel(&self, open_channel: &OpenChannel) -> AcceptChannel {
+        let pubkeys = self.pubkeys();
+        AcceptChannel {
+            temporary_channel_id: open_channel.temporary_channel_id,
+            dust_limit_satoshis: DUST_LIMIT_SATOSHIS,
+            max_htlc_value_in_flight_msat: open_channel.funding_satoshis * 1000,
+            channel_reserve_satoshis: open_channel.funding_satoshis / 100,
+            htlc_minimum_msat: 1,
+            minimum_depth: 3,
+            to_self_delay: TO_SELF_DELAY,
+            max_accepted_htlcs: 483,
+            funding_pubkey: pubkeys.funding_pubkey,
+            revocation_basepoint: pubkeys.revocation_basepoint,
+            payment_basepoint: pubkeys.payment_basepoint,
+            delayed_payment_basepoint: pubkeys.delayed_payment_basepoint,
+            htlc_basepoint: pubkeys.htlc_basepoint,
+            first_per_commitment_point: self.first_per_commitment_point(),
+            shutdown_scriptpubkey: None,
+            channel_type: None,
+        }
+    }
+
+    pub fn channel(
+        &self,
+        open_channel: &OpenChannel,
+        accept_channel: &AcceptChannel,
+        funding_outpoint: OutPoint,
+        is_outbound: bool,
+    ) -> Channel {
+        let params = ChannelParameters {
+            is_outbound,
+            funding_outpoint,
+            channel_value_satoshis: open_channel.funding_satoshis,
+            push_msat: open_channel.push_msat,
+            feerate_per_kw: open_channel.feerate_per_kw,
+            dust_limit_satoshis: if is_outbound { open_channel.dust_limit_satoshis } else { accept_channel.dust_limit_satoshis },
+            holder_selected_contest_delay: if is_outbound { open_channel.to_self_delay } else { accept_channel.to_self_delay },
+            counterparty_selected_contest_delay: if is_outbound { accept_channel.to_self_delay } else { open_channel.to_self_delay },
+        };
+
+        let (counterparty_pubkeys, counterparty_first_per_commitment_point) = if is_outbound {
+            (
+                ChannelPublicKeys {
+                    funding_pubkey: accept_channel.funding_pubkey,
+                    revocation_basepoint: accept_channel.revocation_basepoint,
+                    payment_basepoint: accept_channel.payment_basepoint,
+                    delayed_payment_basepoint: accept_channel.delayed_payment_basepoint,
+                    htlc_basepoint: accept_channel.htlc_basepoint,
+                },
+                accept_channel.first_per_commitment_point,
+            )
+        } else {
+            (
+                ChannelPublicKeys {
+                    funding_pubkey: open_channel.funding_pubkey,
+                    revocation_basepoint: open_channel.revocation_basepoint,
+                    payment_basepoint: open_channel.payment_basepoint,
+                    delayed_payment_basepoint: open_channel.delayed_payment_basepoint,
+                    htlc_basepoint: open_channel.htlc_basepoint,
+                },
+                open_channel.first_per_commitment_point,
+            )
+        };
+
+        Channel::new(
+            params,
+            self.funding_key,
+            self.keys.clone(),
+            counterparty_pubkeys,
+            counterparty_first_per_commitment_point,
+        )
+    }
+}
+
+/// Alice is us, running on the store's node seed. Bob plays the
+/// counterparty in the same process, so his seed comes from hers and every
+/// command can rebuild both sides' keys from the store.
+pub fn channel_peers(node_seed: [u8; 32]) -> (Peer, Peer) {
+    let mut sha = Sha256::engine();
+    sha.input(&node_seed);
+    sha.input(&b"counterparty seed"[..]);
+    let counterparty_seed = Sha256::from_engine(sha).to_byte_array();
+
+    (Peer::new("Alice", node_seed), Peer::new("Bob", counterparty_seed))
+}
+
+fn send<M: WireMessage>(from: &Peer, to: &Peer, name: &str, msg: &M) -> Vec<u8> {
+    let bytes = msg.encode();
+    println!("{} -> {}: {} ({} bytes)", from.name, to.name, name, bytes.len());
+    println!("  {}", hex_str(&bytes));
+    bytes
+}
+
 pub async fn build_funding_tx(bitcoind: BitcoindClient,
                                         tx_input: TxIn,
-                                        tx_in_amount: u64) {
+                                        utxo_amount: u64,
+                                        tx_in_amount: u64,
+                                        feerate_per_kw: u32,
+                                        channel_name: String) {
+
+    let mut store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+
+    // each peer holds its own keys
+    let (alice, bob) = channel_peers(store.node_seed());
 
-    // we're locking to a 2-of-2 multisig, so we need two public keys
-    // normally, we would generate our own public key
-    //   and the counterparty would send us theirs
-    let our_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let counterparty_pubkey = pubkey_from_private_key(&[0x02; 32]);
+    // Alice proposes the channel
+    let temporary_channel_id: [u8; 32] = rand::random();
+    let open_channel = alice.open_channel(temporary_channel_id, tx_in_amount, 0, feerate_per_kw);
+    let bytes = send(&alice, &bob, "open_channel", &open_channel);
+    let bob_open_channel = OpenChannel::decode(&bytes).unwrap();
 
-    // build funding transaction using the function we created
-    let tx = build_funding_transaction(
+    // Bob accepts it
+    let accept_channel = bob.accept_channel(&bob_open_channel);
+    let bytes = send(&bob, &alice, "accept_channel", &accept_channel);
+    let alice_accept_channel = AcceptChannel::decode(&bytes).unwrap();
+
+    // Alice can now build the funding transaction, using the funding key
+    // that Bob sent in accept_channel
+    let mut tx = build_funding_transaction(
             vec![tx_input],
-            &our_public_key,
-            &counterparty_pubkey,
+            &PublicKey::new(open_channel.funding_pubkey),
+            &PublicKey::new(alice_accept_channel.funding_pubkey),
             tx_in_amount,
         );
 
+    // whatever the fee doesn't need goes back to the wallet. Sign once to
+    // learn the weight, then again with the change filled in
+    let change_script = bitcoind.get_new_address().await.script_pubkey();
+    tx.output.push(build_output(0, change_script));
+    let weight = sign_raw_transaction(bitcoind.clone(), tx.clone()).await.weight();
+    let fee = fee_for_weight(feerate_per_kw, weight);
+    if utxo_amount < tx_in_amount + fee {
+        panic!(
+            "The UTXO holds {} sats, but the channel needs {} sats plus a {} sat fee",
+            utxo_amount, tx_in_amount, fee
+        );
+    }
+    let change = utxo_amount - tx_in_amount - fee;
+    if change >= DUST_LIMIT_SATOSHIS {
+        tx.output[1].value = Amount::from_sat(change);
+    } else {
+        tx.output.pop();
+    }
+
     let signed_tx = sign_raw_transaction(bitcoind.clone(), tx).await;
+    let fee_paid = utxo_amount
+        .checked_sub(signed_tx.output.iter().map(|output| output.value.to_sat()).sum::<u64>())
+        .expect("The funding transaction pays out more than the UTXO holds");
+    println!("Funding fee: {} sats at {} sat/kW", fee_paid, feerate_per_kw);
+
+    let funding_outpoint = OutPoint { txid: signed_tx.compute_txid(), vout: 0 };
+
+    // Alice signs Bob's first commitment transaction
+    let mut alice_channel = alice.channel(&open_channel, &alice_accept_channel, funding_outpoint, true);
+    let commitment_signed = alice_channel.sign_commitment().unwrap();
+    let funding_created = FundingCreated {
+        temporary_channel_id,
+        funding_txid: funding_outpoint.txid,
+        funding_output_index: funding_outpoint.vout as u16,
+        signature: commitment_signed.signature,
+    };
+    let bytes = send(&alice, &bob, "funding_created", &funding_created);
+    let funding_created = FundingCreated::decode(&bytes).unwrap();
+
+    // Bob checks Alice's signature and signs Alice's first commitment transaction
+    let bob_funding_outpoint = OutPoint {
+        txid: funding_created.funding_txid,
+        vout: funding_created.funding_output_index as u32,
+    };
+    let mut bob_channel = bob.channel(&bob_open_channel, &accept_channel, bob_funding_outpoint, false);
+    bob_channel
+        .receive_commitment_signed(&CommitmentSigned {
+            channel_id: bob_channel.channel_id,
+            signature: funding_created.signature,
+            htlc_signatures: Vec::new(),
+        })
+        .expect("Alice's commitment signature is invalid");
+    let commitment_signed = bob_channel.sign_commitment().unwrap();
+    let funding_signed = FundingSigned {
+        channel_id: bob_channel.channel_id,
+        signature: commitment_signed.signature,
+    };
+    let bytes = send(&bob, &alice, "funding_signed", &funding_signed);
+    let funding_signed = FundingSigned::decode(&bytes).unwrap();
+
+    // only once Alice holds a signed commitment is it safe to broadcast the funding transaction
+    alice_channel
+        .receive_commitment_signed(&CommitmentSigned {
+            channel_id: funding_signed.channel_id,
+            signature: funding_signed.signature,
+            htlc_signatures: Vec::new(),
+        })
+        .expect("Bob's commitment signature is invalid");
 
     println!("\n");
     println!("Tx ID: {}", signed_tx.compute_txid());
     println!("\n");
     println!("Tx Hex: {}", serialize_hex(&signed_tx));
+    println!("\n");
+
+    // after the funding transaction confirms, both sides send channel_ready
+    let channel_ready = ChannelReady {
+        channel_id: alice_channel.channel_id,
+        next_per_commitment_point: alice_channel.next_per_commitment_point(),
+        short_channel_id_alias: None,
+    };
+    let bytes = send(&alice, &bob, "channel_ready", &channel_ready);
+    bob_channel.receive_channel_ready(ChannelReady::decode(&bytes).unwrap().next_per_commitment_point);
+
+    let channel_ready = ChannelReady {
+        channel_id: bob_channel.channel_id,
+        next_per_commitment_point: bob_channel.next_per_commitment_point(),
+        short_channel_id_alias: None,
+    };
+    let bytes = send(&bob, &alice, "channel_ready", &channel_ready);
+    alice_channel.receive_channel_ready(ChannelReady::decode(&bytes).unwrap().next_per_commitment_point);
+
+    println!("\n");
+    println!("Channel ID: {}", hex_str(&alice_channel.channel_id));
+
+    // later commands find the funding txid and channel state under this name
+    store.upsert_channel(&channel_name, &alice_channel);
+    store.save().expect("Failed to save the store");
+    println!("Saved channel as '{}'", channel_name);
 }
 
-pub async fn run() {
+pub async fn run(channel_name: String) {
 
     // get bitcoin client
     let bitcoind = get_bitcoind_client().await;
 
     // get an unspent output for funding transaction
-    let tx_input = get_unspent_output(bitcoind.clone()).await;
+    let (tx_input, utxo_amount) = get_unspent_utxo(bitcoind.clone()).await;
 
     let tx_in_amount = 5_000_000;
-    
-        build_funding_tx(bitcoind, tx_input, tx_in_amount).await;
+
+    // the same rate is used for the funding transaction and the channel's commitments
+    let feerate_per_kw = bitcoind.get_feerate(ConfirmationTarget::NonAnchorChannelFee).await;
+
+        build_funding_tx(bitcoind, tx_input, utxo_amount, tx_in_amount, feerate_per_kw, channel_name).await;
 
     // Add a delay to allow the spawned task to complete
     sleep(Duration::from_secs(2)).await;
 }
-
@@ -13,14 +13,19 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::invoice::{decode_invoice, payment_hash160};
+use internal::store::{Store, StoredHtlc, DEFAULT_STORE_PATH};
+use crate::interactive::funding::channel_peers;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
 use std::time::Duration;
 use tokio::time::sleep;
 
-/// hash160 of the empty string
-const HASH160_DUMMY: [u8; 20] = [
-    0xb4, 0x72, 0xa2, 0x66, 0xd0, 0xbd, 0x89, 0xc1, 0x37, 0x06, 0xa4, 0x13, 0x2c, 0xcf, 0xb1, 0x6f,
-    0x7c, 0x3b, 0x9f, 0xcb,
-];
+/// The HTLC every run offers. HtlcTimeout falls back to these when it isn't
+/// given a channel to look the HTLC up in.
+pub const HTLC_AMOUNT_SATOSHI: u64 = 405_000;
+pub const HTLC_CLTV_EXPIRY: u32 = 300;
 
 
 pub struct KeyManager{
@@ -30,27 +35,32 @@ pub struct KeyManager{
     pub delayed_pubkey: PublicKey,
     pub pubkey: PublicKey,
     pub revocation_pubkey: PublicKey,
+    pub payment_basepoint: secp256k1PublicKey,
 }
 
 pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                          txid: String,
                                         our_key_manager: KeyManager,
-                                        counterparty_key_manager: KeyManager) {
+                                        counterparty_key_manager: KeyManager,
+                                        commitment_number: u64,
+                                        payment_hash160: [u8; 20]) -> Txid {
 
     let txid_index = 0;
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
     let funding_amount = 5_000_000;
 
-    let payment_hash160 = HASH160_DUMMY;
     let to_self_delay: i64 = 144;
 
-    let htlc_amount = 405_000;
-    let local_amount = 3_593_500;
+    // the HTLC comes out of our balance
+    let htlc_amount = HTLC_AMOUNT_SATOSHI;
+    let local_amount = 3_998_500 - HTLC_AMOUNT_SATOSHI;
     let remote_amount = 1_000_500;
 
-
     let tx = build_htlc_commitment_transaction(
-        funding_txin,
+        funding_txin.previous_output,
+        &our_key_manager.payment_basepoint,
+        &counterparty_key_manager.payment_basepoint,
+        commitment_number,
         &our_key_manager.revocation_pubkey,
         &counterparty_key_manager.htlc_pubkey,
         &our_key_manager.htlc_pubkey,
@@ -74,51 +84,87 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
     println!("\n");
     println!("Tx Hex: {}", serialize_hex(&signed_tx));
 
+    signed_tx.compute_txid()
 }
 
-pub async fn run(funding_txid: String) {
+pub async fn run(funding_txid: Option<String>, channel: Option<String>, invoice: Option<String>) {
+
+    // lock the HTLC to the invoice's payment hash, if we were given one. The
+    // default is sha256(""), giving a payment_hash160 of payment_hash160(sha256(""))
+    let payment_hash = match invoice {
+        Some(invoice) => {
+            let invoice = decode_invoice(&invoice).expect("Invalid invoice");
+            println!("Paying invoice for payment hash {}", hex::encode(invoice.payment_hash));
+            invoice.payment_hash
+        }
+        None => Sha256::hash(&[]).to_byte_array(),
+    };
+    let payment_hash160 = payment_hash160(&payment_hash);
 
     // get bitcoin client
     let bitcoind = get_bitcoind_client().await;
 
-    // Parse the argument as txid
-    let txid = funding_txid;
+    let mut store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (alice, bob) = channel_peers(store.node_seed());
 
-    // Get our keys
-    let our_funding_private_key = secp256k1_private_key(&[0x01; 32]);
-    let our_funding_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let local_htlc_pubkey = pubkey_from_private_key(&[0x11; 32]);
-    let revocation_pubkey = pubkey_from_private_key(&[0x12; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x13; 32]);
-    let local_pubkey = pubkey_from_private_key(&[0x14; 32]);
+    // Parse the argument as txid, or look it up by channel name. Adding the
+    // HTLC takes a new commitment, so a named channel moves on to its next one
+    let (txid, commitment_number) = match (funding_txid, &channel) {
+        (Some(txid), _) => (txid, 0),
+        (None, Some(name)) => {
+            let channel = store.channel_mut(name).expect("Unknown channel");
+            let commitment_number = channel.advance_commitments(|revoked| bob.per_commitment_secret(revoked));
+            (channel.funding_txid.clone(), commitment_number)
+        }
+        (None, None) => panic!("Pass a funding txid or a channel name"),
+    };
 
+    // every key comes from the node seed, and the commitment number is
+    // obscured with the payment basepoints the channel was opened with
+
+    // Get our keys
+    let our_keys = alice.commitment_keys(&bob, commitment_number);
     let our_key_manager = KeyManager{
-            funding_private_key: our_funding_private_key,
-            funding_public_key: our_funding_public_key,
-            htlc_pubkey: local_htlc_pubkey,
-            delayed_pubkey: to_local_delayed_pubkey,
-            pubkey: local_pubkey,
-            revocation_pubkey: revocation_pubkey,
+            funding_private_key: alice.funding_key(),
+            funding_public_key: PublicKey::new(alice.pubkeys().funding_pubkey),
+            htlc_pubkey: PublicKey::new(our_keys.broadcaster_htlc_pubkey),
+            delayed_pubkey: PublicKey::new(our_keys.broadcaster_delayed_pubkey),
+            pubkey: PublicKey::new(alice.pubkeys().payment_basepoint),
+            revocation_pubkey: PublicKey::new(our_keys.revocation_pubkey),
+            payment_basepoint: alice.pubkeys().payment_basepoint,
         };
 
     // Get our Counterparty Pubkey
-    let counterparty_funding_private_key = secp256k1_private_key(&[0x02; 32]);
-    let counterparty_funding_public_key = pubkey_from_private_key(&[0x02; 32]);
-    let counterparty_htlc_pubkey = pubkey_from_private_key(&[0x21; 32]);
-    let counterparty_pubkey = pubkey_from_private_key(&[0x22; 32]);
-    let counterparty_delayed_key = pubkey_from_private_key(&[0x23; 32]);
-    let counterparty_revocation_key = pubkey_from_private_key(&[0x24; 32]);
-
+    let counterparty_keys = bob.commitment_keys(&alice, commitment_number);
     let counterparty_key_manager = KeyManager{
-            funding_private_key: counterparty_funding_private_key,
-            funding_public_key: counterparty_funding_public_key,
-            htlc_pubkey: counterparty_htlc_pubkey,
-            delayed_pubkey: counterparty_delayed_key,
-            pubkey: counterparty_pubkey,
-            revocation_pubkey: counterparty_revocation_key,
+            funding_private_key: bob.funding_key(),
+            funding_public_key: PublicKey::new(bob.pubkeys().funding_pubkey),
+            htlc_pubkey: PublicKey::new(our_keys.countersignatory_htlc_pubkey),
+            delayed_pubkey: PublicKey::new(counterparty_keys.broadcaster_delayed_pubkey),
+            pubkey: PublicKey::new(our_keys.countersignatory_payment_pubkey),
+            revocation_pubkey: PublicKey::new(counterparty_keys.revocation_pubkey),
+            payment_basepoint: bob.pubkeys().payment_basepoint,
         };
 
-    create_broadcast_funding_tx(bitcoind, txid, our_key_manager, counterparty_key_manager).await;
+    let htlc_txid = create_broadcast_funding_tx(bitcoind, txid, our_key_manager, counterparty_key_manager, commitment_number,
+                                                payment_hash160).await;
+
+    // remember the HTLC so HtlcTimeout can find it by channel name
+    if let Some(name) = channel {
+        let stored_channel = store.channel_mut(&name).expect("Unknown channel");
+        let htlc_id = stored_channel.next_htlc_id();
+        stored_channel.htlcs.push(StoredHtlc {
+            htlc_id,
+            offered: true,
+            amount_msat: HTLC_AMOUNT_SATOSHI * 1000,
+            payment_hash: hex::encode(payment_hash),
+            cltv_expiry: HTLC_CLTV_EXPIRY,
+            commitment_number,
+            txid: Some(htlc_txid.to_string()),
+        });
+        store.save().expect("Failed to save the store");
+        println!("Saved HTLC {} on channel '{}'", htlc_id, name);
+    }
 
     // Add a delay to allow the spawned task to complete
     sleep(Duration::from_secs(2)).await;
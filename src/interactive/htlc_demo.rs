@@ -17,6 +17,8 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction};
+use internal::script_asm::{disassemble, ScriptNames};
+use internal::policy::{after, and, hash160, or, pk, Policy, Satisfier};
 use std::time::Duration;
 use tokio::time::sleep;
 use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
@@ -55,6 +57,10 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
     let output_script = build_hash_locked_script(&our_public_key,
                                                 &payment_hash160);
     println!("Witness Script (hex): {}", output_script.to_hex_string());
+    let mut names = ScriptNames::new();
+    names.add_pubkey("our_pubkey", &our_public_key.inner);
+    names.add("payment_hash160", &payment_hash160);
+    println!("Witness Script (asm): {}", disassemble(&output_script, &names).unwrap());
     
 
     let htlc_output = build_output(405_000, output_script.to_p2wsh());
@@ -88,19 +94,14 @@ pub async fn run() {
     sleep(Duration::from_secs(2)).await;
 }
 
+// or(hash160(H), and(after(200), pk(K))): the preimage, or our key after
+// block 200. htlc_demo2 spends the output built from it
+pub fn hash_locked_policy(pubkey: &PublicKey,
+                      payment_hash160: &[u8; 20]) -> Policy {
+    or(hash160(payment_hash160), and(after(200), pk(pubkey)))
+}
+
 fn build_hash_locked_script(pubkey: &PublicKey,
                      payment_hash160: &[u8; 20]) -> ScriptBuf {
-    Builder::new()
-        .push_opcode(opcodes::OP_IF)
-        .push_opcode(opcodes::OP_HASH160)
-        .push_slice(payment_hash160)
-        .push_opcode(opcodes::OP_EQUAL)
-        .push_opcode(opcodes::OP_ELSE)
-        .push_int(200)
-        .push_opcode(opcodes::OP_CLTV)
-        .push_opcode(opcodes::OP_DROP)
-        .push_key(pubkey)
-        .push_opcode(opcodes::OP_CHECKSIG)
-        .push_opcode(opcodes::OP_ENDIF)
-    .into_script()
+    hash_locked_policy(pubkey, payment_hash160).compile()
 }
\ No newline at end of file
@@ -10,7 +10,7 @@ use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::transaction::Version;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey};
 use bitcoin::PublicKey;
-use bitcoin::{TxIn};
+use bitcoin::{Sequence, TxIn};
 use bitcoin::hashes::sha256::Hash as Sha256;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -20,7 +20,8 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
       pubkey_from_private_key, secp256k1_private_key};
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
-use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature, sign_p2wsh_input, SpendPath, WitnessSigner};
+use crate::interactive::htlc_demo::hash_locked_policy;
 use bitcoin::script::{Builder};
 
 
@@ -66,7 +67,8 @@ pub async fn run(funding_txid: String) {
 pub fn sign_transaction(tx: Transaction)-> Transaction {
 
     let funding_amount = 405_000;
-    let txid_index = 2;
+    // the HTLC output is the only input we spend
+    let txid_index = 0;
     
     let our_public_key = pubkey_from_private_key(&[0x01; 32]);
     let our_private_key = secp256k1_private_key(&[0x01; 32]);
@@ -76,46 +78,26 @@ pub fn sign_transaction(tx: Transaction)-> Transaction {
     let payment_hash = Sha256::hash(secret_bytes).to_byte_array();
     let payment_hash160 = Ripemd160::hash(&payment_hash).to_byte_array();
 
-    // build funding transaction using the function we created
-    let redeem_script = build_hash_locked_script(&our_public_key,
-                                                &payment_hash160);
+    // the hash lock htlc_demo locked the output to
+    let redeem_script = hash_locked_policy(&our_public_key, &payment_hash160).compile();
 
     let path = true;
 
-    let mut signed_tx = tx.clone();
-
-    if path {
-
-        // Push secret
-        signed_tx.input[0].witness.push(secret_bytes);
-
-
-        signed_tx.input[0].witness.push(vec!(1));
-
+    // the secret takes the OP_IF branch, our key the OP_ELSE one
+    let (spend_path, signers) = if path {
+        (SpendPath::Preimage(secret_bytes.to_vec()), vec![])
     } else {
+        (SpendPath::Delayed, vec![WitnessSigner::Key(our_private_key)])
+    };
 
-    let signature = generate_p2wsh_signature(
-         tx.clone(), 
-         txid_index,
-         &redeem_script,
-         funding_amount,
-         EcdsaSighashType::All,
-        our_private_key);
-
-    // Convert signature to DER and append SigHashType
-    let mut signature_der = signature.serialize_der().to_vec();
-    signature_der.push(EcdsaSighashType::All as u8);
-
-    signed_tx.input[0].witness.push(signature_der);
-
-    signed_tx.input[0].witness.push(vec![]);
-
+    // OP_CHECKLOCKTIMEVERIFY needs the lock time set and an input that enforces it
+    let mut signed_tx = tx.clone();
+    if !path {
+        signed_tx.lock_time = LockTime::from_height(200).unwrap();
+        signed_tx.input[txid_index].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
     }
-
-    // push witness
-    signed_tx.input[0]
-        .witness
-        .push(redeem_script.clone().into_bytes());
+    sign_p2wsh_input(&mut signed_tx, txid_index, &redeem_script, funding_amount, &spend_path, &signers)
+        .expect("Nothing to spend the hash lock with");
 
     signed_tx
 }
@@ -134,20 +116,3 @@ fn build_p2wpkh_tx(txin: TxIn, pubkey: PublicKey) -> Transaction {
     tx
     
 }
-
-fn build_hash_locked_script(pubkey: &PublicKey,
-                     payment_hash160: &[u8; 20]) -> ScriptBuf {
-    Builder::new()
-        .push_opcode(opcodes::OP_IF)
-        .push_opcode(opcodes::OP_HASH160)
-        .push_slice(payment_hash160)
-        .push_opcode(opcodes::OP_EQUAL)
-        .push_opcode(opcodes::OP_ELSE)
-        .push_int(200)
-        .push_opcode(opcodes::OP_CLTV)
-        .push_opcode(opcodes::OP_DROP)
-        .push_key(pubkey)
-        .push_opcode(opcodes::OP_CHECKSIG)
-        .push_opcode(opcodes::OP_ENDIF)
-    .into_script()
-}
\ No newline at end of file
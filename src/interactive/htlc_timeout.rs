@@ -7,12 +7,18 @@ use exercises::exercises::{build_htlc_timeout_transaction,
 };
 use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::PublicKey;
+use lightning::chain::chaininterface::ConfirmationTarget;
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
-use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
+use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input, fee_for_weight};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
-use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature, sign_p2wsh_input, SpendPath, WitnessSigner};
+use internal::invoice::decode_invoice;
+use internal::channel::htlc_tx_fee_sat;
+use internal::store::{Store, StoreError, DEFAULT_STORE_PATH};
+use crate::interactive::funding::channel_peers;
+use crate::interactive::htlc::{HTLC_AMOUNT_SATOSHI, HTLC_CLTV_EXPIRY};
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -32,29 +38,25 @@ pub struct KeyManager{
     pub revocation_pubkey: PublicKey,
 }
 
+/// The HTLC output we're timing out on our commitment.
+pub struct OfferedHtlc {
+    pub payment_hash160: [u8; 20],
+    pub amount_satoshi: u64,
+    pub cltv_expiry: u32,
+}
+
 pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                          txid: String,
                                         our_key_manager: KeyManager,
-                                        counterparty_key_manager: KeyManager) {
+                                        counterparty_key_manager: KeyManager,
+                                        htlc: OfferedHtlc,
+                                        feerate_per_kw: u32) {
 
     let txid_index = 0;
     let funding_txin = get_htlc_funding_input(txid.to_string(), txid_index);
-    let funding_amount = 405_000;
+    let OfferedHtlc { payment_hash160, amount_satoshi: funding_amount, cltv_expiry } = htlc;
 
-    let payment_hash160 = HASH160_DUMMY;
     let to_self_delay: i64 = 144;
-    let cltv_expiry: u32 = 300;
-    let htlc_amount = 404_000;
-
-
-    let tx = build_htlc_timeout_transaction(
-        funding_txin,
-        &our_key_manager.revocation_pubkey,
-        &our_key_manager.delayed_pubkey,
-        to_self_delay,
-        cltv_expiry,
-        htlc_amount
-        );
 
     // Prepare the redeem script for signing (e.g., P2PKH or P2WPKH)
     let redeem_script =
@@ -64,47 +66,46 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
             &our_key_manager.htlc_pubkey,
             &payment_hash160);
 
-    let our_signature = generate_p2wsh_signature(
-         tx.clone(), 
-         0,
-         &redeem_script,
-         funding_amount,
-         EcdsaSighashType::All,
-        our_key_manager.htlc_private_key);
-
-    let counterparty_signature = generate_p2wsh_signature(
-         tx.clone(), 
-         0,
-         &redeem_script,
-         funding_amount,
-         EcdsaSighashType::All,
-        counterparty_key_manager.htlc_private_key);
-
-    // Convert signature to DER and append SigHashType
-    let mut our_signature_der = our_signature.serialize_der().to_vec();
-    our_signature_der.push(EcdsaSighashType::All as u8);
-
-    let mut counterparty_signature_der = counterparty_signature.serialize_der().to_vec();
-    counterparty_signature_der.push(EcdsaSighashType::All as u8);
-
-    // Determine signature order based on pubkey comparison
-    let our_sig_first = our_key_manager.funding_public_key.inner.serialize()[..] > counterparty_key_manager.funding_public_key.inner.serialize()[..];
-
-    // Add the signature and public key to the witness
-    let mut signed_tx = tx.clone();
-
-    // First push empty element for NULLDUMMY compliance
-    signed_tx.input[0].witness.push(Vec::new());
-
-
-    signed_tx.input[0].witness.push(counterparty_signature_der);
-    signed_tx.input[0].witness.push(our_signature_der);
-
-    signed_tx.input[0].witness.push(vec![]);
-
-    signed_tx.input[0]
-        .witness
-        .push(redeem_script.clone().into_bytes());
+    let build_and_sign = |htlc_amount: u64| {
+        let tx = build_htlc_timeout_transaction(
+            funding_txin.clone(),
+            &our_key_manager.revocation_pubkey,
+            &our_key_manager.delayed_pubkey,
+            to_self_delay,
+            cltv_expiry,
+            htlc_amount
+            );
+
+        // 0 <remotehtlcsig> <localhtlcsig> <>
+        let mut signed_tx = tx.clone();
+        sign_p2wsh_input(&mut signed_tx,
+                         0,
+                         &redeem_script,
+                         funding_amount,
+                         &SpendPath::HtlcTimeout,
+                         &[WitnessSigner::Key(our_key_manager.htlc_private_key),
+                           WitnessSigner::Key(counterparty_key_manager.htlc_private_key)])
+            .expect("HTLC keys are not in the offered HTLC script");
+
+        signed_tx
+    };
+
+    // BOLT 3 fixes the fee at the commitment's feerate and takes it out of
+    // the HTLC. An HTLC that can't pay it, or is dust after paying, should
+    // have been trimmed from the commitment
+    let fee = htlc_tx_fee_sat(feerate_per_kw, true);
+    let Some(htlc_amount) = funding_amount.checked_sub(fee) else {
+        println!("The {} sat HTLC can't pay its {} sat fee at {} sat/kW", funding_amount, fee, feerate_per_kw);
+        return;
+    };
+    let signed_tx = build_and_sign(htlc_amount);
+    let dust_limit = signed_tx.output[0].script_pubkey.minimal_non_dust().to_sat();
+    if htlc_amount < dust_limit {
+        println!("The {} sat HTLC leaves {} sats after its {} sat fee, under the {} sat dust limit",
+                 funding_amount, htlc_amount, fee, dust_limit);
+        return;
+    }
+    println!("HTLC timeout fee: {} sats at {} sat/kW", fee, feerate_per_kw);
 
     println!("\n");
     println!("Tx ID: {}", signed_tx.compute_txid());
@@ -115,53 +116,87 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
     //bitcoind.broadcast_transactions(&[&signed_tx]);
 }
 
-pub async fn run(htlc_txid: String) {
+pub async fn run(htlc_txid: Option<String>, channel: Option<String>, invoice: Option<String>) {
+
+    // a named channel remembers the last HTLC we offered on it, and the
+    // feerate its commitments pay
+    let (stored_htlc, channel_feerate_per_kw) = match (&htlc_txid, &channel) {
+        (None, Some(name)) => {
+            let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+            let channel = store.channel(name).expect("Unknown channel");
+            let htlc = channel.last_offered_htlc().cloned();
+            (Some(htlc.ok_or(StoreError::UnknownHtlc(name.clone())).expect("No HTLC offered on this channel")),
+             Some(channel.feerate_per_kw))
+        }
+        _ => (None, None),
+    };
+
+    // lock the HTLC to the invoice's payment hash, if we were given one. A
+    // stored HTLC is already locked to its own, which the invoice must match
+    let payment_hash160 = match (invoice, &stored_htlc) {
+        (Some(invoice), stored_htlc) => {
+            let invoice = decode_invoice(&invoice).expect("Invalid invoice");
+            if let Some(htlc) = stored_htlc.as_ref().filter(|htlc| invoice.payment_hash != htlc.payment_hash()) {
+                panic!(
+                    "The invoice is for payment hash {}, but the channel's HTLC is locked to {}",
+                    hex::encode(invoice.payment_hash),
+                    htlc.payment_hash
+                );
+            }
+            println!("Timing out HTLC for payment hash {}", hex::encode(invoice.payment_hash));
+            invoice.payment_hash160()
+        }
+        (None, Some(htlc)) => htlc.payment_hash160(),
+        (None, None) => HASH160_DUMMY,
+    };
 
     // get bitcoin client
     let bitcoind = get_bitcoind_client().await;
 
-    // Parse the argument as txid
-    let txid = htlc_txid;
+    // Parse the argument as txid. A bare txid is taken to be the HTLC Htlc
+    // offers on the first commitment
+    let (txid, htlc_amount, cltv_expiry, commitment_number) = match (htlc_txid, stored_htlc) {
+        (Some(txid), _) => (txid, HTLC_AMOUNT_SATOSHI, HTLC_CLTV_EXPIRY, 0),
+        (None, Some(htlc)) => (htlc.txid.unwrap(), htlc.amount_msat / 1000, htlc.cltv_expiry, htlc.commitment_number),
+        (None, None) => panic!("Pass an HTLC txid or a channel name"),
+    };
 
-    // Get our keys
-    let our_funding_private_key = secp256k1_private_key(&[0x01; 32]);
-    let our_funding_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let local_htlc_pubkey = pubkey_from_private_key(&[0x11; 32]);
-    let local_htlc_private_key = secp256k1_private_key(&[0x11; 32]);
-    let revocation_pubkey = pubkey_from_private_key(&[0x12; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x13; 32]);
-    let local_pubkey = pubkey_from_private_key(&[0x14; 32]);
+    // every key comes from the node seed. Both HTLC keys are tweaked with
+    // the point of the commitment the HTLC is in
+    let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (alice, bob) = channel_peers(store.node_seed());
 
+    // Get our keys
+    let our_keys = alice.commitment_keys(&bob, commitment_number);
     let our_key_manager = KeyManager{
-            funding_private_key: our_funding_private_key,
-            funding_public_key: our_funding_public_key,
-            htlc_pubkey: local_htlc_pubkey,
-            htlc_private_key: local_htlc_private_key,
-            delayed_pubkey: to_local_delayed_pubkey,
-            pubkey: local_pubkey,
-            revocation_pubkey: revocation_pubkey,
+            funding_private_key: alice.funding_key(),
+            funding_public_key: PublicKey::new(alice.pubkeys().funding_pubkey),
+            htlc_pubkey: PublicKey::new(our_keys.broadcaster_htlc_pubkey),
+            htlc_private_key: alice.htlc_key(&our_keys.per_commitment_point),
+            delayed_pubkey: PublicKey::new(our_keys.broadcaster_delayed_pubkey),
+            pubkey: PublicKey::new(alice.pubkeys().payment_basepoint),
+            revocation_pubkey: PublicKey::new(our_keys.revocation_pubkey),
         };
 
     // Get our Counterparty Pubkey
-    let counterparty_funding_private_key = secp256k1_private_key(&[0x02; 32]);
-    let counterparty_funding_public_key = pubkey_from_private_key(&[0x02; 32]);
-    let counterparty_htlc_pubkey = pubkey_from_private_key(&[0x21; 32]);
-    let counterparty_htlc_private_key = secp256k1_private_key(&[0x21; 32]);
-    let counterparty_pubkey = pubkey_from_private_key(&[0x22; 32]);
-    let counterparty_delayed_key = pubkey_from_private_key(&[0x23; 32]);
-    let counterparty_revocation_key = pubkey_from_private_key(&[0x24; 32]);
-
+    let counterparty_keys = bob.commitment_keys(&alice, commitment_number);
     let counterparty_key_manager = KeyManager{
-            funding_private_key: counterparty_funding_private_key,
-            funding_public_key: counterparty_funding_public_key,
-            htlc_pubkey: counterparty_htlc_pubkey,
-            htlc_private_key: counterparty_htlc_private_key,
-            delayed_pubkey: counterparty_delayed_key,
-            pubkey: counterparty_pubkey,
-            revocation_pubkey: counterparty_revocation_key,
+            funding_private_key: bob.funding_key(),
+            funding_public_key: PublicKey::new(bob.pubkeys().funding_pubkey),
+            htlc_pubkey: PublicKey::new(our_keys.countersignatory_htlc_pubkey),
+            htlc_private_key: bob.htlc_key(&our_keys.per_commitment_point),
+            delayed_pubkey: PublicKey::new(counterparty_keys.broadcaster_delayed_pubkey),
+            pubkey: PublicKey::new(our_keys.countersignatory_payment_pubkey),
+            revocation_pubkey: PublicKey::new(counterparty_keys.revocation_pubkey),
         };
 
-    create_broadcast_funding_tx(bitcoind, txid, our_key_manager, counterparty_key_manager).await;
+    // without a channel, take the rate Fund gives new commitments
+    let feerate_per_kw = match channel_feerate_per_kw {
+        Some(feerate_per_kw) => feerate_per_kw,
+        None => bitcoind.get_feerate(ConfirmationTarget::NonAnchorChannelFee).await,
+    };
+    let htlc = OfferedHtlc { payment_hash160, amount_satoshi: htlc_amount, cltv_expiry };
+    create_broadcast_funding_tx(bitcoind, txid, our_key_manager, counterparty_key_manager, htlc, feerate_per_kw).await;
 
     // Add a delay to allow the spawned task to complete
     sleep(Duration::from_secs(2)).await;
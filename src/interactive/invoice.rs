@@ -0,0 +1,60 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::interactive::funding::channel_peers;
+use internal::invoice::{create_invoice, decode_invoice, generate_preimage, DEFAULT_EXPIRY_SECS};
+use internal::store::{Store, DEFAULT_STORE_PATH};
+
+pub fn run(amount_msat: Option<u64>, description: String) {
+
+    // Bob is the one receiving the HTLC in the `htlc` command, so the
+    // invoice is signed with his node key
+    let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (_, bob) = channel_peers(store.node_seed());
+
+    let (preimage, payment_hash) = generate_preimage();
+
+    let invoice = create_invoice(
+        &bob.node_secret(),
+        payment_hash,
+        amount_msat,
+        &description,
+        DEFAULT_EXPIRY_SECS,
+        vec![],
+    )
+    .expect("Failed to build invoice");
+
+    println!("\n");
+    println!("Payment Preimage: {}", hex::encode(preimage));
+    println!("Payment Hash: {}", hex::encode(payment_hash));
+    println!("\n");
+    println!("Invoice: {}", invoice);
+}
+
+pub fn decode(invoice: String) {
+    let invoice = decode_invoice(&invoice).expect("Invalid invoice");
+
+    println!("\n");
+    println!("Payee: {}", invoice.payee);
+    match invoice.amount_msat {
+        Some(amount_msat) => println!("Amount: {} msat", amount_msat),
+        None => println!("Amount: any"),
+    }
+    println!("Payment Hash: {}", hex::encode(invoice.payment_hash));
+    println!("Payment Hash160: {}", hex::encode(invoice.payment_hash160()));
+    println!("Description: {}", invoice.description);
+    println!("Timestamp: {}", invoice.timestamp);
+    println!("Expiry: {} seconds", invoice.expiry_secs);
+    println!("Min Final CLTV Expiry Delta: {}", invoice.min_final_cltv_expiry_delta);
+    for route_hint in invoice.route_hints.iter() {
+        for hop in route_hint.0.iter() {
+            println!(
+                "Route Hint: node {} scid {} fee {} msat + {} ppm cltv delta {}",
+                hop.src_node_id,
+                hop.short_channel_id,
+                hop.fees.base_msat,
+                hop.fees.proportional_millionths,
+                hop.cltv_expiry_delta
+            );
+        }
+    }
+}
@@ -4,13 +4,14 @@ use bitcoin::blockdata::script::ScriptBuf;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::locktime::absolute::LockTime;
 use bitcoin::transaction::Version;
-use bitcoin::{TxIn};
+use bitcoin::{Amount, TxIn, TxOut};
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::standardness::{check_transaction, Finding};
 use std::time::Duration;
 use tokio::time::sleep;
 use clap::{ValueEnum};
@@ -76,12 +77,33 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
 
     let signed_tx = sign_raw_transaction(bitcoind.clone(), tx).await;
 
+    // check the transaction against bitcoind's rules before anyone broadcasts it
+    let spent_utxo = bitcoind.list_unspent().await.0.into_iter()
+        .find(|utxo| utxo.txid == signed_tx.input[0].previous_output.txid && utxo.vout == signed_tx.input[0].previous_output.vout)
+        .expect("Spent output is not in the wallet");
+    let prevout = TxOut { value: Amount::from_sat(spent_utxo.amount), script_pubkey: spent_utxo.address.script_pubkey() };
+    let chain_tip = bitcoind.get_chain_tip().await;
+    print_findings(&check_transaction(&signed_tx, &[prevout], &chain_tip));
+
     println!("\n");
     println!("Tx ID: {}", signed_tx.compute_txid());
     println!("\n");
     println!("Tx Hex: {}", serialize_hex(&signed_tx));
 }
 
+fn print_findings(findings: &[Finding]) {
+    println!("\n");
+    if findings.is_empty() {
+        println!("Standardness: no problems found");
+        return;
+    }
+    println!("Standardness:");
+    for finding in findings {
+        let rule = if finding.is_consensus() { "consensus" } else { "policy" };
+        println!("  [{}] {}", rule, finding);
+    }
+}
+
 pub async fn run(mempool_command: MempoolCommand) {
 
     // get bitcoin client
@@ -6,3 +6,12 @@ pub mod htlc_demo2;
 pub mod htlc_timeout;
 pub mod refund;
 pub mod mempool;
+pub mod transport;
+pub mod invoice;
+pub mod onion;
+pub mod multihop;
+pub mod route;
+pub mod scid;
+pub mod node;
+pub mod script;
+pub mod cpfp;
@@ -0,0 +1,91 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::hex_utils::hex_str;
+use internal::invoice::{generate_preimage, DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA};
+use internal::multihop::{FeePolicy, PaymentSimulator};
+
+const AMOUNT_MSAT: u64 = 10_000_000;
+const CHANNEL_VALUE_SATOSHIS: u64 = 1_000_000;
+const START_HEIGHT: u32 = 100;
+
+fn new_simulator() -> PaymentSimulator {
+    // the policy on each channel belongs to the node sending over it, so Alice's is never used
+    let policies = [
+        FeePolicy { base_fee_msat: 0, fee_proportional_millionths: 0, cltv_expiry_delta: 0 },
+        FeePolicy { base_fee_msat: 1_000, fee_proportional_millionths: 100, cltv_expiry_delta: 40 },
+        FeePolicy { base_fee_msat: 500, fee_proportional_millionths: 1_000, cltv_expiry_delta: 144 },
+    ];
+    PaymentSimulator::new(&["Alice", "Bob", "Carol", "Dave"], &policies, CHANNEL_VALUE_SATOSHIS, START_HEIGHT)
+}
+
+fn print_balances(sim: &PaymentSimulator) {
+    for (i, channel) in sim.channels.iter().enumerate() {
+        println!(
+            "  {} <-> {}: {} / {} msat{}",
+            sim.nodes[i].name,
+            sim.nodes[i + 1].name,
+            channel.upstream.holder_balance_msat(),
+            channel.downstream.holder_balance_msat(),
+            if channel.closed { " (closed)" } else { "" }
+        );
+    }
+}
+
+fn send(sim: &mut PaymentSimulator, payment_hash: [u8; 32]) -> u32 {
+    let hops = sim
+        .send_payment(AMOUNT_MSAT, payment_hash, rand::random(), DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA as u32)
+        .unwrap();
+
+    for (i, hop) in hops.iter().enumerate() {
+        println!(
+            "{} -> {}: HTLC of {} msat expiring at block {}",
+            sim.nodes[i].name,
+            sim.nodes[i + 1].name,
+            hop.amount_msat,
+            hop.cltv_expiry
+        );
+    }
+    println!("\n");
+    print_balances(sim);
+    println!("\n");
+
+    hops[hops.len() - 1].cltv_expiry
+}
+
+pub fn run() {
+    println!("Payment 1: Dave is online and claims the payment");
+    println!("\n");
+
+    let mut sim = new_simulator();
+    let (preimage, payment_hash) = generate_preimage();
+    println!("Payment hash: {}", hex_str(&payment_hash));
+    send(&mut sim, payment_hash);
+
+    sim.fulfill_payment(preimage).unwrap();
+    println!("Dave reveals preimage {}, which flows back to Alice", hex_str(&preimage));
+    print_balances(&sim);
+    println!("\n");
+
+    println!("Payment 2: Dave goes offline without resolving the HTLC");
+    println!("\n");
+
+    let mut sim = new_simulator();
+    let (_, payment_hash) = generate_preimage();
+    let final_cltv_expiry = send(&mut sim, payment_hash);
+    sim.nodes[3].online = false;
+
+    while sim.height < final_cltv_expiry {
+        for force_close in sim.connect_block().unwrap() {
+            let channel = force_close.channel_index;
+            println!("Block {}: {} force-closes the channel with {}", sim.height, sim.nodes[channel].name, sim.nodes[channel + 1].name);
+            println!("  commitment tx:   {}", force_close.commitment_tx.compute_txid());
+            match &force_close.htlc_timeout_tx {
+                Some(htlc_timeout_tx) => println!("  htlc timeout tx: {}", htlc_timeout_tx.compute_txid()),
+                None => println!("  the HTLC was trimmed as dust, so its value goes to fees"),
+            }
+            println!("  and fails the HTLC back towards Alice");
+        }
+    }
+    println!("\n");
+    print_balances(&sim);
+}
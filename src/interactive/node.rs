@@ -0,0 +1,81 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::Network;
+use internal::bitcoind_client::get_bitcoind_client;
+use internal::node::{parse_peer, LdkNode, NodeConfig};
+use lightning::util::logger::Level;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMER_TICK_SECS: u64 = 60;
+
+pub fn default_data_dir(port: u16) -> String {
+    format!("{}/data/ldk-{}", env!("CARGO_MANIFEST_DIR"), port)
+}
+
+pub async fn run(port: u16, data_dir: Option<String>, peer: Option<String>, open_channel: Option<u64>) {
+    let bitcoind = Arc::new(get_bitcoind_client().await);
+
+    let data_dir = data_dir.unwrap_or_else(|| default_data_dir(port));
+    let config = NodeConfig {
+        alias: format!("node-{}", port),
+        data_dir: data_dir.clone().into(),
+        network: Network::Regtest,
+        log_level: Level::Info,
+    };
+    let node = Arc::new(
+        LdkNode::start(config, bitcoind.clone(), bitcoind.clone(), bitcoind.clone(), bitcoind.clone())
+            .await
+            .expect("Failed to start the node"),
+    );
+
+    let addr = node.listen(SocketAddr::from(([127, 0, 0, 1], port))).await.expect("Failed to listen");
+    println!("\n");
+    println!("Node ID: {}", node.node_id());
+    println!("Listening on {}", addr);
+    println!("Open a channel to this node from another one with:");
+    println!("  cargo run -- node --port <other port> --peer {}@{} --open-channel 1000000", node.node_id(), addr);
+    println!("\n");
+
+    // blocks arrive in the background while we handle events below
+    let sync_node = node.clone();
+    let block_source = bitcoind.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sync_node.sync_chain(block_source, Duration::from_secs(1), || false).await {
+            println!("Stopped syncing the chain: {:?}", e);
+        }
+    });
+
+    if let Some(peer) = peer {
+        let (their_node_id, their_addr) = parse_peer(&peer).expect("Peers look like pubkey@host:port");
+        node.connect(their_node_id, their_addr).await.expect("Failed to connect");
+        println!("Connected to {}", their_node_id);
+
+        if let Some(channel_value_satoshis) = open_channel {
+            let channel_id = node.open_channel(their_node_id, channel_value_satoshis).expect("Failed to open the channel");
+            println!("Opening channel {} for {} sats", channel_id, channel_value_satoshis);
+        }
+    }
+
+    let mut events = tokio::time::interval(Duration::from_millis(100));
+    let mut timer = tokio::time::interval(Duration::from_secs(TIMER_TICK_SECS));
+    loop {
+        tokio::select! {
+            _ = events.tick() => {
+                if let Err(e) = node.process_events().await {
+                    println!("Failed to process events: {:?}", e);
+                }
+            }
+            _ = timer.tick() => {
+                bitcoind.update_fee_estimates().await;
+                node.timer_tick();
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    // one last pass so the latest channel state is on disk
+    node.process_events().await;
+    println!("Stopped. Channels are saved in {}", data_dir);
+}
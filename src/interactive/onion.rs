@@ -0,0 +1,63 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix;
+use bitcoin::secp256k1::SecretKey;
+use exercises_appendix::exercises::NodeKeysManager;
+use internal::hex_utils::hex_str;
+use internal::invoice::generate_preimage;
+use internal::onion::{create_onion_packet, peel_onion, HopPayload, PaymentData, PeeledOnion};
+
+const AMOUNT_MSAT: u64 = 100_000;
+const FINAL_CLTV_EXPIRY: u32 = 200;
+const FEE_MSAT: u64 = 1_000;
+const CLTV_EXPIRY_DELTA: u32 = 40;
+
+pub fn run() {
+    // Alice pays Dave through Bob and Carol
+    let names = ["Bob", "Carol", "Dave"];
+    let nodes: Vec<NodeKeysManager> = [[0x02; 32], [0x03; 32], [0x04; 32]]
+        .into_iter()
+        .map(NodeKeysManager::new)
+        .collect();
+    let route: Vec<_> = nodes.iter().map(|node| node.node_id).collect();
+
+    let (_, payment_hash) = generate_preimage();
+    let payment_secret: [u8; 32] = rand::random();
+
+    // each payload tells a hop what to send on, so fees and CLTV deltas are
+    // added walking backwards from the destination
+    let payloads = vec![
+        HopPayload::forward(2, AMOUNT_MSAT + FEE_MSAT, FINAL_CLTV_EXPIRY + CLTV_EXPIRY_DELTA),
+        HopPayload::forward(3, AMOUNT_MSAT, FINAL_CLTV_EXPIRY),
+        HopPayload::receive(
+            AMOUNT_MSAT,
+            FINAL_CLTV_EXPIRY,
+            Some(PaymentData { payment_secret, total_msat: AMOUNT_MSAT }),
+        ),
+    ];
+
+    let session_key = SecretKey::from_slice(&rand::random::<[u8; 32]>()).unwrap();
+    let mut packet = create_onion_packet(&session_key, &route, &payloads, &payment_hash).unwrap();
+
+    println!("Payment hash: {}", hex_str(&payment_hash));
+    println!("\n");
+
+    for (name, node) in names.iter().zip(&nodes) {
+        println!("{} receives onion", name);
+        println!("  ephemeral key: {}", packet.public_key);
+        println!("  hmac:          {}", hex_str(&packet.hmac));
+
+        match peel_onion(&node.node_secret, &packet, &payment_hash).unwrap() {
+            PeeledOnion::Forward { payload, next_packet } => {
+                println!("  forward {} msat over channel {} with cltv {}",
+                         payload.amt_to_forward, payload.short_channel_id.unwrap(), payload.outgoing_cltv_value);
+                packet = *next_packet;
+            }
+            PeeledOnion::Receive { payload } => {
+                println!("  final hop: receive {} msat with cltv {}", payload.amt_to_forward, payload.outgoing_cltv_value);
+                println!("  payment secret: {}", hex_str(&payload.payment_data.unwrap().payment_secret));
+            }
+        }
+        println!("\n");
+    }
+}
@@ -4,14 +4,16 @@ use crate::exercises;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
 use bitcoin::PublicKey;
-use lightning::chain::chaininterface::{BroadcasterInterface,};
+use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget};
 use exercises::exercises::{build_refund_transaction, };
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
-use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
+use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input, fee_for_weight};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::store::{Store, DEFAULT_STORE_PATH};
+use crate::interactive::funding::channel_peers;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -27,25 +29,34 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                         our_key_manager: KeyManager,
                                         counterparty_key_manager: KeyManager,
                                         funding_amount: u64,
-                                        our_balance: u64,
-                                        counterparty_balance: u64) {
+                                        counterparty_balance: u64,
+                                        feerate_per_kw: u32) {
 
     let txid_index = 0;
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
 
-    let tx = build_refund_transaction(
-        funding_txin,
-        our_key_manager.commitment_pubkey,
-        counterparty_key_manager.commitment_pubkey,
-        our_balance,
-        counterparty_balance);
+    let build_and_sign = |our_balance: u64| {
+        let tx = build_refund_transaction(
+            funding_txin.clone(),
+            our_key_manager.commitment_pubkey,
+            counterparty_key_manager.commitment_pubkey,
+            our_balance,
+            counterparty_balance);
 
-    let signed_tx = sign_funding_transaction(tx,
+        sign_funding_transaction(tx,
             our_key_manager.funding_public_key,
             our_key_manager.funding_private_key,
             counterparty_key_manager.funding_public_key,
             counterparty_key_manager.funding_private_key,
-           );
+           )
+    };
+
+    // we opened the channel, so the fee comes out of our balance. Sign once
+    // to learn the weight, then again with the fee taken out
+    let weight = build_and_sign(funding_amount - counterparty_balance).weight();
+    let fee = fee_for_weight(feerate_per_kw, weight);
+    let signed_tx = build_and_sign(funding_amount - counterparty_balance - fee);
+    println!("Closing fee: {} sats at {} sat/kW", fee, feerate_per_kw);
 
     println!("\n");
     println!("Tx ID: {}", signed_tx.compute_txid());
@@ -65,34 +76,31 @@ pub async fn run(funding_txid: String) {
     // get bitcoin client
     let bitcoind = get_bitcoind_client().await;
 
-    // Get our keys
-    let our_funding_private_key = secp256k1_private_key(&[0x01; 32]);
-    let our_funding_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let our_commitment_pubkey = pubkey_from_private_key(&[0x11; 32]);
+    // every key comes from the node seed. The refund pays both sides to
+    // their payment basepoints
+    let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (alice, bob) = channel_peers(store.node_seed());
 
+    // Get our keys
     let our_key_manager = KeyManager{
-            funding_private_key: our_funding_private_key,
-            funding_public_key: our_funding_public_key,
-            commitment_pubkey: our_commitment_pubkey
+            funding_private_key: alice.funding_key(),
+            funding_public_key: PublicKey::new(alice.pubkeys().funding_pubkey),
+            commitment_pubkey: PublicKey::new(alice.pubkeys().payment_basepoint),
         };
 
     // Get our Counterparty Pubkey
-    let counterparty_funding_private_key = secp256k1_private_key(&[0x02; 32]);
-    let counterparty_funding_public_key = pubkey_from_private_key(&[0x02; 32]);
-    let counterparty_commitment_pubkey = pubkey_from_private_key(&[0x21; 32]);
-
     let counterparty_key_manager = KeyManager{
-            funding_private_key: counterparty_funding_private_key,
-            funding_public_key: counterparty_funding_public_key,
-            commitment_pubkey: counterparty_commitment_pubkey,
+            funding_private_key: bob.funding_key(),
+            funding_public_key: PublicKey::new(bob.pubkeys().funding_pubkey),
+            commitment_pubkey: PublicKey::new(bob.pubkeys().payment_basepoint),
         };
     
     let funding_amount = 5_000_000;
-    let our_balance = 4_998_500;
     let counterparty_balance = 500;
+    let feerate_per_kw = bitcoind.get_feerate(ConfirmationTarget::ChannelCloseMinimum).await;
     
     create_broadcast_funding_tx(bitcoind, txid.clone(), our_key_manager, counterparty_key_manager, funding_amount,
-                               our_balance, counterparty_balance).await;
+                               counterparty_balance, feerate_per_kw).await;
 
     // Add a delay to allow the spawned task to complete
     sleep(Duration::from_secs(2)).await;
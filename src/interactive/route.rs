@@ -0,0 +1,59 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::SecretKey;
+use internal::hex_utils::hex_str;
+use internal::invoice::{generate_preimage, DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA};
+use internal::onion::create_onion_packet;
+use internal::router::{find_route, RoutingGraph};
+use internal::scid::{format_scid, parse_scid};
+
+pub const DEFAULT_GRAPH_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/network_graph.json");
+
+const CURRENT_HEIGHT: u32 = 100;
+
+pub fn run(from: String, to: String, amount_msat: u64, excluded_channels: Vec<String>, graph_path: String) {
+    let graph = RoutingGraph::load(&graph_path).expect("Failed to load network graph");
+    let source = graph.resolve_node(&from).expect("Unknown source node");
+    let target = graph.resolve_node(&to).expect("Unknown destination node");
+    let excluded_channels: Vec<u64> = excluded_channels
+        .iter()
+        .map(|scid| parse_scid(scid).expect("Invalid short channel id"))
+        .collect();
+
+    let final_cltv_expiry = CURRENT_HEIGHT + DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA as u32;
+    let route = match find_route(&graph, &source, &target, amount_msat, final_cltv_expiry, &excluded_channels) {
+        Ok(route) => route,
+        Err(e) => {
+            println!("No route from {} to {} for {} msat: {:?}", from, to, amount_msat, e);
+            return;
+        }
+    };
+
+    println!("\n");
+    let mut sender = graph.alias(&source);
+    for hop in &route.hops {
+        let receiver = graph.alias(&hop.node_id);
+        println!(
+            "{} -> {} over {}: HTLC of {} msat expiring at block {}",
+            sender, receiver, format_scid(hop.short_channel_id), hop.amount_msat, hop.cltv_expiry
+        );
+        sender = receiver;
+    }
+    println!("\n");
+    println!("Total amount: {} msat", route.total_amount_msat());
+    println!("Total fees:   {} msat", route.total_fee_msat());
+    println!("Total CLTV:   block {}", route.total_cltv_expiry());
+    println!("\n");
+
+    let (_, payment_hash) = generate_preimage();
+    let payloads = route.onion_payloads(rand::random());
+    for (hop, payload) in route.hops.iter().zip(&payloads) {
+        println!("Payload for {}: {}", graph.alias(&hop.node_id), hex_str(&payload.encode()));
+    }
+
+    let session_key = SecretKey::from_slice(&rand::random::<[u8; 32]>()).unwrap();
+    let packet = create_onion_packet(&session_key, &route.node_ids(), &payloads, &payment_hash).unwrap();
+    println!("\n");
+    println!("Onion ephemeral key: {}", packet.public_key);
+    println!("Onion hmac:          {}", hex_str(&packet.hmac));
+}
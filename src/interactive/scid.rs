@@ -0,0 +1,34 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::{OutPoint, Txid};
+use internal::bitcoind_client::get_bitcoind_client;
+use internal::scid::{format_scid, parse_scid};
+use lightning::util::scid_utils::{block_from_scid, tx_index_from_scid, vout_from_scid};
+use std::str::FromStr;
+
+fn print_scid(short_channel_id: u64) {
+    println!("\n");
+    println!("Short Channel ID: {}", format_scid(short_channel_id));
+    println!("As u64: {}", short_channel_id);
+    println!("  block height: {}", block_from_scid(short_channel_id));
+    println!("  tx index:     {}", tx_index_from_scid(short_channel_id));
+    println!("  output index: {}", vout_from_scid(short_channel_id));
+}
+
+pub async fn run(funding_txid: Option<String>, vout: u32, scid: Option<String>) {
+    if let Some(scid) = scid {
+        // accept both the 123x4x0 form and the raw u64
+        let short_channel_id = scid.parse::<u64>().or_else(|_| parse_scid(&scid)).expect("Invalid short channel id");
+        print_scid(short_channel_id);
+        return;
+    }
+
+    let funding_txid = funding_txid.expect("Pass a funding txid or a short channel id");
+    let funding_outpoint = OutPoint { txid: Txid::from_str(&funding_txid).unwrap(), vout };
+
+    let bitcoind = get_bitcoind_client().await;
+    match bitcoind.get_funding_scid(&funding_outpoint).await {
+        Ok(short_channel_id) => print_scid(short_channel_id),
+        Err(e) => println!("Could not find the funding output: {:?}", e),
+    }
+}
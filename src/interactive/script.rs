@@ -0,0 +1,105 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::interactive::funding::channel_peers;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::script::ScriptBuf;
+use clap::Subcommand;
+use internal::policy::parse_policy;
+use internal::script_asm::{assemble, disassemble, workshop_script_names, ScriptNames};
+use internal::store::{Store, DEFAULT_STORE_PATH};
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ScriptCommand {
+    /// Turn text like "OP_DUP OP_HASH160 <revocation_pubkey_hash160> OP_EQUAL" into a script
+    Asm {
+        #[arg(help = "Script text; pushes are <hex> or a key name like <revocation_pubkey>")]
+        text: String,
+    },
+    /// Compile a policy like "or(pk(revocation_pubkey), and(older(144), pk(to_local_delayed_pubkey)))"
+    Policy {
+        #[arg(help = "Policy; keys and hashes are hex or workshop names")]
+        text: String,
+    },
+    /// Print a script as text, naming the channel's keys and payment hash
+    Disasm {
+        #[arg(help = "Script hex")]
+        hex: String,
+        #[arg(short = 'c', long = "channel", help = "Channel whose current commitment keys to name, the first commitment's by default")]
+        channel: Option<String>,
+        #[arg(short = 'n', long = "name", help = "Name a push as name=hex, may be repeated")]
+        names: Vec<String>,
+    },
+}
+
+/// The keys `commit` and `htlc` put in the channel's current commitment, or
+/// in the first one when no channel is named, as they do for a bare txid.
+fn channel_script_names(channel: Option<String>) -> ScriptNames {
+    let store = Store::open(DEFAULT_STORE_PATH).expect("Failed to open the store");
+    let (alice, bob) = channel_peers(store.node_seed());
+
+    let stored = channel.map(|name| store.channel(&name).expect("Unknown channel").clone());
+    let commitment_number = stored.as_ref().map_or(0, |channel| channel.current_commitment_number());
+    let payment_hash = stored
+        .as_ref()
+        .and_then(|channel| channel.last_offered_htlc())
+        .map_or(Sha256::hash(&[]).to_byte_array(), |htlc| htlc.payment_hash());
+
+    let our_keys = alice.commitment_keys(&bob, commitment_number);
+    let counterparty_keys = bob.commitment_keys(&alice, commitment_number);
+
+    let mut names = ScriptNames::new();
+    names.add_pubkey("our_funding_pubkey", &alice.pubkeys().funding_pubkey);
+    names.add_pubkey("local_htlc_pubkey", &our_keys.broadcaster_htlc_pubkey);
+    names.add_pubkey("revocation_pubkey", &our_keys.revocation_pubkey);
+    names.add_pubkey("to_local_delayed_pubkey", &our_keys.broadcaster_delayed_pubkey);
+    names.add_pubkey("local_pubkey", &alice.pubkeys().payment_basepoint);
+    names.add_pubkey("counterparty_funding_pubkey", &bob.pubkeys().funding_pubkey);
+    names.add_pubkey("counterparty_htlc_pubkey", &our_keys.countersignatory_htlc_pubkey);
+    names.add_pubkey("counterparty_pubkey", &our_keys.countersignatory_payment_pubkey);
+    names.add_pubkey("counterparty_delayed_pubkey", &counterparty_keys.broadcaster_delayed_pubkey);
+    names.add_pubkey("counterparty_revocation_pubkey", &counterparty_keys.revocation_pubkey);
+    names.add_payment_hash("payment_hash", &payment_hash);
+    names
+}
+
+fn script_names(channel: Option<String>, extra: &[String]) -> ScriptNames {
+    let mut names = channel_script_names(channel);
+    for entry in extra {
+        let (name, hex) = entry.split_once('=').expect("Names are given as name=hex");
+        names.add(name, &hex::decode(hex).expect("Invalid hex"));
+    }
+    names
+}
+
+pub fn run(command: ScriptCommand) {
+    match command {
+        ScriptCommand::Asm { text } => {
+            let names = workshop_script_names();
+            let script = assemble(&text, &names).unwrap_or_else(|e| panic!("Could not assemble script: {:?}", e));
+
+            println!("\n");
+            println!("Witness Script (hex): {}", script.to_hex_string());
+            println!("Witness Script (asm): {}", disassemble(&script, &names).unwrap());
+            println!("P2WSH Output Script: {}", script.to_p2wsh().to_hex_string());
+        }
+        ScriptCommand::Policy { text } => {
+            let names = workshop_script_names();
+            let policy = parse_policy(&text, &names).unwrap_or_else(|e| panic!("Could not parse policy: {:?}", e));
+            let script = policy.compile();
+
+            println!("\n");
+            println!("Witness Script (hex): {}", script.to_hex_string());
+            println!("Witness Script (asm): {}", disassemble(&script, &names).unwrap());
+            println!("P2WSH Output Script: {}", script.to_p2wsh().to_hex_string());
+        }
+        ScriptCommand::Disasm { hex, channel, names } => {
+            let names = script_names(channel, &names);
+            let script = ScriptBuf::from_bytes(hex::decode(hex).expect("Invalid hex"));
+            let asm = disassemble(&script, &names).unwrap_or_else(|e| panic!("Could not disassemble script: {:?}", e));
+
+            println!("\n");
+            println!("Witness Script (asm): {}", asm);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use exercises_appendix::exercises::NodeKeysManager;
+use internal::hex_utils::hex_str;
+use internal::noise::{NoiseError, NoiseHandshake, NoiseTransport, ACT_ONE_LEN, ACT_THREE_LEN, ACT_TWO_LEN,
+                      LENGTH_HEADER_LEN, MAC_LEN};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// enough messages to see the keys rotate twice
+const NUM_MESSAGES: usize = 1005;
+
+fn random_ephemeral_key() -> SecretKey {
+    loop {
+        let bytes: [u8; 32] = rand::random();
+        if let Ok(key) = SecretKey::from_slice(&bytes) {
+            return key;
+        }
+    }
+}
+
+pub async fn read_message(stream: &mut TcpStream, transport: &mut NoiseTransport) -> Result<Vec<u8>, NoiseError> {
+    let mut header = [0u8; LENGTH_HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    let len = transport.decrypt_length_header(&header)?;
+
+    let mut body = vec![0u8; len as usize + MAC_LEN];
+    stream.read_exact(&mut body).await?;
+    transport.decrypt_message_body(&body)
+}
+
+pub async fn write_message(stream: &mut TcpStream, transport: &mut NoiseTransport, msg: &[u8]) -> Result<(), NoiseError> {
+    let packet = transport.encrypt_message(msg)?;
+    stream.write_all(&packet).await?;
+    Ok(())
+}
+
+pub async fn connect_outbound(stream: &mut TcpStream, node_secret: SecretKey, remote_node_id: PublicKey) -> Result<NoiseTransport, NoiseError> {
+    let mut handshake = NoiseHandshake::new_outbound(node_secret, remote_node_id, random_ephemeral_key());
+
+    stream.write_all(&handshake.get_act_one()).await?;
+
+    let mut act_two = [0u8; ACT_TWO_LEN];
+    stream.read_exact(&mut act_two).await?;
+    let (act_three, transport) = handshake.process_act_two(&act_two)?;
+
+    stream.write_all(&act_three).await?;
+    Ok(transport)
+}
+
+pub async fn accept_inbound(stream: &mut TcpStream, node_secret: SecretKey) -> Result<NoiseTransport, NoiseError> {
+    let mut handshake = NoiseHandshake::new_inbound(node_secret, random_ephemeral_key());
+
+    let mut act_one = [0u8; ACT_ONE_LEN];
+    stream.read_exact(&mut act_one).await?;
+    let act_two = handshake.process_act_one(&act_one)?;
+    stream.write_all(&act_two).await?;
+
+    let mut act_three = [0u8; ACT_THREE_LEN];
+    stream.read_exact(&mut act_three).await?;
+    handshake.process_act_three(&act_three)
+}
+
+pub async fn run() {
+    let alice = NodeKeysManager::new([0x01; 32]);
+    let bob = NodeKeysManager::new([0x02; 32]);
+
+    println!("Alice node id: {}", alice.node_id);
+    println!("Bob node id:   {}", bob.node_id);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    println!("Bob listening on {}", addr);
+
+    // Bob accepts one connection and echoes back how many messages he decrypted
+    let bob_secret = bob.node_secret;
+    let bob_task = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut transport = accept_inbound(&mut stream, bob_secret).await.unwrap();
+        println!("Bob: handshake complete, peer is {}", transport.remote_node_id);
+
+        for i in 0..NUM_MESSAGES {
+            let msg = read_message(&mut stream, &mut transport).await.unwrap();
+            if i == 0 {
+                println!("Bob: first message: {}", String::from_utf8_lossy(&msg));
+            }
+        }
+
+        let reply = format!("received {} messages", NUM_MESSAGES);
+        write_message(&mut stream, &mut transport, reply.as_bytes()).await.unwrap();
+    });
+
+    // Alice knows Bob's node id in advance, as Noise_XK requires
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut transport = connect_outbound(&mut stream, alice.node_secret, bob.node_id).await.unwrap();
+    println!("Alice: handshake complete, sending key {}", hex_str(&transport.sk));
+
+    for i in 0..NUM_MESSAGES {
+        let sending_key = transport.sk;
+        write_message(&mut stream, &mut transport, b"hello").await.unwrap();
+        if transport.sk != sending_key {
+            println!("Alice: rotated sending key after message {}: {}", i, hex_str(&transport.sk));
+        }
+    }
+
+    let reply = read_message(&mut stream, &mut transport).await.unwrap();
+    println!("Alice: Bob replied: {}", String::from_utf8_lossy(&reply));
+
+    bob_task.await.unwrap();
+}
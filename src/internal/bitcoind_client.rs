@@ -12,14 +12,26 @@ use bitcoin::blockdata::transaction::Transaction;
 use std::sync::Arc;
 use bitcoin::consensus::{encode};
 use crate::internal::convert::{
-    ListUnspentResponse, NewAddress, SignedTx, BlockchainInfo, AddressPubkey, MempoolInfo
+    ListUnspentResponse, NewAddress, SignedTx, BlockchainInfo, AddressPubkey, MempoolInfo, TxOutStatus, WalletTransaction,
+    RawTx, FundedTx, FeeResponse, MempoolMinFee
 };
-use lightning::chain::chaininterface::{BroadcasterInterface};
+use crate::internal::scid::{get_short_channel_id, ScidError};
+use crate::internal::standardness::ChainTip;
+use crate::internal::chain::{ChainError, ChainResult, ChainSource};
+use bitcoin::{Block, OutPoint, TxOut};
+use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub mod test;
 
 #[derive(Clone)]
 pub struct BitcoindClient {
     pub bitcoind_rpc_client: Arc<RpcClient>,
     pub handle: tokio::runtime::Handle,
+    // the last feerate fetched for each target, and when
+    fee_cache: Arc<Mutex<HashMap<ConfirmationTarget, (u32, Instant)>>>,
 }
 
 impl BlockSource for BitcoindClient {
@@ -48,7 +60,36 @@ impl BlockSource for BitcoindClient {
 }
 
 /// The minimum feerate we are allowed to send, as specify by LDK.
-const MIN_FEERATE: u32 = 253;
+pub const MIN_FEERATE: u32 = 253;
+
+/// How long a cached estimate is used before asking bitcoind again.
+pub(crate) const FEE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+const CONFIRMATION_TARGETS: [ConfirmationTarget; 8] = [
+    ConfirmationTarget::MaximumFeeEstimate,
+    ConfirmationTarget::UrgentOnChainSweep,
+    ConfirmationTarget::MinAllowedAnchorChannelRemoteFee,
+    ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee,
+    ConfirmationTarget::AnchorChannelFee,
+    ConfirmationTarget::NonAnchorChannelFee,
+    ConfirmationTarget::ChannelCloseMinimum,
+    ConfirmationTarget::OutputSpendingFee,
+];
+
+// the estimatesmartfee arguments for each target. The minimums we accept
+// from peers are whatever the mempool currently takes
+fn estimate_smart_fee_args(confirmation_target: ConfirmationTarget) -> Option<(u16, &'static str)> {
+    match confirmation_target {
+        ConfirmationTarget::MaximumFeeEstimate => Some((1, "CONSERVATIVE")),
+        ConfirmationTarget::UrgentOnChainSweep => Some((6, "CONSERVATIVE")),
+        ConfirmationTarget::MinAllowedAnchorChannelRemoteFee => None,
+        ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee => None,
+        ConfirmationTarget::AnchorChannelFee => Some((1008, "ECONOMICAL")),
+        ConfirmationTarget::NonAnchorChannelFee => Some((6, "ECONOMICAL")),
+        ConfirmationTarget::ChannelCloseMinimum => Some((144, "ECONOMICAL")),
+        ConfirmationTarget::OutputSpendingFee => Some((12, "ECONOMICAL")),
+    }
+}
 
 impl BitcoindClient {
     pub async fn new(
@@ -69,7 +110,9 @@ impl BitcoindClient {
         let client =Self {
             bitcoind_rpc_client: Arc::new(bitcoind_rpc_client),
             handle: tokio::runtime::Handle::current(),
+            fee_cache: Arc::new(Mutex::new(HashMap::new())),
         };
+        client.update_fee_estimates().await;
 
         Ok(client)
     }
@@ -111,12 +154,168 @@ impl BitcoindClient {
         signed_tx
     }
 
+    /// Like `sign_raw_transaction_with_wallet`, for inputs spending outputs the
+    /// wallet hasn't seen confirmed, such as a parent that is still unconfirmed.
+    pub async fn sign_raw_transaction_with_wallet_prevouts(&self, tx_hex: String, prevouts: &[(OutPoint, TxOut)]) -> SignedTx {
+        let prevtxs: Vec<serde_json::Value> = prevouts
+            .iter()
+            .map(|(outpoint, output)| serde_json::json!({
+                "txid": outpoint.txid.to_string(),
+                "vout": outpoint.vout,
+                "scriptPubKey": output.script_pubkey.to_hex_string(),
+                "amount": output.value.to_btc(),
+            }))
+            .collect();
+        self.bitcoind_rpc_client
+            .call_method("signrawtransactionwithwallet", &[serde_json::json!(tx_hex), serde_json::json!(prevtxs)])
+            .await
+            .unwrap()
+    }
+
+    /// An unsigned transaction paying `amount_sat` to `address`, with no inputs yet.
+    pub async fn create_raw_transaction(&self, address: &Address, amount_sat: u64) -> RawTx {
+        let outputs = serde_json::json!([{ address.to_string(): bitcoin::Amount::from_sat(amount_sat).to_btc() }]);
+        self.bitcoind_rpc_client
+            .call_method("createrawtransaction", &[serde_json::json!([]), outputs])
+            .await
+            .unwrap()
+    }
+
+    /// Lets the wallet pick inputs and add change to `tx_hex`.
+    pub async fn fund_raw_transaction(&self, tx_hex: String) -> FundedTx {
+        // only segwit inputs, so the txid can't change after signing
+        let options = serde_json::json!({ "change_type": "bech32" });
+        self.bitcoind_rpc_client
+            .call_method("fundrawtransaction", &[serde_json::json!(tx_hex), options])
+            .await
+            .unwrap()
+    }
+
+    /// Submits `txs`, parents first, as one package. Unlike
+    /// `broadcast_transactions` this waits for bitcoind's answer.
+    pub async fn submit_package(&self, txs: &[&Transaction]) -> std::io::Result<serde_json::Value> {
+        let txn = txs.iter().copied().map(encode::serialize_hex).collect::<Vec<_>>();
+        self.bitcoind_rpc_client
+            .call_method::<serde_json::Value>("submitpackage", &[serde_json::json!(txn)])
+            .await
+    }
+
     pub async fn get_raw_mempool(&self) -> MempoolInfo {
     self.bitcoind_rpc_client
     .call_method("getrawmempool", &[])
     .await
     .unwrap()
     }
+
+    /// The feerate for `confirmation_target` in sat/kW, from the cache if it's
+    /// recent enough. Never below the mempool's minimum or `MIN_FEERATE`.
+    pub async fn get_feerate(&self, confirmation_target: ConfirmationTarget) -> u32 {
+        let cached = self.fee_cache.lock().unwrap().get(&confirmation_target).copied();
+        if let Some((feerate, fetched_at)) = cached && fetched_at.elapsed() < FEE_CACHE_TTL {
+            return feerate;
+        }
+
+        let mempool_min_feerate = self.get_mempool_min_feerate().await;
+        let feerate = self.fetch_feerate(confirmation_target, mempool_min_feerate).await;
+        self.fee_cache.lock().unwrap().insert(confirmation_target, (feerate, Instant::now()));
+        feerate
+    }
+
+    /// Refreshes every target. LDK's `FeeEstimator` can't wait on RPCs, so it
+    /// only ever reads what this last fetched.
+    pub async fn update_fee_estimates(&self) {
+        let mempool_min_feerate = self.get_mempool_min_feerate().await;
+        for confirmation_target in CONFIRMATION_TARGETS {
+            let feerate = self.fetch_feerate(confirmation_target, mempool_min_feerate).await;
+            self.fee_cache.lock().unwrap().insert(confirmation_target, (feerate, Instant::now()));
+        }
+    }
+
+    async fn get_mempool_min_feerate(&self) -> u32 {
+        self.bitcoind_rpc_client
+            .call_method::<MempoolMinFee>("getmempoolinfo", &[])
+            .await
+            .map(|mempool| mempool.feerate_sat_per_kw)
+            .unwrap_or(MIN_FEERATE)
+    }
+
+    // falls back to the mempool minimum when bitcoind has no estimate yet
+    async fn fetch_feerate(&self, confirmation_target: ConfirmationTarget, mempool_min_feerate: u32) -> u32 {
+        let estimate = match estimate_smart_fee_args(confirmation_target) {
+            Some((blocks, mode)) => self.bitcoind_rpc_client
+                .call_method::<FeeResponse>("estimatesmartfee", &[serde_json::json!(blocks), serde_json::json!(mode)])
+                .await
+                .ok()
+                .and_then(|fee| fee.feerate_sat_per_kw),
+            None => None,
+        };
+        estimate.unwrap_or(mempool_min_feerate).max(mempool_min_feerate).max(MIN_FEERATE)
+    }
+
+    /// The height and median time past the next block's lock times are checked against.
+    pub async fn get_chain_tip(&self) -> ChainTip {
+        let info = self.bitcoind_rpc_client
+            .call_method::<BlockchainInfo>("getblockchaininfo", &[])
+            .await
+            .unwrap();
+
+        ChainTip { height: info.latest_height as u32, median_time_past: info.median_time }
+    }
+
+    /// The short_channel_id of a funding output. Our funding transactions
+    /// spend wallet coins, so the wallet can tell us which block they're in.
+    pub async fn get_funding_scid(&self, funding_outpoint: &OutPoint) -> Result<u64, ScidError> {
+        let wallet_tx = self.bitcoind_rpc_client
+            .call_method::<WalletTransaction>("gettransaction", &[serde_json::json!(funding_outpoint.txid.to_string())])
+            .await
+            .map_err(|_| ScidError::UnknownTransaction(funding_outpoint.txid))?;
+        let block_hash = wallet_tx.block_hash.ok_or(ScidError::Unconfirmed(funding_outpoint.txid))?;
+
+        get_short_channel_id(self, &block_hash, funding_outpoint).await
+    }
+}
+
+impl ChainSource for BitcoindClient {
+    fn get_block_hash(&self, height: u32) -> ChainResult<'_, BlockHash> {
+        Box::pin(async move {
+            self.bitcoind_rpc_client
+                .call_method::<BlockHash>("getblockhash", &[serde_json::json!(height)])
+                .await
+                .map_err(|_| ChainError::BlockNotFound(height))
+        })
+    }
+
+    fn get_block<'a>(&'a self, block_hash: &'a BlockHash) -> ChainResult<'a, Block> {
+        Box::pin(async move {
+            let args = [serde_json::json!(block_hash.to_string()), serde_json::json!(0)];
+            self.bitcoind_rpc_client
+                .call_method::<Block>("getblock", &args)
+                .await
+                .map_err(|e| ChainError::Rpc(e.kind()))
+        })
+    }
+
+    fn is_unspent<'a>(&'a self, outpoint: &'a OutPoint) -> ChainResult<'a, bool> {
+        Box::pin(async move {
+            let args = [serde_json::json!(outpoint.txid.to_string()), serde_json::json!(outpoint.vout)];
+            let status = self.bitcoind_rpc_client
+                .call_method::<TxOutStatus>("gettxout", &args)
+                .await
+                .map_err(|e| ChainError::Rpc(e.kind()))?;
+            Ok(status.unspent)
+        })
+    }
+}
+
+impl FeeEstimator for BitcoindClient {
+    fn get_est_sat_per_1000_weight(&self, confirmation_target: ConfirmationTarget) -> u32 {
+        self.fee_cache
+            .lock()
+            .unwrap()
+            .get(&confirmation_target)
+            .map(|(feerate, _)| *feerate)
+            .unwrap_or(MIN_FEERATE)
+    }
 }
 
 impl BroadcasterInterface for BitcoindClient {
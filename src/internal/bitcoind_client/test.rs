@@ -0,0 +1,166 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::convert::{btc_per_kvb_to_sat_per_kw, FeeResponse, MempoolMinFee};
+use internal::tx_utils::fee_for_weight;
+use internal::bitcoind_client::{BitcoindClient, FEE_CACHE_TTL, MIN_FEERATE};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning_block_sync::http::{HttpEndpoint, JsonResponse};
+use lightning_block_sync::rpc::RpcClient;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+#[test]
+fn test_fee_estimate_responses() {
+    // 1 sat/vB is 250 sat/kW
+    assert_eq!(btc_per_kvb_to_sat_per_kw(0.00001), 250);
+    assert_eq!(btc_per_kvb_to_sat_per_kw(0.0002), 5_000);
+
+    let estimate: FeeResponse = JsonResponse(serde_json::json!({ "feerate": 0.0001, "blocks": 6 })).try_into().unwrap();
+    assert_eq!(estimate.feerate_sat_per_kw, Some(2_500));
+
+    // a fresh regtest chain has nothing to estimate from
+    let no_data: FeeResponse = JsonResponse(serde_json::json!({ "errors": ["Insufficient data or no feerate found"], "blocks": 0 }))
+        .try_into()
+        .unwrap();
+    assert_eq!(no_data.feerate_sat_per_kw, None);
+
+    let mempool: MempoolMinFee = JsonResponse(serde_json::json!({ "size": 0, "mempoolminfee": 0.00001 })).try_into().unwrap();
+    assert_eq!(mempool.feerate_sat_per_kw, 250);
+    assert!(TryInto::<MempoolMinFee>::try_into(JsonResponse(serde_json::json!({ "size": 0 }))).is_err());
+
+    assert_eq!(fee_for_weight(253, bitcoin::Weight::from_wu(724)), 183);
+}
+
+// A bitcoind that only answers the fee RPCs, with whatever `responses` holds
+// for each method, and counts the calls it gets.
+pub struct FakeBitcoind {
+    pub responses: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    pub calls: Arc<Mutex<HashMap<String, usize>>>,
+    pub port: u16,
+}
+
+impl FakeBitcoind {
+    pub async fn start() -> FakeBitcoind {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let responses: Arc<Mutex<HashMap<String, serde_json::Value>>> = Arc::new(Mutex::new(HashMap::new()));
+        let calls: Arc<Mutex<HashMap<String, usize>>> = Arc::new(Mutex::new(HashMap::new()));
+
+        let (server_responses, server_calls) = (Arc::clone(&responses), Arc::clone(&calls));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (responses, calls) = (Arc::clone(&server_responses), Arc::clone(&server_calls));
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    // the client keeps the connection open between requests
+                    loop {
+                        let mut content_length = 0;
+                        loop {
+                            let mut line = String::new();
+                            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                                content_length = length.trim().parse().unwrap();
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                        }
+                        let mut body = vec![0; content_length];
+                        stream.read_exact(&mut body).await.unwrap();
+                        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+                        let method = request["method"].as_str().unwrap().to_string();
+                        *calls.lock().unwrap().entry(method.clone()).or_insert(0) += 1;
+                        let result = responses.lock().unwrap().get(&method).cloned().unwrap_or(serde_json::Value::Null);
+
+                        let response = serde_json::json!({ "result": result, "error": null, "id": request["id"] }).to_string();
+                        let http = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                                           response.len(), response);
+                        stream.get_mut().write_all(http.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        FakeBitcoind { responses, calls, port }
+    }
+
+    pub fn respond(&self, method: &str, result: serde_json::Value) {
+        self.responses.lock().unwrap().insert(method.to_string(), result);
+    }
+
+    pub fn calls(&self, method: &str) -> usize {
+        self.calls.lock().unwrap().get(method).copied().unwrap_or(0)
+    }
+
+    // skips `BitcoindClient::new`, which would fill the cache up front
+    pub fn client(&self) -> BitcoindClient {
+        let endpoint = HttpEndpoint::for_host("127.0.0.1".to_string()).with_port(self.port);
+        BitcoindClient {
+            bitcoind_rpc_client: Arc::new(RpcClient::new("dXNlcjpwYXNz", endpoint).unwrap()),
+            handle: tokio::runtime::Handle::current(),
+            fee_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_feerate() {
+    let bitcoind = FakeBitcoind::start().await;
+    let client = bitcoind.client();
+
+    // 0.5 sat/vB from estimatesmartfee and 0.1 from the mempool: both under the floor
+    bitcoind.respond("getmempoolinfo", serde_json::json!({ "size": 0, "mempoolminfee": 0.0000004 }));
+    bitcoind.respond("estimatesmartfee", serde_json::json!({ "feerate": 0.000005, "blocks": 6 }));
+    assert_eq!(client.get_feerate(ConfirmationTarget::NonAnchorChannelFee).await, MIN_FEERATE);
+
+    // an estimate over the floor and the mempool minimum is used as it is
+    bitcoind.respond("estimatesmartfee", serde_json::json!({ "feerate": 0.0002, "blocks": 6 }));
+    assert_eq!(client.get_feerate(ConfirmationTarget::UrgentOnChainSweep).await, 5_000);
+
+    // no estimate yet falls back to what the mempool takes
+    bitcoind.respond("getmempoolinfo", serde_json::json!({ "size": 0, "mempoolminfee": 0.00002 }));
+    bitcoind.respond("estimatesmartfee", serde_json::json!({ "errors": ["Insufficient data or no feerate found"], "blocks": 0 }));
+    assert_eq!(client.get_feerate(ConfirmationTarget::ChannelCloseMinimum).await, 500);
+
+    // the minimums we accept from peers never ask for an estimate
+    let estimates = bitcoind.calls("estimatesmartfee");
+    assert_eq!(client.get_feerate(ConfirmationTarget::MinAllowedNonAnchorChannelRemoteFee).await, 500);
+    assert_eq!(bitcoind.calls("estimatesmartfee"), estimates);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fee_cache() {
+    let bitcoind = FakeBitcoind::start().await;
+    let client = bitcoind.client();
+    bitcoind.respond("getmempoolinfo", serde_json::json!({ "size": 0, "mempoolminfee": 0.00001 }));
+    bitcoind.respond("estimatesmartfee", serde_json::json!({ "feerate": 0.0001, "blocks": 6 }));
+
+    // LDK reads the floor until anything has been fetched
+    assert_eq!(client.get_est_sat_per_1000_weight(ConfirmationTarget::OutputSpendingFee), MIN_FEERATE);
+
+    assert_eq!(client.get_feerate(ConfirmationTarget::OutputSpendingFee).await, 2_500);
+    assert_eq!(client.get_est_sat_per_1000_weight(ConfirmationTarget::OutputSpendingFee), 2_500);
+
+    // a fresh estimate comes from the cache, even once bitcoind's changes
+    bitcoind.respond("estimatesmartfee", serde_json::json!({ "feerate": 0.0002, "blocks": 6 }));
+    assert_eq!(client.get_feerate(ConfirmationTarget::OutputSpendingFee).await, 2_500);
+    assert_eq!(bitcoind.calls("estimatesmartfee"), 1);
+
+    // a stale one is fetched again
+    let stale = Instant::now().checked_sub(FEE_CACHE_TTL).unwrap();
+    client.fee_cache.lock().unwrap().insert(ConfirmationTarget::OutputSpendingFee, (2_500, stale));
+    assert_eq!(client.get_feerate(ConfirmationTarget::OutputSpendingFee).await, 5_000);
+    assert_eq!(bitcoind.calls("estimatesmartfee"), 2);
+
+    // updating refreshes every target at once
+    client.update_fee_estimates().await;
+    assert_eq!(client.get_est_sat_per_1000_weight(ConfirmationTarget::AnchorChannelFee), 5_000);
+    assert_eq!(client.get_est_sat_per_1000_weight(ConfirmationTarget::MinAllowedAnchorChannelRemoteFee), MIN_FEERATE);
+}
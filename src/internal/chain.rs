@@ -0,0 +1,231 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::absolute::LockTime;
+use bitcoin::block::{Header, Version as BlockVersion};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::hash_types::{BlockHash, TxMerkleNode};
+use bitcoin::hashes::Hash;
+use bitcoin::pow::Work;
+use bitcoin::script::{Builder, ScriptBuf};
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, Block, CompactTarget, Network, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use lightning_block_sync::{AsyncBlockSourceResult, BlockData, BlockHeaderData, BlockSource, BlockSourceError};
+use lightning::chain::chaininterface::BroadcasterInterface;
+use lightning::util::scid_utils::{block_from_scid, tx_index_from_scid, vout_from_scid};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+// regtest's minimum difficulty, so mining a block takes a couple of hashes
+const REGTEST_BITS: u32 = 0x207fffff;
+const BLOCK_INTERVAL_SECS: u32 = 600;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    BlockNotFound(u32),
+    UnknownBlock(BlockHash),
+    TransactionNotFound(u64),
+    OutputNotFound(u64),
+    Rpc(std::io::ErrorKind),
+}
+
+pub type ChainResult<'a, T> = Pin<Box<dyn Future<Output = Result<T, ChainError>> + 'a + Send>>;
+
+/// The blockchain lookups we need to validate gossip and watch channels,
+/// served by bitcoind or by a `MockChain` in tests.
+pub trait ChainSource {
+    fn get_block_hash(&self, height: u32) -> ChainResult<'_, BlockHash>;
+
+    fn get_block<'a>(&'a self, block_hash: &'a BlockHash) -> ChainResult<'a, Block>;
+
+    fn is_unspent<'a>(&'a self, outpoint: &'a OutPoint) -> ChainResult<'a, bool>;
+}
+
+/// Finds the output a short_channel_id points at: block height, transaction
+/// index within the block and output index.
+pub async fn get_output_by_scid<C: ChainSource>(chain: &C, short_channel_id: u64) -> Result<(OutPoint, TxOut), ChainError> {
+    let block_hash = chain.get_block_hash(block_from_scid(short_channel_id)).await?;
+    let block = chain.get_block(&block_hash).await?;
+
+    let tx = block
+        .txdata
+        .get(tx_index_from_scid(short_channel_id) as usize)
+        .ok_or(ChainError::TransactionNotFound(short_channel_id))?;
+    let vout = vout_from_scid(short_channel_id) as u32;
+    let output = tx
+        .output
+        .get(vout as usize)
+        .ok_or(ChainError::OutputNotFound(short_channel_id))?;
+
+    Ok((OutPoint { txid: tx.compute_txid(), vout }, output.clone()))
+}
+
+/// An in-memory regtest chain. Blocks carry real proof of work at regtest
+/// difficulty, so anything that checks headers will accept them.
+pub struct MockChain {
+    blocks: Mutex<Vec<Block>>,
+    // broadcast transactions waiting for the next block
+    mempool: Mutex<Vec<Transaction>>,
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        MockChain::new()
+    }
+}
+
+impl MockChain {
+    pub fn new() -> MockChain {
+        MockChain {
+            blocks: Mutex::new(vec![genesis_block(Network::Regtest)]),
+            mempool: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn tip_height(&self) -> u32 {
+        self.blocks.lock().unwrap().len() as u32 - 1
+    }
+
+    pub fn tip_hash(&self) -> BlockHash {
+        self.blocks.lock().unwrap().last().unwrap().block_hash()
+    }
+
+    pub fn block_at(&self, height: u32) -> Option<Block> {
+        self.blocks.lock().unwrap().get(height as usize).cloned()
+    }
+
+    pub fn mempool(&self) -> Vec<Transaction> {
+        self.mempool.lock().unwrap().clone()
+    }
+
+    /// Mines a block containing the mempool and then `txs` after the coinbase
+    /// and returns its height. Transactions are not checked, this only orders them.
+    pub fn mine_block(&self, txs: Vec<Transaction>) -> u32 {
+        let mut mempool: Vec<Transaction> = self.mempool.lock().unwrap().drain(..).collect();
+        mempool.extend(txs);
+        let txs = mempool;
+
+        let mut blocks = self.blocks.lock().unwrap();
+        let height = blocks.len() as u32;
+        let prev = blocks.last().unwrap().header;
+
+        // BIP 34: the coinbase commits to the height, which also keeps every coinbase txid unique
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(height as i64).push_int(0).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([]) }],
+        };
+
+        // BIP 141: blocks with segwit spends commit to their wtxids in the coinbase
+        let has_witness = txs.iter().any(|tx| tx.input.iter().any(|input| !input.witness.is_empty()));
+
+        let mut txdata = vec![coinbase];
+        txdata.extend(txs);
+
+        if has_witness {
+            let reserved_value = [0u8; 32];
+            txdata[0].input[0].witness = Witness::from_slice(&[reserved_value]);
+            let witness_root = Block { header: prev, txdata: txdata.clone() }.witness_root().unwrap();
+            let commitment = Block::compute_witness_commitment(&witness_root, &reserved_value);
+
+            let mut script = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+            script.extend_from_slice(commitment.as_byte_array());
+            txdata[0].output.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::from_bytes(script) });
+        }
+
+        let mut block = Block {
+            header: Header {
+                version: BlockVersion::TWO,
+                prev_blockhash: prev.block_hash(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: prev.time + BLOCK_INTERVAL_SECS,
+                bits: CompactTarget::from_consensus(REGTEST_BITS),
+                nonce: 0,
+            },
+            txdata,
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+
+        while block.header.validate_pow(block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+
+        blocks.push(block);
+        height
+    }
+
+    pub fn mine_blocks(&self, count: u32) -> u32 {
+        for _ in 0..count {
+            self.mine_block(Vec::new());
+        }
+        self.tip_height()
+    }
+
+    /// The height and index within the block of a confirmed transaction.
+    pub fn find_transaction(&self, txid: &Txid) -> Option<(u32, u32)> {
+        let blocks = self.blocks.lock().unwrap();
+        blocks.iter().enumerate().find_map(|(height, block)| {
+            block
+                .txdata
+                .iter()
+                .position(|tx| tx.compute_txid() == *txid)
+                .map(|index| (height as u32, index as u32))
+        })
+    }
+}
+
+impl ChainSource for MockChain {
+    fn get_block_hash(&self, height: u32) -> ChainResult<'_, BlockHash> {
+        let block_hash = self.block_at(height).map(|block| block.block_hash());
+        Box::pin(async move { block_hash.ok_or(ChainError::BlockNotFound(height)) })
+    }
+
+    fn get_block<'a>(&'a self, block_hash: &'a BlockHash) -> ChainResult<'a, Block> {
+        let block = self.blocks.lock().unwrap().iter().find(|block| block.block_hash() == *block_hash).cloned();
+        Box::pin(async move { block.ok_or(ChainError::UnknownBlock(*block_hash)) })
+    }
+
+    fn is_unspent<'a>(&'a self, outpoint: &'a OutPoint) -> ChainResult<'a, bool> {
+        let blocks = self.blocks.lock().unwrap();
+        let txs = || blocks.iter().flat_map(|block| block.txdata.iter());
+
+        let exists = txs().any(|tx| tx.compute_txid() == outpoint.txid && (outpoint.vout as usize) < tx.output.len());
+        let spent = txs().any(|tx| tx.input.iter().any(|input| input.previous_output == *outpoint));
+        Box::pin(async move { Ok(exists && !spent) })
+    }
+}
+
+// lets the mock stand in for bitcoind wherever LDK's block source is expected
+impl BlockSource for MockChain {
+    fn get_header<'a>(&'a self, header_hash: &'a BlockHash, height_hint: Option<u32>) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+        let blocks = self.blocks.lock().unwrap();
+        let height = blocks.iter().position(|block| block.block_hash() == *header_hash);
+        let header = height.map(|height| BlockHeaderData {
+            header: blocks[height].header,
+            height: height as u32,
+            chainwork: blocks[..=height].iter().fold(Work::from_be_bytes([0; 32]), |work, block| work + block.header.work()),
+        });
+        Box::pin(async move { header.ok_or_else(|| BlockSourceError::persistent("unknown block")) })
+    }
+
+    fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, BlockData> {
+        let block = self.blocks.lock().unwrap().iter().find(|block| block.block_hash() == *header_hash).cloned();
+        Box::pin(async move { block.map(BlockData::FullBlock).ok_or_else(|| BlockSourceError::persistent("unknown block")) })
+    }
+
+    fn get_best_block(&self) -> AsyncBlockSourceResult<'_, (BlockHash, Option<u32>)> {
+        let best = (self.tip_hash(), Some(self.tip_height()));
+        Box::pin(async move { Ok(best) })
+    }
+}
+
+impl BroadcasterInterface for MockChain {
+    fn broadcast_transactions(&self, txs: &[&Transaction]) {
+        self.mempool.lock().unwrap().extend(txs.iter().map(|tx| (*tx).clone()));
+    }
+}
@@ -0,0 +1,998 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises;
+use crate::exercises_appendix;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::PublicKey as BitcoinPublicKey;
+use bitcoin::script::ScriptBuf;
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+use exercises::solutions::{
+    build_commitment_transaction, build_htlc_timeout_transaction, two_of_two_multisig_witness_script,
+};
+use exercises_appendix::exercises::ChannelKeysManager;
+use internal::key_utils::{add_privkeys, add_pubkeys, hash_pubkeys, pubkey_from_secret};
+use internal::script_utils::{build_htlc_offerer_witness_script, build_htlc_receiver_witness_script};
+use internal::sign_utils::{generate_p2wsh_signature, sign_p2wsh_input, verify_p2wsh_signature, SpendPath, WitnessSigner};
+use internal::tx_utils::build_output;
+use internal::wire::{CommitmentSigned, OnionPacket, MAX_FAILURE_REASON_LEN, RevokeAndAck, UpdateAddHtlc, UpdateFailHtlc, UpdateFulfillHtlc};
+use exercises::solutions::{generate_revocation_privkey, generate_revocation_pubkey, to_local};
+use std::collections::BTreeMap;
+
+pub mod test;
+
+/// Per-commitment secrets are derived by counting down from this index, as LDK does.
+pub const INITIAL_COMMITMENT_NUMBER: u64 = (1 << 48) - 1;
+
+/// BOLT 3 weights used to compute the commitment and HTLC transaction fees.
+const COMMITMENT_TX_BASE_WEIGHT: u64 = 724;
+const COMMITMENT_TX_WEIGHT_PER_HTLC: u64 = 172;
+const HTLC_TIMEOUT_TX_WEIGHT: u64 = 663;
+const HTLC_SUCCESS_TX_WEIGHT: u64 = 703;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    InsufficientBalance,
+    UnknownHtlc(u64),
+    UnexpectedHtlcId(u64),
+    InvalidPreimage,
+    InvalidSignature,
+    InvalidRevocation,
+    MissingPerCommitmentPoint(u64),
+    NothingToRevoke,
+    UnknownCommitment(u64),
+    NotRevoked(u64),
+    JusticeBelowDust,
+    FailureReasonTooLong(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPublicKeys {
+    pub funding_pubkey: PublicKey,
+    pub revocation_basepoint: PublicKey,
+    pub payment_basepoint: PublicKey,
+    pub delayed_payment_basepoint: PublicKey,
+    pub htlc_basepoint: PublicKey,
+}
+
+impl ChannelPublicKeys {
+    pub fn new(funding_key: &SecretKey, keys: &ChannelKeysManager) -> ChannelPublicKeys {
+        ChannelPublicKeys {
+            funding_pubkey: pubkey_from_secret(*funding_key),
+            revocation_basepoint: pubkey_from_secret(keys.revocation_base_key),
+            payment_basepoint: pubkey_from_secret(keys.payment_key),
+            delayed_payment_basepoint: pubkey_from_secret(keys.delayed_payment_base_key),
+            htlc_basepoint: pubkey_from_secret(keys.htlc_base_key),
+        }
+    }
+}
+
+/// The values both peers agree on when the channel is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelParameters {
+    pub is_outbound: bool,
+    pub funding_outpoint: OutPoint,
+    pub channel_value_satoshis: u64,
+    pub push_msat: u64,
+    pub feerate_per_kw: u32,
+    pub dust_limit_satoshis: u64,
+    // the delay we impose on the counterparty's to_local output
+    pub holder_selected_contest_delay: u16,
+    // the delay the counterparty imposes on our to_local output
+    pub counterparty_selected_contest_delay: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtlcDirection {
+    Offered,
+    Received,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HtlcResolution {
+    Fulfilled([u8; 32]),
+    Failed,
+}
+
+/// Tracks which commitment transactions an update has made it into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpdateStatus {
+    pub in_holder_commitment: bool,
+    pub in_counterparty_commitment: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Htlc {
+    pub htlc_id: u64,
+    pub direction: HtlcDirection,
+    pub amount_msat: u64,
+    pub payment_hash: [u8; 32],
+    pub cltv_expiry: u32,
+    pub onion_routing_packet: OnionPacket,
+    pub add: UpdateStatus,
+    pub removal: Option<(HtlcResolution, UpdateStatus)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentKeys {
+    pub per_commitment_point: PublicKey,
+    pub revocation_pubkey: PublicKey,
+    pub broadcaster_delayed_pubkey: PublicKey,
+    pub broadcaster_htlc_pubkey: PublicKey,
+    pub countersignatory_htlc_pubkey: PublicKey,
+    pub countersignatory_payment_pubkey: PublicKey,
+    pub to_self_delay: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtlcOutput {
+    pub htlc_id: u64,
+    // offered from the point of view of whoever broadcasts the commitment
+    pub offered: bool,
+    pub amount_msat: u64,
+    pub payment_hash: [u8; 32],
+    pub cltv_expiry: u32,
+    pub output_index: u32,
+    pub witness_script: ScriptBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitmentTransaction {
+    pub commitment_number: u64,
+    pub keys: CommitmentKeys,
+    pub to_broadcaster_value_sat: u64,
+    pub to_countersignatory_value_sat: u64,
+    pub htlcs: Vec<HtlcOutput>,
+    pub tx: Transaction,
+}
+
+/// One side of a two-party channel. Each peer holds its own `Channel` and the two
+/// stay in sync by exchanging the update, `commitment_signed` and
+/// `revoke_and_ack` messages produced here.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub channel_id: [u8; 32],
+    pub params: ChannelParameters,
+    funding_key: SecretKey,
+    keys: ChannelKeysManager,
+    pub holder_pubkeys: ChannelPublicKeys,
+    pub counterparty_pubkeys: ChannelPublicKeys,
+    // the next commitment numbers to be signed for each side
+    pub next_holder_commitment_number: u64,
+    pub next_counterparty_commitment_number: u64,
+    counterparty_per_commitment_points: BTreeMap<u64, PublicKey>,
+    pub counterparty_per_commitment_secrets: BTreeMap<u64, [u8; 32]>,
+    // balances with every fully resolved HTLC already applied
+    settled_holder_balance_msat: u64,
+    settled_counterparty_balance_msat: u64,
+    pub htlcs: Vec<Htlc>,
+    next_holder_htlc_id: u64,
+    next_counterparty_htlc_id: u64,
+    holder_commitment: Option<CommitmentTransaction>,
+    holder_commitment_signature: Option<Signature>,
+    pub holder_htlc_signatures: Vec<Signature>,
+    pub counterparty_commitments: BTreeMap<u64, CommitmentTransaction>,
+}
+
+pub fn derive_public_key(basepoint: &PublicKey, per_commitment_point: &PublicKey) -> PublicKey {
+    let tweak = hash_pubkeys(*per_commitment_point, *basepoint);
+    let tweak_point = pubkey_from_secret(SecretKey::from_slice(&tweak).unwrap());
+    add_pubkeys(*basepoint, tweak_point)
+}
+
+pub fn derive_private_key(base_secret: &SecretKey, per_commitment_point: &PublicKey) -> SecretKey {
+    let basepoint = pubkey_from_secret(*base_secret);
+    let tweak = hash_pubkeys(*per_commitment_point, basepoint);
+    add_privkeys(*base_secret, SecretKey::from_slice(&tweak).unwrap())
+}
+
+/// BOLT 2: the channel id is the funding txid with its last two bytes XORed with the output index.
+pub fn channel_id_from_funding_outpoint(funding_outpoint: &OutPoint) -> [u8; 32] {
+    let mut channel_id = funding_outpoint.txid.to_byte_array();
+    channel_id[30] ^= (funding_outpoint.vout >> 8) as u8;
+    channel_id[31] ^= (funding_outpoint.vout & 0xff) as u8;
+    channel_id
+}
+
+pub fn commitment_tx_fee_sat(feerate_per_kw: u32, num_htlcs: usize) -> u64 {
+    feerate_per_kw as u64 * (COMMITMENT_TX_BASE_WEIGHT + COMMITMENT_TX_WEIGHT_PER_HTLC * num_htlcs as u64) / 1000
+}
+
+pub fn htlc_tx_fee_sat(feerate_per_kw: u32, offered: bool) -> u64 {
+    let weight = if offered { HTLC_TIMEOUT_TX_WEIGHT } else { HTLC_SUCCESS_TX_WEIGHT };
+    feerate_per_kw as u64 * weight / 1000
+}
+
+// one full update round initiated by `from`, for peers held in the same process
+pub(crate) fn commitment_round(from: &mut Channel, to: &mut Channel) -> Result<(), ChannelError> {
+    let commitment_signed = from.sign_commitment()?;
+    to.receive_commitment_signed(&commitment_signed)?;
+    let revoke_and_ack = to.revoke_and_ack()?;
+    from.receive_revoke_and_ack(&revoke_and_ack)?;
+
+    let commitment_signed = to.sign_commitment()?;
+    from.receive_commitment_signed(&commitment_signed)?;
+    let revoke_and_ack = from.revoke_and_ack()?;
+    to.receive_revoke_and_ack(&revoke_and_ack)?;
+    Ok(())
+}
+
+impl Channel {
+    pub fn new(
+        params: ChannelParameters,
+        funding_key: SecretKey,
+        keys: ChannelKeysManager,
+        counterparty_pubkeys: ChannelPublicKeys,
+        counterparty_first_per_commitment_point: PublicKey,
+    ) -> Channel {
+        let holder_pubkeys = ChannelPublicKeys::new(&funding_key, &keys);
+
+        let opener_balance_msat = params.channel_value_satoshis * 1000 - params.push_msat;
+        let (holder_balance_msat, counterparty_balance_msat) = if params.is_outbound {
+            (opener_balance_msat, params.push_msat)
+        } else {
+            (params.push_msat, opener_balance_msat)
+        };
+
+        let mut counterparty_per_commitment_points = BTreeMap::new();
+        counterparty_per_commitment_points.insert(0, counterparty_first_per_commitment_point);
+
+        Channel {
+            channel_id: channel_id_from_funding_outpoint(&params.funding_outpoint),
+            params,
+            funding_key,
+            keys,
+            holder_pubkeys,
+            counterparty_pubkeys,
+            next_holder_commitment_number: 0,
+            next_counterparty_commitment_number: 0,
+            counterparty_per_commitment_points,
+            counterparty_per_commitment_secrets: BTreeMap::new(),
+            settled_holder_balance_msat: holder_balance_msat,
+            settled_counterparty_balance_msat: counterparty_balance_msat,
+            htlcs: Vec::new(),
+            next_holder_htlc_id: 0,
+            next_counterparty_htlc_id: 0,
+            holder_commitment: None,
+            holder_commitment_signature: None,
+            holder_htlc_signatures: Vec::new(),
+            counterparty_commitments: BTreeMap::new(),
+        }
+    }
+
+    pub fn funding_witness_script(&self) -> ScriptBuf {
+        // the opener's key always comes first, matching `build_funding_transaction`
+        let (opener, accepter) = self.opener_and_accepter(
+            &self.holder_pubkeys.funding_pubkey,
+            &self.counterparty_pubkeys.funding_pubkey,
+        );
+        two_of_two_multisig_witness_script(&BitcoinPublicKey::new(*opener), &BitcoinPublicKey::new(*accepter))
+    }
+
+    pub fn holder_per_commitment_secret(&self, commitment_number: u64) -> [u8; 32] {
+        self.keys.build_commitment_secret(INITIAL_COMMITMENT_NUMBER - commitment_number)
+    }
+
+    pub fn holder_per_commitment_point(&self, commitment_number: u64) -> PublicKey {
+        let secret = self.holder_per_commitment_secret(commitment_number);
+        pubkey_from_secret(SecretKey::from_slice(&secret).unwrap())
+    }
+
+    /// The point for our next commitment, sent in `channel_ready` and `revoke_and_ack`.
+    pub fn next_per_commitment_point(&self) -> PublicKey {
+        self.holder_per_commitment_point(self.next_holder_commitment_number)
+    }
+
+    pub fn receive_channel_ready(&mut self, next_per_commitment_point: PublicKey) {
+        self.counterparty_per_commitment_points
+            .insert(self.next_counterparty_commitment_number, next_per_commitment_point);
+    }
+
+    pub fn holder_balance_msat(&self) -> u64 {
+        let (holder_balance_msat, _, _) = self.commitment_view(true);
+        holder_balance_msat
+    }
+
+    pub fn counterparty_balance_msat(&self) -> u64 {
+        let (_, counterparty_balance_msat, _) = self.commitment_view(false);
+        counterparty_balance_msat
+    }
+
+    pub fn holder_commitment(&self) -> Option<&CommitmentTransaction> {
+        self.holder_commitment.as_ref()
+    }
+
+    pub fn counterparty_commitment(&self) -> Option<&CommitmentTransaction> {
+        self.counterparty_commitments.values().next_back()
+    }
+
+    //
+    // HTLC updates
+    //
+
+    pub fn add_htlc(
+        &mut self,
+        amount_msat: u64,
+        payment_hash: [u8; 32],
+        cltv_expiry: u32,
+        onion_routing_packet: OnionPacket,
+    ) -> Result<UpdateAddHtlc, ChannelError> {
+        if amount_msat > self.available_balance_msat() {
+            return Err(ChannelError::InsufficientBalance);
+        }
+
+        let htlc_id = self.next_holder_htlc_id;
+        self.next_holder_htlc_id += 1;
+
+        self.htlcs.push(Htlc {
+            htlc_id,
+            direction: HtlcDirection::Offered,
+            amount_msat,
+            payment_hash,
+            cltv_expiry,
+            onion_routing_packet: onion_routing_packet.clone(),
+            add: UpdateStatus::default(),
+            removal: None,
+        });
+
+        Ok(UpdateAddHtlc {
+            channel_id: self.channel_id,
+            htlc_id,
+            amount_msat,
+            payment_hash,
+            cltv_expiry,
+            onion_routing_packet,
+            blinding_point: None,
+            skimmed_fee_msat: None,
+        })
+    }
+
+    pub fn receive_update_add_htlc(&mut self, msg: &UpdateAddHtlc) -> Result<(), ChannelError> {
+        if msg.htlc_id != self.next_counterparty_htlc_id {
+            return Err(ChannelError::UnexpectedHtlcId(msg.htlc_id));
+        }
+        if msg.amount_msat > self.counterparty_available_balance_msat() {
+            return Err(ChannelError::InsufficientBalance);
+        }
+        self.next_counterparty_htlc_id += 1;
+
+        self.htlcs.push(Htlc {
+            htlc_id: msg.htlc_id,
+            direction: HtlcDirection::Received,
+            amount_msat: msg.amount_msat,
+            payment_hash: msg.payment_hash,
+            cltv_expiry: msg.cltv_expiry,
+            onion_routing_packet: msg.onion_routing_packet.clone(),
+            add: UpdateStatus::default(),
+            removal: None,
+        });
+
+        Ok(())
+    }
+
+    pub fn fulfill_htlc(
+        &mut self,
+        htlc_id: u64,
+        payment_preimage: [u8; 32],
+    ) -> Result<UpdateFulfillHtlc, ChannelError> {
+        self.remove_htlc(HtlcDirection::Received, htlc_id, HtlcResolution::Fulfilled(payment_preimage))?;
+
+        Ok(UpdateFulfillHtlc {
+            channel_id: self.channel_id,
+            htlc_id,
+            payment_preimage,
+        })
+    }
+
+    pub fn receive_update_fulfill_htlc(&mut self, msg: &UpdateFulfillHtlc) -> Result<(), ChannelError> {
+        self.remove_htlc(
+            HtlcDirection::Offered,
+            msg.htlc_id,
+            HtlcResolution::Fulfilled(msg.payment_preimage),
+        )
+    }
+
+    pub fn fail_htlc(&mut self, htlc_id: u64, reason: Vec<u8>) -> Result<UpdateFailHtlc, ChannelError> {
+        if reason.len() > MAX_FAILURE_REASON_LEN {
+            return Err(ChannelError::FailureReasonTooLong(reason.len()));
+        }
+        self.remove_htlc(HtlcDirection::Received, htlc_id, HtlcResolution::Failed)?;
+
+        Ok(UpdateFailHtlc {
+            channel_id: self.channel_id,
+            htlc_id,
+            reason,
+        })
+    }
+
+    pub fn receive_update_fail_htlc(&mut self, msg: &UpdateFailHtlc) -> Result<(), ChannelError> {
+        self.remove_htlc(HtlcDirection::Offered, msg.htlc_id, HtlcResolution::Failed)
+    }
+
+    fn remove_htlc(
+        &mut self,
+        direction: HtlcDirection,
+        htlc_id: u64,
+        resolution: HtlcResolution,
+    ) -> Result<(), ChannelError> {
+        let htlc = self
+            .htlcs
+            .iter_mut()
+            .find(|htlc| htlc.direction == direction && htlc.htlc_id == htlc_id && htlc.removal.is_none())
+            .ok_or(ChannelError::UnknownHtlc(htlc_id))?;
+
+        // HTLCs can only be removed once they are irrevocably committed on both sides
+        if !(htlc.add.in_holder_commitment && htlc.add.in_counterparty_commitment) {
+            return Err(ChannelError::UnknownHtlc(htlc_id));
+        }
+
+        if let HtlcResolution::Fulfilled(preimage) = resolution
+            && Sha256::hash(&preimage).to_byte_array() != htlc.payment_hash
+        {
+            return Err(ChannelError::InvalidPreimage);
+        }
+
+        htlc.removal = Some((resolution, UpdateStatus::default()));
+        Ok(())
+    }
+
+    //
+    // Commitment updates
+    //
+
+    /// Signs the counterparty's next commitment, covering every update we sent and
+    /// every update of theirs that is already in our own commitment.
+    pub fn sign_commitment(&mut self) -> Result<CommitmentSigned, ChannelError> {
+        let commitment_number = self.next_counterparty_commitment_number;
+        let per_commitment_point = *self
+            .counterparty_per_commitment_points
+            .get(&commitment_number)
+            .ok_or(ChannelError::MissingPerCommitmentPoint(commitment_number))?;
+
+        let commitment = self.build_commitment(false, commitment_number, per_commitment_point);
+
+        let signature = generate_p2wsh_signature(
+            commitment.tx.clone(),
+            0,
+            &self.funding_witness_script(),
+            self.params.channel_value_satoshis,
+            EcdsaSighashType::All,
+            self.funding_key,
+        );
+
+        let htlc_key = derive_private_key(&self.keys.htlc_base_key, &per_commitment_point);
+        let htlc_signatures = commitment
+            .htlcs
+            .iter()
+            .map(|htlc| {
+                let htlc_tx = self.build_htlc_transaction(&commitment, htlc);
+                generate_p2wsh_signature(
+                    htlc_tx,
+                    0,
+                    &htlc.witness_script,
+                    htlc.amount_msat / 1000,
+                    EcdsaSighashType::All,
+                    htlc_key,
+                )
+            })
+            .collect();
+
+        self.mark_committed(false);
+        self.counterparty_commitments.insert(commitment_number, commitment);
+        self.next_counterparty_commitment_number += 1;
+
+        Ok(CommitmentSigned {
+            channel_id: self.channel_id,
+            signature,
+            htlc_signatures,
+        })
+    }
+
+    pub fn receive_commitment_signed(&mut self, msg: &CommitmentSigned) -> Result<(), ChannelError> {
+        let commitment_number = self.next_holder_commitment_number;
+        let per_commitment_point = self.holder_per_commitment_point(commitment_number);
+
+        let commitment = self.build_commitment(true, commitment_number, per_commitment_point);
+
+        if !verify_p2wsh_signature(
+            &commitment.tx,
+            0,
+            &self.funding_witness_script(),
+            self.params.channel_value_satoshis,
+            EcdsaSighashType::All,
+            &msg.signature,
+            &self.counterparty_pubkeys.funding_pubkey,
+        ) {
+            return Err(ChannelError::InvalidSignature);
+        }
+
+        if msg.htlc_signatures.len() != commitment.htlcs.len() {
+            return Err(ChannelError::InvalidSignature);
+        }
+
+        let counterparty_htlc_pubkey = commitment.keys.countersignatory_htlc_pubkey;
+        for (htlc, signature) in commitment.htlcs.iter().zip(msg.htlc_signatures.iter()) {
+            let htlc_tx = self.build_htlc_transaction(&commitment, htlc);
+            if !verify_p2wsh_signature(
+                &htlc_tx,
+                0,
+                &htlc.witness_script,
+                htlc.amount_msat / 1000,
+                EcdsaSighashType::All,
+                signature,
+                &counterparty_htlc_pubkey,
+            ) {
+                return Err(ChannelError::InvalidSignature);
+            }
+        }
+
+        self.mark_committed(true);
+        self.holder_commitment = Some(commitment);
+        self.holder_commitment_signature = Some(msg.signature);
+        self.holder_htlc_signatures = msg.htlc_signatures.clone();
+        self.next_holder_commitment_number += 1;
+
+        Ok(())
+    }
+
+    /// Revokes our previous commitment now that the counterparty has signed a new one.
+    pub fn revoke_and_ack(&mut self) -> Result<RevokeAndAck, ChannelError> {
+        if self.next_holder_commitment_number < 2 {
+            return Err(ChannelError::NothingToRevoke);
+        }
+
+        let revoked_commitment_number = self.next_holder_commitment_number - 2;
+
+        Ok(RevokeAndAck {
+            channel_id: self.channel_id,
+            per_commitment_secret: self.holder_per_commitment_secret(revoked_commitment_number),
+            next_per_commitment_point: self.next_per_commitment_point(),
+        })
+    }
+
+    pub fn receive_revoke_and_ack(&mut self, msg: &RevokeAndAck) -> Result<(), ChannelError> {
+        if self.next_counterparty_commitment_number < 2 {
+            return Err(ChannelError::NothingToRevoke);
+        }
+
+        let revoked_commitment_number = self.next_counterparty_commitment_number - 2;
+        let expected_point = self
+            .counterparty_per_commitment_points
+            .get(&revoked_commitment_number)
+            .ok_or(ChannelError::MissingPerCommitmentPoint(revoked_commitment_number))?;
+
+        let secret = SecretKey::from_slice(&msg.per_commitment_secret)
+            .map_err(|_| ChannelError::InvalidRevocation)?;
+        if pubkey_from_secret(secret) != *expected_point {
+            return Err(ChannelError::InvalidRevocation);
+        }
+
+        self.counterparty_per_commitment_secrets
+            .insert(revoked_commitment_number, msg.per_commitment_secret);
+        self.counterparty_per_commitment_points
+            .insert(self.next_counterparty_commitment_number, msg.next_per_commitment_point);
+
+        Ok(())
+    }
+
+    /// Our current commitment transaction with both funding signatures in the witness.
+    pub fn signed_holder_commitment_transaction(&self) -> Option<Transaction> {
+        let commitment = self.holder_commitment.as_ref()?;
+        let counterparty_signature = self.holder_commitment_signature?;
+
+        let funding_script = self.funding_witness_script();
+        let signers = [
+            WitnessSigner::Key(self.funding_key),
+            WitnessSigner::Signature(self.counterparty_pubkeys.funding_pubkey, counterparty_signature),
+        ];
+
+        let mut signed_tx = commitment.tx.clone();
+        sign_p2wsh_input(&mut signed_tx, 0, &funding_script, self.params.channel_value_satoshis, &SpendPath::Multisig, &signers)
+            .ok()?;
+
+        Some(signed_tx)
+    }
+
+    //
+    // Commitment construction
+    //
+
+    fn opener_and_accepter<T>(&self, holder: T, counterparty: T) -> (T, T) {
+        if self.params.is_outbound {
+            (holder, counterparty)
+        } else {
+            (counterparty, holder)
+        }
+    }
+
+    fn is_included(status: &UpdateStatus, sent_by_holder: bool, holder_commitment: bool) -> bool {
+        // A commitment always includes the signer's own updates, and the broadcaster's
+        // updates once they are part of the signer's commitment.
+        if holder_commitment {
+            !sent_by_holder || status.in_counterparty_commitment
+        } else {
+            sent_by_holder || status.in_holder_commitment
+        }
+    }
+
+    /// Returns the balances and live HTLCs of the next commitment for either side.
+    fn commitment_view(&self, holder_commitment: bool) -> (u64, u64, Vec<&Htlc>) {
+        let mut holder_balance_msat = self.settled_holder_balance_msat;
+        let mut counterparty_balance_msat = self.settled_counterparty_balance_msat;
+        let mut live_htlcs = Vec::new();
+
+        for htlc in self.htlcs.iter() {
+            let offered = htlc.direction == HtlcDirection::Offered;
+            if !Self::is_included(&htlc.add, offered, holder_commitment) {
+                continue;
+            }
+
+            let (offerer_balance, receiver_balance) = if offered {
+                (&mut holder_balance_msat, &mut counterparty_balance_msat)
+            } else {
+                (&mut counterparty_balance_msat, &mut holder_balance_msat)
+            };
+            *offerer_balance -= htlc.amount_msat;
+
+            match &htlc.removal {
+                Some((resolution, status)) if Self::is_included(status, !offered, holder_commitment) => {
+                    match resolution {
+                        HtlcResolution::Fulfilled(_) => *receiver_balance += htlc.amount_msat,
+                        HtlcResolution::Failed => *offerer_balance += htlc.amount_msat,
+                    }
+                }
+                _ => live_htlcs.push(htlc),
+            }
+        }
+
+        (holder_balance_msat, counterparty_balance_msat, live_htlcs)
+    }
+
+    fn available_balance_msat(&self) -> u64 {
+        let (balance_msat, _, htlcs) = self.commitment_view(false);
+        let fee_msat = if self.params.is_outbound {
+            commitment_tx_fee_sat(self.params.feerate_per_kw, htlcs.len() + 1) * 1000
+        } else {
+            0
+        };
+        balance_msat.saturating_sub(fee_msat)
+    }
+
+    fn counterparty_available_balance_msat(&self) -> u64 {
+        let (_, balance_msat, htlcs) = self.commitment_view(true);
+        let fee_msat = if self.params.is_outbound {
+            0
+        } else {
+            commitment_tx_fee_sat(self.params.feerate_per_kw, htlcs.len() + 1) * 1000
+        };
+        balance_msat.saturating_sub(fee_msat)
+    }
+
+    fn commitment_keys(&self, holder_commitment: bool, per_commitment_point: PublicKey) -> CommitmentKeys {
+        let (broadcaster, countersignatory, to_self_delay) = if holder_commitment {
+            (&self.holder_pubkeys, &self.counterparty_pubkeys, self.params.counterparty_selected_contest_delay)
+        } else {
+            (&self.counterparty_pubkeys, &self.holder_pubkeys, self.params.holder_selected_contest_delay)
+        };
+
+        CommitmentKeys {
+            per_commitment_point,
+            revocation_pubkey: generate_revocation_pubkey(countersignatory.revocation_basepoint, per_commitment_point),
+            broadcaster_delayed_pubkey: derive_public_key(&broadcaster.delayed_payment_basepoint, &per_commitment_point),
+            broadcaster_htlc_pubkey: derive_public_key(&broadcaster.htlc_basepoint, &per_commitment_point),
+            countersignatory_htlc_pubkey: derive_public_key(&countersignatory.htlc_basepoint, &per_commitment_point),
+            // option_static_remotekey: the to_remote output pays the payment basepoint directly
+            countersignatory_payment_pubkey: countersignatory.payment_basepoint,
+            to_self_delay,
+        }
+    }
+
+    fn build_commitment(
+        &self,
+        holder_commitment: bool,
+        commitment_number: u64,
+        per_commitment_point: PublicKey,
+    ) -> CommitmentTransaction {
+        let keys = self.commitment_keys(holder_commitment, per_commitment_point);
+        let feerate_per_kw = self.params.feerate_per_kw;
+        let dust_limit = self.params.dust_limit_satoshis;
+
+        let (holder_balance_msat, counterparty_balance_msat, live_htlcs) =
+            self.commitment_view(holder_commitment);
+
+        // HTLCs too small to pay for their second-stage transaction are left out
+        // of the commitment and their value goes to fees
+        let mut untrimmed_htlcs = Vec::new();
+        for htlc in live_htlcs {
+            let offered = (htlc.direction == HtlcDirection::Offered) == holder_commitment;
+            if htlc.amount_msat / 1000 >= dust_limit + htlc_tx_fee_sat(feerate_per_kw, offered) {
+                untrimmed_htlcs.push((htlc, offered));
+            }
+        }
+
+        let fee_sat = commitment_tx_fee_sat(feerate_per_kw, untrimmed_htlcs.len());
+        let mut holder_value_sat = holder_balance_msat / 1000;
+        let mut counterparty_value_sat = counterparty_balance_msat / 1000;
+        if self.params.is_outbound {
+            holder_value_sat = holder_value_sat.saturating_sub(fee_sat);
+        } else {
+            counterparty_value_sat = counterparty_value_sat.saturating_sub(fee_sat);
+        }
+
+        let (to_broadcaster_value_sat, to_countersignatory_value_sat) = if holder_commitment {
+            (holder_value_sat, counterparty_value_sat)
+        } else {
+            (counterparty_value_sat, holder_value_sat)
+        };
+
+        let (open_payment_basepoint, accept_payment_basepoint) = self.opener_and_accepter(
+            self.holder_pubkeys.payment_basepoint,
+            self.counterparty_pubkeys.payment_basepoint,
+        );
+
+        let mut tx = build_commitment_transaction(
+            self.params.funding_outpoint,
+            &open_payment_basepoint,
+            &accept_payment_basepoint,
+            commitment_number,
+            &BitcoinPublicKey::new(keys.revocation_pubkey),
+            &BitcoinPublicKey::new(keys.broadcaster_delayed_pubkey),
+            BitcoinPublicKey::new(keys.countersignatory_payment_pubkey),
+            keys.to_self_delay as i64,
+            to_broadcaster_value_sat,
+            to_countersignatory_value_sat,
+        );
+
+        // drop dust balance outputs and add the HTLC outputs
+        let mut outputs: Vec<(TxOut, Option<HtlcOutput>)> = tx
+            .output
+            .drain(..)
+            .filter(|output| output.value.to_sat() >= dust_limit)
+            .map(|output| (output, None))
+            .collect();
+
+        for (htlc, offered) in untrimmed_htlcs {
+            let payment_hash160 = Ripemd160::hash(&htlc.payment_hash).to_byte_array();
+            let witness_script = if offered {
+                build_htlc_offerer_witness_script(
+                    &BitcoinPublicKey::new(keys.revocation_pubkey),
+                    &BitcoinPublicKey::new(keys.countersignatory_htlc_pubkey),
+                    &BitcoinPublicKey::new(keys.broadcaster_htlc_pubkey),
+                    &payment_hash160,
+                )
+            } else {
+                build_htlc_receiver_witness_script(
+                    &BitcoinPublicKey::new(keys.revocation_pubkey),
+                    &BitcoinPublicKey::new(keys.countersignatory_htlc_pubkey),
+                    &BitcoinPublicKey::new(keys.broadcaster_htlc_pubkey),
+                    &payment_hash160,
+                    htlc.cltv_expiry,
+                )
+            };
+
+            let output = build_output(htlc.amount_msat / 1000, witness_script.to_p2wsh());
+            outputs.push((
+                output,
+                Some(HtlcOutput {
+                    htlc_id: htlc.htlc_id,
+                    offered,
+                    amount_msat: htlc.amount_msat,
+                    payment_hash: htlc.payment_hash,
+                    cltv_expiry: htlc.cltv_expiry,
+                    output_index: 0,
+                    witness_script,
+                }),
+            ));
+        }
+
+        // BIP 69 ordering, with cltv_expiry breaking ties between identical HTLC outputs
+        outputs.sort_by(|(a, a_htlc), (b, b_htlc)| {
+            a.value
+                .cmp(&b.value)
+                .then(a.script_pubkey.cmp(&b.script_pubkey))
+                .then(
+                    a_htlc.as_ref().map(|htlc| htlc.cltv_expiry)
+                        .cmp(&b_htlc.as_ref().map(|htlc| htlc.cltv_expiry)),
+                )
+        });
+
+        let mut htlcs = Vec::new();
+        for (output_index, (output, htlc)) in outputs.into_iter().enumerate() {
+            if let Some(mut htlc) = htlc {
+                htlc.output_index = output_index as u32;
+                htlcs.push(htlc);
+            }
+            tx.output.push(output);
+        }
+
+        CommitmentTransaction {
+            commitment_number,
+            keys,
+            to_broadcaster_value_sat,
+            to_countersignatory_value_sat,
+            htlcs,
+            tx,
+        }
+    }
+
+    /// Builds the HTLC-timeout (offered) or HTLC-success (received) transaction
+    /// spending one HTLC output of a commitment.
+    pub fn build_htlc_transaction(&self, commitment: &CommitmentTransaction, htlc: &HtlcOutput) -> Transaction {
+        let htlc_txin = TxIn {
+            previous_output: OutPoint {
+                txid: commitment.tx.compute_txid(),
+                vout: htlc.output_index,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        };
+
+        let fee_sat = htlc_tx_fee_sat(self.params.feerate_per_kw, htlc.offered);
+
+        // an HTLC-success transaction is an HTLC-timeout transaction without the locktime
+        let locktime = if htlc.offered { htlc.cltv_expiry } else { 0 };
+
+        build_htlc_timeout_transaction(
+            htlc_txin,
+            &BitcoinPublicKey::new(commitment.keys.revocation_pubkey),
+            &BitcoinPublicKey::new(commitment.keys.broadcaster_delayed_pubkey),
+            commitment.keys.to_self_delay as i64,
+            locktime,
+            htlc.amount_msat / 1000 - fee_sat,
+        )
+    }
+
+    //
+    // Justice
+    //
+
+    /// Sweeps every output of a revoked counterparty commitment that is ours
+    /// to take with the revocation key: their to_local output and all HTLCs.
+    /// The to_remote output already pays us, so it's left alone.
+    pub fn build_justice_transaction(
+        &self,
+        commitment_number: u64,
+        destination_script: ScriptBuf,
+        feerate_per_kw: u32,
+    ) -> Result<Transaction, ChannelError> {
+        let commitment = self
+            .counterparty_commitments
+            .get(&commitment_number)
+            .ok_or(ChannelError::UnknownCommitment(commitment_number))?;
+        let per_commitment_secret = self
+            .counterparty_per_commitment_secrets
+            .get(&commitment_number)
+            .ok_or(ChannelError::NotRevoked(commitment_number))?;
+
+        let revocation_key = generate_revocation_privkey(
+            SecretKey::from_slice(per_commitment_secret).unwrap(),
+            self.keys.revocation_base_key,
+        );
+        let keys = &commitment.keys;
+        let revocation_pubkey = BitcoinPublicKey::new(keys.revocation_pubkey);
+
+        // (output index, witness script, value, revocation witness element)
+        let mut spends: Vec<(u32, ScriptBuf, u64, Vec<u8>)> = Vec::new();
+
+        let to_local_script = to_local(
+            &revocation_pubkey,
+            &BitcoinPublicKey::new(keys.broadcaster_delayed_pubkey),
+            keys.to_self_delay as i64,
+        );
+        let to_local_spk = to_local_script.to_p2wsh();
+        if let Some(index) = commitment.tx.output.iter().position(|output| output.script_pubkey == to_local_spk) {
+            // OP_IF takes the revocation branch
+            spends.push((index as u32, to_local_script, commitment.tx.output[index].value.to_sat(), vec![0x01]));
+        }
+        for htlc in &commitment.htlcs {
+            let value = commitment.tx.output[htlc.output_index as usize].value.to_sat();
+            spends.push((htlc.output_index, htlc.witness_script.clone(), value, revocation_pubkey.to_bytes()));
+        }
+
+        let txid = commitment.tx.compute_txid();
+        let total_sat: u64 = spends.iter().map(|(_, _, value, _)| value).sum();
+
+        let sign = |output_value_sat: u64| {
+            let mut tx = Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: spends
+                    .iter()
+                    .map(|(vout, _, _, _)| TxIn {
+                        previous_output: OutPoint { txid, vout: *vout },
+                        script_sig: ScriptBuf::new(),
+                        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                        witness: Witness::new(),
+                    })
+                    .collect(),
+                output: vec![build_output(output_value_sat, destination_script.clone())],
+            };
+
+            let unsigned_tx = tx.clone();
+            for (input_index, (_, witness_script, value, branch)) in spends.iter().enumerate() {
+                let signature = generate_p2wsh_signature(
+                    unsigned_tx.clone(),
+                    input_index,
+                    witness_script,
+                    *value,
+                    EcdsaSighashType::All,
+                    revocation_key,
+                );
+                let mut signature_der = signature.serialize_der().to_vec();
+                signature_der.push(EcdsaSighashType::All as u8);
+
+                let witness = &mut tx.input[input_index].witness;
+                witness.push(signature_der);
+                witness.push(branch.clone());
+                witness.push(witness_script.as_bytes());
+            }
+            tx
+        };
+
+        // sign once to learn the weight, then again with the fee taken out
+        let fee_sat = feerate_per_kw as u64 * sign(total_sat).weight().to_wu() / 1000;
+        if total_sat < fee_sat + self.params.dust_limit_satoshis {
+            return Err(ChannelError::JusticeBelowDust);
+        }
+        Ok(sign(total_sat - fee_sat))
+    }
+
+    fn mark_committed(&mut self, holder_commitment: bool) {
+        for htlc in self.htlcs.iter_mut() {
+            let offered = htlc.direction == HtlcDirection::Offered;
+
+            if Self::is_included(&htlc.add, offered, holder_commitment) {
+                Self::set_included(&mut htlc.add, holder_commitment);
+            }
+            if let Some((_, status)) = htlc.removal.as_mut()
+                && Self::is_included(status, !offered, holder_commitment)
+            {
+                Self::set_included(status, holder_commitment);
+            }
+        }
+
+        // once a removal is in both commitments the HTLC can be folded into the balances
+        let mut settled_holder_balance_msat = self.settled_holder_balance_msat;
+        let mut settled_counterparty_balance_msat = self.settled_counterparty_balance_msat;
+        self.htlcs.retain(|htlc| {
+            let resolution = match &htlc.removal {
+                Some((resolution, status)) if status.in_holder_commitment && status.in_counterparty_commitment => resolution,
+                _ => return true,
+            };
+
+            let offered = htlc.direction == HtlcDirection::Offered;
+            let holder_gets_funds = match resolution {
+                HtlcResolution::Fulfilled(_) => !offered,
+                HtlcResolution::Failed => offered,
+            };
+            if offered {
+                settled_holder_balance_msat -= htlc.amount_msat;
+            } else {
+                settled_counterparty_balance_msat -= htlc.amount_msat;
+            }
+            if holder_gets_funds {
+                settled_holder_balance_msat += htlc.amount_msat;
+            } else {
+                settled_counterparty_balance_msat += htlc.amount_msat;
+            }
+            false
+        });
+        self.settled_holder_balance_msat = settled_holder_balance_msat;
+        self.settled_counterparty_balance_msat = settled_counterparty_balance_msat;
+    }
+
+    fn set_included(status: &mut UpdateStatus, holder_commitment: bool) {
+        if holder_commitment {
+            status.in_holder_commitment = true;
+        } else {
+            status.in_counterparty_commitment = true;
+        }
+    }
+}
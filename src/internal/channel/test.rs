@@ -0,0 +1,190 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix::exercises::{NodeKeysManager, ChannelKeysManager};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hash_types::Txid;
+use bitcoin::OutPoint;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use internal::channel::{channel_id_from_funding_outpoint, commitment_round, INITIAL_COMMITMENT_NUMBER, Channel, ChannelError, ChannelParameters, ChannelPublicKeys};
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key, secp256k1pubkey_from_private_key};
+use internal::tx_utils::recover_commitment_number;
+use internal::wire::{self, OnionPacket, MAX_FAILURE_REASON_LEN};
+
+pub fn test_onion_packet() -> OnionPacket {
+    OnionPacket {
+        version: 0,
+        public_key: secp256k1pubkey_from_private_key(&[0x08; 32]),
+        hop_data: [0x5a; wire::ONION_HOP_DATA_LEN],
+        hmac: [0x33; 32],
+    }
+}
+
+pub fn first_per_commitment_point(keys: &ChannelKeysManager) -> PublicKey {
+    let secret = keys.build_commitment_secret(INITIAL_COMMITMENT_NUMBER);
+    pubkey_from_secret(SecretKey::from_slice(&secret).unwrap())
+}
+
+pub fn channel_pair() -> (Channel, Channel) {
+    let funding_outpoint = OutPoint::new(
+        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
+            .parse::<Txid>()
+            .unwrap(),
+        0,
+    );
+
+    let alice_funding_key = secp256k1_private_key(&[0x01; 32]);
+    let alice_keys = NodeKeysManager::new([0x01; 32]).derive_channel_keys(1);
+    let alice_pubkeys = ChannelPublicKeys::new(&alice_funding_key, &alice_keys);
+
+    let bob_funding_key = secp256k1_private_key(&[0x02; 32]);
+    let bob_keys = NodeKeysManager::new([0x02; 32]).derive_channel_keys(1);
+    let bob_pubkeys = ChannelPublicKeys::new(&bob_funding_key, &bob_keys);
+
+    let alice_params = ChannelParameters {
+        is_outbound: true,
+        funding_outpoint,
+        channel_value_satoshis: 5_000_000,
+        push_msat: 1_000_000_000,
+        feerate_per_kw: 253,
+        dust_limit_satoshis: 546,
+        holder_selected_contest_delay: 144,
+        counterparty_selected_contest_delay: 144,
+    };
+    let bob_params = ChannelParameters { is_outbound: false, ..alice_params.clone() };
+
+    // the first per-commitment points are exchanged in open_channel / accept_channel
+    let alice_first_point = first_per_commitment_point(&alice_keys);
+    let bob_first_point = first_per_commitment_point(&bob_keys);
+
+    let alice = Channel::new(alice_params, alice_funding_key, alice_keys, bob_pubkeys, bob_first_point);
+    let bob = Channel::new(bob_params, bob_funding_key, bob_keys, alice_pubkeys, alice_first_point);
+
+    (alice, bob)
+}
+
+pub fn assert_in_sync(alice: &Channel, bob: &Channel) {
+    assert_eq!(
+        alice.counterparty_commitment().unwrap().tx,
+        bob.holder_commitment().unwrap().tx,
+        "Alice's view of Bob's commitment doesn't match Bob's"
+    );
+    assert_eq!(
+        bob.counterparty_commitment().unwrap().tx,
+        alice.holder_commitment().unwrap().tx,
+        "Bob's view of Alice's commitment doesn't match Alice's"
+    );
+}
+
+#[test]
+fn test_channel_simulation() {
+    let (mut alice, mut bob) = channel_pair();
+
+    // funding_created / funding_signed: exchange signatures for the initial commitments
+    let bob_commitment_signed = alice.sign_commitment().unwrap();
+    bob.receive_commitment_signed(&bob_commitment_signed).unwrap();
+    let alice_commitment_signed = bob.sign_commitment().unwrap();
+    alice.receive_commitment_signed(&alice_commitment_signed).unwrap();
+    assert_in_sync(&alice, &bob);
+
+    // channel_ready
+    alice.receive_channel_ready(bob.next_per_commitment_point());
+    bob.receive_channel_ready(alice.next_per_commitment_point());
+
+    assert_eq!(alice.holder_balance_msat(), 4_000_000_000);
+    assert_eq!(bob.holder_balance_msat(), 1_000_000_000);
+
+    // the channel id is the one LDK, and every other node, derives from the funding outpoint
+    let ldk_outpoint = |outpoint: OutPoint| lightning::chain::transaction::OutPoint { txid: outpoint.txid, index: outpoint.vout as u16 };
+    assert_eq!(alice.channel_id, lightning::ln::types::ChannelId::v1_from_funding_outpoint(ldk_outpoint(alice.params.funding_outpoint)).0);
+    let outpoint = OutPoint { txid: alice.params.funding_outpoint.txid, vout: 0x0102 };
+    assert_eq!(channel_id_from_funding_outpoint(&outpoint), lightning::ln::types::ChannelId::v1_from_funding_outpoint(ldk_outpoint(outpoint)).0);
+
+    // the commitment number can be recovered from either side's commitment
+    let alice_commitment = alice.holder_commitment().unwrap();
+    assert_eq!(
+        recover_commitment_number(
+            &alice_commitment.tx,
+            &alice.holder_pubkeys.payment_basepoint,
+            &alice.counterparty_pubkeys.payment_basepoint
+        ),
+        Some(0)
+    );
+
+    // Alice offers Bob an HTLC
+    let payment_preimage = [0x42; 32];
+    let payment_hash = Sha256::hash(&payment_preimage).to_byte_array();
+    let update_add = alice.add_htlc(400_000_000, payment_hash, 500, test_onion_packet()).unwrap();
+    bob.receive_update_add_htlc(&update_add).unwrap();
+
+    commitment_round(&mut alice, &mut bob).unwrap();
+    assert_in_sync(&alice, &bob);
+
+    let alice_commitment = alice.holder_commitment().unwrap();
+    assert_eq!(alice_commitment.commitment_number, 1);
+    assert_eq!(alice_commitment.htlcs.len(), 1);
+    assert!(alice_commitment.htlcs[0].offered);
+    assert_eq!(alice_commitment.tx.output.len(), 3);
+
+    let bob_commitment = bob.holder_commitment().unwrap();
+    assert_eq!(bob_commitment.htlcs.len(), 1);
+    assert!(!bob_commitment.htlcs[0].offered);
+    assert_eq!(bob.holder_htlc_signatures.len(), 1);
+
+    assert_eq!(alice.holder_balance_msat(), 3_600_000_000);
+    assert_eq!(bob.holder_balance_msat(), 1_000_000_000);
+
+    // Bob's first commitment has been revoked and Alice holds the secret
+    assert!(alice.counterparty_per_commitment_secrets.contains_key(&0));
+    assert!(bob.counterparty_per_commitment_secrets.contains_key(&0));
+
+    // a wrong preimage is rejected
+    assert_eq!(bob.fulfill_htlc(update_add.htlc_id, [0x00; 32]), Err(ChannelError::InvalidPreimage));
+
+    // Bob settles the HTLC
+    let update_fulfill = bob.fulfill_htlc(update_add.htlc_id, payment_preimage).unwrap();
+    alice.receive_update_fulfill_htlc(&update_fulfill).unwrap();
+
+    commitment_round(&mut bob, &mut alice).unwrap();
+    assert_in_sync(&alice, &bob);
+
+    assert!(alice.htlcs.is_empty());
+    assert!(bob.htlcs.is_empty());
+    assert_eq!(alice.holder_balance_msat(), 3_600_000_000);
+    assert_eq!(bob.holder_balance_msat(), 1_400_000_000);
+    assert_eq!(alice.holder_commitment().unwrap().commitment_number, 2);
+    assert_eq!(alice.holder_commitment().unwrap().tx.output.len(), 2);
+
+    // Bob offers Alice an HTLC that she fails
+    let update_add = bob.add_htlc(100_000_000, [0x11; 32], 600, test_onion_packet()).unwrap();
+    alice.receive_update_add_htlc(&update_add).unwrap();
+    commitment_round(&mut bob, &mut alice).unwrap();
+    assert_in_sync(&alice, &bob);
+    assert_eq!(bob.holder_balance_msat(), 1_300_000_000);
+
+    // a reason too long for update_fail_htlc's length prefix is refused and the HTLC stays pending
+    assert_eq!(
+        alice.fail_htlc(update_add.htlc_id, vec![0; MAX_FAILURE_REASON_LEN + 1]),
+        Err(ChannelError::FailureReasonTooLong(MAX_FAILURE_REASON_LEN + 1))
+    );
+    let update_fail = alice.fail_htlc(update_add.htlc_id, Vec::new()).unwrap();
+    bob.receive_update_fail_htlc(&update_fail).unwrap();
+    commitment_round(&mut alice, &mut bob).unwrap();
+    assert_in_sync(&alice, &bob);
+
+    assert_eq!(alice.holder_balance_msat(), 3_600_000_000);
+    assert_eq!(bob.holder_balance_msat(), 1_400_000_000);
+    assert_eq!(alice.holder_commitment().unwrap().commitment_number, 4);
+
+    // the latest commitment carries both signatures and the funding script
+    let signed_commitment = alice.signed_holder_commitment_transaction().unwrap();
+    assert_eq!(signed_commitment.input[0].witness.len(), 4);
+}
+
+#[test]
+fn test_channel_rejects_overspend() {
+    let (mut alice, mut bob) = channel_pair();
+
+    assert_eq!(bob.add_htlc(1_000_000_001, [0x11; 32], 500, test_onion_packet()), Err(ChannelError::InsufficientBalance));
+    assert_eq!(alice.add_htlc(4_000_000_000, [0x11; 32], 500, test_onion_packet()), Err(ChannelError::InsufficientBalance));
+}
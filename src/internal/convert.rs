@@ -11,6 +11,7 @@ pub struct BlockchainInfo {
   pub latest_height: usize,
   pub latest_blockhash: BlockHash,
   pub chain: String,
+  pub median_time: u32,
 }
 
 impl TryInto<BlockchainInfo> for JsonResponse {
@@ -21,6 +22,7 @@ impl TryInto<BlockchainInfo> for JsonResponse {
       latest_blockhash: BlockHash::from_str(self.0["bestblockhash"].as_str().unwrap())
         .unwrap(),
       chain: self.0["chain"].as_str().unwrap().to_string(),
+      median_time: self.0["mediantime"].as_u64().unwrap() as u32,
     })
   }
 }
@@ -137,4 +139,94 @@ pub struct ListUnspentUtxo {
   pub vout: u32,
   pub amount: u64,
   pub address: Address,
-}
\ No newline at end of file
+}
+// gettxout returns null once the output is spent (or never existed)
+#[derive(Debug)]
+pub struct TxOutStatus {
+  pub unspent: bool,
+}
+
+impl TryInto<TxOutStatus> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<TxOutStatus> {
+    Ok(TxOutStatus { unspent: !self.0.is_null() })
+  }
+}
+
+// gettransaction only includes a blockhash once the transaction confirms
+#[derive(Debug)]
+pub struct WalletTransaction {
+  pub block_hash: Option<BlockHash>,
+}
+
+impl TryInto<WalletTransaction> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<WalletTransaction> {
+    let block_hash = match self.0["blockhash"].as_str() {
+      Some(hash) => Some(
+        BlockHash::from_str(hash).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+      ),
+      None => None,
+    };
+    Ok(WalletTransaction { block_hash })
+  }
+}
+
+#[derive(Debug)]
+pub struct RawTx(pub String);
+
+impl TryInto<RawTx> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<RawTx> {
+    Ok(RawTx(self.0.as_str().unwrap().to_string()))
+  }
+}
+
+#[derive(Debug)]
+pub struct FundedTx {
+  pub changepos: i64,
+  pub hex: String,
+}
+
+impl TryInto<FundedTx> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<FundedTx> {
+    Ok(FundedTx {
+      changepos: self.0["changepos"].as_i64().unwrap(),
+      hex: self.0["hex"].as_str().unwrap().to_string(),
+    })
+  }
+}
+
+// bitcoind quotes feerates in BTC/kvB, LDK wants sat per 1000 weight units
+pub fn btc_per_kvb_to_sat_per_kw(btc_per_kvb: f64) -> u32 {
+  (btc_per_kvb * 100_000_000.0 / 4.0).round() as u32
+}
+
+// estimatesmartfee leaves out the feerate when it doesn't have enough data yet, as on a fresh regtest chain
+#[derive(Debug)]
+pub struct FeeResponse {
+  pub feerate_sat_per_kw: Option<u32>,
+}
+
+impl TryInto<FeeResponse> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<FeeResponse> {
+    Ok(FeeResponse { feerate_sat_per_kw: self.0["feerate"].as_f64().map(btc_per_kvb_to_sat_per_kw) })
+  }
+}
+
+#[derive(Debug)]
+pub struct MempoolMinFee {
+  pub feerate_sat_per_kw: u32,
+}
+
+impl TryInto<MempoolMinFee> for JsonResponse {
+  type Error = std::io::Error;
+  fn try_into(self) -> std::io::Result<MempoolMinFee> {
+    let min_fee = self.0["mempoolminfee"]
+      .as_f64()
+      .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "missing mempoolminfee"))?;
+    Ok(MempoolMinFee { feerate_sat_per_kw: btc_per_kvb_to_sat_per_kw(min_fee) })
+  }
+}
@@ -0,0 +1,285 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::consensus::encode;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::transaction::{predict_weight, InputWeightPrediction, Version};
+use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness};
+use internal::bitcoind_client::{BitcoindClient, MIN_FEERATE};
+use internal::convert::{ListUnspentUtxo, SignedTx};
+use internal::hex_utils;
+use internal::script_utils::{build_anchor_witness_script, p2wpkh_output_script};
+use internal::sign_utils::generate_p2wsh_signature;
+use internal::tx_utils::{build_output, build_transaction, fee_for_weight};
+
+pub mod test;
+
+// Child pays for parent: a pre-signed commitment can't change its own fee,
+// but a child spending one of its outputs can pay enough for both, and
+// bitcoind accepts the two together as a package.
+
+pub const ANCHOR_OUTPUT_VALUE_SATOSHI: u64 = 330;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpfpError {
+    NoSuchOutput(u32),
+    // the output isn't the anchor for the funding key we were given
+    NotAnAnchor(u32),
+    // the output doesn't pay the payment key we were given
+    NotToRemote(u32),
+    // only P2WPKH outputs are signed by the wallet
+    NotWalletSpendable(u32),
+    AlreadyAtFeerate { parent_feerate_per_kw: u32 },
+    InsufficientFunds { needed: u64, available: u64 },
+    // bitcoind's signed child didn't decode
+    InvalidSignedTx(String),
+    // the wallet couldn't sign all of its inputs
+    IncompleteSignature,
+    // bitcoind didn't take the parent and child, with its reason
+    PackageRejected(String),
+}
+
+/// The parent output the child spends.
+#[derive(Debug, Clone)]
+pub enum BumpOutput {
+    /// Our anchor, signed with our funding key.
+    Anchor { vout: u32, funding_key: SecretKey },
+    /// A static_remote_key to_remote output, signed with the payment key it pays.
+    ToRemote { vout: u32, payment_key: SecretKey },
+    /// A P2WPKH output the bitcoind wallet has the key for.
+    Wallet { vout: u32 },
+}
+
+impl BumpOutput {
+    pub fn vout(&self) -> u32 {
+        match self {
+            BumpOutput::Anchor { vout, .. } | BumpOutput::ToRemote { vout, .. } | BumpOutput::Wallet { vout } => *vout,
+        }
+    }
+
+    fn anchor_script(&self) -> Option<ScriptBuf> {
+        match self {
+            BumpOutput::Anchor { funding_key, .. } => {
+                let funding_pubkey = PublicKey::from_secret_key(&Secp256k1::new(), funding_key);
+                Some(build_anchor_witness_script(&bitcoin::PublicKey::new(funding_pubkey)))
+            }
+            BumpOutput::ToRemote { .. } | BumpOutput::Wallet { .. } => None,
+        }
+    }
+
+    fn to_remote_pubkey(&self) -> Option<bitcoin::PublicKey> {
+        match self {
+            BumpOutput::ToRemote { payment_key, .. } => {
+                Some(bitcoin::PublicKey::new(PublicKey::from_secret_key(&Secp256k1::new(), payment_key)))
+            }
+            BumpOutput::Anchor { .. } | BumpOutput::Wallet { .. } => None,
+        }
+    }
+
+    // an anchor is spent with `<sig> <witness script>`
+    fn weight_prediction(&self) -> InputWeightPrediction {
+        match self.anchor_script() {
+            Some(anchor_script) => InputWeightPrediction::new(0, [72, anchor_script.len()]),
+            None => InputWeightPrediction::P2WPKH_MAX,
+        }
+    }
+}
+
+/// An unsigned child and what it pays.
+#[derive(Debug, Clone)]
+pub struct CpfpChild {
+    pub tx: Transaction,
+    pub fee: u64,
+    pub package_feerate_per_kw: u32,
+    pub bumped_output: TxOut,
+    pub spent_utxos: Vec<ListUnspentUtxo>,
+}
+
+pub fn feerate_per_kw(fee: u64, weight: Weight) -> u32 {
+    (fee * 1000 / weight.to_wu()) as u32
+}
+
+/// The child fee that lifts parent and child together to `target_feerate_per_kw`.
+/// The child never pays less than its own minimum relay fee.
+pub fn child_fee_for_package(parent_fee: u64, parent_weight: Weight, child_weight: Weight, target_feerate_per_kw: u32) -> u64 {
+    let package_weight = parent_weight.to_wu() + child_weight.to_wu();
+    let package_fee = (target_feerate_per_kw as u64 * package_weight).div_ceil(1000);
+    package_fee.saturating_sub(parent_fee).max(fee_for_weight(MIN_FEERATE, child_weight))
+}
+
+/// Builds the child spending `bump` from `parent`, adding the largest wallet
+/// UTXOs until they cover the fee and leave non-dust change.
+pub fn build_cpfp_child(
+    parent: &Transaction,
+    parent_fee: u64,
+    bump: &BumpOutput,
+    utxos: &[ListUnspentUtxo],
+    change_script: ScriptBuf,
+    target_feerate_per_kw: u32,
+) -> Result<CpfpChild, CpfpError> {
+    let vout = bump.vout();
+    let bumped_output = parent.output.get(vout as usize).ok_or(CpfpError::NoSuchOutput(vout))?.clone();
+    match (bump.anchor_script(), bump.to_remote_pubkey()) {
+        (Some(anchor_script), _) if bumped_output.script_pubkey != anchor_script.to_p2wsh() => return Err(CpfpError::NotAnAnchor(vout)),
+        (_, Some(payment_pubkey)) if bumped_output.script_pubkey != p2wpkh_output_script(payment_pubkey) => {
+            return Err(CpfpError::NotToRemote(vout))
+        }
+        (None, None) if !bumped_output.script_pubkey.is_p2wpkh() => return Err(CpfpError::NotWalletSpendable(vout)),
+        _ => {}
+    }
+
+    let parent_weight = parent.weight();
+    let parent_feerate_per_kw = feerate_per_kw(parent_fee, parent_weight);
+    if parent_feerate_per_kw >= target_feerate_per_kw {
+        return Err(CpfpError::AlreadyAtFeerate { parent_feerate_per_kw });
+    }
+
+    let mut candidates = utxos.to_vec();
+    candidates.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+    let mut candidates = candidates.into_iter();
+    let mut spent_utxos: Vec<ListUnspentUtxo> = Vec::new();
+    let dust_limit = change_script.minimal_non_dust().to_sat();
+
+    loop {
+        let inputs = std::iter::once(bump.weight_prediction())
+            .chain(spent_utxos.iter().map(|_| InputWeightPrediction::P2WPKH_MAX));
+        let child_weight = predict_weight(inputs, [change_script.len()]);
+        let fee = child_fee_for_package(parent_fee, parent_weight, child_weight, target_feerate_per_kw);
+        let available = bumped_output.value.to_sat() + spent_utxos.iter().map(|utxo| utxo.amount).sum::<u64>();
+
+        if available >= fee + dust_limit {
+            // signal RBF so the child can be replaced by a bigger bump later
+            let spend = |previous_output: OutPoint| TxIn {
+                previous_output,
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                script_sig: ScriptBuf::new(),
+                witness: Witness::new(),
+            };
+            let inputs = std::iter::once(spend(OutPoint { txid: parent.compute_txid(), vout }))
+                .chain(spent_utxos.iter().map(|utxo| spend(OutPoint { txid: utxo.txid, vout: utxo.vout })))
+                .collect();
+            let change = build_output(available - fee, change_script);
+            let tx = build_transaction(Version::TWO, LockTime::ZERO, inputs, vec![change]);
+
+            let package_feerate_per_kw = feerate_per_kw(parent_fee + fee, parent_weight + child_weight);
+            return Ok(CpfpChild { tx, fee, package_feerate_per_kw, bumped_output, spent_utxos });
+        }
+
+        match candidates.next() {
+            Some(utxo) => spent_utxos.push(utxo),
+            None => return Err(CpfpError::InsufficientFunds { needed: fee + dust_limit, available }),
+        }
+    }
+}
+
+/// Signs the wallet's inputs through bitcoind, and the anchor or to_remote,
+/// if that's what we're spending, with its key.
+pub async fn sign_cpfp_child(bitcoind: &BitcoindClient, child: &CpfpChild, bump: &BumpOutput) -> Result<Transaction, CpfpError> {
+    let bumped_outpoint = child.tx.input[0].previous_output;
+    let signed = bitcoind
+        .sign_raw_transaction_with_wallet_prevouts(serialize_hex(&child.tx), &[(bumped_outpoint, child.bumped_output.clone())])
+        .await;
+    finish_cpfp_child(child, bump, &signed)
+}
+
+/// Checks what the wallet signed and adds the anchor's or to_remote's witness.
+pub fn finish_cpfp_child(child: &CpfpChild, bump: &BumpOutput, signed: &SignedTx) -> Result<Transaction, CpfpError> {
+    let bytes = hex_utils::to_vec(&signed.hex).ok_or(CpfpError::InvalidSignedTx("not hex".to_string()))?;
+    let mut signed_tx: Transaction = encode::deserialize(&bytes).map_err(|e| CpfpError::InvalidSignedTx(e.to_string()))?;
+
+    // bitcoind can't sign the anchor or to_remote, so for those it never
+    // reports complete and only the inputs after it are the wallet's to sign
+    let wallet_signed = match bump {
+        BumpOutput::Wallet { .. } => signed.complete,
+        BumpOutput::Anchor { .. } | BumpOutput::ToRemote { .. } => {
+            signed_tx.input.iter().skip(1).all(|input| !input.witness.is_empty())
+        }
+    };
+    if !wallet_signed {
+        return Err(CpfpError::IncompleteSignature);
+    }
+
+    if let (BumpOutput::Anchor { funding_key, .. }, Some(anchor_script)) = (bump, bump.anchor_script()) {
+        let signature = generate_p2wsh_signature(
+            signed_tx.clone(),
+            0,
+            &anchor_script,
+            child.bumped_output.value.to_sat(),
+            EcdsaSighashType::All,
+            *funding_key,
+        );
+        let mut signature_der = signature.serialize_der().to_vec();
+        signature_der.push(EcdsaSighashType::All as u8);
+
+        signed_tx.input[0].witness.push(signature_der);
+        signed_tx.input[0].witness.push(anchor_script.into_bytes());
+    }
+
+    // P2WPKH signs over the P2PKH script of its key, spent with `<sig> <pubkey>`
+    if let (BumpOutput::ToRemote { payment_key, .. }, Some(payment_pubkey)) = (bump, bump.to_remote_pubkey()) {
+        let script_code = ScriptBuf::new_p2pkh(&payment_pubkey.pubkey_hash());
+        let signature = generate_p2wsh_signature(
+            signed_tx.clone(),
+            0,
+            &script_code,
+            child.bumped_output.value.to_sat(),
+            EcdsaSighashType::All,
+            *payment_key,
+        );
+        let mut signature_der = signature.serialize_der().to_vec();
+        signature_der.push(EcdsaSighashType::All as u8);
+
+        signed_tx.input[0].witness.push(signature_der);
+        signed_tx.input[0].witness.push(payment_pubkey.to_bytes());
+    }
+
+    Ok(signed_tx)
+}
+
+/// Bumps `parent` to `target_feerate_per_kw` with a child paying from the
+/// wallet, and submits both as a package.
+pub async fn bump_fee(
+    bitcoind: &BitcoindClient,
+    parent: &Transaction,
+    parent_fee: u64,
+    bump: &BumpOutput,
+    target_feerate_per_kw: u32,
+) -> Result<(Transaction, CpfpChild), CpfpError> {
+    let utxos = bitcoind.list_unspent().await.0;
+    let change_script = bitcoind.get_new_address().await.script_pubkey();
+
+    let child = build_cpfp_child(parent, parent_fee, bump, &utxos, change_script, target_feerate_per_kw)?;
+    let signed_child = sign_cpfp_child(bitcoind, &child, bump).await?;
+
+    let result = bitcoind
+        .submit_package(&[parent, &signed_child])
+        .await
+        .map_err(|e| CpfpError::PackageRejected(e.to_string()))?;
+    if let Some(reason) = package_rejection(&result) {
+        return Err(CpfpError::PackageRejected(reason));
+    }
+
+    Ok((signed_child, child))
+}
+
+/// Why bitcoind's `submitpackage` result turned the package down, if it did.
+/// Older versions only report per-transaction errors, newer ones also set
+/// `package_msg`.
+pub fn package_rejection(result: &serde_json::Value) -> Option<String> {
+    let tx_errors: Vec<&str> = result["tx-results"]
+        .as_object()
+        .into_iter()
+        .flat_map(|results| results.values())
+        .filter_map(|tx_result| tx_result["error"].as_str())
+        .collect();
+    if !tx_errors.is_empty() {
+        return Some(tx_errors.join(", "));
+    }
+
+    match result["package_msg"].as_str() {
+        Some("success") | None => None,
+        Some(package_msg) => Some(package_msg.to_string()),
+    }
+}
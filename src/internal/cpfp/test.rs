@@ -0,0 +1,161 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::Hash;
+use bitcoin::OutPoint;
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key, secp256k1pubkey_from_private_key};
+use bitcoin::script::ScriptBuf;
+use bitcoin::Network;
+use internal::script_utils::p2wpkh_output_script;
+use internal::tx_utils::fee_for_weight;
+use internal::tx_utils::get_funding_input;
+use internal::tx_utils::{build_output, build_transaction};
+use internal::sign_utils::{generate_p2wsh_signature, verify_p2wsh_signature};
+use internal::cpfp::{build_cpfp_child, child_fee_for_package, feerate_per_kw, finish_cpfp_child, package_rejection, BumpOutput, CpfpError,
+                     ANCHOR_OUTPUT_VALUE_SATOSHI};
+use internal::convert::{ListUnspentUtxo, SignedTx};
+use bitcoin::consensus::encode::serialize_hex;
+use internal::script_utils::build_anchor_witness_script;
+
+pub fn cpfp_wallet_utxo(amount: u64, last_byte: u8) -> ListUnspentUtxo {
+    let script = p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x31; 32])));
+    let txid = format!("c0ffee00000000000000000000000000000000000000000000000000000000{:02x}", last_byte);
+    ListUnspentUtxo {
+        txid: txid.parse().unwrap(),
+        vout: 0,
+        amount,
+        address: bitcoin::Address::from_script(&script, Network::Regtest).unwrap(),
+    }
+}
+
+#[test]
+fn test_cpfp_child_lifts_the_package_feerate() {
+    let funding_key = secp256k1_private_key(&[0x01; 32]);
+    let funding_pubkey = bitcoin::PublicKey::new(pubkey_from_secret(funding_key));
+    let anchor_script = build_anchor_witness_script(&funding_pubkey);
+    assert_eq!(anchor_script, lightning::ln::chan_utils::get_anchor_redeemscript(&funding_pubkey.inner));
+
+    // a commitment-sized parent paying 253 sat/kW: to_remote, to_local and our anchor
+    let wallet_script = p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x31; 32])));
+    let mut parent = build_transaction(bitcoin::transaction::Version::TWO, bitcoin::absolute::LockTime::ZERO,
+                                       vec![get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000006".to_string(), 0)],
+                                       vec![build_output(1_000_000, wallet_script.clone()),
+                                            build_output(3_998_000, ScriptBuf::new_p2wsh(&anchor_script.wscript_hash())),
+                                            build_output(ANCHOR_OUTPUT_VALUE_SATOSHI, anchor_script.to_p2wsh())]);
+    parent.input[0].witness = bitcoin::Witness::from_slice(&[vec![], vec![0; 72], vec![0; 72], vec![0; 71]]);
+    let parent_fee = fee_for_weight(253, parent.weight());
+    let target = 5_000;
+
+    let utxos = vec![cpfp_wallet_utxo(2_000, 1), cpfp_wallet_utxo(50_000, 2), cpfp_wallet_utxo(10_000, 3)];
+    let bump = BumpOutput::Anchor { vout: 2, funding_key };
+    let child = build_cpfp_child(&parent, parent_fee, &bump, &utxos, wallet_script.clone(), target).unwrap();
+
+    // the anchor alone can't pay, so the largest UTXO comes in
+    assert_eq!(child.spent_utxos.len(), 1);
+    assert_eq!(child.spent_utxos[0].amount, 50_000);
+    assert_eq!(child.tx.input[0].previous_output, OutPoint { txid: parent.compute_txid(), vout: 2 });
+    assert_eq!(child.tx.output[0].value.to_sat(), ANCHOR_OUTPUT_VALUE_SATOSHI + 50_000 - child.fee);
+    assert!(child.package_feerate_per_kw >= target);
+
+    // sign the anchor for real and give the wallet input its largest witness:
+    // the prediction the fee came from must not undershoot
+    let mut signed = child.tx.clone();
+    let mut anchor_signature = generate_p2wsh_signature(signed.clone(), 0, &anchor_script, ANCHOR_OUTPUT_VALUE_SATOSHI,
+                                                        bitcoin::sighash::EcdsaSighashType::All, funding_key).serialize_der().to_vec();
+    anchor_signature.push(bitcoin::sighash::EcdsaSighashType::All as u8);
+    signed.input[0].witness = bitcoin::Witness::from_slice(&[anchor_signature, anchor_script.to_bytes()]);
+    signed.input[1].witness = bitcoin::Witness::from_slice(&[vec![0; 72], vec![0; 33]]);
+    assert!(feerate_per_kw(parent_fee + child.fee, parent.weight() + signed.weight()) >= target);
+
+    // the child pays for the parent's shortfall, and never less than its own relay fee
+    let parent_weight = parent.weight();
+    let child_weight = bitcoin::Weight::from_wu(700);
+    assert_eq!(child_fee_for_package(parent_fee, parent_weight, child_weight, target),
+               (target as u64 * (parent_weight.to_wu() + 700)).div_ceil(1000) - parent_fee);
+    assert_eq!(child_fee_for_package(1_000_000, parent_weight, child_weight, target), fee_for_weight(253, child_weight));
+
+    // spending to_remote through the wallet instead
+    let child = build_cpfp_child(&parent, parent_fee, &BumpOutput::Wallet { vout: 0 }, &utxos, wallet_script.clone(), target).unwrap();
+    assert!(child.spent_utxos.is_empty());
+
+    // or with the payment key it pays, which must be the one in the output
+    let payment_key = secp256k1_private_key(&[0x31; 32]);
+    let child = build_cpfp_child(&parent, parent_fee, &BumpOutput::ToRemote { vout: 0, payment_key }, &utxos, wallet_script.clone(), target).unwrap();
+    assert!(child.spent_utxos.is_empty());
+    assert_eq!(build_cpfp_child(&parent, parent_fee, &BumpOutput::ToRemote { vout: 0, payment_key: funding_key }, &utxos, wallet_script.clone(), target).unwrap_err(),
+               CpfpError::NotToRemote(0));
+
+    assert_eq!(build_cpfp_child(&parent, parent_fee, &BumpOutput::Wallet { vout: 1 }, &utxos, wallet_script.clone(), target).unwrap_err(),
+               CpfpError::NotWalletSpendable(1));
+    assert_eq!(build_cpfp_child(&parent, parent_fee, &BumpOutput::Anchor { vout: 0, funding_key }, &utxos, wallet_script.clone(), target).unwrap_err(),
+               CpfpError::NotAnAnchor(0));
+    assert_eq!(build_cpfp_child(&parent, parent_fee, &BumpOutput::Wallet { vout: 3 }, &utxos, wallet_script.clone(), target).unwrap_err(),
+               CpfpError::NoSuchOutput(3));
+    assert!(matches!(build_cpfp_child(&parent, parent_fee, &bump, &utxos, wallet_script.clone(), 250),
+                     Err(CpfpError::AlreadyAtFeerate { .. })));
+    assert!(matches!(build_cpfp_child(&parent, parent_fee, &bump, &utxos[..1], wallet_script, target),
+                     Err(CpfpError::InsufficientFunds { available: 2_330, .. })));
+}
+
+#[test]
+fn test_cpfp_child_signing_checks_what_the_wallet_signed() {
+    let funding_key = secp256k1_private_key(&[0x01; 32]);
+    let anchor_script = build_anchor_witness_script(&bitcoin::PublicKey::new(pubkey_from_secret(funding_key)));
+    let wallet_script = p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x31; 32])));
+    let parent = build_transaction(bitcoin::transaction::Version::TWO, bitcoin::absolute::LockTime::ZERO,
+                                   vec![get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000006".to_string(), 0)],
+                                   vec![build_output(1_000_000, wallet_script.clone()),
+                                        build_output(ANCHOR_OUTPUT_VALUE_SATOSHI, anchor_script.to_p2wsh())]);
+    let utxos = vec![cpfp_wallet_utxo(50_000, 1)];
+    let bump = BumpOutput::Anchor { vout: 1, funding_key };
+    let child = build_cpfp_child(&parent, 0, &bump, &utxos, wallet_script.clone(), 5_000).unwrap();
+
+    // bitcoind signs the wallet input but can't finish the anchor
+    let mut wallet_signed = child.tx.clone();
+    wallet_signed.input[1].witness = bitcoin::Witness::from_slice(&[vec![0; 72], vec![0; 33]]);
+    let signed = SignedTx { complete: false, hex: serialize_hex(&wallet_signed) };
+    let signed_tx = finish_cpfp_child(&child, &bump, &signed).unwrap();
+    assert_eq!(signed_tx.input[0].witness.len(), 2);
+    assert_eq!(signed_tx.input[0].witness.last().unwrap(), anchor_script.as_bytes());
+
+    // a wallet input left unsigned fails instead of broadcasting
+    let signed = SignedTx { complete: false, hex: serialize_hex(&child.tx) };
+    assert_eq!(finish_cpfp_child(&child, &bump, &signed), Err(CpfpError::IncompleteSignature));
+
+    // to_remote is signed with its payment key over the P2PKH script code
+    let payment_key = secp256k1_private_key(&[0x31; 32]);
+    let to_remote_bump = BumpOutput::ToRemote { vout: 0, payment_key };
+    let child = build_cpfp_child(&parent, 0, &to_remote_bump, &utxos, wallet_script.clone(), 5_000).unwrap();
+    let signed = SignedTx { complete: false, hex: serialize_hex(&child.tx) };
+    let signed_tx = finish_cpfp_child(&child, &to_remote_bump, &signed).unwrap();
+    let payment_pubkey = bitcoin::PublicKey::new(pubkey_from_secret(payment_key));
+    assert_eq!(signed_tx.input[0].witness.last().unwrap(), payment_pubkey.to_bytes().as_slice());
+    let signature = bitcoin::ecdsa::Signature::from_slice(signed_tx.input[0].witness.nth(0).unwrap()).unwrap();
+    assert!(verify_p2wsh_signature(&signed_tx, 0, &ScriptBuf::new_p2pkh(&payment_pubkey.pubkey_hash()), 1_000_000,
+                                   bitcoin::sighash::EcdsaSighashType::All, &signature.signature, &payment_pubkey.inner));
+
+    let wallet_bump = BumpOutput::Wallet { vout: 0 };
+    let child = build_cpfp_child(&parent, 0, &wallet_bump, &utxos, wallet_script, 5_000).unwrap();
+    let signed = SignedTx { complete: false, hex: serialize_hex(&child.tx) };
+    assert_eq!(finish_cpfp_child(&child, &wallet_bump, &signed), Err(CpfpError::IncompleteSignature));
+
+    let signed = SignedTx { complete: true, hex: "zz".to_string() };
+    assert!(matches!(finish_cpfp_child(&child, &wallet_bump, &signed), Err(CpfpError::InvalidSignedTx(_))));
+}
+
+#[test]
+fn test_cpfp_package_rejection() {
+    let accepted = serde_json::json!({
+        "package_msg": "success",
+        "tx-results": { "aa": { "txid": "aa" }, "bb": { "txid": "bb" } },
+    });
+    assert_eq!(package_rejection(&accepted), None);
+
+    let rejected = serde_json::json!({
+        "package_msg": "transaction failed",
+        "tx-results": { "aa": { "txid": "aa" }, "bb": { "txid": "bb", "error": "min relay fee not met" } },
+    });
+    assert_eq!(package_rejection(&rejected), Some("min relay fee not met".to_string()));
+
+    let rejected = serde_json::json!({ "package_msg": "package-not-child-with-parents" });
+    assert_eq!(package_rejection(&rejected), Some("package-not-child-with-parents".to_string()));
+}
@@ -0,0 +1,380 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises;
+use bitcoin::constants::ChainHash;
+use bitcoin::hashes::sha256d::Hash as Sha256d;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::PublicKey as BitcoinPublicKey;
+use bitcoin::{Network, OutPoint, ScriptBuf};
+use exercises::solutions::two_of_two_multisig_witness_script;
+use internal::chain::{get_output_by_scid, ChainError, ChainSource};
+use internal::key_utils::pubkey_from_secret;
+use internal::multihop::FeePolicy;
+use internal::wire::{ChannelAnnouncement, ChannelUpdate, DecodeError, NodeAnnouncement, UnsignedChannelAnnouncement,
+                     UnsignedChannelUpdate, UnsignedNodeAnnouncement};
+use std::collections::BTreeMap;
+
+pub mod test;
+
+// channel_update message_flags: htlc_maximum_msat is present
+const MESSAGE_FLAGS_HTLC_MAXIMUM: u8 = 1;
+// channel_update channel_flags
+const CHANNEL_FLAGS_DIRECTION: u8 = 1;
+const CHANNEL_FLAGS_DISABLED: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GossipError {
+    Decode(DecodeError),
+    InvalidSignature,
+    WrongChain,
+    Chain(ChainError),
+    FundingOutputSpent(u64),
+    FundingScriptMismatch(u64),
+    UnknownChannel(u64),
+    UnknownNode(PublicKey),
+    OutdatedUpdate,
+    // a FeePolicy field too big for its channel_update field
+    PolicyOutOfRange(&'static str),
+}
+
+impl From<DecodeError> for GossipError {
+    fn from(e: DecodeError) -> GossipError {
+        GossipError::Decode(e)
+    }
+}
+
+impl From<ChainError> for GossipError {
+    fn from(e: ChainError) -> GossipError {
+        GossipError::Chain(e)
+    }
+}
+
+/// The node key and funding key one side of a channel signs its
+/// channel_announcement with.
+#[derive(Debug, Clone, Copy)]
+pub struct AnnouncementKeys {
+    pub node_secret: SecretKey,
+    pub funding_key: SecretKey,
+}
+
+impl AnnouncementKeys {
+    pub fn node_id(&self) -> PublicKey {
+        pubkey_from_secret(self.node_secret)
+    }
+
+    pub fn funding_pubkey(&self) -> PublicKey {
+        pubkey_from_secret(self.funding_key)
+    }
+}
+
+//
+// Signing
+//
+
+// BOLT 7: gossip signatures commit to the double-SHA256 of the signed contents
+fn gossip_message_hash(contents: &[u8]) -> Message {
+    Message::from_digest(Sha256d::hash(contents).to_byte_array())
+}
+
+fn sign_contents(contents: &[u8], secret: &SecretKey) -> Signature {
+    let secp = Secp256k1::new();
+    secp.sign_ecdsa(&gossip_message_hash(contents), secret)
+}
+
+fn verify_contents(contents: &[u8], signature: &Signature, pubkey: &PublicKey) -> Result<(), GossipError> {
+    let secp = Secp256k1::new();
+    secp.verify_ecdsa(&gossip_message_hash(contents), signature, pubkey)
+        .map_err(|_| GossipError::InvalidSignature)
+}
+
+fn regtest_chain_hash() -> ChainHash {
+    ChainHash::using_genesis_block(Network::Regtest)
+}
+
+/// Both sides sign the announcement with their node and funding keys. The
+/// node with the lesser node id always comes first.
+pub fn create_channel_announcement(short_channel_id: u64, a: &AnnouncementKeys, b: &AnnouncementKeys) -> ChannelAnnouncement {
+    let (first, second) = if a.node_id().serialize() < b.node_id().serialize() { (a, b) } else { (b, a) };
+
+    let contents = UnsignedChannelAnnouncement {
+        features: Vec::new(),
+        chain_hash: regtest_chain_hash(),
+        short_channel_id,
+        node_id_1: first.node_id(),
+        node_id_2: second.node_id(),
+        bitcoin_key_1: first.funding_pubkey(),
+        bitcoin_key_2: second.funding_pubkey(),
+        excess_data: Vec::new(),
+    };
+
+    let mut bytes = Vec::new();
+    contents.write(&mut bytes);
+
+    ChannelAnnouncement {
+        node_signature_1: sign_contents(&bytes, &first.node_secret),
+        node_signature_2: sign_contents(&bytes, &second.node_secret),
+        bitcoin_signature_1: sign_contents(&bytes, &first.funding_key),
+        bitcoin_signature_2: sign_contents(&bytes, &second.funding_key),
+        contents,
+    }
+}
+
+pub fn create_node_announcement(node_secret: &SecretKey, timestamp: u32, alias: &str, rgb: [u8; 3]) -> NodeAnnouncement {
+    // the alias is UTF-8 padded with zeros to 32 bytes
+    let mut alias_bytes = [0u8; 32];
+    let len = alias.len().min(32);
+    alias_bytes[..len].copy_from_slice(&alias.as_bytes()[..len]);
+
+    let contents = UnsignedNodeAnnouncement {
+        features: Vec::new(),
+        timestamp,
+        node_id: pubkey_from_secret(*node_secret),
+        rgb,
+        alias: alias_bytes,
+        addresses: Vec::new(),
+        excess_data: Vec::new(),
+    };
+
+    let mut bytes = Vec::new();
+    contents.write(&mut bytes);
+
+    NodeAnnouncement { signature: sign_contents(&bytes, node_secret), contents }
+}
+
+/// Announces how the sending node charges for forwarding over the channel.
+/// `direction` is 0 when sent by node_id_1 and 1 when sent by node_id_2.
+/// Fails rather than truncate a policy that doesn't fit the message.
+pub fn create_channel_update(
+    node_secret: &SecretKey,
+    short_channel_id: u64,
+    direction: u8,
+    timestamp: u32,
+    policy: &FeePolicy,
+    htlc_minimum_msat: u64,
+    htlc_maximum_msat: u64,
+) -> Result<ChannelUpdate, GossipError> {
+    let cltv_expiry_delta =
+        u16::try_from(policy.cltv_expiry_delta).map_err(|_| GossipError::PolicyOutOfRange("cltv_expiry_delta"))?;
+    let fee_base_msat = u32::try_from(policy.base_fee_msat).map_err(|_| GossipError::PolicyOutOfRange("base_fee_msat"))?;
+    let fee_proportional_millionths = u32::try_from(policy.fee_proportional_millionths)
+        .map_err(|_| GossipError::PolicyOutOfRange("fee_proportional_millionths"))?;
+
+    let contents = UnsignedChannelUpdate {
+        chain_hash: regtest_chain_hash(),
+        short_channel_id,
+        timestamp,
+        message_flags: MESSAGE_FLAGS_HTLC_MAXIMUM,
+        channel_flags: direction & CHANNEL_FLAGS_DIRECTION,
+        cltv_expiry_delta,
+        htlc_minimum_msat,
+        fee_base_msat,
+        fee_proportional_millionths,
+        htlc_maximum_msat,
+        excess_data: Vec::new(),
+    };
+
+    let mut bytes = Vec::new();
+    contents.write(&mut bytes);
+
+    Ok(ChannelUpdate { signature: sign_contents(&bytes, node_secret), contents })
+}
+
+//
+// Verification
+//
+
+pub fn verify_channel_announcement(msg: &ChannelAnnouncement) -> Result<(), GossipError> {
+    let mut bytes = Vec::new();
+    msg.contents.write(&mut bytes);
+
+    verify_contents(&bytes, &msg.node_signature_1, &msg.contents.node_id_1)?;
+    verify_contents(&bytes, &msg.node_signature_2, &msg.contents.node_id_2)?;
+    verify_contents(&bytes, &msg.bitcoin_signature_1, &msg.contents.bitcoin_key_1)?;
+    verify_contents(&bytes, &msg.bitcoin_signature_2, &msg.contents.bitcoin_key_2)
+}
+
+pub fn verify_node_announcement(msg: &NodeAnnouncement) -> Result<(), GossipError> {
+    let mut bytes = Vec::new();
+    msg.contents.write(&mut bytes);
+    verify_contents(&bytes, &msg.signature, &msg.contents.node_id)
+}
+
+pub fn verify_channel_update(msg: &ChannelUpdate, node_id: &PublicKey) -> Result<(), GossipError> {
+    let mut bytes = Vec::new();
+    msg.contents.write(&mut bytes);
+    verify_contents(&bytes, &msg.signature, node_id)
+}
+
+/// The P2WSH scripts a funding output for these two keys could have. BOLT 3
+/// sorts the keys, but our funding transactions put the opener's key first,
+/// so either order is accepted.
+fn funding_script_pubkeys(bitcoin_key_1: &PublicKey, bitcoin_key_2: &PublicKey) -> [ScriptBuf; 2] {
+    let key_1 = BitcoinPublicKey::new(*bitcoin_key_1);
+    let key_2 = BitcoinPublicKey::new(*bitcoin_key_2);
+    [
+        two_of_two_multisig_witness_script(&key_1, &key_2).to_p2wsh(),
+        two_of_two_multisig_witness_script(&key_2, &key_1).to_p2wsh(),
+    ]
+}
+
+//
+// Network graph
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub announcement: ChannelAnnouncement,
+    pub funding_outpoint: OutPoint,
+    pub capacity_sat: u64,
+    // the latest channel_update from node_id_1 and node_id_2
+    pub one_to_two: Option<ChannelUpdate>,
+    pub two_to_one: Option<ChannelUpdate>,
+}
+
+impl ChannelInfo {
+    pub fn node_id_1(&self) -> PublicKey {
+        self.announcement.contents.node_id_1
+    }
+
+    pub fn node_id_2(&self) -> PublicKey {
+        self.announcement.contents.node_id_2
+    }
+
+    /// The fee policy for forwarding from `node_id` over this channel, if it has
+    /// sent an update and hasn't disabled the channel.
+    pub fn policy_from(&self, node_id: &PublicKey) -> Option<FeePolicy> {
+        let update = if *node_id == self.node_id_1() {
+            self.one_to_two.as_ref()
+        } else if *node_id == self.node_id_2() {
+            self.two_to_one.as_ref()
+        } else {
+            None
+        }?;
+
+        if update.contents.channel_flags & CHANNEL_FLAGS_DISABLED != 0 {
+            return None;
+        }
+
+        Some(FeePolicy {
+            base_fee_msat: update.contents.fee_base_msat as u64,
+            fee_proportional_millionths: update.contents.fee_proportional_millionths as u64,
+            cltv_expiry_delta: update.contents.cltv_expiry_delta as u32,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub channels: Vec<u64>,
+    pub announcement: Option<NodeAnnouncement>,
+}
+
+/// Everything we've learnt from gossip, keyed by short_channel_id.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkGraph {
+    pub channels: BTreeMap<u64, ChannelInfo>,
+    pub nodes: BTreeMap<PublicKey, NodeInfo>,
+}
+
+impl NetworkGraph {
+    pub fn new() -> NetworkGraph {
+        NetworkGraph::default()
+    }
+
+    /// Checks the four signatures, then looks the funding output up on chain:
+    /// it must exist, be unspent and pay to the 2-of-2 of the announced keys.
+    pub async fn add_channel_announcement<C: ChainSource>(&mut self, msg: &ChannelAnnouncement, chain: &C) -> Result<(), GossipError> {
+        if msg.contents.chain_hash != regtest_chain_hash() {
+            return Err(GossipError::WrongChain);
+        }
+        verify_channel_announcement(msg)?;
+
+        let short_channel_id = msg.contents.short_channel_id;
+        let (funding_outpoint, funding_output) = get_output_by_scid(chain, short_channel_id).await?;
+
+        if !funding_script_pubkeys(&msg.contents.bitcoin_key_1, &msg.contents.bitcoin_key_2).contains(&funding_output.script_pubkey) {
+            return Err(GossipError::FundingScriptMismatch(short_channel_id));
+        }
+        if !chain.is_unspent(&funding_outpoint).await? {
+            return Err(GossipError::FundingOutputSpent(short_channel_id));
+        }
+
+        for node_id in [msg.contents.node_id_1, msg.contents.node_id_2] {
+            let node = self.nodes.entry(node_id).or_default();
+            if !node.channels.contains(&short_channel_id) {
+                node.channels.push(short_channel_id);
+            }
+        }
+
+        self.channels.insert(
+            short_channel_id,
+            ChannelInfo {
+                announcement: msg.clone(),
+                funding_outpoint,
+                capacity_sat: funding_output.value.to_sat(),
+                one_to_two: None,
+                two_to_one: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Node announcements are only accepted for nodes with an announced channel.
+    pub fn add_node_announcement(&mut self, msg: &NodeAnnouncement) -> Result<(), GossipError> {
+        verify_node_announcement(msg)?;
+
+        let node = self
+            .nodes
+            .get_mut(&msg.contents.node_id)
+            .ok_or(GossipError::UnknownNode(msg.contents.node_id))?;
+
+        if let Some(existing) = &node.announcement
+            && existing.contents.timestamp >= msg.contents.timestamp
+        {
+            return Err(GossipError::OutdatedUpdate);
+        }
+
+        node.announcement = Some(msg.clone());
+        Ok(())
+    }
+
+    pub fn add_channel_update(&mut self, msg: &ChannelUpdate) -> Result<(), GossipError> {
+        if msg.contents.chain_hash != regtest_chain_hash() {
+            return Err(GossipError::WrongChain);
+        }
+
+        let short_channel_id = msg.contents.short_channel_id;
+        let channel = self
+            .channels
+            .get_mut(&short_channel_id)
+            .ok_or(GossipError::UnknownChannel(short_channel_id))?;
+
+        let from_node_2 = msg.contents.channel_flags & CHANNEL_FLAGS_DIRECTION != 0;
+        let node_id = if from_node_2 { channel.node_id_2() } else { channel.node_id_1() };
+        verify_channel_update(msg, &node_id)?;
+
+        let existing = if from_node_2 { &mut channel.two_to_one } else { &mut channel.one_to_two };
+        if let Some(update) = existing
+            && update.contents.timestamp >= msg.contents.timestamp
+        {
+            return Err(GossipError::OutdatedUpdate);
+        }
+
+        *existing = Some(msg.clone());
+        Ok(())
+    }
+
+    /// Drops a channel, e.g. once its funding output has been spent.
+    pub fn remove_channel(&mut self, short_channel_id: u64) -> Option<ChannelInfo> {
+        let channel = self.channels.remove(&short_channel_id)?;
+        for node_id in [channel.node_id_1(), channel.node_id_2()] {
+            if let Some(node) = self.nodes.get_mut(&node_id) {
+                node.channels.retain(|scid| *scid != short_channel_id);
+            }
+        }
+        Some(channel)
+    }
+}
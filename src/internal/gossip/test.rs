@@ -0,0 +1,181 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix::exercises::NodeKeysManager;
+use bitcoin::hashes::Hash;
+use bitcoin::hash_types::Txid;
+use bitcoin::OutPoint;
+use internal::key_utils::secp256k1_private_key;
+use internal::wire::{ChannelAnnouncement, ChannelUpdate, NodeAnnouncement, WireMessage};
+use bitcoin::secp256k1::Secp256k1;
+use internal::multihop::FeePolicy;
+use internal::chain::{ChainError, MockChain};
+use internal::gossip::{create_channel_announcement, create_channel_update, create_node_announcement, verify_channel_announcement,
+                       verify_channel_update, AnnouncementKeys, GossipError, NetworkGraph};
+use lightning::ln::msgs;
+use lightning::util::ser::{Readable, Writeable};
+use lightning::util::scid_utils::scid_from_parts;
+use crate::exercises::solutions::two_of_two_multisig_witness_script;
+use internal::wire::test::payload;
+
+pub fn gossip_keys(seed: u8) -> AnnouncementKeys {
+    AnnouncementKeys {
+        node_secret: NodeKeysManager::new([seed; 32]).node_secret,
+        funding_key: secp256k1_private_key(&[seed; 32]),
+    }
+}
+
+// mines a transaction paying to the 2-of-2 of both funding keys, opener first
+// as our funding transactions do, and returns its short_channel_id
+pub fn mine_funding_output(chain: &MockChain, opener: &AnnouncementKeys, acceptor: &AnnouncementKeys) -> (u64, bitcoin::Transaction) {
+    let script = two_of_two_multisig_witness_script(
+        &bitcoin::PublicKey::new(opener.funding_pubkey()),
+        &bitcoin::PublicKey::new(acceptor.funding_pubkey()),
+    );
+    let funding_tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array([0x11; 32]), chain.tip_height()),
+            ..Default::default()
+        }],
+        output: vec![bitcoin::TxOut { value: bitcoin::Amount::from_sat(5_000_000), script_pubkey: script.to_p2wsh() }],
+    };
+
+    let height = chain.mine_block(vec![funding_tx.clone()]);
+    chain.mine_blocks(5);
+    (scid_from_parts(height as u64, 1, 0).unwrap(), funding_tx)
+}
+
+#[test]
+fn test_gossip_messages_match_ldk() {
+    let secp = Secp256k1::new();
+    let (alice, bob) = (gossip_keys(0x01), gossip_keys(0x02));
+    let policy = FeePolicy { base_fee_msat: 1_000, fee_proportional_millionths: 100, cltv_expiry_delta: 40 };
+
+    let channel_announcement = create_channel_announcement(scid_from_parts(101, 1, 0).unwrap(), &alice, &bob);
+    let ldk_channel_announcement: msgs::ChannelAnnouncement = Readable::read(&mut &payload(&channel_announcement)[..]).unwrap();
+    assert_eq!(ldk_channel_announcement.encode(), payload(&channel_announcement));
+    assert!(lightning::routing::gossip::verify_channel_announcement(&ldk_channel_announcement, &secp).is_ok());
+    assert!(verify_channel_announcement(&channel_announcement).is_ok());
+    assert_eq!(ChannelAnnouncement::decode(&channel_announcement.encode()), Ok(channel_announcement.clone()));
+    assert!(channel_announcement.contents.node_id_1.serialize() < channel_announcement.contents.node_id_2.serialize());
+
+    let node_announcement = create_node_announcement(&alice.node_secret, 1_700_000_000, "alice", [0xff, 0x00, 0x00]);
+    let ldk_node_announcement: msgs::NodeAnnouncement = Readable::read(&mut &payload(&node_announcement)[..]).unwrap();
+    assert_eq!(ldk_node_announcement.encode(), payload(&node_announcement));
+    assert!(lightning::routing::gossip::verify_node_announcement(&ldk_node_announcement, &secp).is_ok());
+    assert_eq!(NodeAnnouncement::decode(&node_announcement.encode()), Ok(node_announcement));
+
+    let channel_update = create_channel_update(&alice.node_secret, 42, 1, 1_700_000_000, &policy, 1, 4_000_000_000).unwrap();
+    let ldk_channel_update: msgs::ChannelUpdate = Readable::read(&mut &payload(&channel_update)[..]).unwrap();
+    assert_eq!(ldk_channel_update.encode(), payload(&channel_update));
+    assert_eq!(ldk_channel_update.contents.fee_base_msat, 1_000);
+    assert_eq!(ldk_channel_update.contents.htlc_maximum_msat, 4_000_000_000);
+    assert!(verify_channel_update(&channel_update, &alice.node_id()).is_ok());
+    assert_eq!(verify_channel_update(&channel_update, &bob.node_id()), Err(GossipError::InvalidSignature));
+    assert_eq!(ChannelUpdate::decode(&channel_update.encode()), Ok(channel_update));
+
+    // a policy the message can't carry is refused, not truncated
+    let too_slow = FeePolicy { cltv_expiry_delta: 70_000, ..policy };
+    assert_eq!(
+        create_channel_update(&alice.node_secret, 42, 1, 1_700_000_000, &too_slow, 1, 4_000_000_000).err(),
+        Some(GossipError::PolicyOutOfRange("cltv_expiry_delta"))
+    );
+}
+
+#[test]
+fn test_network_graph_validates_against_chain() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    chain.mine_blocks(100);
+
+    let (alice, bob, carol) = (gossip_keys(0x01), gossip_keys(0x02), gossip_keys(0x03));
+    let (short_channel_id, _) = mine_funding_output(&chain, &alice, &bob);
+    let mut graph = NetworkGraph::new();
+
+    // no node announcements before the node has a channel
+    let alice_announcement = create_node_announcement(&alice.node_secret, 1, "alice", [0; 3]);
+    assert_eq!(graph.add_node_announcement(&alice_announcement), Err(GossipError::UnknownNode(alice.node_id())));
+
+    // a bad signature, the wrong output and a channel that doesn't exist are all rejected
+    let mut tampered = create_channel_announcement(short_channel_id, &alice, &bob);
+    tampered.contents.excess_data = vec![0x00];
+    assert_eq!(rt.block_on(graph.add_channel_announcement(&tampered, &chain)), Err(GossipError::InvalidSignature));
+
+    let coinbase_scid = scid_from_parts(chain.tip_height() as u64 - 5, 0, 0).unwrap();
+    let wrong_output = create_channel_announcement(coinbase_scid, &alice, &bob);
+    assert_eq!(
+        rt.block_on(graph.add_channel_announcement(&wrong_output, &chain)),
+        Err(GossipError::FundingScriptMismatch(coinbase_scid))
+    );
+
+    let wrong_keys = create_channel_announcement(short_channel_id, &alice, &carol);
+    assert_eq!(
+        rt.block_on(graph.add_channel_announcement(&wrong_keys, &chain)),
+        Err(GossipError::FundingScriptMismatch(short_channel_id))
+    );
+
+    let future_scid = scid_from_parts(1_000, 1, 0).unwrap();
+    assert!(matches!(
+        rt.block_on(graph.add_channel_announcement(&create_channel_announcement(future_scid, &alice, &bob), &chain)),
+        Err(GossipError::Chain(ChainError::BlockNotFound(1_000)))
+    ));
+
+    let announcement = create_channel_announcement(short_channel_id, &alice, &bob);
+    rt.block_on(graph.add_channel_announcement(&announcement, &chain)).unwrap();
+    let channel = graph.channels.get(&short_channel_id).unwrap();
+    assert_eq!(channel.capacity_sat, 5_000_000);
+    assert_eq!(graph.nodes.get(&alice.node_id()).unwrap().channels, vec![short_channel_id]);
+
+    graph.add_node_announcement(&alice_announcement).unwrap();
+    assert_eq!(graph.add_node_announcement(&alice_announcement), Err(GossipError::OutdatedUpdate));
+
+    // each side's update is checked against its own node id
+    let policy = FeePolicy { base_fee_msat: 1_000, fee_proportional_millionths: 100, cltv_expiry_delta: 40 };
+    let alice_direction = if announcement.contents.node_id_1 == alice.node_id() { 0 } else { 1 };
+    let update = create_channel_update(&alice.node_secret, short_channel_id, alice_direction, 10, &policy, 1, 5_000_000_000).unwrap();
+    graph.add_channel_update(&update).unwrap();
+    assert_eq!(graph.add_channel_update(&update), Err(GossipError::OutdatedUpdate));
+
+    let forged = create_channel_update(&carol.node_secret, short_channel_id, 1 - alice_direction, 10, &policy, 1, 5_000_000_000).unwrap();
+    assert_eq!(graph.add_channel_update(&forged), Err(GossipError::InvalidSignature));
+
+    let unknown = create_channel_update(&alice.node_secret, coinbase_scid, 0, 10, &policy, 1, 5_000_000_000).unwrap();
+    assert_eq!(graph.add_channel_update(&unknown), Err(GossipError::UnknownChannel(coinbase_scid)));
+
+    let channel = graph.channels.get(&short_channel_id).unwrap();
+    assert_eq!(channel.policy_from(&alice.node_id()), Some(policy));
+    assert_eq!(channel.policy_from(&bob.node_id()), None);
+}
+
+#[test]
+fn test_network_graph_rejects_spent_funding() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    let (alice, bob) = (gossip_keys(0x01), gossip_keys(0x02));
+    let (short_channel_id, funding_tx) = mine_funding_output(&chain, &alice, &bob);
+
+    // the mock chain checks proof of work like a real one would
+    let tip = chain.block_at(chain.tip_height()).unwrap();
+    assert!(tip.header.validate_pow(tip.header.target()).is_ok());
+    assert_eq!(tip.header.prev_blockhash, chain.block_at(chain.tip_height() - 1).unwrap().block_hash());
+
+    let closing_tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+            ..Default::default()
+        }],
+        output: Vec::new(),
+    };
+    chain.mine_block(vec![closing_tx]);
+
+    let mut graph = NetworkGraph::new();
+    let announcement = create_channel_announcement(short_channel_id, &alice, &bob);
+    assert_eq!(
+        rt.block_on(graph.add_channel_announcement(&announcement, &chain)),
+        Err(GossipError::FundingOutputSpent(short_channel_id))
+    );
+    assert!(graph.channels.is_empty());
+}
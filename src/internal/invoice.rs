@@ -0,0 +1,115 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use lightning_invoice::{
+    Bolt11Invoice, Bolt11InvoiceDescription, CreationError, Currency, InvoiceBuilder, ParseOrSemanticError,
+    PaymentSecret, RouteHint,
+};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod test;
+
+// BOLT 11 defaults when the invoice doesn't say otherwise
+pub const DEFAULT_EXPIRY_SECS: u64 = 3600;
+pub const DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA: u64 = 18;
+
+#[derive(Debug)]
+pub enum InvoiceError {
+    Creation(CreationError),
+    Parse(ParseOrSemanticError),
+    WrongNetwork(Currency),
+}
+
+/// Everything a payer needs out of an invoice to build an HTLC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInvoice {
+    pub payee: PublicKey,
+    pub amount_msat: Option<u64>,
+    pub payment_hash: [u8; 32],
+    pub payment_secret: [u8; 32],
+    pub description: String,
+    pub timestamp: u64,
+    pub expiry_secs: u64,
+    pub min_final_cltv_expiry_delta: u64,
+    pub route_hints: Vec<RouteHint>,
+}
+
+impl DecodedInvoice {
+    /// The hash160 of the payment hash, as locked into the offered HTLC script.
+    pub fn payment_hash160(&self) -> [u8; 20] {
+        payment_hash160(&self.payment_hash)
+    }
+}
+
+/// Returns a fresh random preimage and its payment hash.
+pub fn generate_preimage() -> ([u8; 32], [u8; 32]) {
+    let preimage: [u8; 32] = rand::random();
+    let payment_hash = Sha256::hash(&preimage).to_byte_array();
+    (preimage, payment_hash)
+}
+
+pub fn payment_hash160(payment_hash: &[u8; 32]) -> [u8; 20] {
+    Ripemd160::hash(payment_hash).to_byte_array()
+}
+
+/// Builds a regtest invoice for `payment_hash`, signed with the node secret so
+/// the payer can recover our node id from it.
+pub fn create_invoice(
+    node_secret: &SecretKey,
+    payment_hash: [u8; 32],
+    amount_msat: Option<u64>,
+    description: &str,
+    expiry_secs: u64,
+    route_hints: Vec<RouteHint>,
+) -> Result<Bolt11Invoice, InvoiceError> {
+    let payment_secret = PaymentSecret(rand::random());
+
+    let mut builder = InvoiceBuilder::new(Currency::Regtest)
+        .description(description.to_string())
+        .payment_hash(Sha256::from_byte_array(payment_hash))
+        .payment_secret(payment_secret)
+        .duration_since_epoch(SystemTime::now().duration_since(UNIX_EPOCH).unwrap())
+        .min_final_cltv_expiry_delta(DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA)
+        .expiry_time(Duration::from_secs(expiry_secs));
+
+    if let Some(amount_msat) = amount_msat {
+        builder = builder.amount_milli_satoshis(amount_msat);
+    }
+    for route_hint in route_hints {
+        builder = builder.private_route(route_hint);
+    }
+
+    let secp = Secp256k1::new();
+    builder
+        .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, node_secret))
+        .map_err(InvoiceError::Creation)
+}
+
+/// Parses an invoice and checks its signature. Only regtest invoices are accepted.
+pub fn decode_invoice(invoice: &str) -> Result<DecodedInvoice, InvoiceError> {
+    let invoice = Bolt11Invoice::from_str(invoice.trim()).map_err(InvoiceError::Parse)?;
+
+    if invoice.currency() != Currency::Regtest {
+        return Err(InvoiceError::WrongNetwork(invoice.currency()));
+    }
+
+    let description = match invoice.description() {
+        Bolt11InvoiceDescription::Direct(description) => description.to_string(),
+        Bolt11InvoiceDescription::Hash(hash) => hash.0.to_string(),
+    };
+
+    Ok(DecodedInvoice {
+        payee: invoice.get_payee_pub_key(),
+        amount_msat: invoice.amount_milli_satoshis(),
+        payment_hash: invoice.payment_hash().to_byte_array(),
+        payment_secret: invoice.payment_secret().0,
+        description,
+        timestamp: invoice.duration_since_epoch().as_secs(),
+        expiry_secs: invoice.expiry_time().as_secs(),
+        min_final_cltv_expiry_delta: invoice.min_final_cltv_expiry_delta(),
+        route_hints: invoice.route_hints(),
+    })
+}
@@ -0,0 +1,52 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix::exercises::NodeKeysManager;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use internal::invoice::{create_invoice, decode_invoice, generate_preimage, InvoiceError};
+use lightning_invoice::{Currency, RouteHint, RouteHintHop, RoutingFees};
+
+#[test]
+fn test_invoice_round_trip() {
+    let node = NodeKeysManager::new([0x02; 32]);
+    let (preimage, payment_hash) = generate_preimage();
+    assert_eq!(Sha256::hash(&preimage).to_byte_array(), payment_hash);
+
+    let route_hint = RouteHint(vec![RouteHintHop {
+        src_node_id: secp256k1pubkey_from_private_key(&[0x03; 32]),
+        short_channel_id: (103 << 40) | (1 << 16),
+        fees: RoutingFees { base_msat: 1_000, proportional_millionths: 100 },
+        cltv_expiry_delta: 40,
+        htlc_minimum_msat: None,
+        htlc_maximum_msat: None,
+    }]);
+
+    let invoice = create_invoice(&node.node_secret, payment_hash, Some(405_000_000), "coffee", 600, vec![route_hint.clone()])
+        .unwrap()
+        .to_string();
+    assert!(invoice.starts_with("lnbcrt4050u1"));
+
+    let decoded = decode_invoice(&invoice).unwrap();
+    assert_eq!(decoded.payee, node.node_id);
+    assert_eq!(decoded.amount_msat, Some(405_000_000));
+    assert_eq!(decoded.payment_hash, payment_hash);
+    assert_eq!(decoded.description, "coffee");
+    assert_eq!(decoded.expiry_secs, 600);
+    assert_eq!(decoded.route_hints, vec![route_hint]);
+    assert_eq!(decoded.payment_hash160(), Ripemd160::hash(&payment_hash).to_byte_array());
+}
+
+#[test]
+fn test_invoice_rejects_other_networks() {
+    // BOLT 11 example: "Please make a donation of any amount using payment_hash 0001020304050607080900010203040506070809000102030405060708090102 to me @03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad"
+    let mainnet_invoice = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+
+    match decode_invoice(mainnet_invoice) {
+        Err(InvoiceError::WrongNetwork(Currency::Bitcoin)) => {}
+        other => panic!("expected a wrong network error, got {:?}", other),
+    }
+
+    assert!(matches!(decode_invoice("lnbcrt1notaninvoice"), Err(InvoiceError::Parse(_))));
+}
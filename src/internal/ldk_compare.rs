@@ -0,0 +1,352 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises::solutions::{build_commitment_transaction, build_funding_transaction, build_htlc_commitment_transaction,
+                                  build_htlc_timeout_transaction};
+use bitcoin::absolute::LockTime;
+use bitcoin::amount::Amount;
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
+use bitcoin::transaction::Version;
+use bitcoin::{OutPoint, PublicKey as BitcoinPublicKey, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use internal::channel::{htlc_tx_fee_sat, ChannelPublicKeys, INITIAL_COMMITMENT_NUMBER};
+use internal::invoice::payment_hash160;
+use internal::script_utils::{build_htlc_offerer_witness_script, build_htlc_receiver_witness_script};
+use lightning::chain::transaction::OutPoint as LdkOutPoint;
+use lightning::ln::chan_utils::{
+    self, ChannelPublicKeys as LdkChannelPublicKeys, ChannelTransactionParameters, CommitmentTransaction as LdkCommitmentTransaction,
+    CounterpartyChannelTransactionParameters, HTLCOutputInCommitment, TxCreationKeys,
+};
+use lightning::ln::features::ChannelTypeFeatures;
+use lightning::ln::types::PaymentHash;
+
+pub mod test;
+
+/// One place where a transaction we built parts ways with LDK's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxDifference {
+    Version { ours: Version, ldk: Version },
+    LockTime { ours: LockTime, ldk: LockTime },
+    InputCount { ours: usize, ldk: usize },
+    InputOutpoint { index: usize, ours: OutPoint, ldk: OutPoint },
+    InputSequence { index: usize, ours: Sequence, ldk: Sequence },
+    OutputCount { ours: usize, ldk: usize },
+    OutputValue { index: usize, ours: Amount, ldk: Amount },
+    OutputScript { index: usize, ours: ScriptBuf, ldk: ScriptBuf },
+}
+
+/// The same transaction built by the exercises and by LDK's `chan_utils`.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub ours: Transaction,
+    pub ldk: Transaction,
+    pub differences: Vec<TxDifference>,
+}
+
+impl Comparison {
+    pub fn new(ours: Transaction, ldk: Transaction) -> Comparison {
+        let differences = diff_transactions(&ours, &ldk);
+        Comparison { ours, ldk, differences }
+    }
+
+    pub fn matches(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// True when the only thing wrong is the output order, e.g. we skipped
+    /// BIP 69 sorting but every output is there with the right value and script.
+    pub fn only_output_order_differs(&self) -> bool {
+        let header_matches = self.differences.iter().all(|difference| {
+            matches!(difference, TxDifference::OutputValue { .. } | TxDifference::OutputScript { .. })
+        });
+        header_matches && !self.matches() && sorted_outputs(&self.ours) == sorted_outputs(&self.ldk)
+    }
+}
+
+/// Diffs two unsigned transactions field by field and output by output.
+/// Witnesses are ignored since neither side has signed yet.
+pub fn diff_transactions(ours: &Transaction, ldk: &Transaction) -> Vec<TxDifference> {
+    let mut differences = Vec::new();
+
+    if ours.version != ldk.version {
+        differences.push(TxDifference::Version { ours: ours.version, ldk: ldk.version });
+    }
+    if ours.lock_time != ldk.lock_time {
+        differences.push(TxDifference::LockTime { ours: ours.lock_time, ldk: ldk.lock_time });
+    }
+
+    if ours.input.len() != ldk.input.len() {
+        differences.push(TxDifference::InputCount { ours: ours.input.len(), ldk: ldk.input.len() });
+    }
+    for (index, (our_input, ldk_input)) in ours.input.iter().zip(ldk.input.iter()).enumerate() {
+        if our_input.previous_output != ldk_input.previous_output {
+            differences.push(TxDifference::InputOutpoint {
+                index,
+                ours: our_input.previous_output,
+                ldk: ldk_input.previous_output,
+            });
+        }
+        if our_input.sequence != ldk_input.sequence {
+            differences.push(TxDifference::InputSequence { index, ours: our_input.sequence, ldk: ldk_input.sequence });
+        }
+    }
+
+    if ours.output.len() != ldk.output.len() {
+        differences.push(TxDifference::OutputCount { ours: ours.output.len(), ldk: ldk.output.len() });
+    }
+    for (index, (our_output, ldk_output)) in ours.output.iter().zip(ldk.output.iter()).enumerate() {
+        if our_output.value != ldk_output.value {
+            differences.push(TxDifference::OutputValue { index, ours: our_output.value, ldk: ldk_output.value });
+        }
+        if our_output.script_pubkey != ldk_output.script_pubkey {
+            differences.push(TxDifference::OutputScript {
+                index,
+                ours: our_output.script_pubkey.clone(),
+                ldk: ldk_output.script_pubkey.clone(),
+            });
+        }
+    }
+
+    differences
+}
+
+fn sorted_outputs(tx: &Transaction) -> Vec<(Amount, ScriptBuf)> {
+    let mut outputs: Vec<(Amount, ScriptBuf)> =
+        tx.output.iter().map(|output| (output.value, output.script_pubkey.clone())).collect();
+    outputs.sort();
+    outputs
+}
+
+//
+// LDK channel setup
+//
+
+pub fn ldk_channel_public_keys(keys: &ChannelPublicKeys) -> LdkChannelPublicKeys {
+    LdkChannelPublicKeys {
+        funding_pubkey: keys.funding_pubkey,
+        revocation_basepoint: keys.revocation_basepoint.into(),
+        payment_point: keys.payment_basepoint,
+        delayed_payment_basepoint: keys.delayed_payment_basepoint.into(),
+        htlc_basepoint: keys.htlc_basepoint.into(),
+    }
+}
+
+/// A static_remote_key channel the holder opened, seen from the holder's
+/// side. Both sides pick the same `to_self_delay`.
+pub fn ldk_channel_parameters(
+    holder_pubkeys: &ChannelPublicKeys,
+    counterparty_pubkeys: &ChannelPublicKeys,
+    funding_outpoint: OutPoint,
+    to_self_delay: u16,
+) -> ChannelTransactionParameters {
+    ChannelTransactionParameters {
+        holder_pubkeys: ldk_channel_public_keys(holder_pubkeys),
+        holder_selected_contest_delay: to_self_delay,
+        is_outbound_from_holder: true,
+        counterparty_parameters: Some(CounterpartyChannelTransactionParameters {
+            pubkeys: ldk_channel_public_keys(counterparty_pubkeys),
+            selected_contest_delay: to_self_delay,
+        }),
+        funding_outpoint: Some(LdkOutPoint { txid: funding_outpoint.txid, index: funding_outpoint.vout as u16 }),
+        channel_type_features: ChannelTypeFeatures::only_static_remote_key(),
+    }
+}
+
+/// The keys for the holder's commitment at `per_commitment_point`, derived by LDK.
+pub fn ldk_commitment_keys(
+    per_commitment_point: &PublicKey,
+    holder_pubkeys: &ChannelPublicKeys,
+    counterparty_pubkeys: &ChannelPublicKeys,
+) -> TxCreationKeys {
+    let secp_ctx = Secp256k1::new();
+    TxCreationKeys::from_channel_static_keys(
+        per_commitment_point,
+        &ldk_channel_public_keys(holder_pubkeys),
+        &ldk_channel_public_keys(counterparty_pubkeys),
+        &secp_ctx,
+    )
+}
+
+pub fn ldk_htlc(offered: bool, amount_msat: u64, payment_hash: [u8; 32], cltv_expiry: u32) -> HTLCOutputInCommitment {
+    HTLCOutputInCommitment {
+        offered,
+        amount_msat,
+        cltv_expiry,
+        payment_hash: PaymentHash(payment_hash),
+        transaction_output_index: None,
+    }
+}
+
+//
+// Side by side builders
+//
+
+/// LDK doesn't build funding transactions, the wallet does, so its side is
+/// our inputs paying to the multisig script from `make_funding_redeemscript`.
+pub fn compare_funding_transaction(
+    txins: Vec<TxIn>,
+    holder_funding_pubkey: &PublicKey,
+    counterparty_funding_pubkey: &PublicKey,
+    amount: u64,
+) -> Comparison {
+    let ours = build_funding_transaction(
+        txins.clone(),
+        &BitcoinPublicKey::new(*holder_funding_pubkey),
+        &BitcoinPublicKey::new(*counterparty_funding_pubkey),
+        amount,
+    );
+
+    let ldk = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: txins,
+        output: vec![TxOut {
+            value: Amount::from_sat(amount),
+            script_pubkey: chan_utils::make_funding_redeemscript(holder_funding_pubkey, counterparty_funding_pubkey).to_p2wsh(),
+        }],
+    };
+
+    Comparison::new(ours, ldk)
+}
+
+/// Builds the holder's commitment, without HTLCs if `htlc` is None. The
+/// exercises only know offered HTLCs, so a received one is left out of ours
+/// and shows up as a missing output.
+///
+/// `commitment_number` counts up from 0 as the exercises do; LDK counts down
+/// from `INITIAL_COMMITMENT_NUMBER`.
+pub fn compare_commitment_transaction(
+    params: &ChannelTransactionParameters,
+    keys: &TxCreationKeys,
+    commitment_number: u64,
+    to_local_sat: u64,
+    to_remote_sat: u64,
+    htlc: Option<&mut HTLCOutputInCommitment>,
+) -> (Comparison, LdkCommitmentTransaction) {
+    let directed = params.as_holder_broadcastable();
+    let holder_pubkeys = directed.broadcaster_pubkeys();
+    let counterparty_pubkeys = directed.countersignatory_pubkeys();
+    let funding_outpoint = directed.funding_outpoint();
+
+    let revocation_pubkey = BitcoinPublicKey::new(keys.revocation_key.to_public_key());
+    let delayed_pubkey = BitcoinPublicKey::new(keys.broadcaster_delayed_payment_key.to_public_key());
+    let remote_pubkey = BitcoinPublicKey::new(counterparty_pubkeys.payment_point);
+
+    let ours = match htlc.as_deref() {
+        Some(htlc) if htlc.offered => build_htlc_commitment_transaction(
+            funding_outpoint,
+            &holder_pubkeys.payment_point,
+            &counterparty_pubkeys.payment_point,
+            commitment_number,
+            &revocation_pubkey,
+            &BitcoinPublicKey::new(keys.countersignatory_htlc_key.to_public_key()),
+            &BitcoinPublicKey::new(keys.broadcaster_htlc_key.to_public_key()),
+            &delayed_pubkey,
+            remote_pubkey,
+            directed.contest_delay() as i64,
+            &payment_hash160(&htlc.payment_hash.0),
+            htlc.amount_msat / 1000,
+            to_local_sat,
+            to_remote_sat,
+        ),
+        _ => build_commitment_transaction(
+            funding_outpoint,
+            &holder_pubkeys.payment_point,
+            &counterparty_pubkeys.payment_point,
+            commitment_number,
+            &revocation_pubkey,
+            &delayed_pubkey,
+            remote_pubkey,
+            directed.contest_delay() as i64,
+            to_local_sat,
+            to_remote_sat,
+        ),
+    };
+
+    let mut htlcs: Vec<(HTLCOutputInCommitment, ())> = htlc.as_deref().map(|htlc| (htlc.clone(), ())).into_iter().collect();
+    let ldk_commitment = LdkCommitmentTransaction::new_with_auxiliary_htlc_data(
+        INITIAL_COMMITMENT_NUMBER - commitment_number,
+        to_local_sat,
+        to_remote_sat,
+        holder_pubkeys.funding_pubkey,
+        counterparty_pubkeys.funding_pubkey,
+        keys.clone(),
+        0,
+        &mut htlcs,
+        &directed,
+    );
+
+    // LDK decides where the HTLC lands, which the HTLC transaction needs to spend it
+    if let (Some(htlc), Some((placed, _))) = (htlc, htlcs.first()) {
+        htlc.transaction_output_index = placed.transaction_output_index;
+    }
+
+    let ldk = ldk_commitment.trust().built_transaction().transaction.clone();
+    (Comparison::new(ours, ldk), ldk_commitment)
+}
+
+/// Builds the second-stage transaction for `htlc`, which must already have
+/// its output index in the commitment. The exercises only have the timeout
+/// builder, so a received HTLC goes through it with a zero locktime like
+/// `Channel::build_htlc_transaction` does.
+pub fn compare_htlc_transaction(
+    commitment_txid: &Txid,
+    htlc: &HTLCOutputInCommitment,
+    keys: &TxCreationKeys,
+    to_self_delay: u16,
+    feerate_per_kw: u32,
+) -> Comparison {
+    let htlc_txin = TxIn {
+        previous_output: OutPoint {
+            txid: *commitment_txid,
+            vout: htlc.transaction_output_index.expect("HTLC has no output in the commitment"),
+        },
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::ZERO,
+        witness: Witness::new(),
+    };
+
+    let locktime = if htlc.offered { htlc.cltv_expiry } else { 0 };
+    let ours = build_htlc_timeout_transaction(
+        htlc_txin,
+        &BitcoinPublicKey::new(keys.revocation_key.to_public_key()),
+        &BitcoinPublicKey::new(keys.broadcaster_delayed_payment_key.to_public_key()),
+        to_self_delay as i64,
+        locktime,
+        htlc.amount_msat / 1000 - htlc_tx_fee_sat(feerate_per_kw, htlc.offered),
+    );
+
+    let ldk = chan_utils::build_htlc_transaction(
+        commitment_txid,
+        feerate_per_kw,
+        to_self_delay,
+        htlc,
+        &ChannelTypeFeatures::only_static_remote_key(),
+        &keys.broadcaster_delayed_payment_key,
+        &keys.revocation_key,
+    );
+
+    Comparison::new(ours, ldk)
+}
+
+/// Our HTLC witness script next to LDK's, for the holder's commitment.
+pub fn compare_htlc_witness_script(htlc: &HTLCOutputInCommitment, keys: &TxCreationKeys) -> (ScriptBuf, ScriptBuf) {
+    let revocation_pubkey = BitcoinPublicKey::new(keys.revocation_key.to_public_key());
+    let remote_htlc_pubkey = BitcoinPublicKey::new(keys.countersignatory_htlc_key.to_public_key());
+    let local_htlc_pubkey = BitcoinPublicKey::new(keys.broadcaster_htlc_key.to_public_key());
+    let payment_hash160 = payment_hash160(&htlc.payment_hash.0);
+
+    let ours = if htlc.offered {
+        build_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &payment_hash160)
+    } else {
+        build_htlc_receiver_witness_script(
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &payment_hash160,
+            htlc.cltv_expiry,
+        )
+    };
+
+    let ldk = chan_utils::get_htlc_redeemscript(htlc, &ChannelTypeFeatures::only_static_remote_key(), keys);
+    (ours, ldk)
+}
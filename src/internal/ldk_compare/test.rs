@@ -0,0 +1,103 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hash_types::Txid;
+use bitcoin::OutPoint;
+use internal::channel::ChannelPublicKeys;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use crate::exercises::solutions::generate_revocation_pubkey;
+use internal::channel::derive_public_key;
+use internal::ldk_compare::{compare_commitment_transaction, compare_funding_transaction, compare_htlc_transaction, compare_htlc_witness_script,
+                            ldk_channel_parameters, ldk_commitment_keys, ldk_htlc, TxDifference};
+use internal::tx_utils::get_funding_input;
+
+pub fn compare_channel_pubkeys(first_byte: u8) -> ChannelPublicKeys {
+    let pubkey = |byte: u8| secp256k1pubkey_from_private_key(&[byte; 32]);
+    ChannelPublicKeys {
+        funding_pubkey: pubkey(first_byte),
+        revocation_basepoint: pubkey(first_byte + 1),
+        payment_basepoint: pubkey(first_byte + 2),
+        delayed_payment_basepoint: pubkey(first_byte + 3),
+        htlc_basepoint: pubkey(first_byte + 4),
+    }
+}
+
+#[test]
+fn test_funding_transaction_matches_ldk() {
+    let txin = get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000001".to_string(), 1);
+    let first = secp256k1pubkey_from_private_key(&[0x01; 32]);
+    let second = secp256k1pubkey_from_private_key(&[0x02; 32]);
+    let (lower, higher) = if first.serialize() < second.serialize() { (first, second) } else { (second, first) };
+
+    let comparison = compare_funding_transaction(vec![txin.clone()], &lower, &higher, 5_000_000);
+    assert!(comparison.matches(), "{:?}", comparison.differences);
+
+    // BOLT 3 sorts the funding keys, the exercise takes them in the order given
+    let comparison = compare_funding_transaction(vec![txin], &higher, &lower, 5_000_000);
+    assert_eq!(comparison.differences.len(), 1);
+    assert!(matches!(comparison.differences[0], TxDifference::OutputScript { index: 0, .. }));
+}
+
+#[test]
+fn test_commitment_transaction_matches_ldk() {
+    let holder = compare_channel_pubkeys(0x10);
+    let counterparty = compare_channel_pubkeys(0x20);
+    let funding_outpoint = OutPoint { txid: Txid::from_byte_array([0x42; 32]), vout: 0 };
+    let per_commitment_point = secp256k1pubkey_from_private_key(&[0x30; 32]);
+    let params = ldk_channel_parameters(&holder, &counterparty, funding_outpoint, 144);
+    let keys = ldk_commitment_keys(&per_commitment_point, &holder, &counterparty);
+
+    // the keys we feed both builders come out of the same derivation on our side
+    assert_eq!(generate_revocation_pubkey(counterparty.revocation_basepoint, per_commitment_point), keys.revocation_key.to_public_key());
+    assert_eq!(derive_public_key(&holder.delayed_payment_basepoint, &per_commitment_point), keys.broadcaster_delayed_payment_key.to_public_key());
+    assert_eq!(derive_public_key(&holder.htlc_basepoint, &per_commitment_point), keys.broadcaster_htlc_key.to_public_key());
+
+    for commitment_number in [0, 1, 1_000_000] {
+        let (comparison, _) = compare_commitment_transaction(&params, &keys, commitment_number, 3_998_500, 1_000_500, None);
+        assert!(comparison.matches(), "{:?}", comparison.differences);
+    }
+
+    // the exercise always puts to_remote first, LDK sorts by value
+    let (comparison, _) = compare_commitment_transaction(&params, &keys, 1, 1_000_500, 3_998_500, None);
+    assert!(comparison.only_output_order_differs(), "{:?}", comparison.differences);
+}
+
+#[test]
+fn test_htlc_transactions_match_ldk() {
+    let holder = compare_channel_pubkeys(0x10);
+    let counterparty = compare_channel_pubkeys(0x20);
+    let funding_outpoint = OutPoint { txid: Txid::from_byte_array([0x42; 32]), vout: 0 };
+    let per_commitment_point = secp256k1pubkey_from_private_key(&[0x30; 32]);
+    let params = ldk_channel_parameters(&holder, &counterparty, funding_outpoint, 144);
+    let keys = ldk_commitment_keys(&per_commitment_point, &holder, &counterparty);
+    let payment_hash = Sha256::hash(&[0x07; 32]).to_byte_array();
+
+    let mut offered = ldk_htlc(true, 405_000_000, payment_hash, 300);
+    let (ours, ldk) = compare_htlc_witness_script(&offered, &keys);
+    assert_eq!(ours, ldk);
+
+    let (comparison, ldk_commitment) = compare_commitment_transaction(&params, &keys, 1, 3_593_500, 1_000_500, Some(&mut offered));
+    assert!(comparison.matches(), "{:?}", comparison.differences);
+    assert_eq!(offered.transaction_output_index, Some(0));
+
+    let comparison = compare_htlc_transaction(&ldk_commitment.trust().txid(), &offered, &keys, 144, 253);
+    assert!(comparison.matches(), "{:?}", comparison.differences);
+
+    // a larger HTLC sorts after the balances in LDK but stays first in ours
+    let mut large = ldk_htlc(true, 2_000_000_000, payment_hash, 300);
+    let (comparison, _) = compare_commitment_transaction(&params, &keys, 1, 1_998_500, 1_000_500, Some(&mut large));
+    assert!(comparison.only_output_order_differs(), "{:?}", comparison.differences);
+    assert_eq!(large.transaction_output_index, Some(2));
+
+    let mut received = ldk_htlc(false, 405_000_000, payment_hash, 300);
+    let (ours, ldk) = compare_htlc_witness_script(&received, &keys);
+    assert_eq!(ours, ldk);
+
+    // the exercise commitment has no received HTLCs at all
+    let (comparison, ldk_commitment) = compare_commitment_transaction(&params, &keys, 1, 3_593_500, 1_000_500, Some(&mut received));
+    assert!(comparison.differences.contains(&TxDifference::OutputCount { ours: 2, ldk: 3 }));
+
+    let comparison = compare_htlc_transaction(&ldk_commitment.trust().txid(), &received, &keys, 144, 253);
+    assert!(comparison.matches(), "{:?}", comparison.differences);
+}
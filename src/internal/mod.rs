@@ -1,8 +1,29 @@
 
 pub mod bitcoind_client;
+pub mod chain;
 pub mod convert;
 pub mod hex_utils;
 pub mod key_utils;
 pub mod tx_utils;
 pub mod script_utils;
-pub mod sign_utils;
\ No newline at end of file
+pub mod sign_utils;
+pub mod channel;
+pub mod wire;
+pub mod noise;
+pub mod invoice;
+pub mod onion;
+pub mod multihop;
+pub mod gossip;
+pub mod router;
+pub mod scid;
+pub mod watcher;
+pub mod watchtower;
+pub mod store;
+pub mod node;
+pub mod ldk_compare;
+pub mod musig;
+pub mod taproot;
+pub mod script_asm;
+pub mod policy;
+pub mod standardness;
+pub mod cpfp;
@@ -0,0 +1,377 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::{OutPoint, Transaction, Txid};
+use exercises_appendix::exercises::NodeKeysManager;
+use internal::channel::{commitment_round, Channel, ChannelError, ChannelParameters, ChannelPublicKeys, HtlcDirection, INITIAL_COMMITMENT_NUMBER};
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key};
+use internal::onion::{create_onion_packet, peel_onion, HopPayload, OnionError, PaymentData, PeeledOnion};
+use internal::wire::OnionPacket;
+
+pub mod test;
+
+const FEERATE_PER_KW: u32 = 253;
+const DUST_LIMIT_SATOSHIS: u64 = 546;
+const TO_SELF_DELAY: u16 = 144;
+
+/// How a node charges for forwarding over one of its channels, as announced
+/// in its channel_update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeePolicy {
+    pub base_fee_msat: u64,
+    pub fee_proportional_millionths: u64,
+    pub cltv_expiry_delta: u32,
+}
+
+/// A fee, an amount with fees added, or a cltv_expiry with deltas added that
+/// no longer fits its integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmountOverflow;
+
+impl FeePolicy {
+    /// Multiplied out in u128, since routes and fixtures can carry any u64.
+    pub fn fee_msat(&self, amount_msat: u64) -> Result<u64, AmountOverflow> {
+        let proportional = amount_msat as u128 * self.fee_proportional_millionths as u128 / 1_000_000;
+        u64::try_from(self.base_fee_msat as u128 + proportional).map_err(|_| AmountOverflow)
+    }
+}
+
+/// The HTLC offered over one channel of the route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HopAmount {
+    pub amount_msat: u64,
+    pub cltv_expiry: u32,
+}
+
+/// Works backwards from the destination. `policies[i]` belongs to the node
+/// sending over channel `i`, so the payer's own policy is never charged and
+/// every other hop adds its fee and cltv_expiry_delta to what it forwards.
+pub fn compute_hop_amounts(amount_msat: u64, final_cltv_expiry: u32, policies: &[FeePolicy]) -> Result<Vec<HopAmount>, AmountOverflow> {
+    let mut hops = vec![HopAmount { amount_msat, cltv_expiry: final_cltv_expiry }; policies.len()];
+
+    for i in (0..policies.len().saturating_sub(1)).rev() {
+        let next = hops[i + 1];
+        hops[i] = HopAmount {
+            amount_msat: next.amount_msat.checked_add(policies[i + 1].fee_msat(next.amount_msat)?).ok_or(AmountOverflow)?,
+            cltv_expiry: next.cltv_expiry.checked_add(policies[i + 1].cltv_expiry_delta).ok_or(AmountOverflow)?,
+        };
+    }
+
+    Ok(hops)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    Channel(ChannelError),
+    Onion(OnionError),
+    NodeOffline(usize),
+    FeeTooLow(usize),
+    CltvExpiryTooSoon(usize),
+    UnknownChannel(u64),
+    IncorrectPaymentDetails,
+    NoPendingPayment,
+    AmountOverflow,
+}
+
+impl From<ChannelError> for SimulationError {
+    fn from(e: ChannelError) -> SimulationError {
+        SimulationError::Channel(e)
+    }
+}
+
+impl From<AmountOverflow> for SimulationError {
+    fn from(_: AmountOverflow) -> SimulationError {
+        SimulationError::AmountOverflow
+    }
+}
+
+impl From<OnionError> for SimulationError {
+    fn from(e: OnionError) -> SimulationError {
+        SimulationError::Onion(e)
+    }
+}
+
+pub struct SimulatedNode {
+    pub name: String,
+    pub keys: NodeKeysManager,
+    pub online: bool,
+}
+
+/// A channel between node `i` (upstream) and node `i + 1` (downstream). Both
+/// sides are held here and kept in sync by passing messages directly.
+pub struct SimulatedChannel {
+    pub short_channel_id: u64,
+    pub policy: FeePolicy,
+    pub upstream: Channel,
+    pub downstream: Channel,
+    // the HTLC carrying the current payment over this channel
+    pub htlc_id: Option<u64>,
+    pub closed: bool,
+}
+
+/// A channel force-closed to time out an HTLC the downstream node never resolved.
+#[derive(Debug, Clone)]
+pub struct ForceClose {
+    pub channel_index: usize,
+    pub commitment_tx: Transaction,
+    // None when the HTLC was trimmed from the commitment, and lost to fees
+    pub htlc_timeout_tx: Option<Transaction>,
+}
+
+/// An in-process line of nodes, each with a channel to the next one.
+pub struct PaymentSimulator {
+    pub nodes: Vec<SimulatedNode>,
+    pub channels: Vec<SimulatedChannel>,
+    pub height: u32,
+}
+
+fn open_channel(
+    upstream: &NodeKeysManager,
+    downstream: &NodeKeysManager,
+    channel_index: u32,
+    channel_value_satoshis: u64,
+) -> (Channel, Channel) {
+    // in-process only, so the funding transaction is never built
+    let funding_outpoint = OutPoint {
+        txid: Txid::from_byte_array(Sha256::hash(&channel_index.to_be_bytes()).to_byte_array()),
+        vout: 0,
+    };
+
+    let funding_key = |node: &NodeKeysManager| {
+        let mut data = node.node_secret.secret_bytes().to_vec();
+        data.extend_from_slice(&channel_index.to_be_bytes());
+        secp256k1_private_key(&Sha256::hash(&data).to_byte_array())
+    };
+    let first_per_commitment_point = |secret: [u8; 32]| pubkey_from_secret(SecretKey::from_slice(&secret).unwrap());
+
+    let upstream_keys = upstream.derive_channel_keys(channel_index);
+    let downstream_keys = downstream.derive_channel_keys(channel_index);
+    let upstream_funding_key = funding_key(upstream);
+    let downstream_funding_key = funding_key(downstream);
+
+    let params = ChannelParameters {
+        is_outbound: true,
+        funding_outpoint,
+        channel_value_satoshis,
+        push_msat: 0,
+        feerate_per_kw: FEERATE_PER_KW,
+        dust_limit_satoshis: DUST_LIMIT_SATOSHIS,
+        holder_selected_contest_delay: TO_SELF_DELAY,
+        counterparty_selected_contest_delay: TO_SELF_DELAY,
+    };
+
+    let mut upstream_channel = Channel::new(
+        params.clone(),
+        upstream_funding_key,
+        upstream_keys.clone(),
+        ChannelPublicKeys::new(&downstream_funding_key, &downstream_keys),
+        first_per_commitment_point(downstream_keys.build_commitment_secret(INITIAL_COMMITMENT_NUMBER)),
+    );
+    let mut downstream_channel = Channel::new(
+        ChannelParameters { is_outbound: false, ..params },
+        downstream_funding_key,
+        downstream_keys,
+        ChannelPublicKeys::new(&upstream_funding_key, &upstream_keys),
+        first_per_commitment_point(upstream_keys.build_commitment_secret(INITIAL_COMMITMENT_NUMBER)),
+    );
+
+    // funding_created / funding_signed, then channel_ready
+    let commitment_signed = upstream_channel.sign_commitment().unwrap();
+    downstream_channel.receive_commitment_signed(&commitment_signed).unwrap();
+    let commitment_signed = downstream_channel.sign_commitment().unwrap();
+    upstream_channel.receive_commitment_signed(&commitment_signed).unwrap();
+    upstream_channel.receive_channel_ready(downstream_channel.next_per_commitment_point());
+    downstream_channel.receive_channel_ready(upstream_channel.next_per_commitment_point());
+
+    (upstream_channel, downstream_channel)
+}
+
+impl PaymentSimulator {
+    /// Node `i` gets seed `[i + 1; 32]` and opens a channel to node `i + 1`
+    /// with short_channel_id `i + 1`, forwarding under `policies[i]`.
+    pub fn new(names: &[&str], policies: &[FeePolicy], channel_value_satoshis: u64, height: u32) -> PaymentSimulator {
+        assert_eq!(names.len(), policies.len() + 1);
+
+        let nodes: Vec<SimulatedNode> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| SimulatedNode {
+                name: name.to_string(),
+                keys: NodeKeysManager::new([i as u8 + 1; 32]),
+                online: true,
+            })
+            .collect();
+
+        let channels = policies
+            .iter()
+            .enumerate()
+            .map(|(i, policy)| {
+                let (upstream, downstream) =
+                    open_channel(&nodes[i].keys, &nodes[i + 1].keys, i as u32 + 1, channel_value_satoshis);
+                SimulatedChannel {
+                    short_channel_id: i as u64 + 1,
+                    policy: *policy,
+                    upstream,
+                    downstream,
+                    htlc_id: None,
+                    closed: false,
+                }
+            })
+            .collect();
+
+        PaymentSimulator { nodes, channels, height }
+    }
+
+    pub fn policies(&self) -> Vec<FeePolicy> {
+        self.channels.iter().map(|channel| channel.policy).collect()
+    }
+
+    /// The first node pays the last one. Each hop peels its layer of the onion,
+    /// checks it's been paid its fee and given enough blocks, and offers the
+    /// next HTLC.
+    pub fn send_payment(
+        &mut self,
+        amount_msat: u64,
+        payment_hash: [u8; 32],
+        payment_secret: [u8; 32],
+        min_final_cltv_expiry_delta: u32,
+    ) -> Result<Vec<HopAmount>, SimulationError> {
+        let hops = compute_hop_amounts(amount_msat, self.height + min_final_cltv_expiry_delta, &self.policies())?;
+
+        let route: Vec<PublicKey> = self.nodes[1..].iter().map(|node| node.keys.node_id).collect();
+        let payloads: Vec<HopPayload> = (0..hops.len())
+            .map(|i| match self.channels.get(i + 1) {
+                Some(next) => HopPayload::forward(next.short_channel_id, hops[i + 1].amount_msat, hops[i + 1].cltv_expiry),
+                None => HopPayload::receive(
+                    hops[i].amount_msat,
+                    hops[i].cltv_expiry,
+                    Some(PaymentData { payment_secret, total_msat: amount_msat }),
+                ),
+            })
+            .collect();
+
+        let session_key = SecretKey::from_slice(&rand::random::<[u8; 32]>()).unwrap();
+        let mut packet = create_onion_packet(&session_key, &route, &payloads, &payment_hash)?;
+        let mut hop = hops[0];
+
+        for i in 0..self.channels.len() {
+            if !self.nodes[i + 1].online {
+                return Err(SimulationError::NodeOffline(i + 1));
+            }
+
+            let channel = &mut self.channels[i];
+            let update_add_htlc = channel.upstream.add_htlc(hop.amount_msat, payment_hash, hop.cltv_expiry, packet.clone())?;
+            channel.downstream.receive_update_add_htlc(&update_add_htlc)?;
+            commitment_round(&mut channel.upstream, &mut channel.downstream)?;
+            channel.htlc_id = Some(update_add_htlc.htlc_id);
+
+            let node_secret = self.nodes[i + 1].keys.node_secret;
+            match peel_onion(&node_secret, &update_add_htlc.onion_routing_packet, &payment_hash)? {
+                PeeledOnion::Forward { payload, next_packet } => {
+                    let next = self
+                        .channels
+                        .get(i + 1)
+                        .filter(|next| Some(next.short_channel_id) == payload.short_channel_id)
+                        .ok_or(SimulationError::UnknownChannel(payload.short_channel_id.unwrap_or_default()))?;
+
+                    // the forwarding node only accepts if it's paid its own policy
+                    let expected_msat =
+                        next.policy.fee_msat(payload.amt_to_forward).ok().and_then(|fee| payload.amt_to_forward.checked_add(fee));
+                    if expected_msat.is_none_or(|expected_msat| hop.amount_msat < expected_msat) {
+                        return Err(SimulationError::FeeTooLow(i + 1));
+                    }
+                    // an outgoing_cltv_value too close to u32::MAX can't leave room for the delta
+                    let expected_cltv_expiry = payload.outgoing_cltv_value.checked_add(next.policy.cltv_expiry_delta);
+                    if expected_cltv_expiry.is_none_or(|expected_cltv_expiry| hop.cltv_expiry < expected_cltv_expiry) {
+                        return Err(SimulationError::CltvExpiryTooSoon(i + 1));
+                    }
+
+                    hop = HopAmount { amount_msat: payload.amt_to_forward, cltv_expiry: payload.outgoing_cltv_value };
+                    packet = *next_packet;
+                }
+                PeeledOnion::Receive { payload } => {
+                    if hop.amount_msat < payload.amt_to_forward || hop.cltv_expiry != payload.outgoing_cltv_value {
+                        return Err(SimulationError::IncorrectPaymentDetails);
+                    }
+                }
+            }
+        }
+
+        Ok(hops)
+    }
+
+    /// The destination reveals the preimage and every hop claims the HTLC it
+    /// received using the preimage it learnt from the next hop.
+    pub fn fulfill_payment(&mut self, payment_preimage: [u8; 32]) -> Result<(), SimulationError> {
+        if !self.nodes[self.nodes.len() - 1].online {
+            return Err(SimulationError::NodeOffline(self.nodes.len() - 1));
+        }
+
+        for i in (0..self.channels.len()).rev() {
+            let channel = &mut self.channels[i];
+            let htlc_id = channel.htlc_id.ok_or(SimulationError::NoPendingPayment)?;
+
+            let update_fulfill_htlc = channel.downstream.fulfill_htlc(htlc_id, payment_preimage)?;
+            channel.upstream.receive_update_fulfill_htlc(&update_fulfill_htlc)?;
+            commitment_round(&mut channel.downstream, &mut channel.upstream)?;
+            channel.htlc_id = None;
+        }
+
+        Ok(())
+    }
+
+    /// Mines a block. An upstream node whose offered HTLC has expired while the
+    /// downstream node is unreachable goes on-chain to time it out, then fails
+    /// the HTLC it received back to the previous hop. That hop still has its
+    /// cltv_expiry_delta left, so the failure cascades back without any more
+    /// channels closing.
+    pub fn connect_block(&mut self) -> Result<Vec<ForceClose>, SimulationError> {
+        self.height += 1;
+        let mut force_closes = Vec::new();
+
+        for i in (0..self.channels.len()).rev() {
+            let channel = &self.channels[i];
+            let Some(htlc_id) = channel.htlc_id else { continue };
+            if channel.closed || self.nodes[i + 1].online {
+                continue;
+            }
+            let expired = channel.upstream.htlcs.iter().any(|htlc| {
+                htlc.htlc_id == htlc_id && htlc.direction == HtlcDirection::Offered && htlc.cltv_expiry <= self.height
+            });
+            if !expired {
+                continue;
+            }
+
+            // an HTLC below the dust limit plus its HTLC-timeout fee has no
+            // output, so the commitment alone closes the channel
+            let commitment = channel.upstream.holder_commitment().unwrap();
+            let htlc_output = commitment.htlcs.iter().find(|htlc| htlc.htlc_id == htlc_id && htlc.offered);
+            force_closes.push(ForceClose {
+                channel_index: i,
+                commitment_tx: channel.upstream.signed_holder_commitment_transaction().unwrap(),
+                htlc_timeout_tx: htlc_output.map(|htlc_output| channel.upstream.build_htlc_transaction(commitment, htlc_output)),
+            });
+
+            let channel = &mut self.channels[i];
+            channel.closed = true;
+            channel.htlc_id = None;
+
+            // now the outgoing HTLC is resolved, fail the incoming one back
+            for j in (0..i).rev() {
+                if !self.nodes[j].online {
+                    break;
+                }
+                let channel = &mut self.channels[j];
+                let htlc_id = channel.htlc_id.take().ok_or(SimulationError::NoPendingPayment)?;
+                let update_fail_htlc = channel.downstream.fail_htlc(htlc_id, Vec::new())?;
+                channel.upstream.receive_update_fail_htlc(&update_fail_htlc)?;
+                commitment_round(&mut channel.downstream, &mut channel.upstream)?;
+            }
+        }
+
+        Ok(force_closes)
+    }
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::channel::ChannelError;
+use internal::multihop::{compute_hop_amounts, AmountOverflow, FeePolicy, HopAmount, PaymentSimulator, SimulationError};
+use internal::invoice::generate_preimage;
+
+pub fn multihop_policies() -> Vec<FeePolicy> {
+    vec![
+        FeePolicy { base_fee_msat: 0, fee_proportional_millionths: 0, cltv_expiry_delta: 0 },
+        FeePolicy { base_fee_msat: 1_000, fee_proportional_millionths: 100, cltv_expiry_delta: 40 },
+        FeePolicy { base_fee_msat: 500, fee_proportional_millionths: 1_000, cltv_expiry_delta: 144 },
+    ]
+}
+
+#[test]
+fn test_compute_hop_amounts() {
+    let hops = compute_hop_amounts(10_000_000, 118, &multihop_policies()).unwrap();
+
+    // Carol charges 500 + 10_000_000 * 1000 / 1_000_000 and adds 144 blocks
+    assert_eq!(hops[2], HopAmount { amount_msat: 10_000_000, cltv_expiry: 118 });
+    assert_eq!(hops[1], HopAmount { amount_msat: 10_010_500, cltv_expiry: 262 });
+    // Bob charges 1000 + 10_010_500 * 100 / 1_000_000 on top
+    assert_eq!(hops[0], HopAmount { amount_msat: 10_012_501, cltv_expiry: 302 });
+
+    // fees past u64::MAX msat are an error rather than a wrapped amount
+    let greedy = FeePolicy { base_fee_msat: 0, fee_proportional_millionths: u64::MAX, cltv_expiry_delta: 0 };
+    assert_eq!(greedy.fee_msat(1_000_000), Ok(u64::MAX));
+    assert_eq!(greedy.fee_msat(2_000_000), Err(AmountOverflow));
+    assert_eq!(compute_hop_amounts(2_000_000, 118, &[greedy, greedy]), Err(AmountOverflow));
+
+    let slow = FeePolicy { base_fee_msat: 0, fee_proportional_millionths: 0, cltv_expiry_delta: u32::MAX };
+    assert_eq!(compute_hop_amounts(1_000, 118, &[slow, slow]), Err(AmountOverflow));
+}
+
+#[test]
+fn test_multihop_payment_fulfilled() {
+    let mut sim = PaymentSimulator::new(&["Alice", "Bob", "Carol", "Dave"], &multihop_policies(), 1_000_000, 100);
+    let (preimage, payment_hash) = generate_preimage();
+
+    let hops = sim.send_payment(10_000_000, payment_hash, [0x07; 32], 18).unwrap();
+    for (channel, hop) in sim.channels.iter().zip(&hops) {
+        assert_eq!(channel.upstream.holder_balance_msat(), 1_000_000_000 - hop.amount_msat);
+        assert_eq!(channel.downstream.holder_balance_msat(), 0);
+    }
+
+    // the wrong preimage can't settle anything
+    assert_eq!(
+        sim.fulfill_payment([0x00; 32]),
+        Err(SimulationError::Channel(ChannelError::InvalidPreimage))
+    );
+
+    sim.fulfill_payment(preimage).unwrap();
+    for (channel, hop) in sim.channels.iter().zip(&hops) {
+        assert!(channel.upstream.htlcs.is_empty());
+        assert_eq!(channel.downstream.holder_balance_msat(), hop.amount_msat);
+        assert_eq!(channel.upstream.counterparty_balance_msat(), hop.amount_msat);
+    }
+
+    // each forwarding node kept exactly its fee
+    assert_eq!(hops[0].amount_msat - hops[1].amount_msat, 2_001);
+    assert_eq!(hops[1].amount_msat - hops[2].amount_msat, 10_500);
+}
+
+#[test]
+fn test_multihop_timeout_cascade() {
+    let mut sim = PaymentSimulator::new(&["Alice", "Bob", "Carol", "Dave"], &multihop_policies(), 1_000_000, 100);
+    let (_, payment_hash) = generate_preimage();
+    let hops = sim.send_payment(10_000_000, payment_hash, [0x07; 32], 18).unwrap();
+
+    // Dave disappears holding the HTLC
+    sim.nodes[3].online = false;
+
+    while sim.height < hops[2].cltv_expiry - 1 {
+        assert!(sim.connect_block().unwrap().is_empty());
+    }
+
+    // at Carol's expiry she closes to Dave and fails back to Bob, well before
+    // Bob's own HTLC to her would expire
+    let force_closes = sim.connect_block().unwrap();
+    assert_eq!(force_closes.len(), 1);
+    assert_eq!(force_closes[0].channel_index, 2);
+    assert_eq!(force_closes[0].htlc_timeout_tx.as_ref().unwrap().lock_time.to_consensus_u32(), hops[2].cltv_expiry);
+    assert!(sim.height < hops[1].cltv_expiry);
+    assert!(sim.channels[2].closed);
+
+    // only the channel to the offline node was closed, the others are back to their opening balances
+    for channel in &sim.channels[..2] {
+        assert!(!channel.closed);
+        assert!(channel.upstream.htlcs.is_empty());
+        assert_eq!(channel.upstream.holder_balance_msat(), 1_000_000_000);
+    }
+
+    // nothing left to time out
+    assert!(sim.connect_block().unwrap().is_empty());
+}
+
+#[test]
+fn test_multihop_timeout_of_a_trimmed_htlc() {
+    let mut sim = PaymentSimulator::new(&["Alice", "Bob", "Carol", "Dave"], &multihop_policies(), 1_000_000, 100);
+    let (_, payment_hash) = generate_preimage();
+    // 500 sat is under the dust limit plus the HTLC-timeout fee, so it has no output
+    let hops = sim.send_payment(500_000, payment_hash, [0x07; 32], 18).unwrap();
+    assert!(sim.channels[2].upstream.holder_commitment().unwrap().htlcs.is_empty());
+
+    sim.nodes[3].online = false;
+    while sim.height < hops[2].cltv_expiry - 1 {
+        assert!(sim.connect_block().unwrap().is_empty());
+    }
+
+    let force_closes = sim.connect_block().unwrap();
+    assert_eq!(force_closes.len(), 1);
+    assert_eq!(force_closes[0].channel_index, 2);
+    assert!(force_closes[0].htlc_timeout_tx.is_none());
+    assert!(sim.channels[2].closed);
+    assert!(sim.channels[1].upstream.htlcs.is_empty());
+}
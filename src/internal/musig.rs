@@ -0,0 +1,471 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::TapSighashType;
+use bitcoin::taproot::TapTweakHash;
+use bitcoin::{Transaction, TxOut, Witness};
+use internal::sign_utils::generate_taproot_keypath_message;
+
+pub mod test;
+
+// BIP 327: MuSig2 for two (or more) signers producing one BIP 340 signature.
+// secp256k1 only gives us key tweaks, so scalars and points are worked with
+// through those, with None standing in for zero and the point at infinity.
+
+// the group order n, for reducing hashes
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+pub const PUBNONCE_LEN: usize = 66;
+pub const SECNONCE_LEN: usize = 97;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MusigError {
+    // the aggregate or tweaked key came out as the point at infinity
+    InfiniteKey,
+    InvalidTweak,
+    InvalidPubNonce(usize),
+    InvalidSecNonce,
+    InvalidPartialSig(usize),
+    // the signer's key isn't one of the aggregated keys
+    UnknownSigner,
+    SecretKeyMismatch,
+}
+
+//
+// Scalar and point arithmetic
+//
+
+fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::hash(tag.as_bytes());
+    let mut engine = Sha256::engine();
+    engine.input(tag_hash.as_byte_array());
+    engine.input(tag_hash.as_byte_array());
+    engine.input(data);
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+// a hash is below 2n, so subtracting n once is enough
+fn scalar_from_hash(hash: [u8; 32]) -> Scalar {
+    if let Ok(scalar) = Scalar::from_be_bytes(hash) {
+        return scalar;
+    }
+    let mut reduced = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut byte = hash[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+        borrow = if byte < 0 { 1 } else { 0 };
+        if byte < 0 {
+            byte += 256;
+        }
+        reduced[i] = byte as u8;
+    }
+    Scalar::from_be_bytes(reduced).unwrap()
+}
+
+fn scalar_add(a: Scalar, b: Scalar) -> Scalar {
+    if a == Scalar::ZERO {
+        return b;
+    }
+    // the only failure is the sum wrapping to zero
+    SecretKey::from_slice(&a.to_be_bytes()).unwrap().add_tweak(&b).map(Scalar::from).unwrap_or(Scalar::ZERO)
+}
+
+fn scalar_mul(a: Scalar, b: Scalar) -> Scalar {
+    if a == Scalar::ZERO || b == Scalar::ZERO {
+        return Scalar::ZERO;
+    }
+    SecretKey::from_slice(&a.to_be_bytes()).unwrap().mul_tweak(&b).map(Scalar::from).unwrap()
+}
+
+fn scalar_negate(a: Scalar) -> Scalar {
+    if a == Scalar::ZERO {
+        return a;
+    }
+    SecretKey::from_slice(&a.to_be_bytes()).unwrap().negate().into()
+}
+
+fn generator_mul(scalar: Scalar) -> Option<PublicKey> {
+    let secret = SecretKey::from_slice(&scalar.to_be_bytes()).ok()?;
+    Some(PublicKey::from_secret_key(&Secp256k1::new(), &secret))
+}
+
+fn point_mul(point: &PublicKey, scalar: Scalar) -> Option<PublicKey> {
+    point.mul_tweak(&Secp256k1::new(), &scalar).ok()
+}
+
+fn point_add(a: Option<PublicKey>, b: Option<PublicKey>) -> Option<PublicKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+        (Some(point), None) | (None, Some(point)) => Some(point),
+        (None, None) => None,
+    }
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+// 1 or -1 depending on whether `point` needs negating to get an even y
+fn even_y_factor(point: &PublicKey) -> Scalar {
+    if has_even_y(point) { Scalar::ONE } else { scalar_negate(Scalar::ONE) }
+}
+
+fn xbytes(point: &PublicKey) -> [u8; 32] {
+    point.x_only_public_key().0.serialize()
+}
+
+// an all zero encoding is the point at infinity, which is fine in an aggregate nonce
+fn parse_point_ext(bytes: &[u8]) -> Result<Option<PublicKey>, ()> {
+    if bytes.iter().all(|byte| *byte == 0) {
+        return Ok(None);
+    }
+    PublicKey::from_slice(bytes).map(Some).map_err(|_| ())
+}
+
+fn serialize_point_ext(point: Option<PublicKey>) -> [u8; 33] {
+    point.map(|point| point.serialize()).unwrap_or([0u8; 33])
+}
+
+//
+// Key aggregation
+//
+
+pub fn key_sort(pubkeys: &[PublicKey]) -> Vec<PublicKey> {
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort_by_key(|pubkey| pubkey.serialize());
+    sorted
+}
+
+/// The aggregate key and everything signing needs to know about how it was
+/// put together, including any tweaks applied since.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    pubkeys: Vec<PublicKey>,
+    aggregate: PublicKey,
+    // accumulated sign flip and tweak, `gacc` and `tacc` in BIP 327
+    sign_factor: Scalar,
+    tweak: Scalar,
+}
+
+impl KeyAggContext {
+    pub fn new(pubkeys: &[PublicKey]) -> Result<KeyAggContext, MusigError> {
+        let mut aggregate = None;
+        for pubkey in pubkeys {
+            let coefficient = key_agg_coefficient(pubkeys, pubkey);
+            aggregate = point_add(aggregate, point_mul(pubkey, coefficient));
+        }
+
+        Ok(KeyAggContext {
+            pubkeys: pubkeys.to_vec(),
+            aggregate: aggregate.ok_or(MusigError::InfiniteKey)?,
+            sign_factor: Scalar::ONE,
+            tweak: Scalar::ZERO,
+        })
+    }
+
+    pub fn aggregate_pubkey(&self) -> PublicKey {
+        self.aggregate
+    }
+
+    pub fn xonly_pubkey(&self) -> XOnlyPublicKey {
+        self.aggregate.x_only_public_key().0
+    }
+
+    /// Adds `tweak` to the aggregate key. An x-only tweak first flips the
+    /// key to even y, as taproot does.
+    pub fn with_tweak(mut self, tweak: [u8; 32], is_xonly: bool) -> Result<KeyAggContext, MusigError> {
+        let tweak = Scalar::from_be_bytes(tweak).map_err(|_| MusigError::InvalidTweak)?;
+        let flip = if is_xonly { even_y_factor(&self.aggregate) } else { Scalar::ONE };
+
+        let flipped = if flip == Scalar::ONE { self.aggregate } else { self.aggregate.negate(&Secp256k1::new()) };
+        self.aggregate = point_add(Some(flipped), generator_mul(tweak)).ok_or(MusigError::InfiniteKey)?;
+        self.sign_factor = scalar_mul(flip, self.sign_factor);
+        self.tweak = scalar_add(tweak, scalar_mul(flip, self.tweak));
+        Ok(self)
+    }
+
+    /// The BIP 86 tweak for a key path only output, which is what
+    /// `ScriptBuf::new_p2tr` commits to with no script tree.
+    pub fn with_taproot_tweak(self) -> Result<KeyAggContext, MusigError> {
+        let tweak = TapTweakHash::from_key_and_tweak(self.xonly_pubkey(), None).to_byte_array();
+        self.with_tweak(tweak, true)
+    }
+}
+
+fn key_agg_coefficient(pubkeys: &[PublicKey], pubkey: &PublicKey) -> Scalar {
+    // the second distinct key gets a coefficient of 1, which saves a multiplication
+    let second_key = pubkeys.iter().find(|key| **key != pubkeys[0]);
+    if second_key == Some(pubkey) {
+        return Scalar::ONE;
+    }
+
+    let mut list = Vec::with_capacity(pubkeys.len() * 33);
+    for key in pubkeys {
+        list.extend_from_slice(&key.serialize());
+    }
+    let list_hash = tagged_hash("KeyAgg list", &list);
+
+    let mut data = list_hash.to_vec();
+    data.extend_from_slice(&pubkey.serialize());
+    scalar_from_hash(tagged_hash("KeyAgg coefficient", &data))
+}
+
+//
+// Nonces
+//
+
+/// The secret half of a signer's nonce pair. It isn't Clone so it can only
+/// be used for one signature: reusing it leaks the secret key.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SecNonce {
+    k1: Scalar,
+    k2: Scalar,
+    pubkey: PublicKey,
+}
+
+impl SecNonce {
+    pub fn from_bytes(bytes: &[u8; SECNONCE_LEN]) -> Result<SecNonce, MusigError> {
+        let k1 = Scalar::from_be_bytes(bytes[..32].try_into().unwrap()).map_err(|_| MusigError::InvalidSecNonce)?;
+        let k2 = Scalar::from_be_bytes(bytes[32..64].try_into().unwrap()).map_err(|_| MusigError::InvalidSecNonce)?;
+        let pubkey = PublicKey::from_slice(&bytes[64..]).map_err(|_| MusigError::InvalidSecNonce)?;
+        Ok(SecNonce { k1, k2, pubkey })
+    }
+
+    /// The BIP 327 encoding, for keeping the nonce between the two rounds.
+    pub fn to_bytes(&self) -> [u8; SECNONCE_LEN] {
+        let mut bytes = [0u8; SECNONCE_LEN];
+        bytes[..32].copy_from_slice(&self.k1.to_be_bytes());
+        bytes[32..64].copy_from_slice(&self.k2.to_be_bytes());
+        bytes[64..].copy_from_slice(&self.pubkey.serialize());
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PubNonce(pub [u8; PUBNONCE_LEN]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggNonce(pub [u8; PUBNONCE_LEN]);
+
+/// Round one. `rand` must be fresh randomness for every signing session;
+/// the other inputs only add defence in depth if it isn't.
+pub fn nonce_gen(
+    rand: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    aggregate_pubkey: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> Result<(SecNonce, PubNonce), MusigError> {
+    let rand = match secret_key {
+        Some(secret_key) => {
+            let mask = tagged_hash("MuSig/aux", &rand);
+            let mut masked = secret_key.secret_bytes();
+            masked.iter_mut().zip(mask.iter()).for_each(|(byte, mask)| *byte ^= mask);
+            masked
+        }
+        None => rand,
+    };
+
+    let aggregate_pubkey = aggregate_pubkey.map(|key| key.serialize().to_vec()).unwrap_or_default();
+    let msg_prefixed = match msg {
+        Some(msg) => {
+            let mut prefixed = vec![1u8];
+            prefixed.extend_from_slice(&(msg.len() as u64).to_be_bytes());
+            prefixed.extend_from_slice(msg);
+            prefixed
+        }
+        None => vec![0u8],
+    };
+    let extra_in = extra_in.unwrap_or_default();
+
+    let nonce = |index: u8| {
+        let mut data = rand.to_vec();
+        data.push(33);
+        data.extend_from_slice(&pubkey.serialize());
+        data.push(aggregate_pubkey.len() as u8);
+        data.extend_from_slice(&aggregate_pubkey);
+        data.extend_from_slice(&msg_prefixed);
+        data.extend_from_slice(&(extra_in.len() as u32).to_be_bytes());
+        data.extend_from_slice(extra_in);
+        data.push(index);
+        scalar_from_hash(tagged_hash("MuSig/nonce", &data))
+    };
+    let (k1, k2) = (nonce(0), nonce(1));
+
+    let r1 = generator_mul(k1).ok_or(MusigError::InvalidSecNonce)?;
+    let r2 = generator_mul(k2).ok_or(MusigError::InvalidSecNonce)?;
+    let mut pubnonce = [0u8; PUBNONCE_LEN];
+    pubnonce[..33].copy_from_slice(&r1.serialize());
+    pubnonce[33..].copy_from_slice(&r2.serialize());
+
+    Ok((SecNonce { k1, k2, pubkey: *pubkey }, PubNonce(pubnonce)))
+}
+
+fn parse_pubnonce(pubnonce: &PubNonce, index: usize) -> Result<(PublicKey, PublicKey), MusigError> {
+    let r1 = PublicKey::from_slice(&pubnonce.0[..33]).map_err(|_| MusigError::InvalidPubNonce(index))?;
+    let r2 = PublicKey::from_slice(&pubnonce.0[33..]).map_err(|_| MusigError::InvalidPubNonce(index))?;
+    Ok((r1, r2))
+}
+
+/// Sums everyone's nonces so each signer only needs the one aggregate in round two.
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> Result<AggNonce, MusigError> {
+    let (mut r1, mut r2) = (None, None);
+    for (index, pubnonce) in pubnonces.iter().enumerate() {
+        let (nonce1, nonce2) = parse_pubnonce(pubnonce, index)?;
+        r1 = point_add(r1, Some(nonce1));
+        r2 = point_add(r2, Some(nonce2));
+    }
+
+    let mut aggnonce = [0u8; PUBNONCE_LEN];
+    aggnonce[..33].copy_from_slice(&serialize_point_ext(r1));
+    aggnonce[33..].copy_from_slice(&serialize_point_ext(r2));
+    Ok(AggNonce(aggnonce))
+}
+
+//
+// Signing
+//
+
+/// Everything the signers agree on in round two: the keys, the aggregate
+/// nonce and the message.
+#[derive(Debug, Clone)]
+pub struct Session {
+    key_agg: KeyAggContext,
+    nonce_coefficient: Scalar,
+    final_nonce: PublicKey,
+    challenge: Scalar,
+}
+
+impl Session {
+    pub fn new(key_agg: &KeyAggContext, aggnonce: &AggNonce, msg: &[u8]) -> Result<Session, MusigError> {
+        let r1 = parse_point_ext(&aggnonce.0[..33]).map_err(|_| MusigError::InvalidPubNonce(0))?;
+        let r2 = parse_point_ext(&aggnonce.0[33..]).map_err(|_| MusigError::InvalidPubNonce(0))?;
+
+        let mut data = aggnonce.0.to_vec();
+        data.extend_from_slice(&xbytes(&key_agg.aggregate));
+        data.extend_from_slice(msg);
+        let nonce_coefficient = scalar_from_hash(tagged_hash("MuSig/noncecoef", &data));
+
+        // an infinite nonce can only come from a malicious signer, BIP 327
+        // falls back to G so the session still produces a valid signature
+        let final_nonce = point_add(r1, r2.and_then(|r2| point_mul(&r2, nonce_coefficient)))
+            .unwrap_or_else(|| generator_mul(Scalar::ONE).unwrap());
+
+        let mut data = xbytes(&final_nonce).to_vec();
+        data.extend_from_slice(&xbytes(&key_agg.aggregate));
+        data.extend_from_slice(msg);
+        let challenge = scalar_from_hash(tagged_hash("BIP0340/challenge", &data));
+
+        Ok(Session { key_agg: key_agg.clone(), nonce_coefficient, final_nonce, challenge })
+    }
+
+    fn signer_coefficient(&self, pubkey: &PublicKey) -> Result<Scalar, MusigError> {
+        if !self.key_agg.pubkeys.contains(pubkey) {
+            return Err(MusigError::UnknownSigner);
+        }
+        Ok(key_agg_coefficient(&self.key_agg.pubkeys, pubkey))
+    }
+
+    /// Round two. Takes the secret nonce by value so it can't be signed with twice.
+    pub fn sign(&self, secnonce: SecNonce, secret_key: &SecretKey) -> Result<[u8; 32], MusigError> {
+        if secnonce.k1 == Scalar::ZERO || secnonce.k2 == Scalar::ZERO {
+            return Err(MusigError::InvalidSecNonce);
+        }
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), secret_key);
+        if pubkey != secnonce.pubkey {
+            return Err(MusigError::SecretKeyMismatch);
+        }
+        let coefficient = self.signer_coefficient(&pubkey)?;
+
+        let key_factor = scalar_mul(even_y_factor(&self.key_agg.aggregate), self.key_agg.sign_factor);
+        let d = scalar_mul(key_factor, Scalar::from(*secret_key));
+
+        let nonce_factor = even_y_factor(&self.final_nonce);
+        let k1 = scalar_mul(nonce_factor, secnonce.k1);
+        let k2 = scalar_mul(nonce_factor, secnonce.k2);
+
+        let s = scalar_add(
+            scalar_add(k1, scalar_mul(self.nonce_coefficient, k2)),
+            scalar_mul(self.challenge, scalar_mul(coefficient, d)),
+        );
+        Ok(s.to_be_bytes())
+    }
+
+    /// Checks one signer's contribution, so a bad aggregate can be blamed on someone.
+    pub fn verify_partial_sig(&self, partial_sig: &[u8; 32], pubnonce: &PubNonce, pubkey: &PublicKey) -> Result<(), MusigError> {
+        let s = Scalar::from_be_bytes(*partial_sig).map_err(|_| MusigError::InvalidPartialSig(0))?;
+        let (r1, r2) = parse_pubnonce(pubnonce, 0)?;
+        let coefficient = self.signer_coefficient(pubkey)?;
+
+        let mut effective_nonce = point_add(Some(r1), point_mul(&r2, self.nonce_coefficient));
+        if !has_even_y(&self.final_nonce) {
+            effective_nonce = effective_nonce.map(|nonce| nonce.negate(&Secp256k1::new()));
+        }
+
+        let key_factor = scalar_mul(even_y_factor(&self.key_agg.aggregate), self.key_agg.sign_factor);
+        let expected = point_add(
+            effective_nonce,
+            point_mul(pubkey, scalar_mul(self.challenge, scalar_mul(coefficient, key_factor))),
+        );
+
+        if generator_mul(s) != expected {
+            return Err(MusigError::InvalidPartialSig(0));
+        }
+        Ok(())
+    }
+
+    /// Sums the partial signatures into a BIP 340 signature for the (tweaked) aggregate key.
+    pub fn aggregate(&self, partial_sigs: &[[u8; 32]]) -> Result<SchnorrSignature, MusigError> {
+        let mut s = scalar_mul(
+            self.challenge,
+            scalar_mul(even_y_factor(&self.key_agg.aggregate), self.key_agg.tweak),
+        );
+        for (index, partial_sig) in partial_sigs.iter().enumerate() {
+            let partial_sig = Scalar::from_be_bytes(*partial_sig).map_err(|_| MusigError::InvalidPartialSig(index))?;
+            s = scalar_add(s, partial_sig);
+        }
+
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&xbytes(&self.final_nonce));
+        signature[32..].copy_from_slice(&s.to_be_bytes());
+        Ok(SchnorrSignature::from_slice(&signature).unwrap())
+    }
+}
+
+//
+// Taproot funding
+//
+
+/// The funding keys sorted and aggregated, so both peers get the same
+/// internal key whichever order they list them in.
+pub fn taproot_funding_key(pubkey1: &PublicKey, pubkey2: &PublicKey) -> Result<KeyAggContext, MusigError> {
+    KeyAggContext::new(&key_sort(&[*pubkey1, *pubkey2]))
+}
+
+/// A key path only P2TR output: unlike the P2WSH 2-of-2 it looks like any
+/// single-sig taproot spend on chain.
+pub fn taproot_funding_script(funding_key: &KeyAggContext) -> ScriptBuf {
+    ScriptBuf::new_p2tr(&Secp256k1::new(), funding_key.xonly_pubkey(), None)
+}
+
+/// What both signers sign to spend the funding output with `tx`'s only input.
+pub fn taproot_keypath_sighash(tx: &Transaction, funding_output: &TxOut) -> [u8; 32] {
+    let message = generate_taproot_keypath_message(tx, 0, std::slice::from_ref(funding_output), TapSighashType::Default);
+    *message.as_ref()
+}
+
+/// A default sighash type signature goes in the witness on its own.
+pub fn add_keypath_witness(mut tx: Transaction, signature: &SchnorrSignature) -> Transaction {
+    let mut witness = Witness::new();
+    witness.push(signature.as_ref());
+    tx.input[0].witness = witness;
+    tx
+}
@@ -0,0 +1,158 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key};
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use internal::tx_utils::get_funding_input;
+use internal::musig::{add_keypath_witness, nonce_agg, nonce_gen, taproot_funding_key, taproot_funding_script, taproot_keypath_sighash, AggNonce,
+                     KeyAggContext, MusigError, PubNonce, SecNonce, Session};
+use crate::exercises::solutions::build_refund_transaction;
+use bitcoin::secp256k1::XOnlyPublicKey;
+
+pub fn musig_pubkey(hex_str: &str) -> PublicKey {
+    PublicKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+// BIP 327 key_agg_vectors.json
+#[test]
+fn test_musig_key_agg_vectors() {
+    let pubkeys = [
+        musig_pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+        musig_pubkey("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+        musig_pubkey("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+    ];
+    let cases: [(&[usize], &str); 4] = [
+        (&[0, 1, 2], "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C"),
+        (&[2, 1, 0], "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B"),
+        (&[0, 0, 0], "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935"),
+        (&[0, 0, 1, 1], "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E"),
+    ];
+
+    for (indices, expected) in cases {
+        let keys: Vec<PublicKey> = indices.iter().map(|index| pubkeys[*index]).collect();
+        let key_agg = KeyAggContext::new(&keys).unwrap();
+        assert_eq!(hex::encode_upper(key_agg.xonly_pubkey().serialize()), expected);
+    }
+
+    // tweaking by the group order is out of range
+    let key_agg = KeyAggContext::new(&pubkeys).unwrap();
+    let order = hex::decode("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141").unwrap();
+    assert_eq!(key_agg.with_tweak(order.try_into().unwrap(), true), Err(MusigError::InvalidTweak));
+}
+
+// BIP 327 sign_verify_vectors.json
+#[test]
+fn test_musig_sign_vectors() {
+    let secret_key = SecretKey::from_slice(&hex::decode("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671").unwrap()).unwrap();
+    let pubkeys = [
+        musig_pubkey("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+        musig_pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+        musig_pubkey("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+    ];
+    let secnonce_bytes: [u8; 97] = hex::decode("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F703935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9").unwrap().try_into().unwrap();
+    let pubnonces: Vec<PubNonce> = [
+        "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+        "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+    ]
+    .iter()
+    .map(|pubnonce| PubNonce(hex::decode(pubnonce).unwrap().try_into().unwrap()))
+    .collect();
+    let msg = hex::decode("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF").unwrap();
+
+    let aggnonce = nonce_agg(&pubnonces).unwrap();
+    assert_eq!(hex::encode_upper(aggnonce.0), "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9");
+
+    let cases: [(&[usize], &str); 2] = [
+        (&[0, 1, 2], "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB"),
+        (&[1, 0, 2], "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52"),
+    ];
+    for (indices, expected) in cases {
+        let keys: Vec<PublicKey> = indices.iter().map(|index| pubkeys[*index]).collect();
+        let session = Session::new(&KeyAggContext::new(&keys).unwrap(), &aggnonce, &msg).unwrap();
+        let partial_sig = session.sign(SecNonce::from_bytes(&secnonce_bytes).unwrap(), &secret_key).unwrap();
+        assert_eq!(hex::encode_upper(partial_sig), expected);
+        assert_eq!(session.verify_partial_sig(&partial_sig, &pubnonces[0], &pubkeys[0]), Ok(()));
+    }
+}
+
+// BIP 327 tweak_vectors.json and nonce_gen_vectors.json
+#[test]
+fn test_musig_tweak_and_nonce_gen_vectors() {
+    let secret_key = SecretKey::from_slice(&hex::decode("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671").unwrap()).unwrap();
+    let keys = [
+        musig_pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+        musig_pubkey("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+        musig_pubkey("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+    ];
+    let secnonce = SecNonce::from_bytes(&hex::decode("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F703935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9").unwrap().try_into().unwrap()).unwrap();
+    let aggnonce = AggNonce(hex::decode("028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9").unwrap().try_into().unwrap());
+    let msg = hex::decode("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF").unwrap();
+    let tweak: [u8; 32] = hex::decode("E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB").unwrap().try_into().unwrap();
+
+    let key_agg = KeyAggContext::new(&keys).unwrap().with_tweak(tweak, true).unwrap();
+    let session = Session::new(&key_agg, &aggnonce, &msg).unwrap();
+    let partial_sig = session.sign(secnonce, &secret_key).unwrap();
+    assert_eq!(hex::encode_upper(partial_sig), "E28A5C66E61E178C2BA19DB77B6CF9F7E2F0F56C17918CD13135E60CC848FE91");
+
+    let secret_key = SecretKey::from_slice(&[0x02; 32]).unwrap();
+    let pubkey = musig_pubkey("024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766");
+    let aggregate_pubkey = XOnlyPublicKey::from_slice(&[0x07; 32]).unwrap();
+    let (secnonce, _) = nonce_gen([0x0f; 32], Some(&secret_key), &pubkey, Some(&aggregate_pubkey), Some(&[0x01; 32]), Some(&[0x08; 32])).unwrap();
+    assert_eq!(
+        hex::encode_upper(secnonce.to_bytes()),
+        "B114E502BEAA4E301DD08A50264172C84E41650E6CB726B410C0694D59EFFB6495B5CAF28D045B973D63E3C99A44B807BDE375FD6CB39E46DC4A511708D0E9D2024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766"
+    );
+}
+
+#[test]
+fn test_taproot_refund_keypath_spend() {
+    let secp = Secp256k1::new();
+    let alice_secret = secp256k1_private_key(&[0x01; 32]);
+    let bob_secret = secp256k1_private_key(&[0x02; 32]);
+    let alice_pubkey = pubkey_from_secret(alice_secret);
+    let bob_pubkey = pubkey_from_secret(bob_secret);
+
+    // both peers end up with the same output whichever way round they list the keys
+    let funding_key = taproot_funding_key(&alice_pubkey, &bob_pubkey).unwrap();
+    assert_eq!(funding_key, taproot_funding_key(&bob_pubkey, &alice_pubkey).unwrap());
+    let funding_output = bitcoin::TxOut { value: bitcoin::Amount::from_sat(5_000_000), script_pubkey: taproot_funding_script(&funding_key) };
+    assert!(funding_output.script_pubkey.is_p2tr());
+
+    let funding_txin = get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000001".to_string(), 0);
+    let refund_tx = build_refund_transaction(
+        funding_txin,
+        bitcoin::PublicKey::new(alice_pubkey),
+        bitcoin::PublicKey::new(bob_pubkey),
+        3_999_000,
+        1_000_000,
+    );
+    let sighash = taproot_keypath_sighash(&refund_tx, &funding_output);
+
+    // the output key commits to the internal key, so the signers sign for the tweaked one
+    let signing_key = funding_key.with_taproot_tweak().unwrap();
+    assert_eq!(funding_output.script_pubkey, ScriptBuf::new_p2tr_tweaked(bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(signing_key.xonly_pubkey())));
+
+    // round one: swap nonces
+    let (alice_secnonce, alice_pubnonce) = nonce_gen(rand::random(), Some(&alice_secret), &alice_pubkey, Some(&signing_key.xonly_pubkey()), Some(&sighash), None).unwrap();
+    let (bob_secnonce, bob_pubnonce) = nonce_gen(rand::random(), Some(&bob_secret), &bob_pubkey, Some(&signing_key.xonly_pubkey()), Some(&sighash), None).unwrap();
+    let aggnonce = nonce_agg(&[alice_pubnonce, bob_pubnonce]).unwrap();
+
+    // round two: swap partial signatures and check each other's
+    let session = Session::new(&signing_key, &aggnonce, &sighash).unwrap();
+    let alice_partial_sig = session.sign(alice_secnonce, &alice_secret).unwrap();
+    let bob_partial_sig = session.sign(bob_secnonce, &bob_secret).unwrap();
+    assert_eq!(session.verify_partial_sig(&alice_partial_sig, &alice_pubnonce, &alice_pubkey), Ok(()));
+    assert_eq!(session.verify_partial_sig(&bob_partial_sig, &bob_pubnonce, &bob_pubkey), Ok(()));
+    assert!(session.verify_partial_sig(&bob_partial_sig, &alice_pubnonce, &alice_pubkey).is_err());
+
+    let signature = session.aggregate(&[alice_partial_sig, bob_partial_sig]).unwrap();
+    let message = Message::from_digest(sighash);
+    assert!(secp.verify_schnorr(&signature, &message, &signing_key.xonly_pubkey()).is_ok());
+
+    let signed_tx = add_keypath_witness(refund_tx, &signature);
+    assert_eq!(signed_tx.input[0].witness.len(), 1);
+    assert_eq!(signed_tx.input[0].witness.nth(0).unwrap(), signature.as_ref());
+}
@@ -0,0 +1,609 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Address, BlockHash, Network, OutPoint, Transaction, TxIn, TxOut, Witness};
+use internal::bitcoind_client::BitcoindClient;
+use internal::chain::MockChain;
+use internal::watcher::poll_blocks_until;
+use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
+use lightning::chain::chainmonitor;
+use lightning::chain::channelmonitor::ChannelMonitor;
+use lightning::chain::{BestBlock, Filter, Listen, Watch};
+use lightning::events::{Event, EventsProvider, ReplayEvent};
+use lightning::ln::channelmanager::{ChainParameters, ChannelManagerReadArgs, SimpleArcChannelManager};
+use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler};
+use lightning::ln::types::ChannelId;
+use lightning::routing::gossip::{NetworkGraph, P2PGossipSync};
+use lightning::routing::router::DefaultRouter;
+use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringDecayParameters, ProbabilisticScoringFeeParameters};
+use lightning::routing::utxo::UtxoLookup;
+use lightning::sign::{EntropySource, InMemorySigner, KeysManager};
+use lightning::util::config::UserConfig;
+use lightning::util::errors::APIError;
+use lightning::util::logger::{Level, Logger, Record};
+use lightning::util::persist::{
+    read_channel_monitors, KVStore, CHANNEL_MANAGER_PERSISTENCE_KEY, CHANNEL_MANAGER_PERSISTENCE_PRIMARY_NAMESPACE,
+    CHANNEL_MANAGER_PERSISTENCE_SECONDARY_NAMESPACE,
+};
+use lightning::util::ser::{ReadableArgs, Writeable};
+use lightning_block_sync::init::{synchronize_listeners, validate_best_block_header};
+use lightning_block_sync::{BlockSource, BlockSourceError, BlockSourceErrorKind, UnboundedCache};
+use lightning_net_tokio::SocketDescriptor;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod test;
+
+/// bitcoind when running for real, a fixed rate against the mock chain.
+pub type NodeFeeEstimator = dyn FeeEstimator + Send + Sync;
+
+pub type ChainMonitor = chainmonitor::ChainMonitor<
+    InMemorySigner,
+    Arc<dyn Filter + Send + Sync>,
+    Arc<NodeBroadcaster>,
+    Arc<NodeFeeEstimator>,
+    Arc<NodeLogger>,
+    Arc<FilesystemStore>,
+>;
+
+pub type ChannelManager = SimpleArcChannelManager<ChainMonitor, NodeBroadcaster, NodeFeeEstimator, NodeLogger>;
+
+pub type GossipSync = P2PGossipSync<Arc<NetworkGraph<Arc<NodeLogger>>>, Arc<dyn UtxoLookup + Send + Sync>, Arc<NodeLogger>>;
+
+pub type PeerManager = lightning::ln::peer_handler::PeerManager<
+    SocketDescriptor,
+    Arc<ChannelManager>,
+    Arc<GossipSync>,
+    IgnoringMessageHandler,
+    Arc<NodeLogger>,
+    IgnoringMessageHandler,
+    Arc<KeysManager>,
+>;
+
+type Router = DefaultRouter<
+    Arc<NetworkGraph<Arc<NodeLogger>>>,
+    Arc<NodeLogger>,
+    Arc<KeysManager>,
+    Arc<RwLock<ProbabilisticScorer<Arc<NetworkGraph<Arc<NodeLogger>>>, Arc<NodeLogger>>>>,
+    ProbabilisticScoringFeeParameters,
+    ProbabilisticScorer<Arc<NetworkGraph<Arc<NodeLogger>>>, Arc<NodeLogger>>,
+>;
+
+const SEED_FILE: &str = "keys_seed";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeError {
+    Io(String),
+    BlockSource(BlockSourceErrorKind),
+    Funding(String),
+    Api(APIError),
+    InvalidPeer(String),
+    ConnectionFailed(SocketAddr),
+}
+
+//
+// LDK's interfaces
+//
+
+/// Prints LDK's log lines at or above `level`, tagged with the node's alias.
+pub struct NodeLogger {
+    alias: String,
+    level: Level,
+}
+
+impl NodeLogger {
+    pub fn new(alias: &str, level: Level) -> NodeLogger {
+        NodeLogger { alias: alias.to_string(), level }
+    }
+}
+
+impl Logger for NodeLogger {
+    fn log(&self, record: Record) {
+        if record.level >= self.level {
+            println!("[{} {} {}:{}] {}", self.alias, record.level, record.module_path, record.line, record.args);
+        }
+    }
+}
+
+/// The same feerate for every confirmation target, for chains like the mock
+/// that have no fee market to estimate from.
+pub struct FixedFeeEstimator {
+    pub sat_per_kw: u32,
+}
+
+impl FeeEstimator for FixedFeeEstimator {
+    fn get_est_sat_per_1000_weight(&self, confirmation_target: ConfirmationTarget) -> u32 {
+        self.sat_per_kw
+    }
+}
+
+/// A `KVStore` that keeps each key in its own file under `root`, which is all
+/// the `ChainMonitor` needs to persist channel monitors.
+pub struct FilesystemStore {
+    root: PathBuf,
+}
+
+impl FilesystemStore {
+    pub fn new(root: PathBuf) -> FilesystemStore {
+        FilesystemStore { root }
+    }
+
+    fn dir(&self, primary_namespace: &str, secondary_namespace: &str) -> PathBuf {
+        self.root.join(primary_namespace).join(secondary_namespace)
+    }
+}
+
+// LDK's io errors are bitcoin's, which wrap the std ones
+impl KVStore for FilesystemStore {
+    fn read(&self, primary_namespace: &str, secondary_namespace: &str, key: &str) -> Result<Vec<u8>, lightning::io::Error> {
+        Ok(std::fs::read(self.dir(primary_namespace, secondary_namespace).join(key))?)
+    }
+
+    // written to a temporary file first so a crash never leaves half a monitor behind
+    fn write(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, buf: &[u8]) -> Result<(), lightning::io::Error> {
+        let dir = self.dir(primary_namespace, secondary_namespace);
+        std::fs::create_dir_all(&dir)?;
+        let tmp_path = dir.join(format!("{}.tmp", key));
+        std::fs::write(&tmp_path, buf)?;
+        Ok(std::fs::rename(&tmp_path, dir.join(key))?)
+    }
+
+    fn remove(&self, primary_namespace: &str, secondary_namespace: &str, key: &str, lazy: bool) -> Result<(), lightning::io::Error> {
+        match std::fs::remove_file(self.dir(primary_namespace, secondary_namespace).join(key)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    fn list(&self, primary_namespace: &str, secondary_namespace: &str) -> Result<Vec<String>, lightning::io::Error> {
+        let dir = self.dir(primary_namespace, secondary_namespace);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut keys = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_file() && !name.ends_with(".tmp") {
+                keys.push(name);
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// Prints every transaction LDK broadcasts before passing it on, so it can be
+/// compared with the ones built by hand.
+pub struct NodeBroadcaster {
+    alias: String,
+    inner: Arc<dyn BroadcasterInterface + Send + Sync>,
+    broadcast: Mutex<Vec<Transaction>>,
+}
+
+impl NodeBroadcaster {
+    pub fn new(alias: &str, inner: Arc<dyn BroadcasterInterface + Send + Sync>) -> NodeBroadcaster {
+        NodeBroadcaster { alias: alias.to_string(), inner, broadcast: Mutex::new(Vec::new()) }
+    }
+
+    /// Everything broadcast so far, oldest first.
+    pub fn broadcast_txs(&self) -> Vec<Transaction> {
+        self.broadcast.lock().unwrap().clone()
+    }
+}
+
+impl BroadcasterInterface for NodeBroadcaster {
+    fn broadcast_transactions(&self, txs: &[&Transaction]) {
+        for tx in txs {
+            println!("[{}] Broadcasting {}", self.alias, tx.compute_txid());
+            println!("Tx Hex: {}", serialize_hex(*tx));
+        }
+        self.broadcast.lock().unwrap().extend(txs.iter().map(|tx| (*tx).clone()));
+        self.inner.broadcast_transactions(txs);
+    }
+}
+
+//
+// Funding
+//
+
+pub type FundingResult<'a> = Pin<Box<dyn Future<Output = Result<Transaction, NodeError>> + Send + 'a>>;
+
+/// Where a node gets the coins for the channels it opens. The transaction
+/// must be fully signed and spend only segwit outputs.
+pub trait FundingSource {
+    fn fund_output(&self, output: TxOut) -> FundingResult<'_>;
+}
+
+impl FundingSource for BitcoindClient {
+    fn fund_output(&self, output: TxOut) -> FundingResult<'_> {
+        Box::pin(async move {
+            let address = Address::from_script(&output.script_pubkey, Network::Regtest)
+                .map_err(|e| NodeError::Funding(e.to_string()))?;
+
+            let raw_tx = self.create_raw_transaction(&address, output.value.to_sat()).await;
+            let funded_tx = self.fund_raw_transaction(raw_tx.0).await;
+            let signed_tx = self.sign_raw_transaction_with_wallet(funded_tx.hex).await;
+            if !signed_tx.complete {
+                return Err(NodeError::Funding("the wallet couldn't sign every input".to_string()));
+            }
+
+            let bytes = hex::decode(&signed_tx.hex).map_err(|e| NodeError::Funding(e.to_string()))?;
+            deserialize(&bytes).map_err(|e| NodeError::Funding(e.to_string()))
+        })
+    }
+}
+
+static MOCK_FUNDING_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// the mock has no wallet and never checks inputs, so the coin being spent is
+// made up. It only needs a witness for LDK to accept it as segwit
+impl FundingSource for MockChain {
+    fn fund_output(&self, output: TxOut) -> FundingResult<'_> {
+        let count = MOCK_FUNDING_COUNT.fetch_add(1, Ordering::Relaxed);
+        let previous_txid = Sha256::hash(&count.to_be_bytes()).to_byte_array();
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: bitcoin::Txid::from_byte_array(previous_txid), vout: 0 },
+                witness: Witness::from_slice(&[[0x01]]),
+                ..Default::default()
+            }],
+            output: vec![output],
+        };
+        Box::pin(async move { Ok(tx) })
+    }
+}
+
+//
+// The node
+//
+
+pub struct NodeConfig {
+    pub alias: String,
+    pub data_dir: PathBuf,
+    pub network: Network,
+    pub log_level: Level,
+}
+
+/// A minimal LDK node: a `ChannelManager` and `ChainMonitor` kept in sync with
+/// a block source, and a `PeerManager` speaking BOLT 8 over TCP.
+pub struct LdkNode {
+    pub alias: String,
+    pub network: Network,
+    pub logger: Arc<NodeLogger>,
+    pub keys_manager: Arc<KeysManager>,
+    pub kv_store: Arc<FilesystemStore>,
+    pub broadcaster: Arc<NodeBroadcaster>,
+    pub fee_estimator: Arc<NodeFeeEstimator>,
+    pub network_graph: Arc<NetworkGraph<Arc<NodeLogger>>>,
+    pub chain_monitor: Arc<ChainMonitor>,
+    pub channel_manager: Arc<ChannelManager>,
+    pub peer_manager: Arc<PeerManager>,
+    wallet: Arc<dyn FundingSource + Send + Sync>,
+}
+
+fn io_error<E: std::fmt::Display>(e: E) -> NodeError {
+    NodeError::Io(e.to_string())
+}
+
+fn block_source_error(e: BlockSourceError) -> NodeError {
+    NodeError::BlockSource(e.kind())
+}
+
+// the seed is created on first start and reused, so the node id never changes
+fn read_or_create_seed(data_dir: &PathBuf) -> Result<[u8; 32], NodeError> {
+    let path = data_dir.join(SEED_FILE);
+    match std::fs::read(&path) {
+        Ok(seed) => seed.try_into().map_err(|_| NodeError::Io(format!("{} isn't 32 bytes", path.display()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let seed: [u8; 32] = rand::random();
+            std::fs::create_dir_all(data_dir).map_err(io_error)?;
+            std::fs::write(&path, seed).map_err(io_error)?;
+            Ok(seed)
+        }
+        Err(e) => Err(io_error(e)),
+    }
+}
+
+/// Parses a peer given as `pubkey@host:port`.
+pub fn parse_peer(peer: &str) -> Result<(PublicKey, SocketAddr), NodeError> {
+    let invalid = || NodeError::InvalidPeer(peer.to_string());
+    let (pubkey, addr) = peer.split_once('@').ok_or_else(invalid)?;
+
+    let pubkey = PublicKey::from_str(pubkey).map_err(|_| invalid())?;
+    let addr = addr.to_socket_addrs().map_err(|_| invalid())?.next().ok_or_else(invalid)?;
+    Ok((pubkey, addr))
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+}
+
+impl LdkNode {
+    /// Starts a node from `config.data_dir`. A node that already has channels
+    /// reloads them and replays the blocks it missed before returning.
+    pub async fn start<B: Deref + Send + Sync>(
+        config: NodeConfig,
+        block_source: B,
+        broadcaster: Arc<dyn BroadcasterInterface + Send + Sync>,
+        fee_estimator: Arc<NodeFeeEstimator>,
+        wallet: Arc<dyn FundingSource + Send + Sync>,
+    ) -> Result<LdkNode, NodeError>
+    where
+        B::Target: BlockSource,
+    {
+        let seed = read_or_create_seed(&config.data_dir)?;
+        let started = now();
+        let keys_manager = Arc::new(KeysManager::new(&seed, started.as_secs(), started.subsec_nanos()));
+
+        let logger = Arc::new(NodeLogger::new(&config.alias, config.log_level));
+        let kv_store = Arc::new(FilesystemStore::new(config.data_dir.clone()));
+        let broadcaster = Arc::new(NodeBroadcaster::new(&config.alias, broadcaster));
+
+        let chain_monitor: Arc<ChainMonitor> = Arc::new(chainmonitor::ChainMonitor::new(
+            None,
+            broadcaster.clone(),
+            logger.clone(),
+            fee_estimator.clone(),
+            kv_store.clone(),
+        ));
+
+        let network_graph = Arc::new(NetworkGraph::new(config.network, logger.clone()));
+        let scorer = Arc::new(RwLock::new(ProbabilisticScorer::new(
+            ProbabilisticScoringDecayParameters::default(),
+            network_graph.clone(),
+            logger.clone(),
+        )));
+        let router: Arc<Router> = Arc::new(DefaultRouter::new(
+            network_graph.clone(),
+            logger.clone(),
+            keys_manager.clone(),
+            scorer,
+            ProbabilisticScoringFeeParameters::default(),
+        ));
+
+        // our own channels only, and unannounced, which is all a two node setup needs
+        let mut user_config = UserConfig::default();
+        user_config.channel_handshake_config.announce_for_forwarding = false;
+        user_config.channel_handshake_limits.force_announced_channel_preference = false;
+
+        let mut channel_monitors =
+            read_channel_monitors(kv_store.clone(), keys_manager.clone(), keys_manager.clone()).map_err(io_error)?;
+
+        let channel_manager = match kv_store.read(
+            CHANNEL_MANAGER_PERSISTENCE_PRIMARY_NAMESPACE,
+            CHANNEL_MANAGER_PERSISTENCE_SECONDARY_NAMESPACE,
+            CHANNEL_MANAGER_PERSISTENCE_KEY,
+        ) {
+            Ok(bytes) => {
+                let read_args = ChannelManagerReadArgs::new(
+                    keys_manager.clone(),
+                    keys_manager.clone(),
+                    keys_manager.clone(),
+                    fee_estimator.clone(),
+                    chain_monitor.clone(),
+                    broadcaster.clone(),
+                    router,
+                    logger.clone(),
+                    user_config,
+                    channel_monitors.iter_mut().map(|(_, monitor)| monitor).collect(),
+                );
+                let (manager_block_hash, channel_manager) =
+                    <(BlockHash, ChannelManager)>::read(&mut io::Cursor::new(bytes), read_args)
+                        .map_err(|e| NodeError::Io(format!("{:?}", e)))?;
+
+                // catch the manager and every monitor up from wherever each left off
+                let monitor_listeners: Vec<_> = channel_monitors
+                    .iter()
+                    .map(|(block_hash, monitor)| (*block_hash, (monitor.clone(), broadcaster.clone(), fee_estimator.clone(), logger.clone())))
+                    .collect();
+                let mut listeners: Vec<(BlockHash, &(dyn Listen + Send + Sync))> =
+                    vec![(manager_block_hash, &channel_manager as &(dyn Listen + Send + Sync))];
+                for (block_hash, listener) in monitor_listeners.iter() {
+                    listeners.push((*block_hash, listener as &(dyn Listen + Send + Sync)));
+                }
+                let mut header_cache = UnboundedCache::new();
+                synchronize_listeners(&*block_source, config.network, &mut header_cache, listeners)
+                    .await
+                    .map_err(block_source_error)?;
+
+                for (_, (monitor, _, _, _)) in monitor_listeners {
+                    let (funding_outpoint, _) = monitor.get_funding_txo();
+                    chain_monitor
+                        .watch_channel(funding_outpoint, monitor)
+                        .map_err(|_| NodeError::Io("couldn't restore a channel monitor".to_string()))?;
+                }
+                channel_manager
+            }
+            Err(e) if e.kind() == lightning::io::ErrorKind::NotFound => {
+                let tip = validate_best_block_header(&*block_source).await.map_err(block_source_error)?;
+                ChannelManager::new(
+                    fee_estimator.clone(),
+                    chain_monitor.clone(),
+                    broadcaster.clone(),
+                    router,
+                    logger.clone(),
+                    keys_manager.clone(),
+                    keys_manager.clone(),
+                    keys_manager.clone(),
+                    user_config,
+                    ChainParameters { network: config.network, best_block: BestBlock::new(tip.header.block_hash(), tip.height) },
+                    started.as_secs() as u32,
+                )
+            }
+            Err(e) => return Err(io_error(e)),
+        };
+        let channel_manager = Arc::new(channel_manager);
+
+        let gossip_sync: Arc<GossipSync> = Arc::new(P2PGossipSync::new(network_graph.clone(), None, logger.clone()));
+        let peer_manager = Arc::new(PeerManager::new(
+            MessageHandler {
+                chan_handler: channel_manager.clone(),
+                route_handler: gossip_sync,
+                onion_message_handler: IgnoringMessageHandler {},
+                custom_message_handler: IgnoringMessageHandler {},
+            },
+            started.as_secs() as u32,
+            &keys_manager.get_secure_random_bytes(),
+            logger.clone(),
+            keys_manager.clone(),
+        ));
+
+        Ok(LdkNode {
+            alias: config.alias,
+            network: config.network,
+            logger,
+            keys_manager,
+            kv_store,
+            broadcaster,
+            fee_estimator,
+            network_graph,
+            chain_monitor,
+            channel_manager,
+            peer_manager,
+            wallet,
+        })
+    }
+
+    pub fn node_id(&self) -> PublicKey {
+        self.channel_manager.get_our_node_id()
+    }
+
+    /// Feeds new blocks to the channel manager and monitors until `done`
+    /// returns true. Run it alongside `process_events`.
+    pub async fn sync_chain<B: Deref, F: FnMut() -> bool>(&self, block_source: B, poll_interval: Duration, done: F) -> Result<(), NodeError>
+    where
+        B::Target: BlockSource,
+    {
+        let listener = (self.chain_monitor.clone(), self.channel_manager.clone());
+        poll_blocks_until(&listener, block_source, self.network, poll_interval, done).await.map_err(block_source_error)
+    }
+
+    /// Accepts peers on `addr` in the background and returns the address it bound to.
+    pub async fn listen(&self, addr: SocketAddr) -> Result<SocketAddr, NodeError> {
+        let listener = tokio::net::TcpListener::bind(addr).await.map_err(io_error)?;
+        let local_addr = listener.local_addr().map_err(io_error)?;
+
+        let peer_manager = self.peer_manager.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else { continue };
+                let peer_manager = peer_manager.clone();
+                tokio::spawn(async move {
+                    lightning_net_tokio::setup_inbound(peer_manager, stream.into_std().unwrap()).await;
+                });
+            }
+        });
+        Ok(local_addr)
+    }
+
+    /// Connects to a peer and waits for the `init` exchange to finish.
+    pub async fn connect(&self, their_node_id: PublicKey, addr: SocketAddr) -> Result<(), NodeError> {
+        if self.peer_manager.peer_by_node_id(&their_node_id).is_some() {
+            return Ok(());
+        }
+
+        let connection = lightning_net_tokio::connect_outbound(self.peer_manager.clone(), their_node_id, addr)
+            .await
+            .ok_or(NodeError::ConnectionFailed(addr))?;
+        tokio::spawn(connection);
+
+        for _ in 0..100 {
+            if self.peer_manager.peer_by_node_id(&their_node_id).is_some() {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Err(NodeError::ConnectionFailed(addr))
+    }
+
+    /// Opens a channel to a connected peer. The funding transaction is built
+    /// once the peer accepts, from `process_events`.
+    pub fn open_channel(&self, their_node_id: PublicKey, channel_value_satoshis: u64) -> Result<ChannelId, NodeError> {
+        self.channel_manager
+            .create_channel(their_node_id, channel_value_satoshis, 0, 0, None, None)
+            .map_err(NodeError::Api)
+    }
+
+    /// Handles pending events, flushes queued peer messages and persists the
+    /// channel manager if it changed. Call it in a loop.
+    pub async fn process_events(&self) -> Result<(), NodeError> {
+        self.channel_manager.process_pending_events_async(|event| self.handle_event(event)).await;
+        self.chain_monitor.process_pending_events(&|event| self.log_event(&event));
+        self.peer_manager.process_events();
+
+        if self.channel_manager.get_and_clear_needs_persistence() {
+            self.kv_store
+                .write(
+                    CHANNEL_MANAGER_PERSISTENCE_PRIMARY_NAMESPACE,
+                    CHANNEL_MANAGER_PERSISTENCE_SECONDARY_NAMESPACE,
+                    CHANNEL_MANAGER_PERSISTENCE_KEY,
+                    &self.channel_manager.encode(),
+                )
+                .map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    /// Lets LDK time out stale peers and channel state. Call it about once a minute.
+    pub fn timer_tick(&self) {
+        self.channel_manager.timer_tick_occurred();
+        self.peer_manager.timer_tick_occurred();
+    }
+
+    async fn handle_event(&self, event: Event) -> Result<(), ReplayEvent> {
+        match event {
+            Event::FundingGenerationReady { temporary_channel_id, counterparty_node_id, channel_value_satoshis, output_script, .. } => {
+                let output = TxOut { value: bitcoin::Amount::from_sat(channel_value_satoshis), script_pubkey: output_script };
+                let funding_tx = match self.wallet.fund_output(output).await {
+                    Ok(funding_tx) => funding_tx,
+                    Err(e) => {
+                        println!("[{}] Failed to fund channel {}: {:?}", self.alias, temporary_channel_id, e);
+                        return Ok(());
+                    }
+                };
+
+                println!("[{}] Funding transaction {}", self.alias, funding_tx.compute_txid());
+                println!("Tx Hex: {}", serialize_hex(&funding_tx));
+                if let Err(e) = self.channel_manager.funding_transaction_generated(temporary_channel_id, counterparty_node_id, funding_tx) {
+                    println!("[{}] LDK rejected the funding transaction: {:?}", self.alias, e);
+                }
+            }
+            event => self.log_event(&event)?,
+        }
+        Ok(())
+    }
+
+    fn log_event(&self, event: &Event) -> Result<(), ReplayEvent> {
+        match event {
+            Event::ChannelPending { channel_id, counterparty_node_id, funding_txo, .. } => {
+                println!("[{}] Channel {} with {} is pending, funded by {}", self.alias, channel_id, counterparty_node_id, funding_txo);
+            }
+            Event::ChannelReady { channel_id, counterparty_node_id, .. } => {
+                println!("[{}] Channel {} with {} is ready", self.alias, channel_id, counterparty_node_id);
+            }
+            Event::ChannelClosed { channel_id, reason, .. } => {
+                println!("[{}] Channel {} closed: {}", self.alias, channel_id, reason);
+            }
+            Event::SpendableOutputs { outputs, .. } => {
+                println!("[{}] {} outputs are ours to spend", self.alias, outputs.len());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::Network;
+use internal::chain::MockChain;
+use internal::node::{FixedFeeEstimator, LdkNode, NodeConfig};
+use std::time::Duration;
+
+pub fn ldk_node_config(alias: &str) -> NodeConfig {
+    let data_dir = std::env::temp_dir().join(format!("pl-00-intro-{}-{}", alias, std::process::id()));
+    let _ = std::fs::remove_dir_all(&data_dir);
+    NodeConfig {
+        alias: alias.to_string(),
+        data_dir,
+        network: Network::Regtest,
+        log_level: lightning::util::logger::Level::Error,
+    }
+}
+
+#[test]
+fn test_ldk_nodes_open_channel() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = std::sync::Arc::new(MockChain::new());
+    chain.mine_blocks(10);
+    let fee_estimator = std::sync::Arc::new(FixedFeeEstimator { sat_per_kw: 253 });
+
+    rt.block_on(async {
+        let alice_config = ldk_node_config("ldk-alice");
+        let alice_data_dir = alice_config.data_dir.clone();
+        let alice = LdkNode::start(alice_config, &*chain, chain.clone(), fee_estimator.clone(), chain.clone()).await.unwrap();
+        let bob_config = ldk_node_config("ldk-bob");
+        let bob_data_dir = bob_config.data_dir.clone();
+        let bob = LdkNode::start(bob_config, &*chain, chain.clone(), fee_estimator.clone(), chain.clone()).await.unwrap();
+
+        let bob_addr = bob.listen("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        alice.connect(bob.node_id(), bob_addr).await.unwrap();
+        let temporary_channel_id = alice.open_channel(bob.node_id(), 100_000).unwrap();
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        let drive = async {
+            let mut funding_tx = None;
+            for _ in 0..200 {
+                alice.process_events().await.unwrap();
+                bob.process_events().await.unwrap();
+                funding_tx = alice.broadcaster.broadcast_txs().first().cloned();
+                if funding_tx.is_some() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            // Bob asks for six confirmations by default
+            chain.mine_blocks(6);
+            for _ in 0..200 {
+                alice.process_events().await.unwrap();
+                bob.process_events().await.unwrap();
+                let ready = |node: &LdkNode| node.channel_manager.list_channels().iter().any(|channel| channel.is_usable);
+                if ready(&alice) && ready(&bob) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            done.store(true, std::sync::atomic::Ordering::SeqCst);
+            funding_tx
+        };
+
+        let is_done = || done.load(std::sync::atomic::Ordering::SeqCst);
+        let (alice_synced, bob_synced, funding_tx) = tokio::join!(
+            alice.sync_chain(&*chain, Duration::from_millis(5), is_done),
+            bob.sync_chain(&*chain, Duration::from_millis(5), is_done),
+            drive
+        );
+        alice_synced.unwrap();
+        bob_synced.unwrap();
+
+        let funding_tx = funding_tx.expect("Alice never broadcast the funding transaction");
+        assert!(chain.find_transaction(&funding_tx.compute_txid()).is_some());
+
+        let channels = alice.channel_manager.list_channels();
+        assert_eq!(channels.len(), 1);
+        let channel = &channels[0];
+        assert!(channel.is_usable);
+        assert_eq!(channel.counterparty.node_id, bob.node_id());
+        assert_eq!(channel.channel_value_satoshis, 100_000);
+
+        // the same 2-of-2 P2WSH output the exercises build by hand
+        let funding_txo = channel.funding_txo.unwrap();
+        assert_eq!(funding_txo.txid, funding_tx.compute_txid());
+        let funding_output = &funding_tx.output[funding_txo.index as usize];
+        assert_eq!(funding_output.value.to_sat(), 100_000);
+        assert!(funding_output.script_pubkey.is_p2wsh());
+        assert_eq!(bob.channel_manager.list_channels()[0].channel_id, channel.channel_id);
+
+        // a restarted node reloads the channel from disk
+        let mut restart_config = ldk_node_config("ldk-alice-restart");
+        restart_config.data_dir = alice_data_dir.clone();
+        let restarted = LdkNode::start(restart_config, &*chain, chain.clone(), fee_estimator.clone(), chain.clone()).await.unwrap();
+        assert_eq!(restarted.node_id(), alice.node_id());
+        let restored = restarted.channel_manager.list_channels();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].channel_id, channel.channel_id);
+        assert_eq!(restored[0].funding_txo, channel.funding_txo);
+
+        std::fs::remove_dir_all(alice_data_dir).unwrap();
+        std::fs::remove_dir_all(bob_data_dir).unwrap();
+    });
+}
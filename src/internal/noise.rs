@@ -0,0 +1,320 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use crate::internal::key_utils::pubkey_from_secret;
+
+pub mod test;
+
+const PROTOCOL_NAME: &[u8] = b"Noise_XK_secp256k1_ChaChaPoly_SHA256";
+const PROLOGUE: &[u8] = b"lightning";
+
+pub const ACT_ONE_LEN: usize = 50;
+pub const ACT_TWO_LEN: usize = 50;
+pub const ACT_THREE_LEN: usize = 66;
+
+// 2-byte encrypted length plus its MAC
+pub const LENGTH_HEADER_LEN: usize = 18;
+pub const MAC_LEN: usize = 16;
+
+// BOLT 8: keys are rotated after they've been used for 1000 encryptions
+// (i.e. every 500 messages, as each message uses one nonce for the length
+// and one for the body)
+const KEY_ROTATION_INDEX: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoiseError {
+    InvalidVersion,
+    InvalidPublicKey,
+    DecryptionFailed,
+    MessageTooLong,
+    UnexpectedAct,
+    Io(std::io::ErrorKind),
+}
+
+impl From<std::io::Error> for NoiseError {
+    fn from(e: std::io::Error) -> NoiseError {
+        NoiseError::Io(e.kind())
+    }
+}
+
+fn hkdf(salt: &[u8; 32], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut engine = HmacEngine::<Sha256>::new(salt);
+    engine.input(ikm);
+    let prk = Hmac::from_engine(engine).to_byte_array();
+
+    let mut engine = HmacEngine::<Sha256>::new(&prk);
+    engine.input(&[1]);
+    let t1 = Hmac::from_engine(engine).to_byte_array();
+
+    let mut engine = HmacEngine::<Sha256>::new(&prk);
+    engine.input(&t1);
+    engine.input(&[2]);
+    let t2 = Hmac::from_engine(engine).to_byte_array();
+
+    (t1, t2)
+}
+
+fn sha256(first: &[u8], second: &[u8]) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    engine.input(first);
+    engine.input(second);
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+fn ecdh(secret: &SecretKey, point: &PublicKey) -> [u8; 32] {
+    // SHA256 of the compressed shared point, as BOLT 8 specifies
+    SharedSecret::new(point, secret).secret_bytes()
+}
+
+// 96-bit nonce: 32 zero bits followed by the little-endian counter
+fn nonce(n: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&n.to_le_bytes());
+    nonce
+}
+
+pub fn encrypt_with_ad(key: &[u8; 32], n: u64, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .encrypt(Nonce::from_slice(&nonce(n)), Payload { msg: plaintext, aad: ad })
+        .expect("encryption never fails for in-memory buffers")
+}
+
+pub fn decrypt_with_ad(key: &[u8; 32], n: u64, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(&nonce(n)), Payload { msg: ciphertext, aad: ad })
+        .map_err(|_| NoiseError::DecryptionFailed)
+}
+
+/// The handshake state shared by both sides of a Noise_XK handshake.
+/// `ck` is the chaining key and `h` the handshake hash.
+pub struct NoiseHandshake {
+    is_initiator: bool,
+    local_static: SecretKey,
+    ephemeral: SecretKey,
+    remote_static: Option<PublicKey>,
+    remote_ephemeral: Option<PublicKey>,
+    ck: [u8; 32],
+    h: [u8; 32],
+    temp_k2: [u8; 32],
+}
+
+impl NoiseHandshake {
+    fn new(is_initiator: bool, local_static: SecretKey, ephemeral: SecretKey, responder_static: &PublicKey) -> NoiseHandshake {
+        let h = Sha256::hash(PROTOCOL_NAME).to_byte_array();
+        let ck = h;
+        let h = sha256(&h, PROLOGUE);
+        // both sides mix in the responder's static key, which the initiator knows in advance
+        let h = sha256(&h, &responder_static.serialize());
+
+        NoiseHandshake {
+            is_initiator,
+            local_static,
+            ephemeral,
+            remote_static: None,
+            remote_ephemeral: None,
+            ck,
+            h,
+            temp_k2: [0; 32],
+        }
+    }
+
+    /// The initiator must already know the node id of the peer it connects to.
+    pub fn new_outbound(node_secret: SecretKey, remote_node_id: PublicKey, ephemeral: SecretKey) -> NoiseHandshake {
+        let mut handshake = NoiseHandshake::new(true, node_secret, ephemeral, &remote_node_id);
+        handshake.remote_static = Some(remote_node_id);
+        handshake
+    }
+
+    pub fn new_inbound(node_secret: SecretKey, ephemeral: SecretKey) -> NoiseHandshake {
+        let node_id = pubkey_from_secret(node_secret);
+        NoiseHandshake::new(false, node_secret, ephemeral, &node_id)
+    }
+
+    // the first two acts share the same shape: 0 || ephemeral pubkey || MAC
+    fn write_act(&mut self, remote_key: &PublicKey) -> ([u8; 50], [u8; 32]) {
+        let ephemeral_pubkey = pubkey_from_secret(self.ephemeral).serialize();
+        self.h = sha256(&self.h, &ephemeral_pubkey);
+
+        let (ck, temp_k) = hkdf(&self.ck, &ecdh(&self.ephemeral, remote_key));
+        self.ck = ck;
+
+        let c = encrypt_with_ad(&temp_k, 0, &self.h, &[]);
+        self.h = sha256(&self.h, &c);
+
+        let mut act = [0u8; 50];
+        act[1..34].copy_from_slice(&ephemeral_pubkey);
+        act[34..].copy_from_slice(&c);
+        (act, temp_k)
+    }
+
+    fn read_act(&mut self, act: &[u8], local_key: &SecretKey) -> Result<(PublicKey, [u8; 32]), NoiseError> {
+        if act.len() != 50 {
+            return Err(NoiseError::UnexpectedAct);
+        }
+        if act[0] != 0 {
+            return Err(NoiseError::InvalidVersion);
+        }
+
+        let remote_ephemeral = PublicKey::from_slice(&act[1..34]).map_err(|_| NoiseError::InvalidPublicKey)?;
+        self.h = sha256(&self.h, &act[1..34]);
+
+        let (ck, temp_k) = hkdf(&self.ck, &ecdh(local_key, &remote_ephemeral));
+        self.ck = ck;
+
+        decrypt_with_ad(&temp_k, 0, &self.h, &act[34..])?;
+        self.h = sha256(&self.h, &act[34..]);
+
+        Ok((remote_ephemeral, temp_k))
+    }
+
+    //
+    // Act One: initiator -> responder
+    //
+
+    pub fn get_act_one(&mut self) -> [u8; ACT_ONE_LEN] {
+        let remote_static = self.remote_static.expect("outbound handshakes know the remote node id");
+        let (act, _) = self.write_act(&remote_static);
+        act
+    }
+
+    /// Processes act one and returns act two.
+    pub fn process_act_one(&mut self, act_one: &[u8]) -> Result<[u8; ACT_TWO_LEN], NoiseError> {
+        if self.is_initiator {
+            return Err(NoiseError::UnexpectedAct);
+        }
+        let local_static = self.local_static;
+        let (remote_ephemeral, _) = self.read_act(act_one, &local_static)?;
+        self.remote_ephemeral = Some(remote_ephemeral);
+
+        //
+        // Act Two: responder -> initiator
+        //
+        let (act_two, temp_k2) = self.write_act(&remote_ephemeral);
+        self.temp_k2 = temp_k2;
+        Ok(act_two)
+    }
+
+    /// Processes act two and returns act three along with the finished transport.
+    pub fn process_act_two(mut self, act_two: &[u8]) -> Result<([u8; ACT_THREE_LEN], NoiseTransport), NoiseError> {
+        if !self.is_initiator {
+            return Err(NoiseError::UnexpectedAct);
+        }
+        let ephemeral = self.ephemeral;
+        let (remote_ephemeral, temp_k2) = self.read_act(act_two, &ephemeral)?;
+
+        //
+        // Act Three: initiator -> responder, revealing the initiator's node id
+        //
+        let local_static_pubkey = pubkey_from_secret(self.local_static).serialize();
+        let c = encrypt_with_ad(&temp_k2, 1, &self.h, &local_static_pubkey);
+        self.h = sha256(&self.h, &c);
+
+        let (ck, temp_k3) = hkdf(&self.ck, &ecdh(&self.local_static, &remote_ephemeral));
+        let t = encrypt_with_ad(&temp_k3, 0, &self.h, &[]);
+
+        let (sk, rk) = hkdf(&ck, &[]);
+
+        let mut act_three = [0u8; ACT_THREE_LEN];
+        act_three[1..50].copy_from_slice(&c);
+        act_three[50..].copy_from_slice(&t);
+
+        let transport = NoiseTransport::new(self.remote_static.unwrap(), ck, sk, rk);
+        Ok((act_three, transport))
+    }
+
+    /// Processes act three and returns the finished transport, which knows the initiator's node id.
+    pub fn process_act_three(mut self, act_three: &[u8]) -> Result<NoiseTransport, NoiseError> {
+        if self.is_initiator || act_three.len() != ACT_THREE_LEN {
+            return Err(NoiseError::UnexpectedAct);
+        }
+        if act_three[0] != 0 {
+            return Err(NoiseError::InvalidVersion);
+        }
+
+        let remote_static_bytes = decrypt_with_ad(&self.temp_k2, 1, &self.h, &act_three[1..50])?;
+        let remote_static = PublicKey::from_slice(&remote_static_bytes).map_err(|_| NoiseError::InvalidPublicKey)?;
+        self.h = sha256(&self.h, &act_three[1..50]);
+
+        let (ck, temp_k3) = hkdf(&self.ck, &ecdh(&self.ephemeral, &remote_static));
+        decrypt_with_ad(&temp_k3, 0, &self.h, &act_three[50..])?;
+
+        let (rk, sk) = hkdf(&ck, &[]);
+
+        Ok(NoiseTransport::new(remote_static, ck, sk, rk))
+    }
+}
+
+/// An established BOLT 8 connection: every message is sent as an encrypted
+/// 2-byte length followed by the encrypted body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoiseTransport {
+    pub remote_node_id: PublicKey,
+    pub sk: [u8; 32],
+    pub sn: u64,
+    pub sck: [u8; 32],
+    pub rk: [u8; 32],
+    pub rn: u64,
+    pub rck: [u8; 32],
+}
+
+fn increment_nonce(n: &mut u64, ck: &mut [u8; 32], k: &mut [u8; 32]) {
+    *n += 1;
+    if *n == KEY_ROTATION_INDEX {
+        let (new_ck, new_k) = hkdf(ck, k);
+        *ck = new_ck;
+        *k = new_k;
+        *n = 0;
+    }
+}
+
+impl NoiseTransport {
+    fn new(remote_node_id: PublicKey, ck: [u8; 32], sk: [u8; 32], rk: [u8; 32]) -> NoiseTransport {
+        NoiseTransport {
+            remote_node_id,
+            sk,
+            sn: 0,
+            sck: ck,
+            rk,
+            rn: 0,
+            rck: ck,
+        }
+    }
+
+    pub fn encrypt_message(&mut self, msg: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if msg.len() > u16::MAX as usize {
+            return Err(NoiseError::MessageTooLong);
+        }
+
+        let mut packet = encrypt_with_ad(&self.sk, self.sn, &[], &(msg.len() as u16).to_be_bytes());
+        increment_nonce(&mut self.sn, &mut self.sck, &mut self.sk);
+
+        packet.extend_from_slice(&encrypt_with_ad(&self.sk, self.sn, &[], msg));
+        increment_nonce(&mut self.sn, &mut self.sck, &mut self.sk);
+
+        Ok(packet)
+    }
+
+    /// Decrypts the 18-byte header and returns the length of the body that
+    /// follows it (not counting the body's 16-byte MAC).
+    pub fn decrypt_length_header(&mut self, header: &[u8]) -> Result<u16, NoiseError> {
+        if header.len() != LENGTH_HEADER_LEN {
+            return Err(NoiseError::DecryptionFailed);
+        }
+        let len = decrypt_with_ad(&self.rk, self.rn, &[], header)?;
+        increment_nonce(&mut self.rn, &mut self.rck, &mut self.rk);
+        Ok(u16::from_be_bytes([len[0], len[1]]))
+    }
+
+    pub fn decrypt_message_body(&mut self, body: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let msg = decrypt_with_ad(&self.rk, self.rn, &[], body)?;
+        increment_nonce(&mut self.rn, &mut self.rck, &mut self.rk);
+        Ok(msg)
+    }
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::SecretKey;
+use internal::key_utils::pubkey_from_secret;
+use internal::noise::{NoiseError, NoiseHandshake, NoiseTransport};
+
+pub fn key_from_hex(hex_str: &str) -> SecretKey {
+    SecretKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+// BOLT 8 Appendix A: transport-initiator / transport-responder successful handshake
+pub fn noise_test_vector_transports() -> (NoiseTransport, NoiseTransport) {
+    let initiator_static = key_from_hex("1111111111111111111111111111111111111111111111111111111111111111");
+    let initiator_ephemeral = key_from_hex("1212121212121212121212121212121212121212121212121212121212121212");
+    let responder_static = key_from_hex("2121212121212121212121212121212121212121212121212121212121212121");
+    let responder_ephemeral = key_from_hex("2222222222222222222222222222222222222222222222222222222222222222");
+
+    let responder_node_id = pubkey_from_secret(responder_static);
+    assert_eq!(
+        hex::encode(responder_node_id.serialize()),
+        "028d7500dd4c12685d1f568b4c2b5048e8534b873319f3a8daa612b469132ec7f7"
+    );
+
+    let mut initiator = NoiseHandshake::new_outbound(initiator_static, responder_node_id, initiator_ephemeral);
+    let mut responder = NoiseHandshake::new_inbound(responder_static, responder_ephemeral);
+
+    let act_one = initiator.get_act_one();
+    assert_eq!(
+        hex::encode(act_one),
+        "00036360e856310ce5d294e8be33fc807077dc56ac80d95d9cd4ddbd21325eff73f70df6086551151f58b8afe6c195782c6a"
+    );
+
+    let act_two = responder.process_act_one(&act_one).unwrap();
+    assert_eq!(
+        hex::encode(act_two),
+        "0002466d7fcae563e5cb09a0d1870bb580344804617879a14949cf22285f1bae3f276e2470b93aac583c9ef6eafca3f730ae"
+    );
+
+    let (act_three, initiator_transport) = initiator.process_act_two(&act_two).unwrap();
+    assert_eq!(
+        hex::encode(act_three),
+        "00b9e3a702e93e3a9948c2ed6e5fd7590a6e1c3a0344cfc9d5b57357049aa22355361aa02e55a8fc28fef5bd6d71ad0c38228dc68b1c466263b47fdf31e560e139ba"
+    );
+
+    let responder_transport = responder.process_act_three(&act_three).unwrap();
+    assert_eq!(
+        hex::encode(responder_transport.remote_node_id.serialize()),
+        "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa"
+    );
+
+    (initiator_transport, responder_transport)
+}
+
+#[test]
+fn test_noise_handshake_vectors() {
+    let (initiator, responder) = noise_test_vector_transports();
+
+    assert_eq!(hex::encode(initiator.sk), "969ab31b4d288cedf6218839b27a3e2140827047f2c0f01bf5c04435d43511a9");
+    assert_eq!(hex::encode(initiator.rk), "bb9020b8965f4df047e07f955f3c4b88418984aadc5cdb35096b9ea8fa5c3442");
+    assert_eq!(hex::encode(initiator.sck), "919219dbb2920afa8db80f9a51787a840bcf111ed8d588caf9ab4be716e42b01");
+    assert_eq!(initiator.sck, initiator.rck);
+
+    assert_eq!(responder.sk, initiator.rk);
+    assert_eq!(responder.rk, initiator.sk);
+    assert_eq!(responder.sck, initiator.sck);
+}
+
+#[test]
+fn test_noise_handshake_rejects_bad_acts() {
+    let responder_static = key_from_hex("2121212121212121212121212121212121212121212121212121212121212121");
+    let responder_ephemeral = key_from_hex("2222222222222222222222222222222222222222222222222222222222222222");
+
+    // transport-responder act1 bad version
+    let act_one = hex::decode("01036360e856310ce5d294e8be33fc807077dc56ac80d95d9cd4ddbd21325eff73f70df6086551151f58b8afe6c195782c6a").unwrap();
+    let mut responder = NoiseHandshake::new_inbound(responder_static, responder_ephemeral);
+    assert_eq!(responder.process_act_one(&act_one), Err(NoiseError::InvalidVersion));
+
+    // transport-responder act1 bad key serialization
+    let act_one = hex::decode("00046360e856310ce5d294e8be33fc807077dc56ac80d95d9cd4ddbd21325eff73f70df6086551151f58b8afe6c195782c6a").unwrap();
+    let mut responder = NoiseHandshake::new_inbound(responder_static, responder_ephemeral);
+    assert_eq!(responder.process_act_one(&act_one), Err(NoiseError::InvalidPublicKey));
+
+    // transport-responder act1 bad MAC
+    let act_one = hex::decode("00036360e856310ce5d294e8be33fc807077dc56ac80d95d9cd4ddbd21325eff73f70df6086551151f58b8afe6c195782c6b").unwrap();
+    let mut responder = NoiseHandshake::new_inbound(responder_static, responder_ephemeral);
+    assert_eq!(responder.process_act_one(&act_one), Err(NoiseError::DecryptionFailed));
+}
+
+#[test]
+fn test_noise_message_encryption_vectors() {
+    let (mut initiator, mut responder) = noise_test_vector_transports();
+    let msg = b"hello";
+
+    for i in 0..1005 {
+        let packet = initiator.encrypt_message(msg).unwrap();
+        assert_eq!(packet.len(), msg.len() + 2 * 16 + 2);
+
+        let expected = match i {
+            0 => Some("cf2b30ddf0cf3f80e7c35a6e6730b59fe802473180f396d88a8fb0db8cbcf25d2f214cf9ea1d95"),
+            1 => Some("72887022101f0b6753e0c7de21657d35a4cb2a1f5cde2650528bbc8f837d0f0d7ad833b1a256a1"),
+            500 => Some("178cb9d7387190fa34db9c2d50027d21793c9bc2d40b1e14dcf30ebeeeb220f48364f7a4c68bf8"),
+            501 => Some("1b186c57d44eb6de4c057c49940d79bb838a145cb528d6e8fd26dbe50a60ca2c104b56b60e45bd"),
+            1000 => Some("4a2f3cc3b5e78ddb83dcb426d9863d9d9a723b0337c89dd0b005d89f8d3c05c52b76b29b740f09"),
+            1001 => Some("2ecd8c8a5629d0d02ab457a0fdd0f7b90a192cd46be5ecb6ca570bfc5e268338b1a16cf4ef2d36"),
+            _ => None,
+        };
+        if let Some(expected) = expected {
+            assert_eq!(hex::encode(&packet), expected, "message {} doesn't match", i);
+        }
+
+        let len = responder.decrypt_length_header(&packet[..18]).unwrap();
+        assert_eq!(len as usize, msg.len());
+        assert_eq!(responder.decrypt_message_body(&packet[18..]).unwrap(), msg.to_vec());
+    }
+}
@@ -0,0 +1,372 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use crate::internal::key_utils::{privkey_multipication_tweak, pubkey_from_secret, pubkey_multipication_tweak};
+use crate::internal::wire::{find_tlv, read_tlv_stream, read_tu64, write_bigsize, write_tlv_stream, write_tu64,
+                            write_u64, DecodeError, OnionPacket, Reader, TlvRecord, ONION_HOP_DATA_LEN};
+
+pub mod test;
+
+const ONION_VERSION: u8 = 0;
+const HMAC_LEN: usize = 32;
+
+// BOLT 4 payload TLV types
+const AMT_TO_FORWARD_TYPE: u64 = 2;
+const OUTGOING_CLTV_VALUE_TYPE: u64 = 4;
+const SHORT_CHANNEL_ID_TYPE: u64 = 6;
+const PAYMENT_DATA_TYPE: u64 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OnionError {
+    InvalidVersion(u8),
+    InvalidHmac,
+    InvalidPayload(DecodeError),
+    MissingField(u64),
+    RouteTooLong,
+    EmptyRoute,
+    PayloadCountMismatch,
+}
+
+impl From<DecodeError> for OnionError {
+    fn from(e: DecodeError) -> OnionError {
+        OnionError::InvalidPayload(e)
+    }
+}
+
+/// payment_secret and total_msat, only sent to the final hop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentData {
+    pub payment_secret: [u8; 32],
+    pub total_msat: u64,
+}
+
+/// What one hop learns from its layer of the onion. Forwarding hops are told
+/// which channel to use next; the final hop gets the payment data instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopPayload {
+    pub amt_to_forward: u64,
+    pub outgoing_cltv_value: u32,
+    pub short_channel_id: Option<u64>,
+    pub payment_data: Option<PaymentData>,
+    pub custom_records: Vec<TlvRecord>,
+}
+
+impl HopPayload {
+    pub fn forward(short_channel_id: u64, amt_to_forward: u64, outgoing_cltv_value: u32) -> HopPayload {
+        HopPayload {
+            amt_to_forward,
+            outgoing_cltv_value,
+            short_channel_id: Some(short_channel_id),
+            payment_data: None,
+            custom_records: Vec::new(),
+        }
+    }
+
+    pub fn receive(amt_to_forward: u64, outgoing_cltv_value: u32, payment_data: Option<PaymentData>) -> HopPayload {
+        HopPayload {
+            amt_to_forward,
+            outgoing_cltv_value,
+            short_channel_id: None,
+            payment_data,
+            custom_records: Vec::new(),
+        }
+    }
+
+    /// The TLV stream, without the BigSize length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut records = Vec::new();
+
+        let mut value = Vec::new();
+        write_tu64(&mut value, self.amt_to_forward);
+        records.push(TlvRecord { tlv_type: AMT_TO_FORWARD_TYPE, value });
+
+        let mut value = Vec::new();
+        write_tu64(&mut value, self.outgoing_cltv_value as u64);
+        records.push(TlvRecord { tlv_type: OUTGOING_CLTV_VALUE_TYPE, value });
+
+        if let Some(short_channel_id) = self.short_channel_id {
+            let mut value = Vec::new();
+            write_u64(&mut value, short_channel_id);
+            records.push(TlvRecord { tlv_type: SHORT_CHANNEL_ID_TYPE, value });
+        }
+        if let Some(payment_data) = &self.payment_data {
+            let mut value = payment_data.payment_secret.to_vec();
+            write_tu64(&mut value, payment_data.total_msat);
+            records.push(TlvRecord { tlv_type: PAYMENT_DATA_TYPE, value });
+        }
+
+        records.extend(self.custom_records.iter().cloned());
+        records.sort_by_key(|record| record.tlv_type);
+
+        let mut w = Vec::new();
+        write_tlv_stream(&mut w, &records);
+        w
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<HopPayload, OnionError> {
+        let known_types = [AMT_TO_FORWARD_TYPE, OUTGOING_CLTV_VALUE_TYPE, SHORT_CHANNEL_ID_TYPE, PAYMENT_DATA_TYPE];
+        let records = read_tlv_stream(&mut Reader::new(bytes), &known_types)?;
+
+        let amt_to_forward = match find_tlv(&records, AMT_TO_FORWARD_TYPE) {
+            Some(value) => read_tu64(value)?,
+            None => return Err(OnionError::MissingField(AMT_TO_FORWARD_TYPE)),
+        };
+        let outgoing_cltv_value = match find_tlv(&records, OUTGOING_CLTV_VALUE_TYPE) {
+            Some(value) => u32::try_from(read_tu64(value)?).map_err(|_| DecodeError::InvalidValue)?,
+            None => return Err(OnionError::MissingField(OUTGOING_CLTV_VALUE_TYPE)),
+        };
+        let short_channel_id = match find_tlv(&records, SHORT_CHANNEL_ID_TYPE) {
+            Some(value) => Some(Reader::new(value).read_u64()?),
+            None => None,
+        };
+        let payment_data = match find_tlv(&records, PAYMENT_DATA_TYPE) {
+            Some(value) => {
+                let mut r = Reader::new(value);
+                let payment_secret = r.read_array()?;
+                let total_msat = read_tu64(r.read_remaining())?;
+                Some(PaymentData { payment_secret, total_msat })
+            }
+            None => None,
+        };
+
+        let custom_records = records.into_iter().filter(|record| !known_types.contains(&record.tlv_type)).collect();
+
+        Ok(HopPayload { amt_to_forward, outgoing_cltv_value, short_channel_id, payment_data, custom_records })
+    }
+}
+
+/// The per-hop secrets the sender derives while building the onion.
+#[derive(Debug, Clone)]
+pub struct OnionHopKeys {
+    pub shared_secret: [u8; 32],
+    pub blinding_factor: [u8; 32],
+    pub ephemeral_pubkey: PublicKey,
+    pub rho: [u8; 32],
+    pub mu: [u8; 32],
+}
+
+/// The result of a hop peeling its layer: either pass the inner onion on, or
+/// we're the destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeeledOnion {
+    Forward { payload: HopPayload, next_packet: Box<OnionPacket> },
+    Receive { payload: HopPayload },
+}
+
+impl PeeledOnion {
+    pub fn payload(&self) -> &HopPayload {
+        match self {
+            PeeledOnion::Forward { payload, .. } => payload,
+            PeeledOnion::Receive { payload } => payload,
+        }
+    }
+}
+
+//
+// Key derivation
+//
+
+fn ecdh(secret: &SecretKey, point: &PublicKey) -> [u8; 32] {
+    // SHA256 of the compressed shared point
+    SharedSecret::new(point, secret).secret_bytes()
+}
+
+// rho, mu, pad and um keys are all HMAC-SHA256(key_type, secret)
+fn generate_key(key_type: &[u8], secret: &[u8; 32]) -> [u8; 32] {
+    let mut engine = HmacEngine::<Sha256>::new(key_type);
+    engine.input(secret);
+    Hmac::from_engine(engine).to_byte_array()
+}
+
+fn blinding_factor(ephemeral_pubkey: &PublicKey, shared_secret: &[u8; 32]) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    engine.input(&ephemeral_pubkey.serialize());
+    engine.input(shared_secret);
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+fn hmac(key: &[u8; 32], data: &[u8], associated_data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<Sha256>::new(key);
+    engine.input(data);
+    engine.input(associated_data);
+    Hmac::from_engine(engine).to_byte_array()
+}
+
+// ChaCha20 keystream with a zero nonce
+fn cipher_stream(key: &[u8; 32], len: usize) -> Vec<u8> {
+    let mut stream = vec![0u8; len];
+    let mut cipher = ChaCha20::new(key.into(), &[0u8; 12].into());
+    cipher.apply_keystream(&mut stream);
+    stream
+}
+
+fn xor_in_place(data: &mut [u8], stream: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(stream) {
+        *byte ^= key;
+    }
+}
+
+/// Walks the route, deriving each hop's shared secret from the session key.
+/// Every hop sees a different ephemeral key, blinded by the hops before it.
+pub fn construct_onion_keys(session_key: &SecretKey, route: &[PublicKey]) -> Vec<OnionHopKeys> {
+    let mut ephemeral_secret = *session_key;
+    let mut keys = Vec::new();
+
+    for node_id in route {
+        let ephemeral_pubkey = pubkey_from_secret(ephemeral_secret);
+        let shared_secret = ecdh(&ephemeral_secret, node_id);
+        let blinding_factor = blinding_factor(&ephemeral_pubkey, &shared_secret);
+
+        keys.push(OnionHopKeys {
+            shared_secret,
+            blinding_factor,
+            ephemeral_pubkey,
+            rho: generate_key(b"rho", &shared_secret),
+            mu: generate_key(b"mu", &shared_secret),
+        });
+
+        ephemeral_secret = privkey_multipication_tweak(ephemeral_secret, blinding_factor);
+    }
+
+    keys
+}
+
+//
+// Construction
+//
+
+// Each hop shifts the routing info left by the size of its own payload and
+// fills the end with its rho stream. The sender precomputes those bytes so
+// the final hop's HMAC still covers them.
+fn generate_filler(keys: &[OnionHopKeys], hop_lens: &[usize]) -> Vec<u8> {
+    let mut filler = Vec::new();
+
+    for (hop_keys, hop_len) in keys.iter().zip(hop_lens) {
+        filler.resize(filler.len() + hop_len, 0);
+        let stream = cipher_stream(&hop_keys.rho, ONION_HOP_DATA_LEN + hop_len);
+        let start = ONION_HOP_DATA_LEN + hop_len - filler.len();
+        xor_in_place(&mut filler, &stream[start..]);
+    }
+
+    filler
+}
+
+/// Builds the onion for `route` from the innermost layer outwards. The
+/// payment hash is passed as associated data so the onion can't be replayed
+/// on a different HTLC.
+pub fn create_onion_packet(
+    session_key: &SecretKey,
+    route: &[PublicKey],
+    payloads: &[HopPayload],
+    associated_data: &[u8],
+) -> Result<OnionPacket, OnionError> {
+    if route.is_empty() {
+        return Err(OnionError::EmptyRoute);
+    }
+    if route.len() != payloads.len() {
+        return Err(OnionError::PayloadCountMismatch);
+    }
+
+    let keys = construct_onion_keys(session_key, route);
+
+    // length-prefixed payloads, each followed by the next hop's HMAC
+    let encoded: Vec<Vec<u8>> = payloads
+        .iter()
+        .map(|payload| {
+            let payload = payload.encode();
+            let mut w = Vec::new();
+            write_bigsize(&mut w, payload.len() as u64);
+            w.extend_from_slice(&payload);
+            w
+        })
+        .collect();
+    let hop_lens: Vec<usize> = encoded.iter().map(|payload| payload.len() + HMAC_LEN).collect();
+    if hop_lens.iter().sum::<usize>() > ONION_HOP_DATA_LEN {
+        return Err(OnionError::RouteTooLong);
+    }
+
+    let filler = generate_filler(&keys[..keys.len() - 1], &hop_lens[..hop_lens.len() - 1]);
+
+    // start from pseudo-random bytes so the unused space doesn't leak the route length
+    let pad_key = generate_key(b"pad", &session_key.secret_bytes());
+    let mut hop_data = cipher_stream(&pad_key, ONION_HOP_DATA_LEN);
+    let mut next_hmac = [0u8; HMAC_LEN];
+
+    for (i, hop_keys) in keys.iter().enumerate().rev() {
+        let shift = hop_lens[i];
+        hop_data.copy_within(..ONION_HOP_DATA_LEN - shift, shift);
+        hop_data[..encoded[i].len()].copy_from_slice(&encoded[i]);
+        hop_data[encoded[i].len()..shift].copy_from_slice(&next_hmac);
+
+        xor_in_place(&mut hop_data, &cipher_stream(&hop_keys.rho, ONION_HOP_DATA_LEN));
+
+        if i == keys.len() - 1 {
+            hop_data[ONION_HOP_DATA_LEN - filler.len()..].copy_from_slice(&filler);
+        }
+
+        next_hmac = hmac(&hop_keys.mu, &hop_data, associated_data);
+    }
+
+    Ok(OnionPacket {
+        version: ONION_VERSION,
+        public_key: keys[0].ephemeral_pubkey,
+        hop_data: hop_data.try_into().unwrap(),
+        hmac: next_hmac,
+    })
+}
+
+//
+// Processing
+//
+
+/// What each hop runs on an incoming onion: check the HMAC, decrypt our
+/// payload and blind the ephemeral key for the next hop.
+pub fn peel_onion(node_secret: &SecretKey, packet: &OnionPacket, associated_data: &[u8]) -> Result<PeeledOnion, OnionError> {
+    if packet.version != ONION_VERSION {
+        return Err(OnionError::InvalidVersion(packet.version));
+    }
+
+    let shared_secret = ecdh(node_secret, &packet.public_key);
+
+    let mu = generate_key(b"mu", &shared_secret);
+    if hmac(&mu, &packet.hop_data, associated_data) != packet.hmac {
+        return Err(OnionError::InvalidHmac);
+    }
+
+    // decrypt twice the routing info so the shifted-in bytes come out of the stream too
+    let rho = generate_key(b"rho", &shared_secret);
+    let mut buf = packet.hop_data.to_vec();
+    buf.resize(2 * ONION_HOP_DATA_LEN, 0);
+    xor_in_place(&mut buf, &cipher_stream(&rho, 2 * ONION_HOP_DATA_LEN));
+
+    let mut r = Reader::new(&buf);
+    let payload_len = r.read_bigsize()? as usize;
+    if payload_len > ONION_HOP_DATA_LEN {
+        return Err(OnionError::InvalidPayload(DecodeError::InvalidValue));
+    }
+    let payload = HopPayload::decode(r.read_bytes(payload_len)?)?;
+    let next_hmac: [u8; HMAC_LEN] = r.read_array()?;
+
+    // an all-zero HMAC marks the end of the route
+    if next_hmac == [0u8; HMAC_LEN] {
+        return Ok(PeeledOnion::Receive { payload });
+    }
+
+    let next_hop_data = r.read_bytes(ONION_HOP_DATA_LEN)?;
+    let blinding_factor = blinding_factor(&packet.public_key, &shared_secret);
+
+    Ok(PeeledOnion::Forward {
+        payload,
+        next_packet: Box::new(OnionPacket {
+            version: ONION_VERSION,
+            public_key: pubkey_multipication_tweak(packet.public_key, blinding_factor),
+            hop_data: next_hop_data.try_into().unwrap(),
+            hmac: next_hmac,
+        }),
+    })
+}
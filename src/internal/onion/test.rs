@@ -0,0 +1,135 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key};
+use internal::wire::{TlvRecord, WireMessage};
+use internal::onion::{construct_onion_keys, create_onion_packet, peel_onion, HopPayload, OnionError, PaymentData, PeeledOnion};
+use lightning::util::ser::Writeable;
+
+// BOLT 4 onion test vector: five hops whose node secrets are 0x41.., 0x42.., ...
+pub fn onion_test_vector_route() -> (Vec<SecretKey>, Vec<PublicKey>) {
+    let node_secrets: Vec<SecretKey> = (0x41..=0x45).map(|b| secp256k1_private_key(&[b; 32])).collect();
+    let route = node_secrets.iter().map(|secret| pubkey_from_secret(*secret)).collect();
+    (node_secrets, route)
+}
+
+pub fn onion_test_vector_payloads() -> Vec<HopPayload> {
+    let mut hop_2 = HopPayload::forward(2, 14000, 1400);
+    hop_2.custom_records.push(TlvRecord {
+        tlv_type: 513,
+        value: hex::decode("0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f").unwrap(),
+    });
+
+    let mut hop_5 = HopPayload::receive(
+        10000,
+        1000,
+        Some(PaymentData {
+            payment_secret: hex::decode("24a33562c54507a9334e79f0dc4f17d407e6d7c61f0e2f3d0d38599502f61704").unwrap().try_into().unwrap(),
+            total_msat: 10000,
+        }),
+    );
+    hop_5.custom_records.push(TlvRecord { tlv_type: 301, value: vec![0x2a; 224] });
+
+    vec![
+        HopPayload::forward(1, 15000, 1500),
+        hop_2,
+        HopPayload::forward(3, 12500, 1250),
+        HopPayload::forward(4, 10000, 1000),
+        hop_5,
+    ]
+}
+
+#[test]
+fn test_onion_vectors() {
+    let (_, route) = onion_test_vector_route();
+    let session_key = secp256k1_private_key(&[0x41; 32]);
+
+    assert_eq!(
+        hex::encode(route[0].serialize()),
+        "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619"
+    );
+    assert_eq!(
+        hex::encode(route[4].serialize()),
+        "02edabbd16b41c8371b92ef2f04c1185b4f03b6dcd52ba9b78d9d7c89c8f221145"
+    );
+
+    let keys = construct_onion_keys(&session_key, &route);
+    assert_eq!(hex::encode(keys[0].shared_secret), "53eb63ea8a3fec3b3cd433b85cd62a4b145e1dda09391b348c4e1cd36a03ea66");
+    assert_eq!(hex::encode(keys[0].blinding_factor), "2ec2e5da605776054187180343287683aa6a51b4b1c04d6dd49c45d8cffb3c36");
+    assert_eq!(hex::encode(keys[0].rho), "ce496ec94def95aadd4bec15cdb41a740c9f2b62347c4917325fcc6fb0453986");
+    assert_eq!(hex::encode(keys[0].mu), "b57061dc6d0a2b9f261ac410c8b26d64ac5506cbba30267a649c28c179400eba");
+    assert_eq!(
+        hex::encode(keys[4].ephemeral_pubkey.serialize()),
+        "03a214ebd875aab6ddfd77f22c5e7311d7f77f17a169e599f157bbcdae8bf071f4"
+    );
+    assert_eq!(hex::encode(keys[4].shared_secret), "b5756b9b542727dbafc6765a49488b023a725d631af688fc031217e90770c328");
+    assert_eq!(hex::encode(keys[4].rho), "034e18b8cc718e8af6339106e706c52d8df89e2b1f7e9142d996acf88df8799b");
+    assert_eq!(hex::encode(keys[4].mu), "8e45e5c61c2b24cb6382444db6698727afb063adecd72aada233d4bf273d975a");
+
+    let payloads = onion_test_vector_payloads();
+    assert_eq!(hex::encode(payloads[0].encode()), "02023a98040205dc06080000000000000001");
+    assert_eq!(hex::encode(payloads[2].encode()), "020230d4040204e206080000000000000003");
+    assert_eq!(hex::encode(payloads[3].encode()), "02022710040203e806080000000000000004");
+
+    let packet = create_onion_packet(&session_key, &route, &payloads, &[0x42; 32]).unwrap();
+    let mut bytes = Vec::new();
+    packet.write(&mut bytes);
+    assert_eq!(
+        hex::encode_upper(bytes),
+        "0002EEC7245D6B7D2CCB30380BFBE2A3648CD7A942653F5AA340EDCEA1F283686619F7F3416A5AA36DC7EEB3EC6D421E9615471AB870A33AC07FA5D5A51DF0A8823AABE3FEA3F90D387529D4F72837F9E687230371CCD8D263072206DBED0234F6505E21E282ABD8C0E4F5B9FF8042800BBAB065036EADD0149B37F27DDE664725A49866E052E809D2B0198AB9610FAA656BBF4EC516763A59F8F42C171B179166BA38958D4F51B39B3E98706E2D14A2DAFD6A5DF808093ABFCA5AEAACA16EDED5DB7D21FB0294DD1A163EDF0FB445D5C8D7D688D6DD9C541762BF5A5123BF9939D957FE648416E88F1B0928BFA034982B22548E1A4D922690EECF546275AFB233ACF4323974680779F1A964CFE687456035CC0FBA8A5428430B390F0057B6D1FE9A8875BFA89693EEB838CE59F09D207A503EE6F6299C92D6361BC335FCBF9B5CD44747AADCE2CE6069CFDC3D671DAEF9F8AE590CF93D957C9E873E9A1BC62D9640DC8FC39C14902D49A1C80239B6C5B7FD91D05878CBF5FFC7DB2569F47C43D6C0D27C438ABFF276E87364DEB8858A37E5A62C446AF95D8B786EAF0B5FCF78D98B41496794F8DCAAC4EEF34B2ACFB94C7E8C32A9E9866A8FA0B6F2A06F00A1CCDE569F97EEC05C803BA7500ACC96691D8898D73D8E6A47B8F43C3D5DE74458D20EDA61474C426359677001FBD75A74D7D5DB6CB4FEB83122F133206203E4E2D293F838BF8C8B3A29ACB321315100B87E80E0EDB272EE80FDA944E3FB6084ED4D7F7C7D21C69D9DA43D31A90B70693F9B0CC3EAC74C11AB8FF655905688916CFA4EF0BD04135F2E50B7C689A21D04E8E981E74C6058188B9B1F9DFC3EEC6838E9FFBCF22CE738D8A177C19318DFFEF090CEE67E12DE1A3E2A39F61247547BA5257489CBC11D7D91ED34617FCC42F7A9DA2E3CF31A94A210A1018143173913C38F60E62B24BF0D7518F38B5BAB3E6A1F8AEB35E31D6442C8ABB5178EFC892D2E787D79C6AD9E2FC271792983FA9955AC4D1D84A36C024071BC6E431B625519D556AF38185601F70E29035EA6A09C8B676C9D88CF7E05E0F17098B584C4168735940263F940033A220F40BE4C85344128B14BEB9E75696DB37014107801A59B13E89CD9D2258C169D523BE6D31552C44C82FF4BB18EC9F099F3BF0E5B1BB2BA9A87D7E26F98D294927B600B5529C47E04D98956677CBCEE8FA2B60F49776D8B8C367465B7C626DA53700684FB6C918EAD0EAB8360E4F60EDD25B4F43816A75ECF70F909301825B512469F8389D79402311D8AECB7B3EF8599E79485A4388D87744D899F7C47EE644361E17040A7958C8911BE6F463AB6A9B2AFACD688EC55EF517B38F1339EFC54487232798BB25522FF4572FF68567FE830F92F7B8113EFCE3E98C3FFFBAEDCE4FD8B50E41DA97C0C08E423A72689CC68E68F752A5E3A9003E64E35C957CA2E1C48BB6F64B05F56B70B575AD2F278D57850A7AD568C24A4D32A3D74B29F03DC125488BC7C637DA582357F40B0A52D16B3B40BB2C2315D03360BC24209E20972C200566BCF3BBE5C5B0AEDD83132A8A4D5B4242BA370B6D67D9B67EB01052D132C7866B9CB502E44796D9D356E4E3CB47CC527322CD24976FE7C9257A2864151A38E568EF7A79F10D6EF27CC04CE382347A2488B1F404FDBF407FE1CA1C9D0D5649E34800E25E18951C98CAE9F43555EEF65FEE1EA8F15828807366C3B612CD5753BF9FB8FCED08855F742CDDD6F765F74254F03186683D646E6F09AC2805586C7CF11998357CAFC5DF3F285329366F475130C928B2DCEBA4AA383758E7A9D20705C4BB9DB619E2992F608A1BA65DB254BB389468741D0502E2588AEB54390AC600C19AF5C8E61383FC1BEBE0029E4474051E4EF908828DB9CCA13277EF65DB3FD47CCC2179126AAEFB627719F421E20"
+    );
+}
+
+#[test]
+fn test_onion_peel_each_hop() {
+    let (node_secrets, route) = onion_test_vector_route();
+    let session_key = secp256k1_private_key(&[0x41; 32]);
+    let payloads = onion_test_vector_payloads();
+    let associated_data = [0x42; 32];
+
+    let mut packet = create_onion_packet(&session_key, &route, &payloads, &associated_data).unwrap();
+
+    for (i, node_secret) in node_secrets.iter().enumerate() {
+        match peel_onion(node_secret, &packet, &associated_data).unwrap() {
+            PeeledOnion::Forward { payload, next_packet } => {
+                assert!(i < 4);
+                assert_eq!(payload, payloads[i]);
+                packet = *next_packet;
+            }
+            PeeledOnion::Receive { payload } => {
+                assert_eq!(i, 4);
+                assert_eq!(payload, payloads[i]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_onion_rejects_tampering() {
+    let (node_secrets, route) = onion_test_vector_route();
+    let session_key = secp256k1_private_key(&[0x41; 32]);
+    let payloads = onion_test_vector_payloads();
+    let packet = create_onion_packet(&session_key, &route, &payloads, &[0x42; 32]).unwrap();
+
+    // bound to the payment hash
+    assert_eq!(peel_onion(&node_secrets[0], &packet, &[0x43; 32]), Err(OnionError::InvalidHmac));
+
+    // only the first hop can read the outer layer
+    assert_eq!(peel_onion(&node_secrets[1], &packet, &[0x42; 32]), Err(OnionError::InvalidHmac));
+
+    let mut tampered = packet.clone();
+    tampered.hop_data[100] ^= 1;
+    assert_eq!(peel_onion(&node_secrets[0], &tampered, &[0x42; 32]), Err(OnionError::InvalidHmac));
+
+    let mut tampered = packet.clone();
+    tampered.version = 1;
+    assert_eq!(peel_onion(&node_secrets[0], &tampered, &[0x42; 32]), Err(OnionError::InvalidVersion(1)));
+
+    // 27 hops of ~50 bytes don't fit in 1300 bytes
+    let long_route = vec![route[0]; 27];
+    let long_payloads = vec![HopPayload::forward(1, 1000, 100); 27];
+    assert_eq!(
+        create_onion_packet(&session_key, &long_route, &long_payloads, &[0x42; 32]),
+        Err(OnionError::RouteTooLong)
+    );
+}
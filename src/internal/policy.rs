@@ -0,0 +1,425 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::blockdata::opcodes::all as opcodes;
+use bitcoin::hashes::hash160::Hash as Hash160;
+use bitcoin::hashes::Hash;
+use bitcoin::script::{Builder, ScriptBuf};
+use bitcoin::{PublicKey, Witness};
+use internal::script_asm::ScriptNames;
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub mod test;
+
+// A small policy language for the channel scripts. The compiler only knows a
+// handful of rules, but they are the ones BOLT 3 uses, so
+//
+//   or(pk(revocation), and(older(144), pk(delayed)))
+//
+// compiles to exactly the to_local script, and the offered HTLC falls out of
+// two rules for choosing a branch without an OP_IF flag in the witness.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    Parse(String),
+    UnknownName(String),
+    InvalidKey(String),
+    InvalidHash(String),
+    InvalidNumber(String),
+    InvalidThreshold(usize, usize),
+    Unsatisfiable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// `pk(K)`: a signature for K
+    Key(PublicKey),
+    /// `pkh(K)`: K and a signature for it, the script only commits to HASH160(K)
+    KeyHash(PublicKey),
+    /// `older(n)`: n blocks since the output confirmed
+    Older(u16),
+    /// `after(n)`: the block height n has passed
+    After(u32),
+    /// `hash160(H)`: a preimage with HASH160(preimage) == H
+    Hash160([u8; 20]),
+    /// `multi(k, K1, K2, ...)`: k of the keys' signatures
+    Multi(usize, Vec<PublicKey>),
+    And(Box<Policy>, Box<Policy>),
+    Or(Box<Policy>, Box<Policy>),
+}
+
+pub fn pk(key: &PublicKey) -> Policy {
+    Policy::Key(*key)
+}
+
+pub fn pkh(key: &PublicKey) -> Policy {
+    Policy::KeyHash(*key)
+}
+
+pub fn older(blocks: u16) -> Policy {
+    Policy::Older(blocks)
+}
+
+pub fn after(height: u32) -> Policy {
+    Policy::After(height)
+}
+
+pub fn hash160(hash: &[u8; 20]) -> Policy {
+    Policy::Hash160(*hash)
+}
+
+pub fn multi(threshold: usize, keys: &[PublicKey]) -> Policy {
+    Policy::Multi(threshold, keys.to_vec())
+}
+
+pub fn and(left: Policy, right: Policy) -> Policy {
+    Policy::And(Box::new(left), Box::new(right))
+}
+
+pub fn or(left: Policy, right: Policy) -> Policy {
+    Policy::Or(Box::new(left), Box::new(right))
+}
+
+//
+// Parsing
+//
+
+// splits "a(b, c), d" at the commas that aren't inside parentheses
+fn split_args(text: &str) -> Result<Vec<&str>, PolicyError> {
+    let mut args = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return Err(PolicyError::Parse(text.to_string()));
+        }
+    }
+    if depth != 0 {
+        return Err(PolicyError::Parse(text.to_string()));
+    }
+    args.push(text[start..].trim());
+
+    Ok(args)
+}
+
+fn named_bytes(arg: &str, names: &ScriptNames) -> Result<Vec<u8>, PolicyError> {
+    match names.bytes_of(arg) {
+        Some(bytes) => Ok(bytes.to_vec()),
+        None => hex::decode(arg).map_err(|_| PolicyError::UnknownName(arg.to_string())),
+    }
+}
+
+fn parse_key(arg: &str, names: &ScriptNames) -> Result<PublicKey, PolicyError> {
+    PublicKey::from_slice(&named_bytes(arg, names)?).map_err(|_| PolicyError::InvalidKey(arg.to_string()))
+}
+
+fn parse_hash(arg: &str, names: &ScriptNames) -> Result<[u8; 20], PolicyError> {
+    named_bytes(arg, names)?.try_into().map_err(|_| PolicyError::InvalidHash(arg.to_string()))
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str) -> Result<T, PolicyError> {
+    arg.parse().map_err(|_| PolicyError::InvalidNumber(arg.to_string()))
+}
+
+/// Parses text like `or(pk(revocation_pubkey), and(older(144), pk(to_local_delayed_pubkey)))`.
+/// Keys and hashes are names from `names` or hex.
+pub fn parse_policy(text: &str, names: &ScriptNames) -> Result<Policy, PolicyError> {
+    let text = text.trim();
+    let open = text.find('(').ok_or(PolicyError::Parse(text.to_string()))?;
+    let inner = text[open + 1..].strip_suffix(')').ok_or(PolicyError::Parse(text.to_string()))?;
+    let args = split_args(inner)?;
+
+    let policy = match (&text[..open], args.as_slice()) {
+        ("pk", [key]) => Policy::Key(parse_key(key, names)?),
+        ("pkh", [key]) => Policy::KeyHash(parse_key(key, names)?),
+        ("older", [blocks]) => Policy::Older(parse_number(blocks)?),
+        ("after", [height]) => Policy::After(parse_number(height)?),
+        ("hash160", [hash]) => Policy::Hash160(parse_hash(hash, names)?),
+        ("multi", [threshold, keys @ ..]) => {
+            let threshold = parse_number(threshold)?;
+            if threshold == 0 || threshold > keys.len() || keys.len() > 16 {
+                return Err(PolicyError::InvalidThreshold(threshold, keys.len()));
+            }
+            let keys = keys.iter().map(|key| parse_key(key, names)).collect::<Result<Vec<_>, _>>()?;
+            Policy::Multi(threshold, keys)
+        }
+        ("and", [left, right]) => and(parse_policy(left, names)?, parse_policy(right, names)?),
+        ("or", [left, right]) => or(parse_policy(left, names)?, parse_policy(right, names)?),
+        _ => return Err(PolicyError::Parse(text.to_string())),
+    };
+
+    Ok(policy)
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Key(key) => write!(f, "pk({})", key),
+            Policy::KeyHash(key) => write!(f, "pkh({})", key),
+            Policy::Older(blocks) => write!(f, "older({})", blocks),
+            Policy::After(height) => write!(f, "after({})", height),
+            Policy::Hash160(hash) => write!(f, "hash160({})", hex::encode(hash)),
+            Policy::Multi(threshold, keys) => {
+                write!(f, "multi({}", threshold)?;
+                for key in keys {
+                    write!(f, ", {}", key)?;
+                }
+                write!(f, ")")
+            }
+            Policy::And(left, right) => write!(f, "and({}, {})", left, right),
+            Policy::Or(left, right) => write!(f, "or({}, {})", left, right),
+        }
+    }
+}
+
+//
+// Compiling
+//
+
+// What a fragment leaves on the stack: a key still waiting for its
+// OP_CHECKSIG, or a true/false result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Key,
+    Bool,
+}
+
+// How an or() picks its branch
+enum OrShape<'a> {
+    // the witness reveals the key on the left, so the script can tell which
+    // branch it is in by comparing its hash: the revocation check of an HTLC
+    KeySelected(&'a PublicKey),
+    // or(multi(2, A, B), and(pk(A), hash160(H))): both branches need A, so it
+    // is pushed once and the size of the top item tells a 32 byte preimage
+    // from the multisig: the rest of the offered HTLC
+    PreimageSelected { shared: &'a PublicKey, other: &'a PublicKey, hash: &'a [u8; 20] },
+    // both branches end in a key, so the OP_CHECKSIG goes after the OP_ENDIF
+    Keys,
+    Bools,
+}
+
+impl Policy {
+    fn or_shape<'a>(left: &'a Policy, right: &'a Policy) -> OrShape<'a> {
+        if let Policy::KeyHash(key) = left {
+            return OrShape::KeySelected(key);
+        }
+        if let (Policy::Multi(2, keys), Policy::And(key, hash)) = (left, right)
+            && let (Policy::Key(shared), Policy::Hash160(hash)) = (key.as_ref(), hash.as_ref())
+            && keys.len() == 2
+            && keys[0] == *shared
+        {
+            return OrShape::PreimageSelected { shared, other: &keys[1], hash };
+        }
+        if left.kind() == Kind::Key && right.kind() == Kind::Key {
+            return OrShape::Keys;
+        }
+        OrShape::Bools
+    }
+
+    fn kind(&self) -> Kind {
+        match self {
+            Policy::Key(_) | Policy::KeyHash(_) => Kind::Key,
+            Policy::And(_, right) => right.kind(),
+            Policy::Or(left, right) => match Policy::or_shape(left, right) {
+                OrShape::Keys => Kind::Key,
+                _ => Kind::Bool,
+            },
+            _ => Kind::Bool,
+        }
+    }
+
+    // leaves the key for a following OP_CHECKSIG, only for Kind::Key
+    fn push_key(&self, builder: Builder) -> Builder {
+        match self {
+            Policy::Key(key) => builder.push_key(key),
+            Policy::KeyHash(key) => builder
+                .push_opcode(opcodes::OP_DUP)
+                .push_opcode(opcodes::OP_HASH160)
+                .push_slice(key.pubkey_hash())
+                .push_opcode(opcodes::OP_EQUALVERIFY),
+            Policy::And(left, right) => right.push_key(left.push_verify(builder)),
+            Policy::Or(left, right) => {
+                let builder = left.push_key(builder.push_opcode(opcodes::OP_IF));
+                right.push_key(builder.push_opcode(opcodes::OP_ELSE)).push_opcode(opcodes::OP_ENDIF)
+            }
+            _ => unreachable!("{} does not leave a key", self),
+        }
+    }
+
+    fn push_bool(&self, builder: Builder) -> Builder {
+        if self.kind() == Kind::Key {
+            return self.push_key(builder).push_opcode(opcodes::OP_CHECKSIG);
+        }
+
+        match self {
+            // leaves the (non-zero) lock on the stack as its result
+            Policy::Older(blocks) => builder.push_int(*blocks as i64).push_opcode(opcodes::OP_CSV),
+            Policy::After(height) => builder.push_int(*height as i64).push_opcode(opcodes::OP_CLTV),
+            Policy::Hash160(hash) => builder
+                .push_opcode(opcodes::OP_HASH160)
+                .push_slice(hash)
+                .push_opcode(opcodes::OP_EQUAL),
+            Policy::Multi(threshold, keys) => keys
+                .iter()
+                .fold(builder.push_int(*threshold as i64), |builder, key| builder.push_key(key))
+                .push_int(keys.len() as i64)
+                .push_opcode(opcodes::OP_CHECKMULTISIG),
+            Policy::And(left, right) => right.push_bool(left.push_verify(builder)),
+            Policy::Or(left, right) => match Policy::or_shape(left, right) {
+                OrShape::KeySelected(key) => {
+                    let builder = builder
+                        .push_opcode(opcodes::OP_DUP)
+                        .push_opcode(opcodes::OP_HASH160)
+                        .push_slice(key.pubkey_hash())
+                        .push_opcode(opcodes::OP_EQUAL)
+                        .push_opcode(opcodes::OP_IF)
+                        .push_opcode(opcodes::OP_CHECKSIG)
+                        .push_opcode(opcodes::OP_ELSE);
+                    right.push_bool(builder).push_opcode(opcodes::OP_ENDIF)
+                }
+                OrShape::PreimageSelected { shared, other, hash } => builder
+                    .push_key(shared)
+                    .push_opcode(opcodes::OP_SWAP)
+                    .push_opcode(opcodes::OP_SIZE)
+                    .push_int(32)
+                    .push_opcode(opcodes::OP_EQUAL)
+                    .push_opcode(opcodes::OP_NOTIF)
+                    .push_opcode(opcodes::OP_DROP)
+                    .push_int(2)
+                    .push_opcode(opcodes::OP_SWAP)
+                    .push_key(other)
+                    .push_int(2)
+                    .push_opcode(opcodes::OP_CHECKMULTISIG)
+                    .push_opcode(opcodes::OP_ELSE)
+                    .push_opcode(opcodes::OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(opcodes::OP_EQUALVERIFY)
+                    .push_opcode(opcodes::OP_CHECKSIG)
+                    .push_opcode(opcodes::OP_ENDIF),
+                OrShape::Keys | OrShape::Bools => {
+                    let builder = left.push_bool(builder.push_opcode(opcodes::OP_IF));
+                    right.push_bool(builder.push_opcode(opcodes::OP_ELSE)).push_opcode(opcodes::OP_ENDIF)
+                }
+            },
+            Policy::Key(_) | Policy::KeyHash(_) => unreachable!(),
+        }
+    }
+
+    // leaves nothing, and fails the script if the fragment isn't satisfied
+    fn push_verify(&self, builder: Builder) -> Builder {
+        if self.kind() == Kind::Key {
+            return self.push_key(builder).push_opcode(opcodes::OP_CHECKSIGVERIFY);
+        }
+
+        match self {
+            Policy::Older(_) | Policy::After(_) => self.push_bool(builder).push_opcode(opcodes::OP_DROP),
+            Policy::Hash160(hash) => builder
+                .push_opcode(opcodes::OP_HASH160)
+                .push_slice(hash)
+                .push_opcode(opcodes::OP_EQUALVERIFY),
+            Policy::And(left, right) => right.push_verify(left.push_verify(builder)),
+            _ => self.push_bool(builder).push_opcode(opcodes::OP_VERIFY),
+        }
+    }
+
+    /// The witness script for this policy.
+    pub fn compile(&self) -> ScriptBuf {
+        self.push_bool(Builder::new()).into_script()
+    }
+}
+
+//
+// Satisfying
+//
+
+/// What the spender has to hand: signatures (DER with the sighash byte),
+/// preimages, and the sequence and lock time the spending transaction sets.
+#[derive(Debug, Clone, Default)]
+pub struct Satisfier {
+    pub signatures: BTreeMap<PublicKey, Vec<u8>>,
+    pub preimages: BTreeMap<[u8; 20], Vec<u8>>,
+    pub sequence: u32,
+    pub lock_time: u32,
+}
+
+impl Satisfier {
+    pub fn new() -> Satisfier {
+        Satisfier::default()
+    }
+
+    pub fn add_signature(&mut self, key: &PublicKey, signature: Vec<u8>) {
+        self.signatures.insert(*key, signature);
+    }
+
+    pub fn add_preimage(&mut self, preimage: &[u8]) {
+        self.preimages.insert(Hash160::hash(preimage).to_byte_array(), preimage.to_vec());
+    }
+
+    fn signature(&self, key: &PublicKey) -> Option<Vec<u8>> {
+        self.signatures.get(key).cloned()
+    }
+}
+
+impl Policy {
+    // the stack this fragment consumes, bottom first. Turning a key into a
+    // bool or a verify only adds opcodes, so one function covers every kind.
+    fn satisfaction(&self, satisfier: &Satisfier) -> Option<Vec<Vec<u8>>> {
+        match self {
+            Policy::Key(key) => Some(vec![satisfier.signature(key)?]),
+            Policy::KeyHash(key) => Some(vec![satisfier.signature(key)?, key.to_bytes()]),
+            Policy::Older(blocks) => ((satisfier.sequence & 0xffff) >= *blocks as u32).then(Vec::new),
+            Policy::After(height) => (satisfier.lock_time >= *height).then(Vec::new),
+            Policy::Hash160(hash) => Some(vec![satisfier.preimages.get(hash)?.clone()]),
+            Policy::Multi(threshold, keys) => {
+                // CHECKMULTISIG pops one item too many
+                let signatures: Vec<Vec<u8>> = keys.iter().filter_map(|key| satisfier.signature(key)).take(*threshold).collect();
+                (signatures.len() == *threshold).then(|| [vec![vec![]], signatures].concat())
+            }
+            // the right side runs last, so its items go underneath
+            Policy::And(left, right) => Some([right.satisfaction(satisfier)?, left.satisfaction(satisfier)?].concat()),
+            Policy::Or(left, right) => match Policy::or_shape(left, right) {
+                OrShape::KeySelected(key) => match satisfier.signature(key) {
+                    Some(signature) => Some(vec![signature, key.to_bytes()]),
+                    // anything on top that isn't the key takes the else branch
+                    None => right.satisfaction(satisfier).filter(|stack| !stack.is_empty()),
+                },
+                OrShape::PreimageSelected { shared, other, hash } => {
+                    let shared_signature = satisfier.signature(shared)?;
+                    match satisfier.preimages.get(hash) {
+                        Some(preimage) => Some(vec![shared_signature, preimage.clone()]),
+                        // the empty item fails the size check and is dropped
+                        None => Some(vec![vec![], shared_signature, satisfier.signature(other)?, vec![]]),
+                    }
+                }
+                OrShape::Keys | OrShape::Bools => match left.satisfaction(satisfier) {
+                    Some(stack) => Some([stack, vec![vec![1]]].concat()),
+                    None => Some([right.satisfaction(satisfier)?, vec![vec![]]].concat()),
+                },
+            },
+        }
+    }
+
+    /// A witness spending the compiled script, taking the leftmost branch of
+    /// each or() the satisfier can manage.
+    pub fn satisfy(&self, satisfier: &Satisfier) -> Result<Witness, PolicyError> {
+        let stack = self.satisfaction(satisfier).ok_or(PolicyError::Unsatisfiable)?;
+
+        let mut witness = Witness::new();
+        for item in stack {
+            witness.push(item);
+        }
+        witness.push(self.compile().as_bytes());
+
+        Ok(witness)
+    }
+}
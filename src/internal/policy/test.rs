@@ -0,0 +1,84 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use internal::script_asm::{disassemble, workshop_script_names, ScriptNames};
+use internal::script_utils::build_htlc_offerer_witness_script;
+use internal::policy::{after, and, hash160, multi, older, or, parse_policy, pk, pkh, PolicyError, Satisfier};
+use crate::exercises::solutions::to_local;
+
+#[test]
+fn test_policy_compiles_channel_scripts() {
+    let names = workshop_script_names();
+    let key = |byte: u8| bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[byte; 32]));
+    let (revocation, delayed, local_htlc, remote_htlc) = (key(0x12), key(0x13), key(0x11), key(0x21));
+    let payment_hash160 = Ripemd160::hash(&Sha256::hash(&[]).to_byte_array()).to_byte_array();
+
+    let to_local_policy = parse_policy("or(pk(revocation_pubkey), and(older(144), pk(to_local_delayed_pubkey)))", &names).unwrap();
+    assert_eq!(to_local_policy, or(pk(&revocation), and(older(144), pk(&delayed))));
+    assert_eq!(to_local_policy.compile(), to_local(&revocation, &delayed, 144));
+
+    let offered_policy = parse_policy(
+        "or(pkh(revocation_pubkey), or(multi(2, counterparty_htlc_pubkey, local_htlc_pubkey), and(pk(counterparty_htlc_pubkey), hash160(payment_hash160))))",
+        &names,
+    ).unwrap();
+    assert_eq!(offered_policy.compile(), build_htlc_offerer_witness_script(&revocation, &remote_htlc, &local_htlc, &payment_hash160));
+
+    // the demos' hash lock
+    let hash_locked = or(hash160(&payment_hash160), and(after(200), pk(&local_htlc)));
+    assert_eq!(
+        disassemble(&hash_locked.compile(), &names).unwrap(),
+        "OP_IF OP_HASH160 <payment_hash160> OP_EQUAL OP_ELSE 200 OP_CLTV OP_DROP <local_htlc_pubkey> OP_CHECKSIG OP_ENDIF"
+    );
+
+    // what Display prints parses back to the same policy
+    assert_eq!(parse_policy(&offered_policy.to_string(), &ScriptNames::new()).unwrap(), offered_policy);
+
+    assert_eq!(parse_policy("pk(nobody)", &names), Err(PolicyError::UnknownName("nobody".to_string())));
+    assert_eq!(parse_policy("hash160(revocation_pubkey)", &names), Err(PolicyError::InvalidHash("revocation_pubkey".to_string())));
+    assert_eq!(parse_policy("multi(3, local_pubkey, counterparty_pubkey)", &names), Err(PolicyError::InvalidThreshold(3, 2)));
+    assert_eq!(parse_policy("and(pk(local_pubkey)", &names), Err(PolicyError::Parse("pk(local_pubkey".to_string())));
+    assert_eq!(parse_policy("xor(older(1), older(2))", &names), Err(PolicyError::Parse("xor(older(1), older(2))".to_string())));
+}
+
+#[test]
+fn test_policy_satisfies_bolt3_witnesses() {
+    let key = |byte: u8| bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[byte; 32]));
+    let (revocation, delayed, local_htlc, remote_htlc) = (key(0x12), key(0x13), key(0x11), key(0x21));
+    let preimage = [0x42; 32];
+    let payment_hash160 = Ripemd160::hash(&Sha256::hash(&preimage).to_byte_array()).to_byte_array();
+    let (revocation_sig, delayed_sig, local_sig, remote_sig) = (vec![0x30, 0x12], vec![0x30, 0x13], vec![0x30, 0x11], vec![0x30, 0x21]);
+    let stack = |witness: bitcoin::Witness| witness.iter().map(|item| item.to_vec()).collect::<Vec<_>>();
+
+    // to_local: <revocation_sig> 1, or <delayed_sig> <> once the delay has passed
+    let to_local_policy = or(pk(&revocation), and(older(144), pk(&delayed)));
+    let script = to_local_policy.compile().to_bytes();
+    let mut satisfier = Satisfier::new();
+    satisfier.add_signature(&delayed, delayed_sig.clone());
+    assert_eq!(to_local_policy.satisfy(&satisfier), Err(PolicyError::Unsatisfiable));
+    satisfier.sequence = 144;
+    assert_eq!(stack(to_local_policy.satisfy(&satisfier).unwrap()), vec![delayed_sig.clone(), vec![], script.clone()]);
+    satisfier.add_signature(&revocation, revocation_sig.clone());
+    assert_eq!(stack(to_local_policy.satisfy(&satisfier).unwrap()), vec![revocation_sig.clone(), vec![1], script]);
+
+    let offered_policy = or(pkh(&revocation),
+                            or(multi(2, &[remote_htlc, local_htlc]), and(pk(&remote_htlc), hash160(&payment_hash160))));
+    let script = offered_policy.compile().to_bytes();
+
+    // the HTLC-timeout transaction: 0 <remotehtlcsig> <localhtlcsig> <>
+    let mut satisfier = Satisfier::new();
+    satisfier.add_signature(&remote_htlc, remote_sig.clone());
+    satisfier.add_signature(&local_htlc, local_sig.clone());
+    assert_eq!(stack(offered_policy.satisfy(&satisfier).unwrap()),
+               vec![vec![], remote_sig.clone(), local_sig, vec![], script.clone()]);
+
+    // the counterparty claiming with the preimage: <remotehtlcsig> <payment_preimage>
+    satisfier.add_preimage(&preimage);
+    assert_eq!(stack(offered_policy.satisfy(&satisfier).unwrap()), vec![remote_sig, preimage.to_vec(), script.clone()]);
+
+    // a revoked commitment: <revocation_sig> <revocationpubkey>
+    satisfier.add_signature(&revocation, revocation_sig.clone());
+    assert_eq!(stack(offered_policy.satisfy(&satisfier).unwrap()), vec![revocation_sig, revocation.to_bytes(), script]);
+}
@@ -0,0 +1,326 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::PublicKey;
+use internal::gossip::NetworkGraph;
+use internal::multihop::{compute_hop_amounts, AmountOverflow, FeePolicy, HopAmount};
+use internal::onion::{HopPayload, PaymentData};
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::str::FromStr;
+
+pub mod test;
+
+// what locking up funds for a block is worth, in parts per billion of the
+// amount. Used to trade fees off against cltv_expiry_delta.
+const RISK_FACTOR_PPB: u64 = 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    InvalidGraph(String),
+    UnknownNode(String),
+    SameNode,
+    NoRoute,
+    AmountOverflow,
+}
+
+impl From<AmountOverflow> for RouteError {
+    fn from(_: AmountOverflow) -> RouteError {
+        RouteError::AmountOverflow
+    }
+}
+
+/// One direction of a channel, with the sending node's policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingEdge {
+    pub short_channel_id: u64,
+    pub from: PublicKey,
+    pub to: PublicKey,
+    pub capacity_sat: u64,
+    pub policy: FeePolicy,
+    pub htlc_minimum_msat: u64,
+    pub htlc_maximum_msat: u64,
+}
+
+impl RoutingEdge {
+    fn can_carry(&self, amount_msat: u64) -> bool {
+        amount_msat >= self.htlc_minimum_msat
+            && amount_msat <= self.htlc_maximum_msat
+            && amount_msat <= self.capacity_sat * 1000
+    }
+}
+
+/// The directed graph the router searches, built from gossip or a fixture.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingGraph {
+    pub aliases: BTreeMap<PublicKey, String>,
+    pub edges: Vec<RoutingEdge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixturePolicy {
+    base_fee_msat: u64,
+    fee_proportional_millionths: u64,
+    cltv_expiry_delta: u32,
+    htlc_minimum_msat: u64,
+    htlc_maximum_msat: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureNode {
+    alias: String,
+    node_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureChannel {
+    short_channel_id: u64,
+    node_1: String,
+    node_2: String,
+    capacity_sat: u64,
+    node_1_policy: Option<FixturePolicy>,
+    node_2_policy: Option<FixturePolicy>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    nodes: Vec<FixtureNode>,
+    channels: Vec<FixtureChannel>,
+}
+
+impl RoutingGraph {
+    /// Loads a graph from JSON. Channels refer to nodes by alias, and a
+    /// direction without a policy can't be routed through.
+    pub fn from_json(json: &str) -> Result<RoutingGraph, RouteError> {
+        let fixture: Fixture = serde_json::from_str(json).map_err(|e| RouteError::InvalidGraph(e.to_string()))?;
+
+        let mut graph = RoutingGraph::default();
+        for node in &fixture.nodes {
+            let node_id = PublicKey::from_str(&node.node_id).map_err(|e| RouteError::InvalidGraph(e.to_string()))?;
+            graph.aliases.insert(node_id, node.alias.clone());
+        }
+
+        for channel in &fixture.channels {
+            let node_1 = graph.resolve_node(&channel.node_1)?;
+            let node_2 = graph.resolve_node(&channel.node_2)?;
+
+            for (from, to, policy) in [(node_1, node_2, &channel.node_1_policy), (node_2, node_1, &channel.node_2_policy)] {
+                if let Some(policy) = policy {
+                    graph.edges.push(RoutingEdge {
+                        short_channel_id: channel.short_channel_id,
+                        from,
+                        to,
+                        capacity_sat: channel.capacity_sat,
+                        policy: FeePolicy {
+                            base_fee_msat: policy.base_fee_msat,
+                            fee_proportional_millionths: policy.fee_proportional_millionths,
+                            cltv_expiry_delta: policy.cltv_expiry_delta,
+                        },
+                        htlc_minimum_msat: policy.htlc_minimum_msat,
+                        htlc_maximum_msat: policy.htlc_maximum_msat,
+                    });
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    pub fn load(path: &str) -> Result<RoutingGraph, RouteError> {
+        let json = std::fs::read_to_string(path).map_err(|e| RouteError::InvalidGraph(e.to_string()))?;
+        RoutingGraph::from_json(&json)
+    }
+
+    /// Every direction of every announced channel that has a channel_update.
+    pub fn from_network_graph(network_graph: &NetworkGraph) -> RoutingGraph {
+        let mut graph = RoutingGraph::default();
+
+        for (node_id, node) in &network_graph.nodes {
+            if let Some(announcement) = &node.announcement {
+                let alias = String::from_utf8_lossy(&announcement.contents.alias);
+                graph.aliases.insert(*node_id, alias.trim_end_matches('\0').to_string());
+            }
+        }
+
+        for (short_channel_id, channel) in &network_graph.channels {
+            let directions = [
+                (channel.node_id_1(), channel.node_id_2(), &channel.one_to_two),
+                (channel.node_id_2(), channel.node_id_1(), &channel.two_to_one),
+            ];
+            for (from, to, update) in directions {
+                let (Some(update), Some(policy)) = (update, channel.policy_from(&from)) else { continue };
+                graph.edges.push(RoutingEdge {
+                    short_channel_id: *short_channel_id,
+                    from,
+                    to,
+                    capacity_sat: channel.capacity_sat,
+                    policy,
+                    htlc_minimum_msat: update.contents.htlc_minimum_msat,
+                    htlc_maximum_msat: update.contents.htlc_maximum_msat,
+                });
+            }
+        }
+
+        graph
+    }
+
+    /// Accepts either an alias or a hex node id.
+    pub fn resolve_node(&self, name: &str) -> Result<PublicKey, RouteError> {
+        if let Some((node_id, _)) = self.aliases.iter().find(|(_, alias)| alias.as_str() == name) {
+            return Ok(*node_id);
+        }
+        PublicKey::from_str(name).map_err(|_| RouteError::UnknownNode(name.to_string()))
+    }
+
+    pub fn alias(&self, node_id: &PublicKey) -> String {
+        self.aliases.get(node_id).cloned().unwrap_or_else(|| node_id.to_string())
+    }
+}
+
+/// One channel of the route and the HTLC offered over it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHop {
+    pub short_channel_id: u64,
+    // the node receiving the HTLC over this channel
+    pub node_id: PublicKey,
+    pub amount_msat: u64,
+    pub cltv_expiry: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub hops: Vec<RouteHop>,
+}
+
+impl Route {
+    pub fn total_amount_msat(&self) -> u64 {
+        self.hops[0].amount_msat
+    }
+
+    pub fn total_fee_msat(&self) -> u64 {
+        self.hops[0].amount_msat - self.hops[self.hops.len() - 1].amount_msat
+    }
+
+    pub fn total_cltv_expiry(&self) -> u32 {
+        self.hops[0].cltv_expiry
+    }
+
+    /// The node ids to build the onion for, in order.
+    pub fn node_ids(&self) -> Vec<PublicKey> {
+        self.hops.iter().map(|hop| hop.node_id).collect()
+    }
+
+    /// Each hop is told about the next channel; the last one gets the payment secret.
+    pub fn onion_payloads(&self, payment_secret: [u8; 32]) -> Vec<HopPayload> {
+        let last = self.hops[self.hops.len() - 1].clone();
+        self.hops
+            .windows(2)
+            .map(|pair| HopPayload::forward(pair[1].short_channel_id, pair[1].amount_msat, pair[1].cltv_expiry))
+            .chain(std::iter::once(HopPayload::receive(
+                last.amount_msat,
+                last.cltv_expiry,
+                Some(PaymentData { payment_secret, total_msat: last.amount_msat }),
+            )))
+            .collect()
+    }
+}
+
+// what a node on the search frontier has to receive to get the payment to the target
+#[derive(Debug, Clone, Copy)]
+struct NodeState {
+    cost: u64,
+    amount_msat: u64,
+    cltv_expiry: u32,
+    // the edge towards the target
+    next_edge: Option<usize>,
+}
+
+/// Dijkstra from the target back to the source, since what each hop must be
+/// sent depends on everything after it. Each hop's cost is its fee plus the
+/// risk of the funds being locked up for its cltv_expiry_delta. The source
+/// doesn't pay itself a fee, so its own policy is ignored.
+pub fn find_route(
+    graph: &RoutingGraph,
+    source: &PublicKey,
+    target: &PublicKey,
+    amount_msat: u64,
+    final_cltv_expiry: u32,
+    excluded_channels: &[u64],
+) -> Result<Route, RouteError> {
+    if source == target {
+        return Err(RouteError::SameNode);
+    }
+
+    let mut best: BTreeMap<PublicKey, NodeState> = BTreeMap::new();
+    let mut queue = BinaryHeap::new();
+
+    best.insert(*target, NodeState { cost: 0, amount_msat, cltv_expiry: final_cltv_expiry, next_edge: None });
+    queue.push(Reverse((0u64, *target)));
+
+    while let Some(Reverse((cost, node_id))) = queue.pop() {
+        let state = best[&node_id];
+        if cost > state.cost {
+            continue;
+        }
+        if node_id == *source {
+            break;
+        }
+
+        for (index, edge) in graph.edges.iter().enumerate() {
+            if edge.to != node_id || excluded_channels.contains(&edge.short_channel_id) {
+                continue;
+            }
+            // the HTLC over this edge is what `node_id` needs to receive
+            if !edge.can_carry(state.amount_msat) {
+                continue;
+            }
+
+            let candidate = if edge.from == *source {
+                NodeState { cost: state.cost, amount_msat: state.amount_msat, cltv_expiry: state.cltv_expiry, next_edge: Some(index) }
+            } else {
+                // a hop whose fee or delta doesn't fit can't carry the payment either
+                let Ok(fee_msat) = edge.policy.fee_msat(state.amount_msat) else { continue };
+                let Some(forward_msat) = state.amount_msat.checked_add(fee_msat) else { continue };
+                let Some(cltv_expiry) = state.cltv_expiry.checked_add(edge.policy.cltv_expiry_delta) else { continue };
+                let risk = state.amount_msat as u128 * edge.policy.cltv_expiry_delta as u128 * RISK_FACTOR_PPB as u128 / 1_000_000_000;
+                NodeState {
+                    cost: state.cost.saturating_add(fee_msat).saturating_add(u64::try_from(risk).unwrap_or(u64::MAX)),
+                    amount_msat: forward_msat,
+                    cltv_expiry,
+                    next_edge: Some(index),
+                }
+            };
+
+            if best.get(&edge.from).is_none_or(|existing| candidate.cost < existing.cost) {
+                best.insert(edge.from, candidate);
+                queue.push(Reverse((candidate.cost, edge.from)));
+            }
+        }
+    }
+
+    // walk forwards from the source collecting edges
+    let mut edges = Vec::new();
+    let mut node_id = *source;
+    while node_id != *target {
+        let index = best.get(&node_id).and_then(|state| state.next_edge).ok_or(RouteError::NoRoute)?;
+        edges.push(&graph.edges[index]);
+        node_id = graph.edges[index].to;
+    }
+
+    // the policy of the node sending over each channel, as compute_hop_amounts expects
+    let policies: Vec<FeePolicy> = edges.iter().map(|edge| edge.policy).collect();
+    let amounts = compute_hop_amounts(amount_msat, final_cltv_expiry, &policies)?;
+
+    Ok(Route {
+        hops: edges
+            .iter()
+            .zip(amounts)
+            .map(|(edge, HopAmount { amount_msat, cltv_expiry })| RouteHop {
+                short_channel_id: edge.short_channel_id,
+                node_id: edge.to,
+                amount_msat,
+                cltv_expiry,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,117 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises_appendix::exercises::NodeKeysManager;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::SecretKey;
+use internal::onion::{create_onion_packet, peel_onion, HopPayload, PaymentData, PeeledOnion};
+use internal::multihop::FeePolicy;
+use internal::chain::MockChain;
+use internal::gossip::{create_channel_announcement, create_channel_update, NetworkGraph};
+use internal::router::{find_route, Route, RouteError, RouteHop, RoutingGraph};
+use internal::gossip::test::{gossip_keys, mine_funding_output};
+
+pub fn fixture_graph() -> RoutingGraph {
+    RoutingGraph::load(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/network_graph.json")).unwrap()
+}
+
+pub fn route_aliases(graph: &RoutingGraph, route: &Route) -> Vec<String> {
+    route.hops.iter().map(|hop| graph.alias(&hop.node_id)).collect()
+}
+
+#[test]
+fn test_find_route_in_fixture() {
+    let graph = fixture_graph();
+    let alice = graph.resolve_node("alice").unwrap();
+    let dave = graph.resolve_node("dave").unwrap();
+
+    // Carol is cheapest, and Alice doesn't pay herself a fee
+    let route = find_route(&graph, &alice, &dave, 100_000_000, 118, &[]).unwrap();
+    assert_eq!(route_aliases(&graph, &route), vec!["carol", "dave"]);
+    assert_eq!(route.total_fee_msat(), 1_100);
+    assert_eq!(route.total_cltv_expiry(), 158);
+    assert_eq!(route.hops[1], RouteHop { short_channel_id: 114349209354240, node_id: dave, amount_msat: 100_000_000, cltv_expiry: 118 });
+
+    // above Carol's htlc_maximum_msat, Eve's long delta still beats Bob's fee to Dave
+    let route = find_route(&graph, &alice, &dave, 300_000_000, 118, &[]).unwrap();
+    assert_eq!(route_aliases(&graph, &route), vec!["bob", "eve", "dave"]);
+    assert_eq!(route.total_fee_msat(), 15_800);
+    assert_eq!(route.total_cltv_expiry(), 118 + 2016 + 40);
+
+    let route = find_route(&graph, &alice, &dave, 300_000_000, 118, &[116548232609792]).unwrap();
+    assert_eq!(route_aliases(&graph, &route), vec!["bob", "dave"]);
+    assert_eq!(route.total_fee_msat(), 605_000);
+
+    let route = find_route(&graph, &alice, &dave, 100_000_000, 118, &[112150186098688]).unwrap();
+    assert_eq!(route_aliases(&graph, &route), vec!["bob", "eve", "dave"]);
+
+    // beyond every channel's capacity, and below every htlc_minimum_msat
+    assert_eq!(find_route(&graph, &alice, &dave, 2_500_000_000, 118, &[]), Err(RouteError::NoRoute));
+    assert_eq!(find_route(&graph, &alice, &dave, 500, 118, &[]), Err(RouteError::NoRoute));
+    assert_eq!(find_route(&graph, &alice, &alice, 1_000, 118, &[]), Err(RouteError::SameNode));
+    assert_eq!(graph.resolve_node("mallory"), Err(RouteError::UnknownNode("mallory".to_string())));
+    assert!(matches!(RoutingGraph::from_json("{}"), Err(RouteError::InvalidGraph(_))));
+}
+
+#[test]
+fn test_route_builds_onion() {
+    let graph = fixture_graph();
+    let alice = graph.resolve_node("alice").unwrap();
+    let dave = graph.resolve_node("dave").unwrap();
+    let route = find_route(&graph, &alice, &dave, 300_000_000, 118, &[]).unwrap();
+
+    let payment_secret = [0x07; 32];
+    let payment_hash = [0x42; 32];
+    let session_key = SecretKey::from_slice(&[0x41; 32]).unwrap();
+    let payloads = route.onion_payloads(payment_secret);
+    let mut packet = create_onion_packet(&session_key, &route.node_ids(), &payloads, &payment_hash).unwrap();
+
+    // fixture nodes use seeds 2 (bob), 5 (eve) and 4 (dave)
+    for (i, seed) in [0x02, 0x05, 0x04].into_iter().enumerate() {
+        let node = NodeKeysManager::new([seed; 32]);
+        assert_eq!(node.node_id, route.hops[i].node_id);
+
+        match peel_onion(&node.node_secret, &packet, &payment_hash).unwrap() {
+            PeeledOnion::Forward { payload, next_packet } => {
+                let next = &route.hops[i + 1];
+                assert_eq!(payload, HopPayload::forward(next.short_channel_id, next.amount_msat, next.cltv_expiry));
+                packet = *next_packet;
+            }
+            PeeledOnion::Receive { payload } => {
+                assert_eq!(i, 2);
+                assert_eq!(payload.amt_to_forward, 300_000_000);
+                assert_eq!(payload.payment_data, Some(PaymentData { payment_secret, total_msat: 300_000_000 }));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_route_over_gossip_graph() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    chain.mine_blocks(100);
+
+    let (alice, bob, carol) = (gossip_keys(0x01), gossip_keys(0x02), gossip_keys(0x03));
+    let mut network_graph = NetworkGraph::new();
+    let policy = FeePolicy { base_fee_msat: 1_000, fee_proportional_millionths: 100, cltv_expiry_delta: 40 };
+
+    for (from, to) in [(&alice, &bob), (&bob, &carol)] {
+        let (short_channel_id, _) = mine_funding_output(&chain, from, to);
+        let announcement = create_channel_announcement(short_channel_id, from, to);
+        rt.block_on(network_graph.add_channel_announcement(&announcement, &chain)).unwrap();
+
+        // only the forward direction gets a channel_update
+        let direction = if announcement.contents.node_id_1 == from.node_id() { 0 } else { 1 };
+        let update = create_channel_update(&from.node_secret, short_channel_id, direction, 1, &policy, 1_000, 5_000_000_000).unwrap();
+        network_graph.add_channel_update(&update).unwrap();
+    }
+
+    let graph = RoutingGraph::from_network_graph(&network_graph);
+    assert_eq!(graph.edges.len(), 2);
+
+    let route = find_route(&graph, &alice.node_id(), &carol.node_id(), 1_000_000, 118, &[]).unwrap();
+    assert_eq!(route.node_ids(), vec![bob.node_id(), carol.node_id()]);
+    assert_eq!(route.total_fee_msat(), 1_100);
+
+    assert_eq!(find_route(&graph, &carol.node_id(), &alice.node_id(), 1_000_000, 118, &[]), Err(RouteError::NoRoute));
+}
@@ -0,0 +1,67 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::{Block, BlockHash, OutPoint, Txid};
+use lightning::util::scid_utils::{block_from_scid, scid_from_parts, tx_index_from_scid, vout_from_scid};
+use lightning_block_sync::{BlockData, BlockSource, BlockSourceErrorKind};
+
+pub mod test;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScidError {
+    InvalidFormat(String),
+    OutOfRange,
+    UnknownTransaction(Txid),
+    Unconfirmed(Txid),
+    TransactionNotInBlock(Txid),
+    OutputNotFound(OutPoint),
+    HeaderOnly(BlockHash),
+    BlockSource(BlockSourceErrorKind),
+}
+
+/// The human readable `block x tx_index x output_index` form, e.g. `123x4x0`.
+pub fn format_scid(short_channel_id: u64) -> String {
+    format!(
+        "{}x{}x{}",
+        block_from_scid(short_channel_id),
+        tx_index_from_scid(short_channel_id),
+        vout_from_scid(short_channel_id)
+    )
+}
+
+/// Parses `123x4x0`. The block and transaction index are 3 bytes and the
+/// output index 2, so anything larger can't be packed into a u64.
+pub fn parse_scid(s: &str) -> Result<u64, ScidError> {
+    let parts: Vec<&str> = s.trim().split('x').collect();
+    let [block, tx_index, vout] = parts[..] else {
+        return Err(ScidError::InvalidFormat(s.to_string()));
+    };
+
+    let parse = |part: &str| part.parse::<u64>().map_err(|_| ScidError::InvalidFormat(s.to_string()));
+    scid_from_parts(parse(block)?, parse(tx_index)?, parse(vout)?).map_err(|_| ScidError::OutOfRange)
+}
+
+/// Where `outpoint` sits in a block already known to be at `height`.
+pub fn scid_in_block(height: u32, block: &Block, outpoint: &OutPoint) -> Result<u64, ScidError> {
+    let (tx_index, tx) = block
+        .txdata
+        .iter()
+        .enumerate()
+        .find(|(_, tx)| tx.compute_txid() == outpoint.txid)
+        .ok_or(ScidError::TransactionNotInBlock(outpoint.txid))?;
+
+    if outpoint.vout as usize >= tx.output.len() {
+        return Err(ScidError::OutputNotFound(*outpoint));
+    }
+
+    scid_from_parts(height as u64, tx_index as u64, outpoint.vout as u64).map_err(|_| ScidError::OutOfRange)
+}
+
+/// The short_channel_id of a funding output confirmed in `block_hash`. The
+/// header gives us the height, and the full block the transaction's index.
+pub async fn get_short_channel_id<B: BlockSource>(source: &B, block_hash: &BlockHash, outpoint: &OutPoint) -> Result<u64, ScidError> {
+    let header = source.get_header(block_hash, None).await.map_err(|e| ScidError::BlockSource(e.kind()))?;
+
+    match source.get_block(block_hash).await.map_err(|e| ScidError::BlockSource(e.kind()))? {
+        BlockData::FullBlock(block) => scid_in_block(header.height, &block, outpoint),
+        BlockData::HeaderOnly(_) => Err(ScidError::HeaderOnly(*block_hash)),
+    }
+}
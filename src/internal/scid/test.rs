@@ -0,0 +1,57 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::OutPoint;
+use internal::chain::{get_output_by_scid, MockChain};
+use internal::scid::{format_scid, get_short_channel_id, parse_scid, ScidError};
+use lightning::util::scid_utils::scid_from_parts;
+use internal::gossip::test::{gossip_keys, mine_funding_output};
+
+#[test]
+fn test_scid_format_and_parse() {
+    let short_channel_id = scid_from_parts(123, 4, 0).unwrap();
+    assert_eq!(format_scid(short_channel_id), "123x4x0");
+    assert_eq!(parse_scid("123x4x0"), Ok(short_channel_id));
+    assert_eq!(parse_scid(&format_scid(116548232609792)), Ok(116548232609792));
+
+    // the largest values each part can hold
+    assert_eq!(parse_scid("16777215x16777215x65535"), Ok(u64::MAX));
+    assert_eq!(parse_scid("16777216x0x0"), Err(ScidError::OutOfRange));
+    assert_eq!(parse_scid("1x2x65536"), Err(ScidError::OutOfRange));
+
+    for invalid in ["", "123x4", "123x4x0x1", "123:4:0", "ax4x0", "123x-4x0"] {
+        assert_eq!(parse_scid(invalid), Err(ScidError::InvalidFormat(invalid.to_string())));
+    }
+}
+
+#[test]
+fn test_scid_from_block_source() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    chain.mine_blocks(100);
+
+    let (alice, bob) = (gossip_keys(0x01), gossip_keys(0x02));
+    let (expected_scid, funding_tx) = mine_funding_output(&chain, &alice, &bob);
+    let funding_outpoint = OutPoint::new(funding_tx.compute_txid(), 0);
+
+    let (height, _) = chain.find_transaction(&funding_tx.compute_txid()).unwrap();
+    let block_hash = chain.block_at(height).unwrap().block_hash();
+    let short_channel_id = rt.block_on(get_short_channel_id(&chain, &block_hash, &funding_outpoint)).unwrap();
+    assert_eq!(short_channel_id, expected_scid);
+    assert_eq!(format_scid(short_channel_id), "101x1x0");
+
+    // and it points back at the funding output
+    let (outpoint, _) = rt.block_on(get_output_by_scid(&chain, short_channel_id)).unwrap();
+    assert_eq!(outpoint, funding_outpoint);
+
+    let missing_output = OutPoint::new(funding_tx.compute_txid(), 1);
+    assert_eq!(
+        rt.block_on(get_short_channel_id(&chain, &block_hash, &missing_output)),
+        Err(ScidError::OutputNotFound(missing_output))
+    );
+
+    let other_block = chain.block_at(height + 1).unwrap().block_hash();
+    assert_eq!(
+        rt.block_on(get_short_channel_id(&chain, &other_block, &funding_outpoint)),
+        Err(ScidError::TransactionNotInBlock(funding_tx.compute_txid()))
+    );
+}
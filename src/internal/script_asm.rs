@@ -0,0 +1,215 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::blockdata::opcodes::all as opcodes;
+use bitcoin::blockdata::opcodes::Opcode;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::script::{read_scriptint, Builder, Instruction, PushBytesBuf, Script, ScriptBuf};
+use bitcoin::secp256k1::PublicKey;
+use internal::invoice::payment_hash160;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use std::collections::BTreeMap;
+
+pub mod test;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownOpcode(String),
+    UnknownName(String),
+    InvalidHex(String),
+    UnterminatedPush(String),
+    // OP_PUSHBYTES_n and OP_PUSHDATAn need their data, write it as <hex> instead
+    BarePush(String),
+    InvalidScript(String),
+}
+
+/// Names for the keys and hashes we know, so `<revocation_pubkey>` can stand
+/// in for 33 bytes of hex in either direction.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptNames {
+    by_bytes: BTreeMap<Vec<u8>, String>,
+    by_name: BTreeMap<String, Vec<u8>>,
+}
+
+impl ScriptNames {
+    pub fn new() -> ScriptNames {
+        ScriptNames::default()
+    }
+
+    pub fn add(&mut self, name: &str, bytes: &[u8]) {
+        self.by_bytes.insert(bytes.to_vec(), name.to_string());
+        self.by_name.insert(name.to_string(), bytes.to_vec());
+    }
+
+    /// A key shows up as itself in P2WSH scripts, as its HASH160 in the HTLC
+    /// revocation check and x-only in tapscript, so all three get a name.
+    pub fn add_pubkey(&mut self, name: &str, pubkey: &PublicKey) {
+        let bitcoin_pubkey = bitcoin::PublicKey::new(*pubkey);
+        self.add(name, &pubkey.serialize());
+        self.add(&format!("{}_hash160", name), bitcoin_pubkey.pubkey_hash().as_byte_array());
+        self.add(&format!("{}_xonly", name), &pubkey.x_only_public_key().0.serialize());
+    }
+
+    /// Registers `name` and `name160`, the RIPEMD160 the HTLC scripts commit to.
+    pub fn add_payment_hash(&mut self, name: &str, payment_hash: &[u8; 32]) {
+        self.add(name, payment_hash);
+        self.add(&format!("{}160", name), &payment_hash160(payment_hash));
+    }
+
+    pub fn name_of(&self, bytes: &[u8]) -> Option<&str> {
+        self.by_bytes.get(bytes).map(|name| name.as_str())
+    }
+
+    pub fn bytes_of(&self, name: &str) -> Option<&[u8]> {
+        self.by_name.get(name).map(|bytes| bytes.as_slice())
+    }
+}
+
+/// Fixed example keys under the names the channel scripts use, and the
+/// payment hash `htlc` uses when it isn't given an invoice. Handy for writing
+/// scripts by hand; the channel commands derive their own keys from the store.
+pub fn workshop_script_names() -> ScriptNames {
+    let mut names = ScriptNames::new();
+    let keys: [(&str, u8); 10] = [
+        ("our_funding_pubkey", 0x01),
+        ("local_htlc_pubkey", 0x11),
+        ("revocation_pubkey", 0x12),
+        ("to_local_delayed_pubkey", 0x13),
+        ("local_pubkey", 0x14),
+        ("counterparty_funding_pubkey", 0x02),
+        ("counterparty_htlc_pubkey", 0x21),
+        ("counterparty_pubkey", 0x22),
+        ("counterparty_delayed_pubkey", 0x23),
+        ("counterparty_revocation_pubkey", 0x24),
+    ];
+    for (name, byte) in keys {
+        names.add_pubkey(name, &secp256k1pubkey_from_private_key(&[byte; 32]));
+    }
+    names.add_payment_hash("payment_hash", &Sha256::hash(&[]).to_byte_array());
+    names
+}
+
+//
+// Opcode names
+//
+
+// the names rust-bitcoin prints plus the ones people actually write
+fn opcode_by_name(name: &str) -> Option<Opcode> {
+    let name = name.to_uppercase();
+    let alias = match name.as_str() {
+        "OP_0" | "OP_FALSE" => Some(opcodes::OP_PUSHBYTES_0),
+        "OP_TRUE" => Some(opcodes::OP_PUSHNUM_1),
+        "OP_1NEGATE" => Some(opcodes::OP_PUSHNUM_NEG1),
+        "OP_CHECKLOCKTIMEVERIFY" | "OP_NOP2" => Some(opcodes::OP_CLTV),
+        "OP_CHECKSEQUENCEVERIFY" | "OP_NOP3" => Some(opcodes::OP_CSV),
+        _ => None,
+    };
+    if alias.is_some() {
+        return alias;
+    }
+
+    if let Some(number) = name.strip_prefix("OP_").and_then(|number| number.parse::<u8>().ok()) {
+        return (1..=16).contains(&number).then(|| Opcode::from(opcodes::OP_PUSHNUM_1.to_u8() + number - 1));
+    }
+
+    (0..=255u8).map(Opcode::from).find(|opcode| opcode.to_string() == name)
+}
+
+fn opcode_name(opcode: Opcode) -> String {
+    let code = opcode.to_u8();
+    if (opcodes::OP_PUSHNUM_1.to_u8()..=opcodes::OP_PUSHNUM_16.to_u8()).contains(&code) {
+        return format!("OP_{}", code - opcodes::OP_PUSHNUM_1.to_u8() + 1);
+    }
+    if opcode == opcodes::OP_PUSHNUM_NEG1 {
+        return "OP_1NEGATE".to_string();
+    }
+    opcode.to_string()
+}
+
+//
+// Assembler
+//
+
+fn tokenize(text: &str) -> Result<Vec<String>, AsmError> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        // a push runs to its closing bracket, spaces and all
+        let end = if rest.starts_with('<') {
+            rest.find('>').map(|end| end + 1).ok_or(AsmError::UnterminatedPush(rest.to_string()))?
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+        tokens.push(rest[..end].to_string());
+        rest = rest[end..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Parses text like `OP_DUP OP_HASH160 <revocation_pubkey_hash160> OP_EQUAL`.
+/// `<...>` pushes either a name from `names` or hex, and bare numbers are
+/// pushed as script numbers, so `144 OP_CSV` works as written.
+pub fn assemble(text: &str, names: &ScriptNames) -> Result<ScriptBuf, AsmError> {
+    let mut builder = Builder::new();
+
+    for token in tokenize(text)? {
+        if let Some(inner) = token.strip_prefix('<').and_then(|token| token.strip_suffix('>')) {
+            let bytes = match names.bytes_of(inner) {
+                Some(bytes) => bytes.to_vec(),
+                None if inner.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    hex::decode(inner).map_err(|_| AsmError::InvalidHex(inner.to_string()))?
+                }
+                None => return Err(AsmError::UnknownName(inner.to_string())),
+            };
+            let push = PushBytesBuf::try_from(bytes).map_err(|_| AsmError::InvalidScript(token.clone()))?;
+            builder = builder.push_slice(push);
+        } else if let Ok(number) = token.parse::<i64>() {
+            builder = builder.push_int(number);
+        } else {
+            let opcode = opcode_by_name(&token).ok_or(AsmError::UnknownOpcode(token.clone()))?;
+            let code = opcode.to_u8();
+            let needs_data = (0x01..=opcodes::OP_PUSHDATA4.to_u8()).contains(&code);
+            if needs_data {
+                return Err(AsmError::BarePush(token));
+            }
+            builder = builder.push_opcode(opcode);
+        }
+    }
+
+    Ok(builder.into_script())
+}
+
+//
+// Disassembler
+//
+
+// small pushes that are exactly what push_int would have written read as numbers
+fn push_as_number(bytes: &[u8]) -> Option<i64> {
+    let number = read_scriptint(bytes).ok()?;
+    let as_int = Builder::new().push_int(number).into_script();
+    let as_push = Builder::new().push_slice(PushBytesBuf::try_from(bytes.to_vec()).ok()?).into_script();
+    (as_int == as_push).then_some(number)
+}
+
+/// Renders `script` in the form `assemble` reads back, naming any push
+/// `names` knows about.
+pub fn disassemble(script: &Script, names: &ScriptNames) -> Result<String, AsmError> {
+    let mut words = Vec::new();
+
+    for instruction in script.instructions() {
+        let word = match instruction.map_err(|e| AsmError::InvalidScript(e.to_string()))? {
+            Instruction::Op(opcode) => opcode_name(opcode),
+            Instruction::PushBytes(bytes) if bytes.is_empty() => "OP_0".to_string(),
+            Instruction::PushBytes(bytes) => match (names.name_of(bytes.as_bytes()), push_as_number(bytes.as_bytes())) {
+                (Some(name), _) => format!("<{}>", name),
+                (None, Some(number)) => number.to_string(),
+                (None, None) => format!("<{}>", hex::encode(bytes.as_bytes())),
+            },
+        };
+        words.push(word);
+    }
+
+    Ok(words.join(" "))
+}
@@ -0,0 +1,55 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use internal::taproot::to_local_delay_script;
+use internal::script_asm::{assemble, disassemble, workshop_script_names, AsmError, ScriptNames};
+use internal::script_utils::{build_htlc_offerer_witness_script, build_htlc_receiver_witness_script};
+
+#[test]
+fn test_script_asm_round_trips_htlc_scripts() {
+    let names = workshop_script_names();
+    let revocation = secp256k1pubkey_from_private_key(&[0x12; 32]);
+    let local_htlc = secp256k1pubkey_from_private_key(&[0x11; 32]);
+    let remote_htlc = secp256k1pubkey_from_private_key(&[0x21; 32]);
+    let payment_hash160 = Ripemd160::hash(&Sha256::hash(&[]).to_byte_array()).to_byte_array();
+
+    let offered = build_htlc_offerer_witness_script(&bitcoin::PublicKey::new(revocation), &bitcoin::PublicKey::new(remote_htlc),
+                                                    &bitcoin::PublicKey::new(local_htlc), &payment_hash160);
+    let asm = disassemble(&offered, &names).unwrap();
+    assert_eq!(asm, "OP_DUP OP_HASH160 <revocation_pubkey_hash160> OP_EQUAL OP_IF OP_CHECKSIG OP_ELSE <counterparty_htlc_pubkey> \
+                     OP_SWAP OP_SIZE 32 OP_EQUAL OP_NOTIF OP_DROP OP_2 OP_SWAP <local_htlc_pubkey> OP_2 OP_CHECKMULTISIG OP_ELSE \
+                     OP_HASH160 <payment_hash160> OP_EQUALVERIFY OP_CHECKSIG OP_ENDIF OP_ENDIF");
+    assert_eq!(assemble(&asm, &names).unwrap(), offered);
+
+    // unnamed pushes come back as hex and the CLTV expiry as a number
+    let received = build_htlc_receiver_witness_script(&bitcoin::PublicKey::new(revocation), &bitcoin::PublicKey::new(remote_htlc),
+                                                      &bitcoin::PublicKey::new(local_htlc), &[0xab; 20], 500_000);
+    let asm = disassemble(&received, &names).unwrap();
+    assert!(asm.contains(&format!("<{}>", hex::encode([0xab; 20]))));
+    assert!(asm.contains("500000 OP_CLTV"));
+    assert_eq!(assemble(&asm, &names).unwrap(), received);
+    assert_eq!(assemble(&asm, &ScriptNames::new()), Err(AsmError::UnknownName("revocation_pubkey_hash160".to_string())));
+
+    let plain = disassemble(&received, &ScriptNames::new()).unwrap();
+    assert_eq!(assemble(&plain, &ScriptNames::new()).unwrap(), received);
+}
+
+#[test]
+fn test_script_asm_parses_aliases_and_taproot_leaves() {
+    let names = workshop_script_names();
+    let delayed = secp256k1pubkey_from_private_key(&[0x13; 32]);
+
+    let leaf = to_local_delay_script(&delayed, 144);
+    assert_eq!(disassemble(&leaf, &names).unwrap(), "<to_local_delayed_pubkey_xonly> OP_CHECKSIG 144 OP_CSV OP_DROP");
+    assert_eq!(assemble("<to_local_delayed_pubkey_xonly> OP_CHECKSIG 144 OP_CHECKSEQUENCEVERIFY OP_DROP", &names).unwrap(), leaf);
+
+    assert_eq!(assemble("OP_TRUE OP_FALSE op_16 -1", &names).unwrap(), assemble("OP_1 OP_0 OP_16 OP_1NEGATE", &names).unwrap());
+    assert_eq!(assemble("OP_PUSHBYTES_20", &names), Err(AsmError::BarePush("OP_PUSHBYTES_20".to_string())));
+    assert_eq!(assemble("OP_DUP OP_FROB", &names), Err(AsmError::UnknownOpcode("OP_FROB".to_string())));
+    assert_eq!(assemble("OP_HASH160 <20 bytes> OP_EQUAL", &names), Err(AsmError::UnknownName("20 bytes".to_string())));
+    assert_eq!(assemble("OP_HASH160 <abc", &names), Err(AsmError::UnterminatedPush("<abc".to_string())));
+    assert_eq!(assemble("<abc>", &names), Err(AsmError::InvalidHex("abc".to_string())));
+}
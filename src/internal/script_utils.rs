@@ -68,3 +68,58 @@ pub fn build_htlc_offerer_witness_script(
         .push_opcode(opcodes::OP_ENDIF)
         .into_script()
 }
+
+pub fn build_htlc_receiver_witness_script(
+    revocation_pubkey: &PublicKey,
+    remote_htlc_pubkey: &PublicKey,
+    local_htlc_pubkey: &PublicKey,
+    payment_hash160: &[u8; 20],
+    cltv_expiry: u32,
+) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(opcodes::OP_DUP)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(revocation_pubkey.pubkey_hash())
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_key(remote_htlc_pubkey)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_opcode(opcodes::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(payment_hash160)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_int(2)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_key(local_htlc_pubkey)
+        .push_int(2)
+        .push_opcode(opcodes::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_opcode(opcodes::OP_DROP)
+        .push_int(cltv_expiry as i64)
+        .push_opcode(opcodes::OP_CLTV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ENDIF)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+/// The 330 sat anchor output of an anchor channel commitment:
+/// `<funding_pubkey> OP_CHECKSIG OP_IFDUP OP_NOTIF OP_16 OP_CHECKSEQUENCEVERIFY OP_ENDIF`.
+/// Either side can spend theirs to bump the commitment, anyone can after 16 blocks.
+pub fn build_anchor_witness_script(funding_pubkey: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_key(funding_pubkey)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_IFDUP)
+        .push_opcode(opcodes::OP_NOTIF)
+        .push_int(16)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
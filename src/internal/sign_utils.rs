@@ -9,7 +9,7 @@ use bitcoin::secp256k1;
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin::secp256k1::{SecretKey, PublicKey as secp256k1PublicKey, Scalar};
 use bitcoin::PublicKey;
-use bitcoin::script::{ScriptBuf};
+use bitcoin::script::{Instruction, ScriptBuf};
 use bitcoin::{OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
 use bitcoin::amount::Amount;
 use bitcoin::transaction::Version;
@@ -26,8 +26,14 @@ use bitcoin::secp256k1::ecdsa::Signature;
 use bitcoin::secp256k1::Message;
 use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::sighash::SighashCache;
+use bitcoin::sighash::{Prevouts, TapSighashType};
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash, Signature as TaprootSignature};
 use exercises::exercises::{ two_of_two_multisig_witness_script};
 
+pub mod test;
+
 pub fn sign_funding_transaction(tx: Transaction,
                                 our_funding_public_key: PublicKey,
                                 our_funding_private_key: SecretKey,
@@ -44,50 +50,15 @@ pub fn sign_funding_transaction(tx: Transaction,
             &our_funding_public_key,
             &counterparty_funding_public_key);
 
-    let our_signature = generate_p2wsh_signature(
-         tx.clone(), 
-         txid_index,
-         &redeem_script,
-         funding_amount,
-         EcdsaSighashType::All,
-        our_funding_private_key);
-
-    let counterparty_signature = generate_p2wsh_signature(
-         tx.clone(), 
-         txid_index,
-         &redeem_script,
-         funding_amount,
-         EcdsaSighashType::All,
-    counterparty_funding_private_key);
-
-    // Convert signature to DER and append SigHashType
-    let mut our_signature_der = our_signature.serialize_der().to_vec();
-    our_signature_der.push(EcdsaSighashType::All as u8);
-
-    let mut counterparty_signature_der = counterparty_signature.serialize_der().to_vec();
-    counterparty_signature_der.push(EcdsaSighashType::All as u8);
-
-    // Determine signature order based on pubkey comparison
-    let our_sig_first = our_funding_public_key.inner.serialize()[..] > counterparty_funding_public_key.inner.serialize()[..];
-
-    // Add the signature and public key to the witness
+    // the witness builder puts the signatures in the script's key order
     let mut signed_tx = tx.clone();
-
-    // First push empty element for NULLDUMMY compliance
-    signed_tx.input[0].witness.push(Vec::new());
-
-    // Push signatures in correct order
-    if our_sig_first {
-        signed_tx.input[0].witness.push(our_signature_der);
-        signed_tx.input[0].witness.push(counterparty_signature_der);
-    } else {
-        signed_tx.input[0].witness.push(counterparty_signature_der);
-        signed_tx.input[0].witness.push(our_signature_der);
-    }
-
-    signed_tx.input[0]
-        .witness
-        .push(redeem_script.clone().into_bytes());
+    sign_p2wsh_input(&mut signed_tx,
+                     txid_index,
+                     &redeem_script,
+                     funding_amount,
+                     &SpendPath::Multisig,
+                     &[WitnessSigner::Key(our_funding_private_key), WitnessSigner::Key(counterparty_funding_private_key)])
+        .expect("Funding script is not a 2-of-2 of our funding keys");
 
     signed_tx
 }
@@ -109,6 +80,23 @@ pub fn generate_p2wsh_signature(
     signature
 }
 
+pub fn verify_p2wsh_signature(
+    transaction: &Transaction,
+    input_idx: usize,
+    witness_script: &ScriptBuf,
+    value: u64,
+    sighash_type: EcdsaSighashType,
+    signature: &Signature,
+    public_key: &secp256k1PublicKey,
+) -> bool {
+    let secp = Secp256k1::new();
+
+    let message =
+        generate_p2wsh_message(transaction.clone(), input_idx, witness_script, value, sighash_type);
+
+    secp.verify_ecdsa(&message, signature, public_key).is_ok()
+}
+
 pub async fn sign_raw_transaction(bitcoind: BitcoindClient,
                                 tx: Transaction) -> Transaction {
 
@@ -147,3 +135,292 @@ fn generate_p2wsh_message(
 
     message
 }
+
+//
+// P2WSH witnesses
+//
+
+/// Which branch of a witness script to spend, named after the channel
+/// scripts that use it. The stack is laid out bottom first, as below.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendPath {
+    /// A bare CHECKMULTISIG like the funding output: `0 <sig>...`
+    Multisig,
+    /// `<sig> 1` through an OP_IF like to_local, or `<sig> <revocationpubkey>`
+    /// for an HTLC that checks the key's hash
+    Revocation,
+    /// The single signature OP_ELSE branch, after a delay or a timeout: `<sig> <>`
+    Delayed,
+    /// The offered HTLC's HTLC-timeout transaction: `0 <remotehtlcsig> <localhtlcsig> <>`
+    HtlcTimeout,
+    /// The received HTLC's HTLC-success transaction: `0 <remotehtlcsig> <localhtlcsig> <preimage>`
+    HtlcSuccess(Vec<u8>),
+    /// `<preimage> 1` through an OP_IF, or `<remotehtlcsig> <preimage>` claiming an offered HTLC
+    Preimage(Vec<u8>),
+}
+
+/// Someone whose signature goes in the witness: either a key we hold, or a
+/// signature the counterparty already sent us.
+#[derive(Debug, Clone)]
+pub enum WitnessSigner {
+    Key(SecretKey),
+    Signature(secp256k1PublicKey, Signature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitnessError {
+    NoSuchInput(usize),
+    UnsupportedPath(SpendPath),
+    WrongSignerCount { expected: usize, got: usize },
+    SignerNotInScript(secp256k1PublicKey),
+}
+
+// how a script decides which branch it is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BranchSelector {
+    Multisig,
+    // a flag in the witness feeds an OP_IF
+    Flag,
+    // OP_DUP OP_HASH160 compares the top item to the revocation key's hash.
+    // Past that, an offered HTLC times out through the 2-of-2 and a
+    // received one through the counterparty's key after OP_CLTV
+    OfferedHtlc,
+    ReceivedHtlc,
+}
+
+fn branch_selector(witness_script: &ScriptBuf) -> Option<BranchSelector> {
+    let bytes = witness_script.as_bytes();
+    if witness_script.is_multisig() {
+        Some(BranchSelector::Multisig)
+    } else if bytes.first() == Some(&opcodes::OP_IF.to_u8()) {
+        Some(BranchSelector::Flag)
+    } else if bytes.starts_with(&[opcodes::OP_DUP.to_u8(), opcodes::OP_HASH160.to_u8()]) {
+        let has_cltv = witness_script.instructions().any(|instruction| instruction == Ok(Instruction::Op(opcodes::OP_CLTV)));
+        Some(if has_cltv { BranchSelector::ReceivedHtlc } else { BranchSelector::OfferedHtlc })
+    } else {
+        None
+    }
+}
+
+impl WitnessSigner {
+    fn pubkey(&self) -> secp256k1PublicKey {
+        match self {
+            WitnessSigner::Key(secret_key) => secp256k1PublicKey::from_secret_key(&Secp256k1::new(), secret_key),
+            WitnessSigner::Signature(pubkey, _) => *pubkey,
+        }
+    }
+
+    /// The signature for `input_idx` as it goes in a witness, sighash type appended.
+    pub fn signature_der(&self, tx: &Transaction, input_idx: usize, witness_script: &ScriptBuf, value: u64) -> Vec<u8> {
+        let signature = match self {
+            WitnessSigner::Key(secret_key) => {
+                generate_p2wsh_signature(tx.clone(), input_idx, witness_script, value, EcdsaSighashType::All, *secret_key)
+            }
+            WitnessSigner::Signature(_, signature) => *signature,
+        };
+        let mut signature_der = signature.serialize_der().to_vec();
+        signature_der.push(EcdsaSighashType::All as u8);
+        signature_der
+    }
+}
+
+/// The witness spending `witness_script` at `input_idx` through `path`.
+/// Signatures go in the order their keys appear in the script, which is
+/// the order CHECKMULTISIG wants them in.
+pub fn build_p2wsh_witness(
+    tx: &Transaction,
+    input_idx: usize,
+    witness_script: &ScriptBuf,
+    value: u64,
+    path: &SpendPath,
+    signers: &[WitnessSigner],
+) -> Result<Witness, WitnessError> {
+    if input_idx >= tx.input.len() {
+        return Err(WitnessError::NoSuchInput(input_idx));
+    }
+    let selector = branch_selector(witness_script).ok_or(WitnessError::UnsupportedPath(path.clone()))?;
+
+    // only the paths the script has a branch for
+    let expected_signers = match (path, selector) {
+        // the script starts with OP_m
+        (SpendPath::Multisig, BranchSelector::Multisig) => (witness_script.as_bytes()[0] - 0x50) as usize,
+        (SpendPath::Revocation, BranchSelector::Flag | BranchSelector::OfferedHtlc | BranchSelector::ReceivedHtlc) => 1,
+        // `<sig> <>` is the received HTLC's timeout, the offered HTLC would
+        // take the empty item as a missing preimage
+        (SpendPath::Delayed, BranchSelector::Flag | BranchSelector::ReceivedHtlc) => 1,
+        (SpendPath::HtlcTimeout, BranchSelector::OfferedHtlc) => 2,
+        (SpendPath::HtlcSuccess(_), BranchSelector::ReceivedHtlc) => 2,
+        (SpendPath::Preimage(_), BranchSelector::Flag) => 0,
+        (SpendPath::Preimage(_), BranchSelector::OfferedHtlc) => 1,
+        _ => return Err(WitnessError::UnsupportedPath(path.clone())),
+    };
+    if signers.len() != expected_signers {
+        return Err(WitnessError::WrongSignerCount { expected: expected_signers, got: signers.len() });
+    }
+
+    let mut ordered_signers = Vec::new();
+    for signer in signers {
+        let pubkey = signer.pubkey();
+        let position = witness_script
+            .as_bytes()
+            .windows(33)
+            .position(|window| window == pubkey.serialize());
+        // single signature branches may only commit to a key's hash
+        if position.is_none() && expected_signers > 1 {
+            return Err(WitnessError::SignerNotInScript(pubkey));
+        }
+        ordered_signers.push((position, signer));
+    }
+    ordered_signers.sort_by_key(|(position, _)| *position);
+    let signatures: Vec<Vec<u8>> = ordered_signers
+        .iter()
+        .map(|(_, signer)| signer.signature_der(tx, input_idx, witness_script, value))
+        .collect();
+
+    // CHECKMULTISIG pops one item more than it uses, NULLDUMMY says it must be empty
+    let null_dummy = vec![Vec::new()];
+    let stack: Vec<Vec<u8>> = match (path, selector) {
+        (SpendPath::Multisig, _) => [null_dummy, signatures].concat(),
+        (SpendPath::Revocation, BranchSelector::Flag) => vec![signatures[0].clone(), vec![1]],
+        (SpendPath::Revocation, _) => vec![signatures[0].clone(), signers[0].pubkey().serialize().to_vec()],
+        (SpendPath::Delayed, _) => vec![signatures[0].clone(), vec![]],
+        (SpendPath::HtlcTimeout, _) => [null_dummy, signatures, vec![vec![]]].concat(),
+        (SpendPath::HtlcSuccess(preimage), _) => [null_dummy, signatures, vec![preimage.clone()]].concat(),
+        (SpendPath::Preimage(preimage), BranchSelector::Flag) => vec![preimage.clone(), vec![1]],
+        (SpendPath::Preimage(preimage), _) => vec![signatures[0].clone(), preimage.clone()],
+    };
+
+    let mut witness = Witness::new();
+    for item in stack {
+        witness.push(item);
+    }
+    witness.push(witness_script.as_bytes());
+
+    Ok(witness)
+}
+
+/// Signs input `input_idx` of `tx` in place, see `build_p2wsh_witness`.
+pub fn sign_p2wsh_input(
+    tx: &mut Transaction,
+    input_idx: usize,
+    witness_script: &ScriptBuf,
+    value: u64,
+    path: &SpendPath,
+    signers: &[WitnessSigner],
+) -> Result<(), WitnessError> {
+    let witness = build_p2wsh_witness(tx, input_idx, witness_script, value, path, signers)?;
+    tx.input[input_idx].witness = witness;
+    Ok(())
+}
+
+//
+// Taproot
+//
+
+// ANYONECANPAY only commits to the output being spent, so that's all the
+// caller needs to pass in: either that one output or every prevout
+fn taproot_prevouts<'a>(prevouts: &'a [TxOut], input_idx: usize, sighash_type: TapSighashType) -> Prevouts<'a, TxOut> {
+    match sighash_type {
+        TapSighashType::AllPlusAnyoneCanPay | TapSighashType::NonePlusAnyoneCanPay | TapSighashType::SinglePlusAnyoneCanPay => {
+            let spent_output = if prevouts.len() == 1 { &prevouts[0] } else { &prevouts[input_idx] };
+            Prevouts::One(input_idx, spent_output.clone())
+        }
+        _ => Prevouts::All(prevouts),
+    }
+}
+
+pub fn generate_taproot_keypath_message(
+    transaction: &Transaction,
+    input_idx: usize,
+    prevouts: &[TxOut],
+    sighash_type: TapSighashType,
+) -> Message {
+    let sighash = SighashCache::new(transaction)
+        .taproot_key_spend_signature_hash(input_idx, &taproot_prevouts(prevouts, input_idx, sighash_type), sighash_type)
+        .unwrap();
+
+    Message::from_digest(sighash.to_byte_array())
+}
+
+pub fn generate_taproot_scriptpath_message(
+    transaction: &Transaction,
+    input_idx: usize,
+    prevouts: &[TxOut],
+    leaf_script: &ScriptBuf,
+    sighash_type: TapSighashType,
+) -> Message {
+    let leaf_hash = TapLeafHash::from_script(leaf_script, LeafVersion::TapScript);
+
+    let sighash = SighashCache::new(transaction)
+        .taproot_script_spend_signature_hash(input_idx, &taproot_prevouts(prevouts, input_idx, sighash_type), leaf_hash, sighash_type)
+        .unwrap();
+
+    Message::from_digest(sighash.to_byte_array())
+}
+
+// BIP 340 recommends fresh auxiliary randomness so a faulty nonce derivation
+// can't leak the key
+fn sign_schnorr(message: &Message, keypair: &Keypair, sighash_type: TapSighashType) -> TaprootSignature {
+    let secp = Secp256k1::new();
+    let aux_rand: [u8; 32] = rand::random();
+
+    TaprootSignature {
+        signature: secp.sign_schnorr_with_aux_rand(message, keypair, &aux_rand),
+        sighash_type,
+    }
+}
+
+/// Signs for the output key: `private_key` is the internal key, tweaked
+/// here with `merkle_root` (None for a key path only output).
+pub fn generate_taproot_keypath_signature(
+    transaction: &Transaction,
+    input_idx: usize,
+    prevouts: &[TxOut],
+    merkle_root: Option<TapNodeHash>,
+    sighash_type: TapSighashType,
+    private_key: secp256k1::SecretKey,
+) -> TaprootSignature {
+    let secp = Secp256k1::new();
+
+    let message = generate_taproot_keypath_message(transaction, input_idx, prevouts, sighash_type);
+    let keypair = Keypair::from_secret_key(&secp, &private_key).tap_tweak(&secp, merkle_root).to_inner();
+
+    sign_schnorr(&message, &keypair, sighash_type)
+}
+
+pub fn generate_taproot_scriptpath_signature(
+    transaction: &Transaction,
+    input_idx: usize,
+    prevouts: &[TxOut],
+    leaf_script: &ScriptBuf,
+    sighash_type: TapSighashType,
+    private_key: secp256k1::SecretKey,
+) -> TaprootSignature {
+    let secp = Secp256k1::new();
+
+    let message = generate_taproot_scriptpath_message(transaction, input_idx, prevouts, leaf_script, sighash_type);
+    let keypair = Keypair::from_secret_key(&secp, &private_key);
+
+    sign_schnorr(&message, &keypair, sighash_type)
+}
+
+/// `leaf_script` is None for a key path spend, where `public_key` is the output key.
+pub fn verify_taproot_signature(
+    transaction: &Transaction,
+    input_idx: usize,
+    prevouts: &[TxOut],
+    leaf_script: Option<&ScriptBuf>,
+    signature: &TaprootSignature,
+    public_key: &XOnlyPublicKey,
+) -> bool {
+    let secp = Secp256k1::new();
+
+    let message = match leaf_script {
+        Some(leaf_script) => {
+            generate_taproot_scriptpath_message(transaction, input_idx, prevouts, leaf_script, signature.sighash_type)
+        }
+        None => generate_taproot_keypath_message(transaction, input_idx, prevouts, signature.sighash_type),
+    };
+
+    secp.verify_schnorr(&signature.signature, &message, public_key).is_ok()
+}
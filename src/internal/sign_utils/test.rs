@@ -0,0 +1,144 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use internal::key_utils::{pubkey_from_secret, secp256k1_private_key, secp256k1pubkey_from_private_key};
+use bitcoin::script::ScriptBuf;
+use internal::script_utils::{build_htlc_offerer_witness_script, build_htlc_receiver_witness_script, p2wpkh_output_script};
+use internal::sign_utils::{generate_taproot_keypath_signature, verify_taproot_signature};
+use bitcoin::sighash::TapSighashType;
+use crate::exercises::solutions::two_of_two_multisig_witness_script;
+use internal::tx_utils::get_funding_input;
+use internal::taproot::htlc_second_level_tree;
+use internal::policy::{and, hash160, multi, older, or, pk, pkh, Satisfier};
+use internal::tx_utils::{build_output, build_transaction};
+use internal::sign_utils::{build_p2wsh_witness, generate_p2wsh_signature, sign_p2wsh_input, SpendPath, WitnessError, WitnessSigner};
+use internal::taproot::test::taproot_spend;
+
+#[test]
+fn test_taproot_sighash_types() {
+    let internal_secret = secp256k1_private_key(&[0x51; 32]);
+    let tree = htlc_second_level_tree(&pubkey_from_secret(internal_secret), &secp256k1pubkey_from_private_key(&[0x52; 32]), 144);
+    let prevout = bitcoin::TxOut { value: bitcoin::Amount::from_sat(100_000), script_pubkey: tree.script_pubkey() };
+    let (tx, prevouts) = taproot_spend(&prevout, 0);
+    let output_key = tree.output_key().to_inner();
+
+    // DEFAULT leaves the sighash byte off, anything else appends it
+    let default = generate_taproot_keypath_signature(&tx, 0, &prevouts, tree.spend_info.merkle_root(), TapSighashType::Default, internal_secret);
+    let all = generate_taproot_keypath_signature(&tx, 0, &prevouts, tree.spend_info.merkle_root(), TapSighashType::All, internal_secret);
+    assert_eq!(default.to_vec().len(), 64);
+    assert_eq!(all.to_vec().len(), 65);
+    assert_eq!(all.to_vec()[64], TapSighashType::All as u8);
+    assert!(verify_taproot_signature(&tx, 0, &prevouts, None, &default, &output_key));
+    assert!(verify_taproot_signature(&tx, 0, &prevouts, None, &all, &output_key));
+
+    // SINGLE|ANYONECANPAY still holds once someone else adds an input and output
+    let single = generate_taproot_keypath_signature(&tx, 0, &prevouts, tree.spend_info.merkle_root(), TapSighashType::SinglePlusAnyoneCanPay, internal_secret);
+    let mut extended = tx.clone();
+    extended.input.push(get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000003".to_string(), 1));
+    extended.output.push(extended.output[0].clone());
+    assert!(verify_taproot_signature(&extended, 0, &prevouts, None, &single, &output_key));
+    assert!(!verify_taproot_signature(&extended, 0, &[prevout.clone(), prevout.clone()], None, &all, &output_key));
+
+    // aux randomness makes every signature different, but all of them valid
+    let again = generate_taproot_keypath_signature(&tx, 0, &prevouts, tree.spend_info.merkle_root(), TapSighashType::Default, internal_secret);
+    assert_ne!(default.signature, again.signature);
+    assert!(verify_taproot_signature(&tx, 0, &prevouts, None, &again, &output_key));
+}
+
+#[test]
+fn test_p2wsh_witness_builder_matches_policy_satisfactions() {
+    let secret = |byte: u8| secp256k1_private_key(&[byte; 32]);
+    let key = |byte: u8| bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[byte; 32]));
+    let (revocation, delayed, local_htlc, remote_htlc) = (key(0x12), key(0x13), key(0x11), key(0x21));
+    let preimage = [0x42; 32];
+    let payment_hash160 = Ripemd160::hash(&Sha256::hash(&preimage).to_byte_array()).to_byte_array();
+    let value = 405_000;
+    let mut tx = build_transaction(bitcoin::transaction::Version::TWO, bitcoin::absolute::LockTime::ZERO,
+                                   vec![get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000005".to_string(), 0),
+                                        get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000005".to_string(), 1)],
+                                   vec![build_output(400_000, p2wpkh_output_script(delayed))]);
+    tx.input[1].sequence = bitcoin::Sequence(144);
+
+    // signatures are deterministic, so the satisfier given the same ones must build the same witness.
+    // Segwit sighashes don't cover witnesses, so signing the unsigned copy is enough
+    let unsigned = tx.clone();
+    let signature = |idx: usize, script: &ScriptBuf, byte: u8| {
+        let mut der = generate_p2wsh_signature(unsigned.clone(), idx, script, value, bitcoin::sighash::EcdsaSighashType::All, secret(byte))
+            .serialize_der().to_vec();
+        der.push(bitcoin::sighash::EcdsaSighashType::All as u8);
+        der
+    };
+
+    let offered_policy = or(pkh(&revocation),
+                            or(multi(2, &[remote_htlc, local_htlc]), and(pk(&remote_htlc), hash160(&payment_hash160))));
+    let offered = offered_policy.compile();
+    let mut satisfier = Satisfier::new();
+    satisfier.add_signature(&remote_htlc, signature(1, &offered, 0x21));
+    satisfier.add_signature(&local_htlc, signature(1, &offered, 0x11));
+
+    // signer order doesn't matter, the script's key order does
+    let timeout = build_p2wsh_witness(&tx, 1, &offered, value, &SpendPath::HtlcTimeout,
+                                      &[WitnessSigner::Key(secret(0x11)), WitnessSigner::Key(secret(0x21))]).unwrap();
+    assert_eq!(timeout, offered_policy.satisfy(&satisfier).unwrap());
+
+    satisfier.add_preimage(&preimage);
+    let claim = build_p2wsh_witness(&tx, 1, &offered, value, &SpendPath::Preimage(preimage.to_vec()),
+                                    &[WitnessSigner::Key(secret(0x21))]).unwrap();
+    assert_eq!(claim, offered_policy.satisfy(&satisfier).unwrap());
+
+    satisfier.add_signature(&revocation, signature(1, &offered, 0x12));
+    let revoked = build_p2wsh_witness(&tx, 1, &offered, value, &SpendPath::Revocation, &[WitnessSigner::Key(secret(0x12))]).unwrap();
+    assert_eq!(revoked, offered_policy.satisfy(&satisfier).unwrap());
+
+    let to_local_policy = or(pk(&revocation), and(older(144), pk(&delayed)));
+    let to_local_script = to_local_policy.compile();
+    let mut satisfier = Satisfier::new();
+    satisfier.sequence = 144;
+    satisfier.add_signature(&delayed, signature(1, &to_local_script, 0x13));
+    sign_p2wsh_input(&mut tx, 1, &to_local_script, value, &SpendPath::Delayed, &[WitnessSigner::Key(secret(0x13))]).unwrap();
+    assert_eq!(tx.input[1].witness, to_local_policy.satisfy(&satisfier).unwrap());
+    assert!(tx.input[0].witness.is_empty());
+
+    // a counterparty signature we were sent works like a key we hold, NULLDUMMY first
+    let funding_script = two_of_two_multisig_witness_script(&key(0x02), &key(0x01));
+    let counterparty_signature = generate_p2wsh_signature(tx.clone(), 0, &funding_script, value, bitcoin::sighash::EcdsaSighashType::All, secret(0x02));
+    let funding = build_p2wsh_witness(&tx, 0, &funding_script, value, &SpendPath::Multisig,
+                                      &[WitnessSigner::Key(secret(0x01)), WitnessSigner::Signature(key(0x02).inner, counterparty_signature)]).unwrap();
+    assert_eq!(funding.len(), 4);
+    assert!(funding.nth(0).unwrap().is_empty());
+    assert_eq!(funding.nth(1).unwrap(), signature(0, &funding_script, 0x02).as_slice());
+    assert_eq!(funding.nth(2).unwrap(), signature(0, &funding_script, 0x01).as_slice());
+
+    assert_eq!(build_p2wsh_witness(&tx, 2, &funding_script, value, &SpendPath::Multisig, &[]), Err(WitnessError::NoSuchInput(2)));
+    assert_eq!(build_p2wsh_witness(&tx, 0, &funding_script, value, &SpendPath::Multisig, &[WitnessSigner::Key(secret(0x01))]),
+               Err(WitnessError::WrongSignerCount { expected: 2, got: 1 }));
+    assert_eq!(build_p2wsh_witness(&tx, 0, &funding_script, value, &SpendPath::Multisig,
+                                   &[WitnessSigner::Key(secret(0x01)), WitnessSigner::Key(secret(0x03))]),
+               Err(WitnessError::SignerNotInScript(key(0x03).inner)));
+    assert_eq!(build_p2wsh_witness(&tx, 0, &funding_script, value, &SpendPath::Delayed, &[WitnessSigner::Key(secret(0x01))]),
+               Err(WitnessError::UnsupportedPath(SpendPath::Delayed)));
+
+    // paths the HTLC scripts have no branch for are refused, not signed
+    let offered = build_htlc_offerer_witness_script(&revocation, &remote_htlc, &local_htlc, &payment_hash160);
+    let received = build_htlc_receiver_witness_script(&revocation, &remote_htlc, &local_htlc, &payment_hash160, 500);
+    let both = [WitnessSigner::Key(secret(0x11)), WitnessSigner::Key(secret(0x21))];
+    assert_eq!(build_p2wsh_witness(&tx, 1, &offered, value, &SpendPath::Delayed, &[WitnessSigner::Key(secret(0x21))]),
+               Err(WitnessError::UnsupportedPath(SpendPath::Delayed)));
+    assert_eq!(build_p2wsh_witness(&tx, 1, &offered, value, &SpendPath::HtlcSuccess(preimage.to_vec()), &both),
+               Err(WitnessError::UnsupportedPath(SpendPath::HtlcSuccess(preimage.to_vec()))));
+    assert_eq!(build_p2wsh_witness(&tx, 1, &received, value, &SpendPath::HtlcTimeout, &both),
+               Err(WitnessError::UnsupportedPath(SpendPath::HtlcTimeout)));
+    assert_eq!(build_p2wsh_witness(&tx, 1, &received, value, &SpendPath::Preimage(preimage.to_vec()), &[WitnessSigner::Key(secret(0x21))]),
+               Err(WitnessError::UnsupportedPath(SpendPath::Preimage(preimage.to_vec()))));
+
+    // and the ones they have still build: 0 <remotehtlcsig> <localhtlcsig> <preimage>, and the counterparty's timeout
+    let success = build_p2wsh_witness(&tx, 1, &received, value, &SpendPath::HtlcSuccess(preimage.to_vec()), &both).unwrap();
+    assert_eq!(success.len(), 5);
+    assert_eq!(success.nth(1).unwrap(), signature(1, &received, 0x21).as_slice());
+    assert_eq!(success.nth(3).unwrap(), preimage.as_slice());
+    let timeout = build_p2wsh_witness(&tx, 1, &received, value, &SpendPath::Delayed, &[WitnessSigner::Key(secret(0x21))]).unwrap();
+    assert_eq!(timeout.len(), 3);
+    assert!(timeout.nth(1).unwrap().is_empty());
+}
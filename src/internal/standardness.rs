@@ -0,0 +1,223 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::absolute::LockTime;
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::{Sequence, Transaction, TxOut};
+use std::fmt;
+
+pub mod test;
+
+// The checks bitcoind runs before a transaction gets into its mempool, so the
+// Mempool command can say what is wrong before broadcasting. Limits are
+// Bitcoin Core's defaults.
+
+/// Largest transaction bitcoind relays.
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+/// Largest transaction that fits in a block at all.
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3_600;
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+/// Consensus limit on any script, witness scripts included.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// OP_RETURN plus 80 bytes of data and its push opcodes.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+pub const MAX_STANDARD_BARE_MULTISIG_KEYS: u8 = 3;
+
+/// Where the chain is, for deciding whether a lock time has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: u32,
+    pub median_time_past: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// Not one of the output templates bitcoind relays.
+    NonStandardScript { vout: usize },
+    /// Bare multisig is only relayed with up to 3 keys.
+    BareMultisig { vout: usize, required: u8, keys: u8 },
+    OpReturnTooLarge { vout: usize, size: usize },
+    /// Costs more to spend than it is worth at the dust relay feerate.
+    Dust { vout: usize, value: u64, threshold: u64 },
+    OutputsExceedInputs { input_sat: u64, output_sat: u64 },
+    WeightTooHigh { weight: u64, max: u64 },
+    WitnessScriptTooLarge { vin: usize, size: usize, max: usize },
+    TooManyWitnessItems { vin: usize, items: usize },
+    WitnessItemTooLarge { vin: usize, item: usize, size: usize },
+    /// The lock time hasn't been reached, so no block can include it yet.
+    NonFinalLockTime { lock_time: LockTime },
+    /// Every input needs the output it spends before anything can be checked.
+    MissingPrevouts { inputs: usize, prevouts: usize },
+}
+
+impl Finding {
+    /// Whether every node would reject the transaction, rather than just
+    /// refuse to relay it.
+    pub fn is_consensus(&self) -> bool {
+        match self {
+            Finding::OutputsExceedInputs { .. } | Finding::NonFinalLockTime { .. } => true,
+            Finding::WeightTooHigh { max, .. } => *max == MAX_BLOCK_WEIGHT,
+            Finding::WitnessScriptTooLarge { max, .. } => *max == MAX_SCRIPT_SIZE,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::NonStandardScript { vout } => write!(f, "output {} has a non-standard script", vout),
+            Finding::BareMultisig { vout, required, keys } => write!(
+                f,
+                "output {} is a bare {}-of-{} multisig, only up to {} keys are standard",
+                vout, required, keys, MAX_STANDARD_BARE_MULTISIG_KEYS
+            ),
+            Finding::OpReturnTooLarge { vout, size } => {
+                write!(f, "output {} is a {} byte OP_RETURN, the limit is {}", vout, size, MAX_OP_RETURN_RELAY)
+            }
+            Finding::Dust { vout, value, threshold } => {
+                write!(f, "output {} is dust: {} sats, at least {} needed", vout, value, threshold)
+            }
+            Finding::OutputsExceedInputs { input_sat, output_sat } => {
+                write!(f, "outputs spend {} sats but the inputs only have {}", output_sat, input_sat)
+            }
+            Finding::WeightTooHigh { weight, max } => write!(f, "weight {} is over {}", weight, max),
+            Finding::WitnessScriptTooLarge { vin, size, max } => {
+                write!(f, "input {} has a {} byte witness script, the limit is {}", vin, size, max)
+            }
+            Finding::TooManyWitnessItems { vin, items } => write!(
+                f,
+                "input {} has {} witness items before its script, the limit is {}",
+                vin, items, MAX_STANDARD_P2WSH_STACK_ITEMS
+            ),
+            Finding::WitnessItemTooLarge { vin, item, size } => write!(
+                f,
+                "input {} witness item {} is {} bytes, the limit is {}",
+                vin, item, size, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE
+            ),
+            Finding::NonFinalLockTime { lock_time } => write!(f, "lock time {} has not been reached", lock_time),
+            Finding::MissingPrevouts { inputs, prevouts } => {
+                write!(f, "{} inputs but {} spent outputs given, nothing else was checked", inputs, prevouts)
+            }
+        }
+    }
+}
+
+//
+// Outputs
+//
+
+// (required, keys) for `m <key>... n OP_CHECKMULTISIG`
+fn bare_multisig(script: &bitcoin::Script) -> Option<(u8, u8)> {
+    if !script.is_multisig() {
+        return None;
+    }
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().ok()?;
+    let pushnum = |instruction: &Instruction| match instruction {
+        // OP_1 to OP_16
+        Instruction::Op(op) => (0x51..=0x60).contains(&op.to_u8()).then(|| op.to_u8() - 0x50),
+        _ => None,
+    };
+    Some((pushnum(instructions.first()?)?, pushnum(&instructions[instructions.len() - 2])?))
+}
+
+fn check_output(vout: usize, output: &TxOut, findings: &mut Vec<Finding>) {
+    let script = &output.script_pubkey;
+
+    if script.is_op_return() {
+        if script.len() > MAX_OP_RETURN_RELAY {
+            findings.push(Finding::OpReturnTooLarge { vout, size: script.len() });
+        }
+        // nobody can spend it, so it can't be dust
+        return;
+    }
+
+    if let Some((required, keys)) = bare_multisig(script) {
+        if keys > MAX_STANDARD_BARE_MULTISIG_KEYS || required == 0 {
+            findings.push(Finding::BareMultisig { vout, required, keys });
+        }
+    } else if !(script.is_p2pkh() || script.is_p2sh() || script.is_p2pk() || script.is_witness_program()) {
+        findings.push(Finding::NonStandardScript { vout });
+    }
+
+    let threshold = script.minimal_non_dust().to_sat();
+    if output.value.to_sat() < threshold {
+        findings.push(Finding::Dust { vout, value: output.value.to_sat(), threshold });
+    }
+}
+
+//
+// Inputs
+//
+
+fn check_p2wsh_witness(vin: usize, witness: &bitcoin::Witness, findings: &mut Vec<Finding>) {
+    let Some(witness_script) = witness.last() else {
+        return;
+    };
+
+    if witness_script.len() > MAX_SCRIPT_SIZE {
+        findings.push(Finding::WitnessScriptTooLarge { vin, size: witness_script.len(), max: MAX_SCRIPT_SIZE });
+    } else if witness_script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+        findings.push(Finding::WitnessScriptTooLarge { vin, size: witness_script.len(), max: MAX_STANDARD_P2WSH_SCRIPT_SIZE });
+    }
+
+    let items = witness.len() - 1;
+    if items > MAX_STANDARD_P2WSH_STACK_ITEMS {
+        findings.push(Finding::TooManyWitnessItems { vin, items });
+    }
+    for (item, bytes) in witness.iter().take(items).enumerate() {
+        if bytes.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE {
+            findings.push(Finding::WitnessItemTooLarge { vin, item, size: bytes.len() });
+        }
+    }
+}
+
+// Core checks against the next block: heights must already be reached, times
+// must be before the tip's median time past
+fn is_final(tx: &Transaction, tip: &ChainTip) -> bool {
+    if tx.input.iter().all(|input| input.sequence == Sequence::MAX) {
+        return true;
+    }
+    match tx.lock_time {
+        LockTime::Blocks(height) => height.to_consensus_u32() <= tip.height,
+        LockTime::Seconds(time) => time.to_consensus_u32() < tip.median_time_past,
+    }
+}
+
+/// Everything about `tx` that would keep it out of bitcoind's mempool.
+/// `prevouts` are the outputs its inputs spend, in input order.
+pub fn check_transaction(tx: &Transaction, prevouts: &[TxOut], tip: &ChainTip) -> Vec<Finding> {
+    if tx.input.len() != prevouts.len() {
+        return vec![Finding::MissingPrevouts { inputs: tx.input.len(), prevouts: prevouts.len() }];
+    }
+    let mut findings = Vec::new();
+
+    for (vout, output) in tx.output.iter().enumerate() {
+        check_output(vout, output, &mut findings);
+    }
+
+    for (vin, (input, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+        if prevout.script_pubkey.is_p2wsh() {
+            check_p2wsh_witness(vin, &input.witness, &mut findings);
+        }
+    }
+
+    let input_sat: u64 = prevouts.iter().map(|prevout| prevout.value.to_sat()).sum();
+    let output_sat: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    if output_sat > input_sat {
+        findings.push(Finding::OutputsExceedInputs { input_sat, output_sat });
+    }
+
+    let weight = tx.weight().to_wu();
+    if weight > MAX_BLOCK_WEIGHT {
+        findings.push(Finding::WeightTooHigh { weight, max: MAX_BLOCK_WEIGHT });
+    } else if weight > MAX_STANDARD_TX_WEIGHT {
+        findings.push(Finding::WeightTooHigh { weight, max: MAX_STANDARD_TX_WEIGHT });
+    }
+
+    if !is_final(tx, tip) {
+        findings.push(Finding::NonFinalLockTime { lock_time: tx.lock_time });
+    }
+
+    findings
+}
@@ -0,0 +1,105 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::Hash;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use bitcoin::script::ScriptBuf;
+use internal::script_utils::p2wpkh_output_script;
+use internal::tx_utils::get_funding_input;
+use internal::standardness::{check_transaction, ChainTip, Finding, MAX_SCRIPT_SIZE, MAX_STANDARD_P2WSH_SCRIPT_SIZE, MAX_STANDARD_TX_WEIGHT};
+use internal::tx_utils::{build_output, build_transaction};
+
+pub fn standardness_spend(outputs: Vec<bitcoin::TxOut>) -> (bitcoin::Transaction, Vec<bitcoin::TxOut>) {
+    let wallet_key = bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x01; 32]));
+    let prevout = build_output(5_000_000, p2wpkh_output_script(wallet_key));
+    let input = get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000004".to_string(), 0);
+    let tx = build_transaction(bitcoin::transaction::Version::TWO, bitcoin::absolute::LockTime::ZERO, vec![input], outputs);
+    (tx, vec![prevout])
+}
+
+#[test]
+fn test_standardness_flags_the_mempool_examples() {
+    let tip = ChainTip { height: 200, median_time_past: 1_700_000_000 };
+    let key = |byte: u8| bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[byte; 32]));
+    let p2wpkh = p2wpkh_output_script(key(0x01));
+
+    let (tx, prevouts) = standardness_spend(vec![build_output(4_999_000, p2wpkh.clone())]);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![]);
+
+    // Mempool nonstandard: a bare 3-of-4 multisig
+    let bare_multisig = [0x01, 0x02, 0x03, 0x04]
+        .iter()
+        .fold(bitcoin::script::Builder::new().push_int(3), |builder, byte| builder.push_key(&key(*byte)))
+        .push_int(4)
+        .push_opcode(bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG)
+        .into_script();
+    let (tx, prevouts) = standardness_spend(vec![build_output(5_000_000, bare_multisig)]);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::BareMultisig { vout: 0, required: 3, keys: 4 }]);
+
+    // Mempool consensus: spending more than the input has
+    let (tx, prevouts) = standardness_spend(vec![build_output(5_500_000, p2wpkh.clone())]);
+    let findings = check_transaction(&tx, &prevouts, &tip);
+    assert_eq!(findings, vec![Finding::OutputsExceedInputs { input_sat: 5_000_000, output_sat: 5_500_000 }]);
+    assert!(findings[0].is_consensus());
+
+    // Mempool policy: a 100 sat output, below the 294 sat P2WPKH dust limit
+    let (tx, prevouts) = standardness_spend(vec![build_output(4_900_000, p2wpkh.clone()), build_output(100, p2wpkh.clone())]);
+    let findings = check_transaction(&tx, &prevouts, &tip);
+    assert_eq!(findings, vec![Finding::Dust { vout: 1, value: 100, threshold: 294 }]);
+    assert!(!findings[0].is_consensus());
+
+    // OP_RETURN is never dust, but only 80 bytes of data are relayed
+    let op_return = |len: usize| ScriptBuf::new_op_return(bitcoin::script::PushBytesBuf::try_from(vec![0u8; len]).unwrap());
+    let (tx, prevouts) = standardness_spend(vec![build_output(0, op_return(80)), build_output(0, op_return(81))]);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::OpReturnTooLarge { vout: 1, size: 84 }]);
+
+    let (tx, prevouts) = standardness_spend(vec![build_output(4_999_000, ScriptBuf::from_bytes(vec![0x51, 0x87]))]);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::NonStandardScript { vout: 0 }]);
+}
+
+#[test]
+fn test_standardness_checks_witness_weight_and_lock_time() {
+    let tip = ChainTip { height: 200, median_time_past: 1_700_000_000 };
+    let p2wpkh = p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x01; 32])));
+    let (mut tx, mut prevouts) = standardness_spend(vec![build_output(4_999_000, p2wpkh.clone())]);
+
+    // a P2WSH spend with an oversized script, too many items and one item too large
+    let witness_script = ScriptBuf::from_bytes(vec![0x51; MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1]);
+    prevouts[0] = build_output(5_000_000, witness_script.to_p2wsh());
+    let mut stack: Vec<Vec<u8>> = vec![vec![]; 101];
+    stack[3] = vec![0xab; 81];
+    stack.push(witness_script.to_bytes());
+    tx.input[0].witness = bitcoin::Witness::from_slice(&stack);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![
+        Finding::WitnessScriptTooLarge { vin: 0, size: MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1, max: MAX_STANDARD_P2WSH_SCRIPT_SIZE },
+        Finding::TooManyWitnessItems { vin: 0, items: 101 },
+        Finding::WitnessItemTooLarge { vin: 0, item: 3, size: 81 },
+    ]);
+
+    stack = vec![ScriptBuf::from_bytes(vec![0x51; MAX_SCRIPT_SIZE + 1]).to_bytes()];
+    prevouts[0] = build_output(5_000_000, ScriptBuf::from_bytes(stack[0].clone()).to_p2wsh());
+    tx.input[0].witness = bitcoin::Witness::from_slice(&stack);
+    let findings = check_transaction(&tx, &prevouts, &tip);
+    assert_eq!(findings, vec![Finding::WitnessScriptTooLarge { vin: 0, size: MAX_SCRIPT_SIZE + 1, max: MAX_SCRIPT_SIZE }]);
+    assert!(findings[0].is_consensus());
+
+    // 3,500 P2WPKH outputs of 31 bytes weigh more than bitcoind relays
+    let (tx, prevouts) = standardness_spend(vec![build_output(1_000, p2wpkh.clone()); 3_500]);
+    let weight = tx.weight().to_wu();
+    assert!(weight > MAX_STANDARD_TX_WEIGHT);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::WeightTooHigh { weight, max: MAX_STANDARD_TX_WEIGHT }]);
+
+    // lock times only count when an input opts in with a non-final sequence
+    let (mut tx, prevouts) = standardness_spend(vec![build_output(4_999_000, p2wpkh)]);
+    tx.lock_time = bitcoin::absolute::LockTime::from_consensus(201);
+    tx.input[0].sequence = bitcoin::Sequence::MAX;
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![]);
+    tx.input[0].sequence = bitcoin::Sequence::ENABLE_LOCKTIME_NO_RBF;
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::NonFinalLockTime { lock_time: tx.lock_time }]);
+    tx.lock_time = bitcoin::absolute::LockTime::from_consensus(200);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![]);
+    tx.lock_time = bitcoin::absolute::LockTime::from_consensus(1_700_000_000);
+    assert_eq!(check_transaction(&tx, &prevouts, &tip), vec![Finding::NonFinalLockTime { lock_time: tx.lock_time }]);
+
+    // without an output for every input there's nothing to check against
+    assert_eq!(check_transaction(&tx, &[], &tip), vec![Finding::MissingPrevouts { inputs: 1, prevouts: 0 }]);
+}
@@ -0,0 +1,244 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::{OutPoint, Txid};
+use internal::channel::{Channel, HtlcDirection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub mod test;
+
+/// Bumped whenever the layout changes, so an old binary never misreads a newer file.
+pub const STORE_VERSION: u32 = 1;
+
+pub const DEFAULT_STORE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/store.json");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    Io(String),
+    Parse(String),
+    UnsupportedVersion(u32),
+    UnknownChannel(String),
+    UnknownHtlc(String),
+    NoChannelGiven,
+}
+
+/// An HTLC as far as the commands need it. Hashes and txids are hex so the
+/// file stays readable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredHtlc {
+    pub htlc_id: u64,
+    pub offered: bool,
+    pub amount_msat: u64,
+    pub payment_hash: String,
+    pub cltv_expiry: u32,
+    // our commitment the HTLC was last built into, which its keys are
+    // tweaked for
+    pub commitment_number: u64,
+    // the transaction with the HTLC output, once one has been built
+    pub txid: Option<String>,
+}
+
+impl StoredHtlc {
+    pub fn payment_hash(&self) -> [u8; 32] {
+        hex::decode(&self.payment_hash).unwrap().try_into().unwrap()
+    }
+
+    pub fn payment_hash160(&self) -> [u8; 20] {
+        internal::invoice::payment_hash160(&self.payment_hash())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredChannel {
+    pub channel_id: String,
+    pub funding_txid: String,
+    pub funding_output_index: u32,
+    pub channel_value_satoshis: u64,
+    pub is_outbound: bool,
+    // what the commitments, and the HTLC transactions spending them, pay
+    pub feerate_per_kw: u32,
+    pub next_holder_commitment_number: u64,
+    pub next_counterparty_commitment_number: u64,
+    pub counterparty_per_commitment_secrets: BTreeMap<u64, String>,
+    pub htlcs: Vec<StoredHtlc>,
+}
+
+impl StoredChannel {
+    pub fn from_channel(channel: &Channel) -> StoredChannel {
+        StoredChannel {
+            channel_id: hex::encode(channel.channel_id),
+            funding_txid: channel.params.funding_outpoint.txid.to_string(),
+            funding_output_index: channel.params.funding_outpoint.vout,
+            channel_value_satoshis: channel.params.channel_value_satoshis,
+            is_outbound: channel.params.is_outbound,
+            feerate_per_kw: channel.params.feerate_per_kw,
+            next_holder_commitment_number: channel.next_holder_commitment_number,
+            next_counterparty_commitment_number: channel.next_counterparty_commitment_number,
+            counterparty_per_commitment_secrets: channel
+                .counterparty_per_commitment_secrets
+                .iter()
+                .map(|(commitment_number, secret)| (*commitment_number, hex::encode(secret)))
+                .collect(),
+            htlcs: channel
+                .htlcs
+                .iter()
+                .map(|htlc| StoredHtlc {
+                    htlc_id: htlc.htlc_id,
+                    offered: htlc.direction == HtlcDirection::Offered,
+                    amount_msat: htlc.amount_msat,
+                    payment_hash: hex::encode(htlc.payment_hash),
+                    cltv_expiry: htlc.cltv_expiry,
+                    commitment_number: channel.next_holder_commitment_number.saturating_sub(1),
+                    txid: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn funding_outpoint(&self) -> OutPoint {
+        OutPoint { txid: Txid::from_str(&self.funding_txid).unwrap(), vout: self.funding_output_index }
+    }
+
+    /// The commitment we hold signatures for, 0 until the first update.
+    pub fn current_commitment_number(&self) -> u64 {
+        self.next_holder_commitment_number.saturating_sub(1)
+    }
+
+    pub fn per_commitment_secret(&self, commitment_number: u64) -> Option<[u8; 32]> {
+        let secret = self.counterparty_per_commitment_secrets.get(&commitment_number)?;
+        hex::decode(secret).ok()?.try_into().ok()
+    }
+
+    /// Moves both sides on to a new commitment, keeping the secret the
+    /// counterparty revokes its previous one with. `counterparty_secret` gets
+    /// the revoked commitment number. Returns our new commitment number.
+    pub fn advance_commitments(&mut self, counterparty_secret: impl FnOnce(u64) -> [u8; 32]) -> u64 {
+        let commitment_number = self.next_holder_commitment_number;
+        if let Some(revoked) = self.next_counterparty_commitment_number.checked_sub(1) {
+            self.counterparty_per_commitment_secrets.insert(revoked, hex::encode(counterparty_secret(revoked)));
+        }
+        self.next_holder_commitment_number += 1;
+        self.next_counterparty_commitment_number += 1;
+        commitment_number
+    }
+
+    pub fn next_htlc_id(&self) -> u64 {
+        self.htlcs.iter().filter(|htlc| htlc.offered).map(|htlc| htlc.htlc_id + 1).max().unwrap_or(0)
+    }
+
+    /// The most recent offered HTLC that made it into a transaction.
+    pub fn last_offered_htlc(&self) -> Option<&StoredHtlc> {
+        self.htlcs.iter().rev().find(|htlc| htlc.offered && htlc.txid.is_some())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredState {
+    pub version: u32,
+    pub node_seed: String,
+    pub channels: BTreeMap<String, StoredChannel>,
+}
+
+/// Node and channel state kept in a JSON file between commands, keyed by a
+/// channel name the user picks when funding.
+#[derive(Debug, Clone)]
+pub struct Store {
+    path: PathBuf,
+    pub state: StoredState,
+}
+
+impl Store {
+    /// Opens the store at `path`, starting a fresh one with a random node
+    /// seed if there's no file yet. Every key the node uses is derived from
+    /// that seed, so it's what keeps the node's identity between commands,
+    /// and a new store is saved straight away so the next command sees it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Store, StoreError> {
+        let path = path.as_ref().to_path_buf();
+
+        let state = match std::fs::read_to_string(&path) {
+            Ok(json) => {
+                let value: serde_json::Value = serde_json::from_str(&json).map_err(|e| StoreError::Parse(e.to_string()))?;
+                let version = value["version"].as_u64().ok_or(StoreError::Parse("missing version".to_string()))? as u32;
+                if version > STORE_VERSION {
+                    return Err(StoreError::UnsupportedVersion(version));
+                }
+                serde_json::from_value(value).map_err(|e| StoreError::Parse(e.to_string()))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let store = Store {
+                    path,
+                    state: StoredState {
+                        version: STORE_VERSION,
+                        node_seed: hex::encode(rand::random::<[u8; 32]>()),
+                        channels: BTreeMap::new(),
+                    },
+                };
+                store.save()?;
+                return Ok(store);
+            }
+            Err(e) => return Err(StoreError::Io(e.to_string())),
+        };
+
+        Ok(Store { path, state })
+    }
+
+    /// Writes to a temporary file first so a crash never leaves half a store behind.
+    pub fn save(&self) -> Result<(), StoreError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| StoreError::Io(e.to_string()))?;
+        }
+        let json = serde_json::to_string_pretty(&self.state).map_err(|e| StoreError::Parse(e.to_string()))?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json).map_err(|e| StoreError::Io(e.to_string()))?;
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| StoreError::Io(e.to_string()))
+    }
+
+    pub fn node_seed(&self) -> [u8; 32] {
+        hex::decode(&self.state.node_seed).unwrap().try_into().unwrap()
+    }
+
+    pub fn channel(&self, name: &str) -> Result<&StoredChannel, StoreError> {
+        self.state.channels.get(name).ok_or(StoreError::UnknownChannel(name.to_string()))
+    }
+
+    pub fn channel_mut(&mut self, name: &str) -> Result<&mut StoredChannel, StoreError> {
+        self.state.channels.get_mut(name).ok_or(StoreError::UnknownChannel(name.to_string()))
+    }
+
+    /// Saves the channel's latest state under `name`, keeping the txids of
+    /// HTLCs we already know about along with the commitment they're in.
+    pub fn upsert_channel(&mut self, name: &str, channel: &Channel) {
+        let mut stored = StoredChannel::from_channel(channel);
+        if let Some(existing) = self.state.channels.get(name) {
+            for htlc in stored.htlcs.iter_mut() {
+                let built = existing
+                    .htlcs
+                    .iter()
+                    .find(|old| old.htlc_id == htlc.htlc_id && old.offered == htlc.offered && old.txid.is_some());
+                if let Some(old) = built {
+                    htlc.txid = old.txid.clone();
+                    htlc.commitment_number = old.commitment_number;
+                }
+            }
+        }
+        self.state.channels.insert(name.to_string(), stored);
+    }
+}
+
+/// What the commands that used to take a txid do now: use it if given,
+/// otherwise look up the named channel's funding txid in the default store.
+pub fn funding_txid_for(txid: Option<String>, channel_name: Option<String>) -> Result<String, StoreError> {
+    match (txid, channel_name) {
+        (Some(txid), _) => Ok(txid),
+        (None, Some(name)) => {
+            let store = Store::open(DEFAULT_STORE_PATH)?;
+            Ok(store.channel(&name)?.funding_txid.clone())
+        }
+        (None, None) => Err(StoreError::NoChannelGiven),
+    }
+}
@@ -0,0 +1,88 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::store::{funding_txid_for, Store, StoreError, STORE_VERSION};
+use internal::channel::commitment_round;
+use internal::channel::test::test_onion_packet;
+use internal::watcher::test::ready_channel_pair;
+
+pub fn temp_store_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("pl-00-intro-{}-{}.json", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_store_round_trips_channel_state() {
+    let path = temp_store_path("store-round-trip");
+
+    let (mut alice, mut bob) = ready_channel_pair();
+    let update_add = alice.add_htlc(400_000_000, [0x42; 32], 500, test_onion_packet()).unwrap();
+    bob.receive_update_add_htlc(&update_add).unwrap();
+    commitment_round(&mut alice, &mut bob).unwrap();
+
+    // a fresh store gets its own random seed, saved as soon as it's made
+    let mut store = Store::open(&path).unwrap();
+    assert_eq!(Store::open(&path).unwrap().node_seed(), store.node_seed());
+    let other_path = temp_store_path("store-other-seed");
+    assert_ne!(store.node_seed(), Store::open(&other_path).unwrap().node_seed());
+    std::fs::remove_file(&other_path).unwrap();
+    store.upsert_channel("alice-bob", &alice);
+    store.channel_mut("alice-bob").unwrap().htlcs[0].txid = Some("aa".repeat(32));
+    store.save().unwrap();
+
+    let reopened = Store::open(&path).unwrap();
+    assert_eq!(reopened.node_seed(), store.node_seed());
+    assert_eq!(reopened.state.version, STORE_VERSION);
+
+    let channel = reopened.channel("alice-bob").unwrap();
+    assert_eq!(channel.funding_outpoint(), alice.params.funding_outpoint);
+    assert_eq!(channel.feerate_per_kw, alice.params.feerate_per_kw);
+    assert_eq!(channel.current_commitment_number(), alice.next_holder_commitment_number - 1);
+    assert_eq!(channel.per_commitment_secret(0), alice.counterparty_per_commitment_secrets.get(&0).copied());
+    assert_eq!(channel.next_htlc_id(), 1);
+    assert_eq!(channel.last_offered_htlc().unwrap().payment_hash(), [0x42; 32]);
+    assert_eq!(reopened.channel("carol"), Err(StoreError::UnknownChannel("carol".to_string())));
+
+    // updating the channel keeps the txid we recorded for its HTLC
+    let mut store = reopened;
+    store.upsert_channel("alice-bob", &alice);
+    assert_eq!(store.channel("alice-bob").unwrap().htlcs[0].txid, Some("aa".repeat(32)));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_store_rejects_newer_versions() {
+    let path = temp_store_path("store-version");
+    std::fs::write(&path, format!("{{\"version\": {}, \"node_seed\": \"\", \"channels\": {{}}}}", STORE_VERSION + 1)).unwrap();
+    assert_eq!(Store::open(&path).err(), Some(StoreError::UnsupportedVersion(STORE_VERSION + 1)));
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(matches!(Store::open(&path), Err(StoreError::Parse(_))));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(funding_txid_for(Some("ab".repeat(32)), Some("ignored".to_string())), Ok("ab".repeat(32)));
+    assert_eq!(funding_txid_for(None, None), Err(StoreError::NoChannelGiven));
+}
+
+#[test]
+fn test_store_advances_commitments() {
+    let (alice, _) = ready_channel_pair();
+    let path = temp_store_path("store-advance");
+    let mut store = Store::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    store.upsert_channel("alice-bob", &alice);
+
+    let channel = store.channel_mut("alice-bob").unwrap();
+    let next_holder = channel.next_holder_commitment_number;
+    let next_counterparty = channel.next_counterparty_commitment_number;
+
+    let commitment_number = channel.advance_commitments(|revoked| {
+        assert_eq!(revoked, next_counterparty - 1);
+        [0x42; 32]
+    });
+    assert_eq!(commitment_number, next_holder);
+    assert_eq!(channel.current_commitment_number(), next_holder);
+    assert_eq!(channel.next_counterparty_commitment_number, next_counterparty + 1);
+    assert_eq!(channel.per_commitment_secret(next_counterparty - 1), Some([0x42; 32]));
+}
@@ -0,0 +1,241 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::blockdata::opcodes::all as opcodes;
+use bitcoin::key::TweakedPublicKey;
+use bitcoin::script::{Builder, ScriptBuf};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::Witness;
+
+pub mod test;
+
+// Tapscript versions of the commitment outputs, following the simple taproot
+// channels proposal. Keys in tapscript are x-only, so only the x coordinate
+// of each of our keys ends up in the scripts.
+
+/// The internal key of outputs that must only be spent through a script:
+/// nobody knows its discrete log, so the key path is unusable.
+pub const TAPROOT_NUMS_POINT: &str = "02dca094751109d0bd055d03565874e8276dd53e926b44e3bd1bb6bf4bc130a279";
+
+pub fn nums_point() -> XOnlyPublicKey {
+    PublicKey::from_slice(&hex::decode(TAPROOT_NUMS_POINT).unwrap()).unwrap().x_only_public_key().0
+}
+
+fn xonly(pubkey: &PublicKey) -> XOnlyPublicKey {
+    pubkey.x_only_public_key().0
+}
+
+/// A taproot output together with the leaves that can spend it.
+#[derive(Debug, Clone)]
+pub struct TaprootTree {
+    pub internal_key: XOnlyPublicKey,
+    pub leaves: Vec<ScriptBuf>,
+    pub spend_info: TaprootSpendInfo,
+}
+
+impl TaprootTree {
+    /// One leaf sits at the root, two are siblings at depth 1.
+    pub fn new(internal_key: XOnlyPublicKey, leaves: Vec<ScriptBuf>) -> TaprootTree {
+        let depth = if leaves.len() == 1 { 0 } else { 1 };
+        let builder = leaves
+            .iter()
+            .fold(TaprootBuilder::new(), |builder, leaf| builder.add_leaf(depth, leaf.clone()).unwrap());
+        let spend_info = builder.finalize(&Secp256k1::new(), internal_key).unwrap();
+
+        TaprootTree { internal_key, leaves, spend_info }
+    }
+
+    pub fn output_key(&self) -> TweakedPublicKey {
+        self.spend_info.output_key()
+    }
+
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_p2tr_tweaked(self.output_key())
+    }
+
+    /// Proves `leaf` is committed to by the output key. Panics if it isn't one of ours.
+    pub fn control_block(&self, leaf: &ScriptBuf) -> ControlBlock {
+        self.spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .expect("leaf is not in this tree")
+    }
+
+    /// The witness for spending through `leaf`: the stack the script
+    /// expects, bottom first, then the script and its control block.
+    pub fn script_path_witness(&self, leaf: &ScriptBuf, stack: Vec<Vec<u8>>) -> Witness {
+        let mut witness = Witness::new();
+        for item in stack {
+            witness.push(item);
+        }
+        witness.push(leaf.as_bytes());
+        witness.push(self.control_block(leaf).serialize());
+        witness
+    }
+}
+
+//
+// to_local and to_remote
+//
+
+/// `<local_delayedpubkey> OP_CHECKSIG <to_self_delay> OP_CHECKSEQUENCEVERIFY OP_DROP`
+pub fn to_local_delay_script(to_local_delayed_pubkey: &PublicKey, to_self_delay: u16) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&xonly(to_local_delayed_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_int(to_self_delay as i64)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .into_script()
+}
+
+/// `<local_delayedpubkey> OP_DROP <revocationkey> OP_CHECKSIG`. The delayed
+/// key is only there so a revoked spend reveals it.
+pub fn to_local_revoke_script(to_local_delayed_pubkey: &PublicKey, revocation_pubkey: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&xonly(to_local_delayed_pubkey))
+        .push_opcode(opcodes::OP_DROP)
+        .push_x_only_key(&xonly(revocation_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Spent by us after the delay with `[delayed_sig]` through the delay
+/// leaf, or by the counterparty with `[revocation_sig]` through the revoke leaf.
+pub fn to_local_tree(revocation_pubkey: &PublicKey, to_local_delayed_pubkey: &PublicKey, to_self_delay: u16) -> TaprootTree {
+    TaprootTree::new(
+        nums_point(),
+        vec![
+            to_local_delay_script(to_local_delayed_pubkey, to_self_delay),
+            to_local_revoke_script(to_local_delayed_pubkey, revocation_pubkey),
+        ],
+    )
+}
+
+/// `<remotepubkey> OP_CHECKSIG 1 OP_CHECKSEQUENCEVERIFY OP_DROP`
+pub fn to_remote_script(remote_pubkey: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&xonly(remote_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .into_script()
+}
+
+/// Spent by the counterparty with `[remote_sig]` one block after confirmation.
+pub fn to_remote_tree(remote_pubkey: &PublicKey) -> TaprootTree {
+    TaprootTree::new(nums_point(), vec![to_remote_script(remote_pubkey)])
+}
+
+//
+// HTLCs
+//
+
+/// `<local_htlcpubkey> OP_CHECKSIGVERIFY <remote_htlcpubkey> OP_CHECKSIG`
+pub fn offered_htlc_timeout_script(local_htlc_pubkey: &PublicKey, remote_htlc_pubkey: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&xonly(local_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIGVERIFY)
+        .push_x_only_key(&xonly(remote_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .into_script()
+}
+
+/// `OP_SIZE 32 OP_EQUALVERIFY OP_HASH160 <RIPEMD160(payment_hash)> OP_EQUALVERIFY
+/// <remote_htlcpubkey> OP_CHECKSIG 1 OP_CHECKSEQUENCEVERIFY OP_DROP`
+pub fn offered_htlc_success_script(remote_htlc_pubkey: &PublicKey, payment_hash160: &[u8; 20]) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(opcodes::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(payment_hash160)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_x_only_key(&xonly(remote_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .into_script()
+}
+
+/// An HTLC we offered, on our commitment. The revocation key is the internal
+/// key, so a revoked output is swept with a key path signature. Otherwise the
+/// HTLC-timeout transaction spends the timeout leaf with
+/// `[remote_sig, local_sig]`, or the counterparty claims through the success
+/// leaf with `[remote_sig, preimage]`.
+pub fn offered_htlc_tree(
+    revocation_pubkey: &PublicKey,
+    local_htlc_pubkey: &PublicKey,
+    remote_htlc_pubkey: &PublicKey,
+    payment_hash160: &[u8; 20],
+) -> TaprootTree {
+    TaprootTree::new(
+        xonly(revocation_pubkey),
+        vec![
+            offered_htlc_timeout_script(local_htlc_pubkey, remote_htlc_pubkey),
+            offered_htlc_success_script(remote_htlc_pubkey, payment_hash160),
+        ],
+    )
+}
+
+/// `OP_SIZE 32 OP_EQUALVERIFY OP_HASH160 <RIPEMD160(payment_hash)> OP_EQUALVERIFY
+/// <local_htlcpubkey> OP_CHECKSIGVERIFY <remote_htlcpubkey> OP_CHECKSIG`
+pub fn received_htlc_success_script(
+    local_htlc_pubkey: &PublicKey,
+    remote_htlc_pubkey: &PublicKey,
+    payment_hash160: &[u8; 20],
+) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(opcodes::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(payment_hash160)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_x_only_key(&xonly(local_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIGVERIFY)
+        .push_x_only_key(&xonly(remote_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .into_script()
+}
+
+/// `<remote_htlcpubkey> OP_CHECKSIG 1 OP_CHECKSEQUENCEVERIFY OP_DROP
+/// <cltv_expiry> OP_CHECKLOCKTIMEVERIFY OP_DROP`
+pub fn received_htlc_timeout_script(remote_htlc_pubkey: &PublicKey, cltv_expiry: u32) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&xonly(remote_htlc_pubkey))
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_int(cltv_expiry as i64)
+        .push_opcode(opcodes::OP_CLTV)
+        .push_opcode(opcodes::OP_DROP)
+        .into_script()
+}
+
+/// An HTLC we received, on our commitment. The HTLC-success transaction
+/// spends the success leaf with `[remote_sig, local_sig, preimage]`; after
+/// `cltv_expiry` the counterparty takes it back with `[remote_sig]`.
+pub fn received_htlc_tree(
+    revocation_pubkey: &PublicKey,
+    local_htlc_pubkey: &PublicKey,
+    remote_htlc_pubkey: &PublicKey,
+    payment_hash160: &[u8; 20],
+    cltv_expiry: u32,
+) -> TaprootTree {
+    TaprootTree::new(
+        xonly(revocation_pubkey),
+        vec![
+            received_htlc_success_script(local_htlc_pubkey, remote_htlc_pubkey, payment_hash160),
+            received_htlc_timeout_script(remote_htlc_pubkey, cltv_expiry),
+        ],
+    )
+}
+
+/// The output of an HTLC-timeout or HTLC-success transaction: the same delay
+/// leaf as to_local, with revocation through the key path.
+pub fn htlc_second_level_tree(revocation_pubkey: &PublicKey, to_local_delayed_pubkey: &PublicKey, to_self_delay: u16) -> TaprootTree {
+    TaprootTree::new(xonly(revocation_pubkey), vec![to_local_delay_script(to_local_delayed_pubkey, to_self_delay)])
+}
+
@@ -0,0 +1,116 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::key_utils::{secp256k1_private_key, secp256k1pubkey_from_private_key};
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::Secp256k1;
+use internal::script_utils::p2wpkh_output_script;
+use internal::sign_utils::{generate_taproot_keypath_signature, generate_taproot_scriptpath_signature, verify_taproot_signature};
+use bitcoin::sighash::TapSighashType;
+use internal::invoice::generate_preimage;
+use internal::tx_utils::get_funding_input;
+use internal::taproot::{htlc_second_level_tree, nums_point, offered_htlc_success_script, offered_htlc_timeout_script, offered_htlc_tree,
+                       received_htlc_success_script, received_htlc_timeout_script, received_htlc_tree,
+                       to_local_delay_script, to_local_revoke_script, to_local_tree, to_remote_script, to_remote_tree, TaprootTree};
+
+// a transaction spending output 0 of `prevout`'s tree, with the leaf's relative delay
+pub fn taproot_spend(prevout: &bitcoin::TxOut, sequence: u32) -> (bitcoin::Transaction, Vec<bitcoin::TxOut>) {
+    let mut txin = get_funding_input("c0ffee0000000000000000000000000000000000000000000000000000000002".to_string(), 0);
+    txin.sequence = bitcoin::Sequence(sequence);
+    let tx = bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![txin],
+        output: vec![bitcoin::TxOut { value: bitcoin::Amount::from_sat(99_000), script_pubkey: p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x09; 32]))) }],
+    };
+    (tx, vec![prevout.clone()])
+}
+
+// spends `leaf` with signatures from `signers`, bottom of the stack first, and checks the result
+pub fn check_leaf_spend(tree: &TaprootTree, leaf: &ScriptBuf, signers: &[[u8; 32]], extra: Option<Vec<u8>>, sequence: u32) {
+    let secp = Secp256k1::new();
+    let prevout = bitcoin::TxOut { value: bitcoin::Amount::from_sat(100_000), script_pubkey: tree.script_pubkey() };
+    let (tx, prevouts) = taproot_spend(&prevout, sequence);
+
+    let control_block = tree.control_block(leaf);
+    assert!(control_block.verify_taproot_commitment(&secp, tree.output_key().to_inner(), leaf));
+
+    let mut stack = Vec::new();
+    for secret in signers {
+        let signature = generate_taproot_scriptpath_signature(&tx, 0, &prevouts, leaf, TapSighashType::Default, secp256k1_private_key(secret));
+        let signer = secp256k1pubkey_from_private_key(secret).x_only_public_key().0;
+        assert!(verify_taproot_signature(&tx, 0, &prevouts, Some(leaf), &signature, &signer));
+        // every signer's key is in the leaf
+        assert!(leaf.as_bytes().windows(32).any(|window| window == signer.serialize()));
+        stack.push(signature.to_vec());
+    }
+    stack.extend(extra);
+
+    let witness = tree.script_path_witness(leaf, stack.clone());
+    assert_eq!(witness.len(), stack.len() + 2);
+    assert_eq!(witness.nth(stack.len()).unwrap(), leaf.as_bytes());
+    assert_eq!(witness.last().unwrap(), control_block.serialize());
+}
+
+#[test]
+fn test_taproot_commitment_outputs_spend_each_leaf() {
+    let revocation_secret = [0x31; 32];
+    let delayed_secret = [0x32; 32];
+    let remote_secret = [0x33; 32];
+    let revocation_pubkey = secp256k1pubkey_from_private_key(&revocation_secret);
+    let delayed_pubkey = secp256k1pubkey_from_private_key(&delayed_secret);
+    let remote_pubkey = secp256k1pubkey_from_private_key(&remote_secret);
+
+    let to_local = to_local_tree(&revocation_pubkey, &delayed_pubkey, 144);
+    assert_eq!(to_local.internal_key, nums_point());
+    assert!(to_local.script_pubkey().is_p2tr());
+    check_leaf_spend(&to_local, &to_local_delay_script(&delayed_pubkey, 144), &[delayed_secret], None, 144);
+    check_leaf_spend(&to_local, &to_local_revoke_script(&delayed_pubkey, &revocation_pubkey), &[revocation_secret], None, 0);
+
+    let to_remote = to_remote_tree(&remote_pubkey);
+    check_leaf_spend(&to_remote, &to_remote_script(&remote_pubkey), &[remote_secret], None, 1);
+
+    // outputs only differ when their keys do
+    assert_ne!(to_local.script_pubkey(), to_local_tree(&revocation_pubkey, &delayed_pubkey, 145).script_pubkey());
+}
+
+#[test]
+fn test_taproot_htlc_outputs_spend_each_leaf() {
+    let secp = Secp256k1::new();
+    let revocation_secret = [0x41; 32];
+    let local_htlc_secret = [0x42; 32];
+    let remote_htlc_secret = [0x43; 32];
+    let delayed_secret = [0x44; 32];
+    let revocation_pubkey = secp256k1pubkey_from_private_key(&revocation_secret);
+    let local_htlc_pubkey = secp256k1pubkey_from_private_key(&local_htlc_secret);
+    let remote_htlc_pubkey = secp256k1pubkey_from_private_key(&remote_htlc_secret);
+    let delayed_pubkey = secp256k1pubkey_from_private_key(&delayed_secret);
+    let (preimage, payment_hash) = generate_preimage();
+    let payment_hash160 = internal::invoice::payment_hash160(&payment_hash);
+
+    let offered = offered_htlc_tree(&revocation_pubkey, &local_htlc_pubkey, &remote_htlc_pubkey, &payment_hash160);
+    check_leaf_spend(&offered, &offered_htlc_timeout_script(&local_htlc_pubkey, &remote_htlc_pubkey), &[remote_htlc_secret, local_htlc_secret], None, 0);
+    check_leaf_spend(&offered, &offered_htlc_success_script(&remote_htlc_pubkey, &payment_hash160), &[remote_htlc_secret], Some(preimage.to_vec()), 1);
+
+    let received = received_htlc_tree(&revocation_pubkey, &local_htlc_pubkey, &remote_htlc_pubkey, &payment_hash160, 500);
+    check_leaf_spend(&received, &received_htlc_success_script(&local_htlc_pubkey, &remote_htlc_pubkey, &payment_hash160), &[remote_htlc_secret, local_htlc_secret], Some(preimage.to_vec()), 0);
+    check_leaf_spend(&received, &received_htlc_timeout_script(&remote_htlc_pubkey, 500), &[remote_htlc_secret], None, 1);
+
+    let second_level = htlc_second_level_tree(&revocation_pubkey, &delayed_pubkey, 144);
+    check_leaf_spend(&second_level, &to_local_delay_script(&delayed_pubkey, 144), &[delayed_secret], None, 144);
+
+    // revoked HTLC outputs are swept through the key path with the revocation key
+    for tree in [&offered, &received, &second_level] {
+        assert_eq!(tree.internal_key, revocation_pubkey.x_only_public_key().0);
+        let prevout = bitcoin::TxOut { value: bitcoin::Amount::from_sat(100_000), script_pubkey: tree.script_pubkey() };
+        let (tx, prevouts) = taproot_spend(&prevout, 0);
+        let signature = generate_taproot_keypath_signature(
+            &tx,
+            0,
+            &prevouts,
+            tree.spend_info.merkle_root(),
+            TapSighashType::Default,
+            secp256k1_private_key(&revocation_secret),
+        );
+        assert!(verify_taproot_signature(&tx, 0, &prevouts, None, &signature, &tree.output_key().to_inner()));
+    }
+}
@@ -27,6 +27,9 @@ use bitcoin::secp256k1::Message;
 use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::sighash::SighashCache;
 use exercises::exercises::{ two_of_two_multisig_witness_script};
+use crate::exercises_appendix::solutions::get_commitment_transaction_number_obscure_factor;
+
+pub mod test;
 
 pub fn get_funding_input(input_tx_id_str: String, vout: usize) -> TxIn {
 
@@ -49,6 +52,12 @@ pub fn get_funding_input(input_tx_id_str: String, vout: usize) -> TxIn {
 }
 
 pub async fn get_unspent_output(bitcoind: BitcoindClient) -> TxIn {
+  get_unspent_utxo(bitcoind).await.0
+}
+
+/// Like `get_unspent_output`, but also returns the output's value so the
+/// caller can work out the fee.
+pub async fn get_unspent_utxo(bitcoind: BitcoindClient) -> (TxIn, u64) {
   let utxos = bitcoind.list_unspent().await;
   let utxo = utxos
       .0
@@ -66,7 +75,12 @@ pub async fn get_unspent_output(bitcoind: BitcoindClient) -> TxIn {
         witness: Witness::new(),
     };
 
-    tx_input
+    (tx_input, utxo.amount)
+}
+
+/// The fee for a transaction of `weight` at `feerate_per_kw`, rounded down as LDK does.
+pub fn fee_for_weight(feerate_per_kw: u32, weight: bitcoin::Weight) -> u64 {
+    feerate_per_kw as u64 * weight.to_wu() / 1000
 }
 
 pub fn get_htlc_funding_input(input_tx_id_str: String, vout: usize) -> TxIn {
@@ -128,3 +142,32 @@ pub fn build_transaction(
     }
 }
 
+
+//
+// Commitment numbers
+//
+
+// BOLT 3: the upper byte of a commitment's nSequence and nLockTime, so it
+// can't be mistaken for anything else spending the funding output. The
+// commitment builders set them and recover_commitment_number checks them
+pub const COMMITMENT_SEQUENCE_MARKER: u32 = 0x80;
+pub const COMMITMENT_LOCKTIME_MARKER: u32 = 0x20;
+
+/// Reads the commitment number back out of a commitment transaction's
+/// nSequence and nLockTime. Returns `None` for anything without the
+/// commitment markers, since its bits don't mean anything.
+pub fn recover_commitment_number(
+    commitment_tx: &Transaction,
+    open_payment_basepoint: &PublicKey,
+    accept_payment_basepoint: &PublicKey,
+) -> Option<u64> {
+    let sequence = commitment_tx.input.first()?.sequence.0;
+    let lock_time = commitment_tx.lock_time.to_consensus_u32();
+    if sequence >> 24 != COMMITMENT_SEQUENCE_MARKER || lock_time >> 24 != COMMITMENT_LOCKTIME_MARKER {
+        return None;
+    }
+
+    // upper 24 bits live in the input's nSequence, lower 24 bits in nLockTime
+    let obscured = ((sequence & 0xffffff) as u64) << 24 | (lock_time & 0xffffff) as u64;
+    Some(obscured ^ get_commitment_transaction_number_obscure_factor(open_payment_basepoint, accept_payment_basepoint))
+}
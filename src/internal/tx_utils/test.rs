@@ -0,0 +1,52 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use crate::exercises::solutions::build_commitment_transaction;
+use bitcoin::hash_types::Txid;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::{OutPoint, Sequence};
+use internal::key_utils::{pubkey_from_private_key, secp256k1pubkey_from_private_key};
+use internal::tx_utils::recover_commitment_number;
+
+#[test]
+fn test_recover_commitment_number() {
+    let outpoint = OutPoint::new(
+        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
+            .parse::<Txid>()
+            .unwrap(),
+        1,
+    );
+
+    let open_basepoint = secp256k1pubkey_from_private_key(&[0x01; 32]);
+    let accept_basepoint = secp256k1pubkey_from_private_key(&[0x02; 32]);
+    let commitment_number: u64 = 1_234_567;
+
+    let mut commitment_tx = build_commitment_transaction(
+        outpoint,
+        &open_basepoint,
+        &accept_basepoint,
+        commitment_number,
+        &pubkey_from_private_key(&[0x03; 32]),
+        &pubkey_from_private_key(&[0x04; 32]),
+        pubkey_from_private_key(&[0x05; 32]),
+        144,
+        3_998_500,
+        1_000_500,
+    );
+
+    assert_eq!(recover_commitment_number(&commitment_tx, &open_basepoint, &accept_basepoint), Some(commitment_number));
+
+    // the basepoints go in opener first
+    assert_ne!(recover_commitment_number(&commitment_tx, &accept_basepoint, &open_basepoint), Some(commitment_number));
+
+    // without both markers the bits aren't a commitment number at all
+    let lock_time = commitment_tx.lock_time;
+    commitment_tx.lock_time = LockTime::from_consensus(lock_time.to_consensus_u32() & 0xffffff);
+    assert_eq!(recover_commitment_number(&commitment_tx, &open_basepoint, &accept_basepoint), None);
+
+    commitment_tx.lock_time = lock_time;
+    commitment_tx.input[0].sequence = Sequence::MAX;
+    assert_eq!(recover_commitment_number(&commitment_tx, &open_basepoint, &accept_basepoint), None);
+
+    commitment_tx.input.clear();
+    assert_eq!(recover_commitment_number(&commitment_tx, &open_basepoint, &accept_basepoint), None);
+}
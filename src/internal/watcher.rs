@@ -0,0 +1,214 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::block::Header;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Network, OutPoint, Transaction, Txid};
+use internal::channel::Channel;
+use internal::tx_utils::recover_commitment_number;
+use lightning::chain::transaction::TransactionData;
+use lightning::chain::Listen;
+use lightning_block_sync::init::validate_best_block_header;
+use lightning_block_sync::poll::ChainPoller;
+use lightning_block_sync::{BlockSource, BlockSourceError, SpvClient, UnboundedCache};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub mod test;
+
+/// What we need to recognise a channel's funding spend without the `Channel` itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedChannel {
+    pub channel_id: [u8; 32],
+    pub funding_outpoint: OutPoint,
+    open_payment_basepoint: PublicKey,
+    accept_payment_basepoint: PublicKey,
+    holder_commitment_txid: Option<Txid>,
+    // our earlier commitments seen since we started watching, so an old one
+    // of ours isn't mistaken for the counterparty's
+    revoked_holder_commitment_txids: BTreeSet<Txid>,
+    counterparty_per_commitment_secrets: BTreeMap<u64, [u8; 32]>,
+}
+
+impl WatchedChannel {
+    pub fn new(channel: &Channel) -> WatchedChannel {
+        let (open_payment_basepoint, accept_payment_basepoint) = if channel.params.is_outbound {
+            (channel.holder_pubkeys.payment_basepoint, channel.counterparty_pubkeys.payment_basepoint)
+        } else {
+            (channel.counterparty_pubkeys.payment_basepoint, channel.holder_pubkeys.payment_basepoint)
+        };
+
+        WatchedChannel {
+            channel_id: channel.channel_id,
+            funding_outpoint: channel.params.funding_outpoint,
+            open_payment_basepoint,
+            accept_payment_basepoint,
+            holder_commitment_txid: channel.holder_commitment().map(|commitment| commitment.tx.compute_txid()),
+            revoked_holder_commitment_txids: BTreeSet::new(),
+            counterparty_per_commitment_secrets: channel.counterparty_per_commitment_secrets.clone(),
+        }
+    }
+
+    /// Works out who broadcast `tx` and whether they were allowed to. Anything
+    /// without the commitment markers can only be a mutual close, and a
+    /// commitment number we hold the secret for has been revoked.
+    pub fn classify_spend(&self, tx: &Transaction) -> SpendKind {
+        let Some(commitment_number) = recover_commitment_number(tx, &self.open_payment_basepoint, &self.accept_payment_basepoint) else {
+            return SpendKind::CooperativeClose;
+        };
+
+        let txid = tx.compute_txid();
+        if Some(txid) == self.holder_commitment_txid {
+            return SpendKind::CurrentCommitment { commitment_number, holder_broadcast: true };
+        }
+        if self.revoked_holder_commitment_txids.contains(&txid) {
+            return SpendKind::RevokedHolderCommitment { commitment_number };
+        }
+
+        match self.counterparty_per_commitment_secrets.get(&commitment_number) {
+            Some(per_commitment_secret) => SpendKind::RevokedCommitment {
+                commitment_number,
+                per_commitment_secret: *per_commitment_secret,
+            },
+            None => SpendKind::CurrentCommitment { commitment_number, holder_broadcast: false },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendKind {
+    CooperativeClose,
+    CurrentCommitment { commitment_number: u64, holder_broadcast: bool },
+    // the secret lets us derive the revocation key and sweep every output
+    RevokedCommitment { commitment_number: u64, per_commitment_secret: [u8; 32] },
+    // one of our own old commitments, which the counterparty can sweep
+    RevokedHolderCommitment { commitment_number: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingSpent {
+    pub channel_id: [u8; 32],
+    pub funding_outpoint: OutPoint,
+    pub spending_tx: Transaction,
+    pub height: u32,
+    pub kind: SpendKind,
+}
+
+/// Tracks funding outpoints and turns their spends into `FundingSpent` events.
+/// It's a `Listen`er, so any lightning-block-sync client can drive it.
+#[derive(Default)]
+pub struct ChainWatcher {
+    channels: Mutex<Vec<WatchedChannel>>,
+    // kept around so a reorg can put them back
+    closed: Mutex<Vec<(u32, WatchedChannel)>>,
+    pending_events: Mutex<Vec<FundingSpent>>,
+}
+
+impl ChainWatcher {
+    pub fn new() -> ChainWatcher {
+        ChainWatcher::default()
+    }
+
+    /// Starts watching the channel, or refreshes what we know about it after
+    /// a commitment update.
+    pub fn watch_channel(&self, channel: &Channel) {
+        let mut watched = WatchedChannel::new(channel);
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(index) = channels.iter().position(|existing| existing.channel_id == watched.channel_id) {
+            let previous = channels.remove(index);
+            watched.revoked_holder_commitment_txids = previous.revoked_holder_commitment_txids;
+            watched.revoked_holder_commitment_txids.extend(previous.holder_commitment_txid);
+            if let Some(current) = watched.holder_commitment_txid {
+                watched.revoked_holder_commitment_txids.remove(&current);
+            }
+        }
+        channels.push(watched);
+    }
+
+    pub fn watched_outpoints(&self) -> Vec<OutPoint> {
+        self.channels.lock().unwrap().iter().map(|channel| channel.funding_outpoint).collect()
+    }
+
+    pub fn get_and_clear_pending_events(&self) -> Vec<FundingSpent> {
+        std::mem::take(&mut *self.pending_events.lock().unwrap())
+    }
+}
+
+impl Listen for ChainWatcher {
+    fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
+        let mut channels = self.channels.lock().unwrap();
+
+        for (_, tx) in txdata.iter() {
+            let spent = channels
+                .iter()
+                .position(|channel| tx.input.iter().any(|input| input.previous_output == channel.funding_outpoint));
+            let Some(index) = spent else { continue };
+
+            let channel = channels.remove(index);
+            self.pending_events.lock().unwrap().push(FundingSpent {
+                channel_id: channel.channel_id,
+                funding_outpoint: channel.funding_outpoint,
+                spending_tx: (*tx).clone(),
+                height,
+                kind: channel.classify_spend(tx),
+            });
+            self.closed.lock().unwrap().push((height, channel));
+        }
+    }
+
+    fn block_disconnected(&self, header: &Header, height: u32) {
+        let mut closed = self.closed.lock().unwrap();
+        let (reorged, still_closed): (Vec<_>, Vec<_>) = closed.drain(..).partition(|(closed_at, _)| *closed_at >= height);
+        *closed = still_closed;
+        self.channels.lock().unwrap().extend(reorged.into_iter().map(|(_, channel)| channel));
+    }
+}
+
+/// Feeds every block after `block_source`'s current tip to `listener` until
+/// `done` returns true. Headers are validated and reorgs are handled by
+/// lightning-block-sync's `SpvClient`.
+pub async fn poll_blocks_until<B: Deref, L: Deref, F: FnMut() -> bool>(
+    listener: L,
+    block_source: B,
+    network: Network,
+    poll_interval: Duration,
+    mut done: F,
+) -> Result<(), BlockSourceError>
+where
+    B::Target: BlockSource,
+    L::Target: Listen,
+{
+    let chain_tip = validate_best_block_header(&*block_source).await?;
+    let chain_poller = ChainPoller::new(&*block_source, network);
+    let mut header_cache = UnboundedCache::new();
+    let mut spv_client = SpvClient::new(chain_tip, chain_poller, &mut header_cache, listener);
+
+    loop {
+        spv_client.poll_best_tip().await?;
+        if done() {
+            return Ok(());
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Polls `block_source` for new blocks from its current tip until one of the
+/// watched funding outputs is spent, and returns what happened.
+pub async fn poll_for_spends<B: Deref>(
+    watcher: &ChainWatcher,
+    block_source: B,
+    network: Network,
+    poll_interval: Duration,
+) -> Result<Vec<FundingSpent>, BlockSourceError>
+where
+    B::Target: BlockSource,
+{
+    let mut events = Vec::new();
+    poll_blocks_until(watcher, block_source, network, poll_interval, || {
+        events = watcher.get_and_clear_pending_events();
+        !events.is_empty()
+    })
+    .await?;
+    Ok(events)
+}
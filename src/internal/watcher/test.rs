@@ -0,0 +1,138 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::OutPoint;
+use internal::channel::Channel;
+use bitcoin::script::ScriptBuf;
+use bitcoin::Network;
+use internal::chain::MockChain;
+use internal::watcher::{poll_for_spends, ChainWatcher, SpendKind, WatchedChannel};
+use lightning::chain::Listen;
+use std::time::Duration;
+use internal::channel::commitment_round;
+use internal::channel::test::{channel_pair, test_onion_packet};
+
+// a channel pair past funding_signed and channel_ready
+pub fn ready_channel_pair() -> (Channel, Channel) {
+    let (mut alice, mut bob) = channel_pair();
+    let bob_commitment_signed = alice.sign_commitment().unwrap();
+    bob.receive_commitment_signed(&bob_commitment_signed).unwrap();
+    let alice_commitment_signed = bob.sign_commitment().unwrap();
+    alice.receive_commitment_signed(&alice_commitment_signed).unwrap();
+
+    alice.receive_channel_ready(bob.next_per_commitment_point());
+    bob.receive_channel_ready(alice.next_per_commitment_point());
+    (alice, bob)
+}
+
+pub fn cooperative_close_tx(funding_outpoint: OutPoint) -> bitcoin::Transaction {
+    bitcoin::Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn { previous_output: funding_outpoint, ..Default::default() }],
+        output: vec![bitcoin::TxOut { value: bitcoin::Amount::from_sat(4_999_000), script_pubkey: ScriptBuf::new() }],
+    }
+}
+
+#[test]
+fn test_watcher_classifies_funding_spends() {
+    let (mut alice, mut bob) = ready_channel_pair();
+    let update_add = alice.add_htlc(400_000_000, [0x42; 32], 500, test_onion_packet()).unwrap();
+    bob.receive_update_add_htlc(&update_add).unwrap();
+    commitment_round(&mut alice, &mut bob).unwrap();
+
+    let watched = WatchedChannel::new(&alice);
+
+    let alice_commitment = alice.signed_holder_commitment_transaction().unwrap();
+    assert_eq!(
+        watched.classify_spend(&alice_commitment),
+        SpendKind::CurrentCommitment { commitment_number: 1, holder_broadcast: true }
+    );
+
+    let bob_commitment = bob.signed_holder_commitment_transaction().unwrap();
+    assert_eq!(
+        watched.classify_spend(&bob_commitment),
+        SpendKind::CurrentCommitment { commitment_number: 1, holder_broadcast: false }
+    );
+
+    // Bob's first commitment was revoked when he sent revoke_and_ack
+    let revoked = alice.counterparty_commitments.get(&0).unwrap().tx.clone();
+    assert_eq!(
+        watched.classify_spend(&revoked),
+        SpendKind::RevokedCommitment { commitment_number: 0, per_commitment_secret: bob.holder_per_commitment_secret(0) }
+    );
+
+    let closing_tx = cooperative_close_tx(alice.params.funding_outpoint);
+    assert_eq!(watched.classify_spend(&closing_tx), SpendKind::CooperativeClose);
+}
+
+#[test]
+fn test_watcher_polls_block_source() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    chain.mine_blocks(10);
+
+    let (mut alice, mut bob) = ready_channel_pair();
+    commitment_round(&mut alice, &mut bob).unwrap();
+    commitment_round(&mut bob, &mut alice).unwrap();
+
+    let watcher = ChainWatcher::new();
+    watcher.watch_channel(&alice);
+    assert_eq!(watcher.watched_outpoints(), vec![alice.params.funding_outpoint]);
+
+    // Bob broadcasts a commitment he has since revoked, a few blocks after we start polling
+    let revoked = alice.counterparty_commitments.get(&1).unwrap().tx.clone();
+    let current = bob.signed_holder_commitment_transaction().unwrap();
+    let events = rt
+        .block_on(async {
+            let mine = async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                chain.mine_blocks(2);
+                chain.mine_block(vec![revoked.clone()]);
+            };
+            let (events, _) = tokio::join!(poll_for_spends(&watcher, &chain, Network::Regtest, Duration::from_millis(5)), mine);
+            events
+        })
+        .unwrap();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].channel_id, alice.channel_id);
+    assert_eq!(events[0].height, 13);
+    assert_eq!(events[0].spending_tx, revoked);
+    assert_eq!(
+        events[0].kind,
+        SpendKind::RevokedCommitment { commitment_number: 1, per_commitment_secret: bob.holder_per_commitment_secret(1) }
+    );
+    assert!(watcher.watched_outpoints().is_empty());
+
+    // a reorg puts the channel back under watch, and segwit spends are accepted
+    let tip = chain.block_at(13).unwrap();
+    watcher.block_disconnected(&tip.header, 13);
+    assert_eq!(watcher.watched_outpoints(), vec![alice.params.funding_outpoint]);
+
+    let height = chain.mine_block(vec![current.clone()]);
+    watcher.block_connected(&chain.block_at(height).unwrap(), height);
+    let events = watcher.get_and_clear_pending_events();
+    assert_eq!(events[0].kind, SpendKind::CurrentCommitment { commitment_number: 2, holder_broadcast: false });
+    assert!(chain.block_at(height).unwrap().check_witness_commitment());
+}
+
+#[test]
+fn test_watcher_recognises_old_holder_commitments() {
+    let chain = MockChain::new();
+    chain.mine_blocks(10);
+
+    let (mut alice, mut bob) = ready_channel_pair();
+    let watcher = ChainWatcher::new();
+    watcher.watch_channel(&alice);
+
+    // Alice moves on to a new commitment, then broadcasts the one she revoked
+    let old_commitment = alice.signed_holder_commitment_transaction().unwrap();
+    commitment_round(&mut alice, &mut bob).unwrap();
+    watcher.watch_channel(&alice);
+
+    let height = chain.mine_block(vec![old_commitment]);
+    watcher.block_connected(&chain.block_at(height).unwrap(), height);
+    let events = watcher.get_and_clear_pending_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, SpendKind::RevokedHolderCommitment { commitment_number: 0 });
+}
@@ -0,0 +1,211 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::script::ScriptBuf;
+use bitcoin::{Transaction, Txid};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use internal::channel::{Channel, ChannelError};
+use lightning::chain::chaininterface::BroadcasterInterface;
+use lightning::chain::transaction::TransactionData;
+use lightning::chain::Listen;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Deref;
+use std::sync::Mutex;
+
+pub mod test;
+
+pub const HINT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+pub type JusticeHint = [u8; HINT_LEN];
+
+// the encrypted justice transactions waiting on one hint
+type Appointment = (JusticeHint, Vec<Vec<u8>>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TowerError {
+    DecryptionFailed,
+    InvalidJusticeTx,
+    Channel(ChannelError),
+}
+
+/// The tower indexes blobs by the first half of the revoked commitment's
+/// txid, so it learns nothing about a channel until that commitment confirms.
+pub fn justice_hint(commitment_txid: &Txid) -> JusticeHint {
+    commitment_txid.as_byte_array()[..HINT_LEN].try_into().unwrap()
+}
+
+// a hash of the whole txid, so the half in the hint can't be used to decrypt
+fn blob_key(commitment_txid: &Txid) -> [u8; 32] {
+    Sha256::hash(commitment_txid.as_byte_array()).to_byte_array()
+}
+
+/// Encrypts the justice transaction so it can only be read once the revoked
+/// commitment is seen on chain. The blob is a random nonce followed by the
+/// ChaCha20-Poly1305 ciphertext.
+pub fn encrypt_justice_tx(commitment_txid: &Txid, justice_tx: &Transaction) -> (JusticeHint, Vec<u8>) {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&blob_key(commitment_txid)));
+    let nonce: [u8; NONCE_LEN] = rand::random();
+
+    let mut blob = nonce.to_vec();
+    blob.extend(
+        cipher
+            .encrypt(Nonce::from_slice(&nonce), serialize(justice_tx).as_slice())
+            .expect("encryption never fails for in-memory buffers"),
+    );
+    (justice_hint(commitment_txid), blob)
+}
+
+pub fn decrypt_justice_tx(commitment_txid: &Txid, blob: &[u8]) -> Result<Transaction, TowerError> {
+    if blob.len() < NONCE_LEN {
+        return Err(TowerError::DecryptionFailed);
+    }
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&blob_key(commitment_txid)));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| TowerError::DecryptionFailed)?;
+    let justice_tx: Transaction = deserialize(&plaintext).map_err(|_| TowerError::InvalidJusticeTx)?;
+
+    // a blob that decrypts but doesn't spend the commitment is someone else's mistake
+    if !justice_tx.input.iter().any(|input| input.previous_output.txid == *commitment_txid) {
+        return Err(TowerError::InvalidJusticeTx);
+    }
+    Ok(justice_tx)
+}
+
+/// Holds encrypted justice transactions and broadcasts one as soon as the
+/// commitment it punishes shows up in a block.
+pub struct Watchtower<B: Deref>
+where
+    B::Target: BroadcasterInterface,
+{
+    appointments: Mutex<HashMap<JusticeHint, Vec<Vec<u8>>>>,
+    // appointments that fired and the height they fired at, kept around so
+    // a reorg can put them back
+    fired: Mutex<Vec<(u32, Appointment)>>,
+    broadcaster: B,
+    broadcast: Mutex<Vec<Transaction>>,
+}
+
+impl<B: Deref> Watchtower<B>
+where
+    B::Target: BroadcasterInterface,
+{
+    pub fn new(broadcaster: B) -> Watchtower<B> {
+        Watchtower {
+            appointments: Mutex::new(HashMap::new()),
+            fired: Mutex::new(Vec::new()),
+            broadcaster,
+            broadcast: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn add_appointment(&self, hint: JusticeHint, blob: Vec<u8>) {
+        self.appointments.lock().unwrap().entry(hint).or_default().push(blob);
+    }
+
+    pub fn appointment_count(&self) -> usize {
+        self.appointments.lock().unwrap().values().map(|blobs| blobs.len()).sum()
+    }
+
+    /// The justice transactions broadcast so far.
+    pub fn broadcast_justice_txs(&self) -> Vec<Transaction> {
+        self.broadcast.lock().unwrap().clone()
+    }
+}
+
+impl<B: Deref> Listen for Watchtower<B>
+where
+    B::Target: BroadcasterInterface,
+{
+    fn filtered_block_connected(&self, header: &Header, txdata: &TransactionData, height: u32) {
+        let mut appointments = self.appointments.lock().unwrap();
+
+        for (_, tx) in txdata.iter() {
+            let txid = tx.compute_txid();
+            let Some(blobs) = appointments.get(&justice_hint(&txid)) else { continue };
+
+            // hints are short enough to collide, so only blobs that decrypt count
+            let justice_txs: Vec<Transaction> = blobs.iter().filter_map(|blob| decrypt_justice_tx(&txid, blob).ok()).collect();
+            if justice_txs.is_empty() {
+                continue;
+            }
+
+            let hint = justice_hint(&txid);
+            if let Some(blobs) = appointments.remove(&hint) {
+                self.fired.lock().unwrap().push((height, (hint, blobs)));
+            }
+            for justice_tx in justice_txs {
+                self.broadcaster.broadcast_transactions(&[&justice_tx]);
+                self.broadcast.lock().unwrap().push(justice_tx);
+            }
+        }
+    }
+
+    // the revoked commitment may not make it back into the new chain, or
+    // the justice transaction with it, so the tower goes back to waiting
+    fn block_disconnected(&self, header: &Header, height: u32) {
+        let mut fired = self.fired.lock().unwrap();
+        let (reorged, still_fired): (Vec<_>, Vec<_>) = fired.drain(..).partition(|(fired_at, _)| *fired_at >= height);
+        *fired = still_fired;
+
+        let mut appointments = self.appointments.lock().unwrap();
+        for (_, (hint, blobs)) in reorged {
+            appointments.entry(hint).or_default().extend(blobs);
+        }
+    }
+}
+
+/// The channel side of the tower: after each `revoke_and_ack` it hands the
+/// tower a justice transaction for every newly revoked counterparty commitment.
+pub struct TowerClient<'a, B: Deref>
+where
+    B::Target: BroadcasterInterface,
+{
+    tower: &'a Watchtower<B>,
+    destination_script: ScriptBuf,
+    feerate_per_kw: u32,
+    registered: BTreeSet<([u8; 32], u64)>,
+}
+
+impl<'a, B: Deref> TowerClient<'a, B>
+where
+    B::Target: BroadcasterInterface,
+{
+    pub fn new(tower: &'a Watchtower<B>, destination_script: ScriptBuf, feerate_per_kw: u32) -> TowerClient<'a, B> {
+        TowerClient { tower, destination_script, feerate_per_kw, registered: BTreeSet::new() }
+    }
+
+    /// Registers every revoked state of `channel` the tower doesn't have yet
+    /// and returns how many were sent. States with nothing worth sweeping are
+    /// skipped, and like any state that failed to build, tried again next time.
+    pub fn register_revoked_states(&mut self, channel: &Channel) -> Result<usize, TowerError> {
+        let mut count = 0;
+
+        for commitment_number in channel.counterparty_per_commitment_secrets.keys() {
+            if self.registered.contains(&(channel.channel_id, *commitment_number)) {
+                continue;
+            }
+
+            let justice_tx = match channel.build_justice_transaction(*commitment_number, self.destination_script.clone(), self.feerate_per_kw) {
+                Ok(justice_tx) => justice_tx,
+                Err(ChannelError::JusticeBelowDust) => continue,
+                Err(e) => return Err(TowerError::Channel(e)),
+            };
+
+            let commitment_txid = channel.counterparty_commitments[commitment_number].tx.compute_txid();
+            let (hint, blob) = encrypt_justice_tx(&commitment_txid, &justice_tx);
+            self.tower.add_appointment(hint, blob);
+            self.registered.insert((channel.channel_id, *commitment_number));
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,155 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use internal::channel::ChannelError;
+use internal::key_utils::secp256k1pubkey_from_private_key;
+use bitcoin::script::ScriptBuf;
+use bitcoin::Network;
+use internal::chain::MockChain;
+use internal::watchtower::{decrypt_justice_tx, encrypt_justice_tx, justice_hint, TowerClient, TowerError, Watchtower, HINT_LEN};
+use internal::script_utils::p2wpkh_output_script;
+use internal::sign_utils::verify_p2wsh_signature;
+use internal::watcher::poll_blocks_until;
+use std::time::Duration;
+use lightning::chain::Listen;
+use internal::channel::commitment_round;
+use internal::channel::test::test_onion_packet;
+use internal::watcher::test::ready_channel_pair;
+
+#[test]
+fn test_watchtower_punishes_revoked_commitment() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let chain = MockChain::new();
+    chain.mine_blocks(10);
+
+    let tower = Watchtower::new(&chain);
+    let sweep_script = p2wpkh_output_script(bitcoin::PublicKey::new(secp256k1pubkey_from_private_key(&[0x09; 32])));
+    let mut client = TowerClient::new(&tower, sweep_script.clone(), 253);
+
+    // Alice hands the tower a justice transaction after every update
+    let (mut alice, mut bob) = ready_channel_pair();
+    let payment_preimage = [0x42; 32];
+    let payment_hash = Sha256::hash(&payment_preimage).to_byte_array();
+    let update_add = alice.add_htlc(400_000_000, payment_hash, 500, test_onion_packet()).unwrap();
+    bob.receive_update_add_htlc(&update_add).unwrap();
+    commitment_round(&mut alice, &mut bob).unwrap();
+    assert_eq!(client.register_revoked_states(&alice), Ok(1));
+
+    let update_fulfill = bob.fulfill_htlc(update_add.htlc_id, payment_preimage).unwrap();
+    alice.receive_update_fulfill_htlc(&update_fulfill).unwrap();
+    commitment_round(&mut bob, &mut alice).unwrap();
+    assert_eq!(client.register_revoked_states(&alice), Ok(1));
+    assert_eq!(client.register_revoked_states(&alice), Ok(0));
+    assert_eq!(tower.appointment_count(), 2);
+
+    // a state whose justice transaction didn't build isn't marked as sent:
+    // at a feerate nothing can pay for it's skipped every time, and once it
+    // builds it's registered
+    let other_tower = Watchtower::new(&chain);
+    let mut other_client = TowerClient::new(&other_tower, sweep_script.clone(), 10_000_000);
+    assert_eq!(other_client.register_revoked_states(&alice), Ok(0));
+    assert_eq!(other_client.register_revoked_states(&alice), Ok(0));
+    let mut other_client = TowerClient { feerate_per_kw: 253, ..other_client };
+    assert_eq!(other_client.register_revoked_states(&alice), Ok(2));
+    assert_eq!(other_tower.appointment_count(), 2);
+
+    // the current commitment isn't revoked, so there's nothing to build
+    let current = bob.holder_commitment().unwrap().commitment_number;
+    assert_eq!(alice.build_justice_transaction(current, sweep_script.clone(), 253), Err(ChannelError::NotRevoked(current)));
+
+    // Bob broadcasts the commitment that still had the HTLC in it
+    let revoked = alice.counterparty_commitments.get(&1).unwrap().clone();
+    assert_eq!(revoked.htlcs.len(), 1);
+
+    rt.block_on(async {
+        let mine = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            chain.mine_block(vec![revoked.tx.clone()]);
+        };
+        let (polled, _) = tokio::join!(
+            poll_blocks_until(&tower, &chain, Network::Regtest, Duration::from_millis(5), || !tower.broadcast_justice_txs().is_empty()),
+            mine
+        );
+        polled
+    })
+    .unwrap();
+
+    let justice_txs = tower.broadcast_justice_txs();
+    assert_eq!(justice_txs.len(), 1);
+    assert_eq!(tower.appointment_count(), 1);
+    assert_eq!(chain.mempool(), justice_txs);
+
+    // it sweeps Bob's to_local and the HTLC, each signed with the revocation key
+    let justice_tx = &justice_txs[0];
+    assert_eq!(justice_tx.input.len(), 2);
+    assert_eq!(justice_tx.output[0].script_pubkey, sweep_script);
+    let swept_sat: u64 = justice_tx
+        .input
+        .iter()
+        .map(|input| {
+            assert_eq!(input.previous_output.txid, revoked.tx.compute_txid());
+            revoked.tx.output[input.previous_output.vout as usize].value.to_sat()
+        })
+        .sum();
+    let fee_sat = swept_sat - justice_tx.output[0].value.to_sat();
+    // 253 sat/kw over a little under 1000 weight units
+    assert!(fee_sat > 200 && fee_sat < 253);
+
+    for (index, input) in justice_tx.input.iter().enumerate() {
+        let witness: Vec<&[u8]> = input.witness.iter().collect();
+        let witness_script = ScriptBuf::from_bytes(witness[2].to_vec());
+        let value = revoked.tx.output[input.previous_output.vout as usize].value.to_sat();
+        let signature = bitcoin::secp256k1::ecdsa::Signature::from_der(&witness[0][..witness[0].len() - 1]).unwrap();
+        assert!(verify_p2wsh_signature(
+            justice_tx,
+            index,
+            &witness_script,
+            value,
+            bitcoin::EcdsaSighashType::All,
+            &signature,
+            &revoked.keys.revocation_pubkey
+        ));
+    }
+
+    let height = chain.mine_block(Vec::new());
+    assert_eq!(chain.find_transaction(&justice_tx.compute_txid()), Some((height, 1)));
+
+    // if the block with the revoked commitment is reorged out, the tower
+    // waits for it again
+    let revoked_height = height - 1;
+    tower.block_disconnected(&chain.block_at(height).unwrap().header, height);
+    assert_eq!(tower.appointment_count(), 1);
+    tower.block_disconnected(&chain.block_at(revoked_height).unwrap().header, revoked_height);
+    assert_eq!(tower.appointment_count(), 2);
+
+    tower.block_connected(&chain.block_at(revoked_height).unwrap(), revoked_height);
+    assert_eq!(tower.broadcast_justice_txs(), vec![justice_tx.clone(), justice_tx.clone()]);
+    assert_eq!(tower.appointment_count(), 1);
+}
+
+#[test]
+fn test_justice_blob_encryption() {
+    let (mut alice, mut bob) = ready_channel_pair();
+    commitment_round(&mut alice, &mut bob).unwrap();
+
+    let revoked_txid = alice.counterparty_commitments.get(&0).unwrap().tx.compute_txid();
+    let justice_tx = alice.build_justice_transaction(0, ScriptBuf::new(), 253).unwrap();
+    let (hint, blob) = encrypt_justice_tx(&revoked_txid, &justice_tx);
+
+    assert_eq!(hint, justice_hint(&revoked_txid));
+    assert_eq!(hint[..], revoked_txid.as_byte_array()[..HINT_LEN]);
+    assert_eq!(decrypt_justice_tx(&revoked_txid, &blob), Ok(justice_tx.clone()));
+
+    let other_txid = alice.counterparty_commitments.get(&1).unwrap().tx.compute_txid();
+    assert_eq!(decrypt_justice_tx(&other_txid, &blob), Err(TowerError::DecryptionFailed));
+
+    let mut tampered = blob.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 0x01;
+    assert_eq!(decrypt_justice_tx(&revoked_txid, &tampered), Err(TowerError::DecryptionFailed));
+
+    // a blob for a different commitment encrypted under this txid is rejected
+    let (_, misdirected) = encrypt_justice_tx(&other_txid, &justice_tx);
+    assert_eq!(decrypt_justice_tx(&other_txid, &misdirected), Err(TowerError::InvalidJusticeTx));
+}
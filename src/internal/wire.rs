@@ -0,0 +1,1001 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::constants::ChainHash;
+use bitcoin::hashes::Hash;
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Txid;
+
+pub mod test;
+
+// BOLT 1 / BOLT 2 message types
+pub const OPEN_CHANNEL_TYPE: u16 = 32;
+pub const ACCEPT_CHANNEL_TYPE: u16 = 33;
+pub const FUNDING_CREATED_TYPE: u16 = 34;
+pub const FUNDING_SIGNED_TYPE: u16 = 35;
+pub const CHANNEL_READY_TYPE: u16 = 36;
+pub const UPDATE_ADD_HTLC_TYPE: u16 = 128;
+pub const UPDATE_FULFILL_HTLC_TYPE: u16 = 130;
+pub const UPDATE_FAIL_HTLC_TYPE: u16 = 131;
+pub const COMMITMENT_SIGNED_TYPE: u16 = 132;
+pub const REVOKE_AND_ACK_TYPE: u16 = 133;
+
+// BOLT 7 gossip message types
+pub const CHANNEL_ANNOUNCEMENT_TYPE: u16 = 256;
+pub const NODE_ANNOUNCEMENT_TYPE: u16 = 257;
+pub const CHANNEL_UPDATE_TYPE: u16 = 258;
+
+// BOLT 4: the routing info in every onion is a fixed 1300 bytes
+pub const ONION_HOP_DATA_LEN: usize = 1300;
+
+// update_fail_htlc carries its reason behind a u16 length prefix
+pub const MAX_FAILURE_REASON_LEN: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    ShortRead,
+    InvalidValue,
+    NonMinimalBigSize,
+    UnknownMessageType(u16),
+    UnknownRequiredTlv(u64),
+    UnorderedTlvStream,
+}
+
+//
+// Primitive encoding
+//
+
+pub fn write_u8(w: &mut Vec<u8>, value: u8) {
+    w.push(value);
+}
+
+pub fn write_u16(w: &mut Vec<u8>, value: u16) {
+    w.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_u32(w: &mut Vec<u8>, value: u32) {
+    w.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_u64(w: &mut Vec<u8>, value: u64) {
+    w.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_bigsize(w: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => w.push(value as u8),
+        0xfd..=0xffff => {
+            w.push(0xfd);
+            write_u16(w, value as u16);
+        }
+        0x10000..=0xffffffff => {
+            w.push(0xfe);
+            write_u32(w, value as u32);
+        }
+        _ => {
+            w.push(0xff);
+            write_u64(w, value);
+        }
+    }
+}
+
+// truncated integers (tu16/tu32/tu64) drop their leading zero bytes and are
+// only ever used as the whole value of a TLV record
+pub fn write_tu64(w: &mut Vec<u8>, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = (value.leading_zeros() / 8) as usize;
+    w.extend_from_slice(&bytes[skip..]);
+}
+
+pub fn read_tu64(bytes: &[u8]) -> Result<u64, DecodeError> {
+    if bytes.len() > 8 || bytes.first() == Some(&0) {
+        return Err(DecodeError::InvalidValue);
+    }
+    Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+pub fn write_pubkey(w: &mut Vec<u8>, pubkey: &PublicKey) {
+    w.extend_from_slice(&pubkey.serialize());
+}
+
+pub fn write_signature(w: &mut Vec<u8>, signature: &Signature) {
+    w.extend_from_slice(&signature.serialize_compact());
+}
+
+/// Reads BOLT 1 fundamental types out of a message buffer.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.remaining() < len {
+            return Err(DecodeError::ShortRead);
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        bytes
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_bigsize(&mut self) -> Result<u64, DecodeError> {
+        // every value must use the shortest possible encoding
+        match self.read_u8()? {
+            0xff => {
+                let value = self.read_u64()?;
+                if value <= 0xffffffff {
+                    return Err(DecodeError::NonMinimalBigSize);
+                }
+                Ok(value)
+            }
+            0xfe => {
+                let value = self.read_u32()? as u64;
+                if value <= 0xffff {
+                    return Err(DecodeError::NonMinimalBigSize);
+                }
+                Ok(value)
+            }
+            0xfd => {
+                let value = self.read_u16()? as u64;
+                if value < 0xfd {
+                    return Err(DecodeError::NonMinimalBigSize);
+                }
+                Ok(value)
+            }
+            value => Ok(value as u64),
+        }
+    }
+
+    pub fn read_pubkey(&mut self) -> Result<PublicKey, DecodeError> {
+        PublicKey::from_slice(self.read_bytes(33)?).map_err(|_| DecodeError::InvalidValue)
+    }
+
+    pub fn read_signature(&mut self) -> Result<Signature, DecodeError> {
+        Signature::from_compact(self.read_bytes(64)?).map_err(|_| DecodeError::InvalidValue)
+    }
+}
+
+//
+// TLV streams
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlvRecord {
+    pub tlv_type: u64,
+    pub value: Vec<u8>,
+}
+
+pub fn write_tlv_stream(w: &mut Vec<u8>, records: &[TlvRecord]) {
+    for record in records {
+        write_bigsize(w, record.tlv_type);
+        write_bigsize(w, record.value.len() as u64);
+        w.extend_from_slice(&record.value);
+    }
+}
+
+/// Reads a TLV stream until the end of the message. Types must be strictly
+/// increasing, and any even type not listed in `known_types` is rejected
+/// ("it's OK to be odd").
+pub fn read_tlv_stream(r: &mut Reader, known_types: &[u64]) -> Result<Vec<TlvRecord>, DecodeError> {
+    let mut records: Vec<TlvRecord> = Vec::new();
+
+    while r.remaining() > 0 {
+        let tlv_type = r.read_bigsize()?;
+        let len = r.read_bigsize()?;
+        let value = r.read_bytes(len as usize)?.to_vec();
+
+        if let Some(last) = records.last()
+            && last.tlv_type >= tlv_type
+        {
+            return Err(DecodeError::UnorderedTlvStream);
+        }
+        if tlv_type % 2 == 0 && !known_types.contains(&tlv_type) {
+            return Err(DecodeError::UnknownRequiredTlv(tlv_type));
+        }
+
+        records.push(TlvRecord { tlv_type, value });
+    }
+
+    Ok(records)
+}
+
+pub fn find_tlv(records: &[TlvRecord], tlv_type: u64) -> Option<&[u8]> {
+    records
+        .iter()
+        .find(|record| record.tlv_type == tlv_type)
+        .map(|record| record.value.as_slice())
+}
+
+// optional shutdown script (type 0) and channel type (type 1) shared by open/accept_channel
+fn write_channel_open_tlvs(w: &mut Vec<u8>, shutdown_scriptpubkey: &Option<ScriptBuf>, channel_type: &Option<Vec<u8>>) {
+    let mut records = Vec::new();
+    if let Some(script) = shutdown_scriptpubkey {
+        records.push(TlvRecord { tlv_type: 0, value: script.to_bytes() });
+    }
+    if let Some(channel_type) = channel_type {
+        records.push(TlvRecord { tlv_type: 1, value: channel_type.clone() });
+    }
+    write_tlv_stream(w, &records);
+}
+
+fn read_channel_open_tlvs(r: &mut Reader) -> Result<(Option<ScriptBuf>, Option<Vec<u8>>), DecodeError> {
+    let records = read_tlv_stream(r, &[0])?;
+    let shutdown_scriptpubkey = find_tlv(&records, 0).map(|script| ScriptBuf::from_bytes(script.to_vec()));
+    let channel_type = find_tlv(&records, 1).map(|channel_type| channel_type.to_vec());
+    Ok((shutdown_scriptpubkey, channel_type))
+}
+
+//
+// Messages
+//
+
+/// A Lightning message: a 2-byte type followed by its payload.
+pub trait WireMessage: Sized {
+    const TYPE: u16;
+
+    fn write(&self, w: &mut Vec<u8>);
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError>;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Vec::new();
+        write_u16(&mut w, Self::TYPE);
+        self.write(&mut w);
+        w
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let message_type = r.read_u16()?;
+        if message_type != Self::TYPE {
+            return Err(DecodeError::UnknownMessageType(message_type));
+        }
+        Self::read(&mut r)
+    }
+}
+
+/// Returns the type of an encoded message without decoding it.
+pub fn peek_message_type(bytes: &[u8]) -> Result<u16, DecodeError> {
+    Reader::new(bytes).read_u16()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenChannel {
+    pub chain_hash: ChainHash,
+    pub temporary_channel_id: [u8; 32],
+    pub funding_satoshis: u64,
+    pub push_msat: u64,
+    pub dust_limit_satoshis: u64,
+    pub max_htlc_value_in_flight_msat: u64,
+    pub channel_reserve_satoshis: u64,
+    pub htlc_minimum_msat: u64,
+    pub feerate_per_kw: u32,
+    pub to_self_delay: u16,
+    pub max_accepted_htlcs: u16,
+    pub funding_pubkey: PublicKey,
+    pub revocation_basepoint: PublicKey,
+    pub payment_basepoint: PublicKey,
+    pub delayed_payment_basepoint: PublicKey,
+    pub htlc_basepoint: PublicKey,
+    pub first_per_commitment_point: PublicKey,
+    pub channel_flags: u8,
+    pub shutdown_scriptpubkey: Option<ScriptBuf>,
+    pub channel_type: Option<Vec<u8>>,
+}
+
+impl WireMessage for OpenChannel {
+    const TYPE: u16 = OPEN_CHANNEL_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(self.chain_hash.as_bytes());
+        w.extend_from_slice(&self.temporary_channel_id);
+        write_u64(w, self.funding_satoshis);
+        write_u64(w, self.push_msat);
+        write_u64(w, self.dust_limit_satoshis);
+        write_u64(w, self.max_htlc_value_in_flight_msat);
+        write_u64(w, self.channel_reserve_satoshis);
+        write_u64(w, self.htlc_minimum_msat);
+        write_u32(w, self.feerate_per_kw);
+        write_u16(w, self.to_self_delay);
+        write_u16(w, self.max_accepted_htlcs);
+        write_pubkey(w, &self.funding_pubkey);
+        write_pubkey(w, &self.revocation_basepoint);
+        write_pubkey(w, &self.payment_basepoint);
+        write_pubkey(w, &self.delayed_payment_basepoint);
+        write_pubkey(w, &self.htlc_basepoint);
+        write_pubkey(w, &self.first_per_commitment_point);
+        write_u8(w, self.channel_flags);
+        write_channel_open_tlvs(w, &self.shutdown_scriptpubkey, &self.channel_type);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let chain_hash = ChainHash::from(r.read_array::<32>()?);
+        let temporary_channel_id = r.read_array()?;
+        let funding_satoshis = r.read_u64()?;
+        let push_msat = r.read_u64()?;
+        let dust_limit_satoshis = r.read_u64()?;
+        let max_htlc_value_in_flight_msat = r.read_u64()?;
+        let channel_reserve_satoshis = r.read_u64()?;
+        let htlc_minimum_msat = r.read_u64()?;
+        let feerate_per_kw = r.read_u32()?;
+        let to_self_delay = r.read_u16()?;
+        let max_accepted_htlcs = r.read_u16()?;
+        let funding_pubkey = r.read_pubkey()?;
+        let revocation_basepoint = r.read_pubkey()?;
+        let payment_basepoint = r.read_pubkey()?;
+        let delayed_payment_basepoint = r.read_pubkey()?;
+        let htlc_basepoint = r.read_pubkey()?;
+        let first_per_commitment_point = r.read_pubkey()?;
+        let channel_flags = r.read_u8()?;
+        let (shutdown_scriptpubkey, channel_type) = read_channel_open_tlvs(r)?;
+
+        Ok(OpenChannel {
+            chain_hash,
+            temporary_channel_id,
+            funding_satoshis,
+            push_msat,
+            dust_limit_satoshis,
+            max_htlc_value_in_flight_msat,
+            channel_reserve_satoshis,
+            htlc_minimum_msat,
+            feerate_per_kw,
+            to_self_delay,
+            max_accepted_htlcs,
+            funding_pubkey,
+            revocation_basepoint,
+            payment_basepoint,
+            delayed_payment_basepoint,
+            htlc_basepoint,
+            first_per_commitment_point,
+            channel_flags,
+            shutdown_scriptpubkey,
+            channel_type,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptChannel {
+    pub temporary_channel_id: [u8; 32],
+    pub dust_limit_satoshis: u64,
+    pub max_htlc_value_in_flight_msat: u64,
+    pub channel_reserve_satoshis: u64,
+    pub htlc_minimum_msat: u64,
+    pub minimum_depth: u32,
+    pub to_self_delay: u16,
+    pub max_accepted_htlcs: u16,
+    pub funding_pubkey: PublicKey,
+    pub revocation_basepoint: PublicKey,
+    pub payment_basepoint: PublicKey,
+    pub delayed_payment_basepoint: PublicKey,
+    pub htlc_basepoint: PublicKey,
+    pub first_per_commitment_point: PublicKey,
+    pub shutdown_scriptpubkey: Option<ScriptBuf>,
+    pub channel_type: Option<Vec<u8>>,
+}
+
+impl WireMessage for AcceptChannel {
+    const TYPE: u16 = ACCEPT_CHANNEL_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.temporary_channel_id);
+        write_u64(w, self.dust_limit_satoshis);
+        write_u64(w, self.max_htlc_value_in_flight_msat);
+        write_u64(w, self.channel_reserve_satoshis);
+        write_u64(w, self.htlc_minimum_msat);
+        write_u32(w, self.minimum_depth);
+        write_u16(w, self.to_self_delay);
+        write_u16(w, self.max_accepted_htlcs);
+        write_pubkey(w, &self.funding_pubkey);
+        write_pubkey(w, &self.revocation_basepoint);
+        write_pubkey(w, &self.payment_basepoint);
+        write_pubkey(w, &self.delayed_payment_basepoint);
+        write_pubkey(w, &self.htlc_basepoint);
+        write_pubkey(w, &self.first_per_commitment_point);
+        write_channel_open_tlvs(w, &self.shutdown_scriptpubkey, &self.channel_type);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let temporary_channel_id = r.read_array()?;
+        let dust_limit_satoshis = r.read_u64()?;
+        let max_htlc_value_in_flight_msat = r.read_u64()?;
+        let channel_reserve_satoshis = r.read_u64()?;
+        let htlc_minimum_msat = r.read_u64()?;
+        let minimum_depth = r.read_u32()?;
+        let to_self_delay = r.read_u16()?;
+        let max_accepted_htlcs = r.read_u16()?;
+        let funding_pubkey = r.read_pubkey()?;
+        let revocation_basepoint = r.read_pubkey()?;
+        let payment_basepoint = r.read_pubkey()?;
+        let delayed_payment_basepoint = r.read_pubkey()?;
+        let htlc_basepoint = r.read_pubkey()?;
+        let first_per_commitment_point = r.read_pubkey()?;
+        let (shutdown_scriptpubkey, channel_type) = read_channel_open_tlvs(r)?;
+
+        Ok(AcceptChannel {
+            temporary_channel_id,
+            dust_limit_satoshis,
+            max_htlc_value_in_flight_msat,
+            channel_reserve_satoshis,
+            htlc_minimum_msat,
+            minimum_depth,
+            to_self_delay,
+            max_accepted_htlcs,
+            funding_pubkey,
+            revocation_basepoint,
+            payment_basepoint,
+            delayed_payment_basepoint,
+            htlc_basepoint,
+            first_per_commitment_point,
+            shutdown_scriptpubkey,
+            channel_type,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingCreated {
+    pub temporary_channel_id: [u8; 32],
+    pub funding_txid: Txid,
+    pub funding_output_index: u16,
+    pub signature: Signature,
+}
+
+impl WireMessage for FundingCreated {
+    const TYPE: u16 = FUNDING_CREATED_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.temporary_channel_id);
+        w.extend_from_slice(&self.funding_txid.to_byte_array());
+        write_u16(w, self.funding_output_index);
+        write_signature(w, &self.signature);
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let temporary_channel_id = r.read_array()?;
+        let funding_txid = Txid::from_byte_array(r.read_array()?);
+        let funding_output_index = r.read_u16()?;
+        let signature = r.read_signature()?;
+        read_tlv_stream(r, &[])?;
+
+        Ok(FundingCreated {
+            temporary_channel_id,
+            funding_txid,
+            funding_output_index,
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingSigned {
+    pub channel_id: [u8; 32],
+    pub signature: Signature,
+}
+
+impl WireMessage for FundingSigned {
+    const TYPE: u16 = FUNDING_SIGNED_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_signature(w, &self.signature);
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let signature = r.read_signature()?;
+        read_tlv_stream(r, &[])?;
+
+        Ok(FundingSigned { channel_id, signature })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelReady {
+    pub channel_id: [u8; 32],
+    pub next_per_commitment_point: PublicKey,
+    pub short_channel_id_alias: Option<u64>,
+}
+
+impl WireMessage for ChannelReady {
+    const TYPE: u16 = CHANNEL_READY_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_pubkey(w, &self.next_per_commitment_point);
+
+        let mut records = Vec::new();
+        if let Some(alias) = self.short_channel_id_alias {
+            records.push(TlvRecord { tlv_type: 1, value: alias.to_be_bytes().to_vec() });
+        }
+        write_tlv_stream(w, &records);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let next_per_commitment_point = r.read_pubkey()?;
+
+        let records = read_tlv_stream(r, &[])?;
+        let short_channel_id_alias = match find_tlv(&records, 1) {
+            Some(value) => Some(u64::from_be_bytes(value.try_into().map_err(|_| DecodeError::InvalidValue)?)),
+            None => None,
+        };
+
+        Ok(ChannelReady {
+            channel_id,
+            next_per_commitment_point,
+            short_channel_id_alias,
+        })
+    }
+}
+
+/// The BOLT 4 onion carried in `update_add_htlc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnionPacket {
+    pub version: u8,
+    pub public_key: PublicKey,
+    pub hop_data: [u8; ONION_HOP_DATA_LEN],
+    pub hmac: [u8; 32],
+}
+
+impl OnionPacket {
+    pub fn write(&self, w: &mut Vec<u8>) {
+        write_u8(w, self.version);
+        write_pubkey(w, &self.public_key);
+        w.extend_from_slice(&self.hop_data);
+        w.extend_from_slice(&self.hmac);
+    }
+
+    pub fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(OnionPacket {
+            version: r.read_u8()?,
+            public_key: r.read_pubkey()?,
+            hop_data: r.read_array()?,
+            hmac: r.read_array()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateAddHtlc {
+    pub channel_id: [u8; 32],
+    pub htlc_id: u64,
+    pub amount_msat: u64,
+    pub payment_hash: [u8; 32],
+    pub cltv_expiry: u32,
+    pub onion_routing_packet: OnionPacket,
+    pub blinding_point: Option<PublicKey>,
+    pub skimmed_fee_msat: Option<u64>,
+}
+
+impl WireMessage for UpdateAddHtlc {
+    const TYPE: u16 = UPDATE_ADD_HTLC_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_u64(w, self.htlc_id);
+        write_u64(w, self.amount_msat);
+        w.extend_from_slice(&self.payment_hash);
+        write_u32(w, self.cltv_expiry);
+        self.onion_routing_packet.write(w);
+
+        let mut records = Vec::new();
+        if let Some(blinding_point) = self.blinding_point {
+            records.push(TlvRecord { tlv_type: 0, value: blinding_point.serialize().to_vec() });
+        }
+        if let Some(skimmed_fee_msat) = self.skimmed_fee_msat {
+            records.push(TlvRecord { tlv_type: 65537, value: skimmed_fee_msat.to_be_bytes().to_vec() });
+        }
+        write_tlv_stream(w, &records);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let htlc_id = r.read_u64()?;
+        let amount_msat = r.read_u64()?;
+        let payment_hash = r.read_array()?;
+        let cltv_expiry = r.read_u32()?;
+        let onion_routing_packet = OnionPacket::read(r)?;
+
+        let records = read_tlv_stream(r, &[0])?;
+        let blinding_point = match find_tlv(&records, 0) {
+            Some(value) => Some(Reader::new(value).read_pubkey()?),
+            None => None,
+        };
+        let skimmed_fee_msat = match find_tlv(&records, 65537) {
+            Some(value) => Some(u64::from_be_bytes(value.try_into().map_err(|_| DecodeError::InvalidValue)?)),
+            None => None,
+        };
+
+        Ok(UpdateAddHtlc {
+            channel_id,
+            htlc_id,
+            amount_msat,
+            payment_hash,
+            cltv_expiry,
+            onion_routing_packet,
+            blinding_point,
+            skimmed_fee_msat,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateFulfillHtlc {
+    pub channel_id: [u8; 32],
+    pub htlc_id: u64,
+    pub payment_preimage: [u8; 32],
+}
+
+impl WireMessage for UpdateFulfillHtlc {
+    const TYPE: u16 = UPDATE_FULFILL_HTLC_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_u64(w, self.htlc_id);
+        w.extend_from_slice(&self.payment_preimage);
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let htlc_id = r.read_u64()?;
+        let payment_preimage = r.read_array()?;
+        read_tlv_stream(r, &[])?;
+
+        Ok(UpdateFulfillHtlc { channel_id, htlc_id, payment_preimage })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateFailHtlc {
+    pub channel_id: [u8; 32],
+    pub htlc_id: u64,
+    // the encrypted failure onion, opaque to everyone but the payer
+    pub reason: Vec<u8>,
+}
+
+impl WireMessage for UpdateFailHtlc {
+    const TYPE: u16 = UPDATE_FAIL_HTLC_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_u64(w, self.htlc_id);
+        let len = u16::try_from(self.reason.len()).expect("failure reason longer than MAX_FAILURE_REASON_LEN");
+        write_u16(w, len);
+        w.extend_from_slice(&self.reason);
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let htlc_id = r.read_u64()?;
+        let len = r.read_u16()?;
+        let reason = r.read_bytes(len as usize)?.to_vec();
+        read_tlv_stream(r, &[])?;
+
+        Ok(UpdateFailHtlc { channel_id, htlc_id, reason })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitmentSigned {
+    pub channel_id: [u8; 32],
+    pub signature: Signature,
+    // one signature per HTLC output, in commitment output order
+    pub htlc_signatures: Vec<Signature>,
+}
+
+impl WireMessage for CommitmentSigned {
+    const TYPE: u16 = COMMITMENT_SIGNED_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        write_signature(w, &self.signature);
+        write_u16(w, self.htlc_signatures.len() as u16);
+        for signature in self.htlc_signatures.iter() {
+            write_signature(w, signature);
+        }
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let signature = r.read_signature()?;
+        let num_htlcs = r.read_u16()?;
+        let htlc_signatures = (0..num_htlcs)
+            .map(|_| r.read_signature())
+            .collect::<Result<Vec<_>, _>>()?;
+        read_tlv_stream(r, &[])?;
+
+        Ok(CommitmentSigned { channel_id, signature, htlc_signatures })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeAndAck {
+    pub channel_id: [u8; 32],
+    pub per_commitment_secret: [u8; 32],
+    pub next_per_commitment_point: PublicKey,
+}
+
+impl WireMessage for RevokeAndAck {
+    const TYPE: u16 = REVOKE_AND_ACK_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&self.channel_id);
+        w.extend_from_slice(&self.per_commitment_secret);
+        write_pubkey(w, &self.next_per_commitment_point);
+        write_tlv_stream(w, &[]);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let channel_id = r.read_array()?;
+        let per_commitment_secret = r.read_array()?;
+        let next_per_commitment_point = r.read_pubkey()?;
+        read_tlv_stream(r, &[])?;
+
+        Ok(RevokeAndAck { channel_id, per_commitment_secret, next_per_commitment_point })
+    }
+}
+
+//
+// Gossip messages
+//
+// Every gossip message is signed over the double-SHA256 of everything after
+// its signatures, so the signed part is kept separately as `contents`.
+//
+
+fn write_features(w: &mut Vec<u8>, features: &[u8]) {
+    write_u16(w, features.len() as u16);
+    w.extend_from_slice(features);
+}
+
+fn read_features(r: &mut Reader) -> Result<Vec<u8>, DecodeError> {
+    let len = r.read_u16()?;
+    Ok(r.read_bytes(len as usize)?.to_vec())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedChannelAnnouncement {
+    pub features: Vec<u8>,
+    pub chain_hash: ChainHash,
+    pub short_channel_id: u64,
+    // node_id_1 is the lexicographically lesser of the two node ids
+    pub node_id_1: PublicKey,
+    pub node_id_2: PublicKey,
+    pub bitcoin_key_1: PublicKey,
+    pub bitcoin_key_2: PublicKey,
+    pub excess_data: Vec<u8>,
+}
+
+impl UnsignedChannelAnnouncement {
+    pub fn write(&self, w: &mut Vec<u8>) {
+        write_features(w, &self.features);
+        w.extend_from_slice(self.chain_hash.as_bytes());
+        write_u64(w, self.short_channel_id);
+        write_pubkey(w, &self.node_id_1);
+        write_pubkey(w, &self.node_id_2);
+        write_pubkey(w, &self.bitcoin_key_1);
+        write_pubkey(w, &self.bitcoin_key_2);
+        w.extend_from_slice(&self.excess_data);
+    }
+
+    pub fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(UnsignedChannelAnnouncement {
+            features: read_features(r)?,
+            chain_hash: ChainHash::from(r.read_array::<32>()?),
+            short_channel_id: r.read_u64()?,
+            node_id_1: r.read_pubkey()?,
+            node_id_2: r.read_pubkey()?,
+            bitcoin_key_1: r.read_pubkey()?,
+            bitcoin_key_2: r.read_pubkey()?,
+            excess_data: r.read_remaining().to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelAnnouncement {
+    pub node_signature_1: Signature,
+    pub node_signature_2: Signature,
+    pub bitcoin_signature_1: Signature,
+    pub bitcoin_signature_2: Signature,
+    pub contents: UnsignedChannelAnnouncement,
+}
+
+impl WireMessage for ChannelAnnouncement {
+    const TYPE: u16 = CHANNEL_ANNOUNCEMENT_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        write_signature(w, &self.node_signature_1);
+        write_signature(w, &self.node_signature_2);
+        write_signature(w, &self.bitcoin_signature_1);
+        write_signature(w, &self.bitcoin_signature_2);
+        self.contents.write(w);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(ChannelAnnouncement {
+            node_signature_1: r.read_signature()?,
+            node_signature_2: r.read_signature()?,
+            bitcoin_signature_1: r.read_signature()?,
+            bitcoin_signature_2: r.read_signature()?,
+            contents: UnsignedChannelAnnouncement::read(r)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedNodeAnnouncement {
+    pub features: Vec<u8>,
+    pub timestamp: u32,
+    pub node_id: PublicKey,
+    pub rgb: [u8; 3],
+    pub alias: [u8; 32],
+    // kept encoded, we never connect to announced addresses
+    pub addresses: Vec<u8>,
+    pub excess_data: Vec<u8>,
+}
+
+impl UnsignedNodeAnnouncement {
+    pub fn write(&self, w: &mut Vec<u8>) {
+        write_features(w, &self.features);
+        write_u32(w, self.timestamp);
+        write_pubkey(w, &self.node_id);
+        w.extend_from_slice(&self.rgb);
+        w.extend_from_slice(&self.alias);
+        write_u16(w, self.addresses.len() as u16);
+        w.extend_from_slice(&self.addresses);
+        w.extend_from_slice(&self.excess_data);
+    }
+
+    pub fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        let features = read_features(r)?;
+        let timestamp = r.read_u32()?;
+        let node_id = r.read_pubkey()?;
+        let rgb = r.read_array()?;
+        let alias = r.read_array()?;
+        let addrlen = r.read_u16()?;
+        let addresses = r.read_bytes(addrlen as usize)?.to_vec();
+
+        Ok(UnsignedNodeAnnouncement {
+            features,
+            timestamp,
+            node_id,
+            rgb,
+            alias,
+            addresses,
+            excess_data: r.read_remaining().to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeAnnouncement {
+    pub signature: Signature,
+    pub contents: UnsignedNodeAnnouncement,
+}
+
+impl WireMessage for NodeAnnouncement {
+    const TYPE: u16 = NODE_ANNOUNCEMENT_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        write_signature(w, &self.signature);
+        self.contents.write(w);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(NodeAnnouncement {
+            signature: r.read_signature()?,
+            contents: UnsignedNodeAnnouncement::read(r)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedChannelUpdate {
+    pub chain_hash: ChainHash,
+    pub short_channel_id: u64,
+    pub timestamp: u32,
+    // bit 0 must be set: htlc_maximum_msat is always present
+    pub message_flags: u8,
+    // bit 0 is the direction (set if sent by node_id_2), bit 1 disables the channel
+    pub channel_flags: u8,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: u64,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub htlc_maximum_msat: u64,
+    pub excess_data: Vec<u8>,
+}
+
+impl UnsignedChannelUpdate {
+    pub fn write(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(self.chain_hash.as_bytes());
+        write_u64(w, self.short_channel_id);
+        write_u32(w, self.timestamp);
+        write_u8(w, self.message_flags);
+        write_u8(w, self.channel_flags);
+        write_u16(w, self.cltv_expiry_delta);
+        write_u64(w, self.htlc_minimum_msat);
+        write_u32(w, self.fee_base_msat);
+        write_u32(w, self.fee_proportional_millionths);
+        write_u64(w, self.htlc_maximum_msat);
+        w.extend_from_slice(&self.excess_data);
+    }
+
+    pub fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(UnsignedChannelUpdate {
+            chain_hash: ChainHash::from(r.read_array::<32>()?),
+            short_channel_id: r.read_u64()?,
+            timestamp: r.read_u32()?,
+            message_flags: r.read_u8()?,
+            channel_flags: r.read_u8()?,
+            cltv_expiry_delta: r.read_u16()?,
+            htlc_minimum_msat: r.read_u64()?,
+            fee_base_msat: r.read_u32()?,
+            fee_proportional_millionths: r.read_u32()?,
+            htlc_maximum_msat: r.read_u64()?,
+            excess_data: r.read_remaining().to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelUpdate {
+    pub signature: Signature,
+    pub contents: UnsignedChannelUpdate,
+}
+
+impl WireMessage for ChannelUpdate {
+    const TYPE: u16 = CHANNEL_UPDATE_TYPE;
+
+    fn write(&self, w: &mut Vec<u8>) {
+        write_signature(w, &self.signature);
+        self.contents.write(w);
+    }
+
+    fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(ChannelUpdate {
+            signature: r.read_signature()?,
+            contents: UnsignedChannelUpdate::read(r)?,
+        })
+    }
+}
@@ -0,0 +1,339 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hash_types::Txid;
+use internal::key_utils::{secp256k1_private_key, secp256k1pubkey_from_private_key};
+use internal::wire::{self, AcceptChannel, ChannelReady, CommitmentSigned, DecodeError, FundingCreated, FundingSigned, OpenChannel,
+                     Reader, RevokeAndAck, UpdateAddHtlc, UpdateFailHtlc, UpdateFulfillHtlc, WireMessage};
+use bitcoin::constants::ChainHash;
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::Network;
+use lightning::ln::features::ChannelTypeFeatures;
+use lightning::ln::msgs;
+use lightning::ln::types::ChannelId;
+use lightning::util::ser::{Readable, Writeable};
+use internal::channel::test::{channel_pair, test_onion_packet};
+
+pub fn test_signature() -> bitcoin::secp256k1::ecdsa::Signature {
+    let secp = Secp256k1::new();
+    let message = Message::from_digest([0x42; 32]);
+    secp.sign_ecdsa(&message, &secp256k1_private_key(&[0x01; 32]))
+}
+
+// strips the 2-byte message type so the payload can be compared with LDK's encoding
+pub fn payload<M: WireMessage>(msg: &M) -> Vec<u8> {
+    msg.encode()[2..].to_vec()
+}
+
+#[test]
+fn test_bigsize_encoding() {
+    let vectors: [(u64, &str); 7] = [
+        (0, "00"),
+        (252, "fc"),
+        (253, "fd00fd"),
+        (65535, "fdffff"),
+        (65536, "fe00010000"),
+        (4294967295, "feffffffff"),
+        (4294967296, "ff0000000100000000"),
+    ];
+
+    for (value, expected) in vectors {
+        let mut w = Vec::new();
+        wire::write_bigsize(&mut w, value);
+        assert_eq!(hex::encode(&w), expected);
+
+        let bytes = hex::decode(expected).unwrap();
+        assert_eq!(Reader::new(&bytes).read_bigsize(), Ok(value));
+    }
+
+    // non-canonical encodings are rejected
+    for non_minimal in ["fd00fc", "fe0000ffff", "ff00000000ffffffff"] {
+        let bytes = hex::decode(non_minimal).unwrap();
+        assert_eq!(Reader::new(&bytes).read_bigsize(), Err(DecodeError::NonMinimalBigSize));
+    }
+}
+
+#[test]
+fn test_tlv_stream_rules() {
+    // unknown odd types are ignored, unknown even types are rejected
+    let bytes = hex::decode("0301ff").unwrap();
+    assert_eq!(wire::read_tlv_stream(&mut Reader::new(&bytes), &[]).unwrap().len(), 1);
+
+    let bytes = hex::decode("0201ff").unwrap();
+    assert_eq!(wire::read_tlv_stream(&mut Reader::new(&bytes), &[]), Err(DecodeError::UnknownRequiredTlv(2)));
+
+    // types must be strictly increasing
+    let bytes = hex::decode("03010105010103010a").unwrap();
+    assert_eq!(wire::read_tlv_stream(&mut Reader::new(&bytes), &[]), Err(DecodeError::UnorderedTlvStream));
+
+    let bytes = hex::decode("0302ff").unwrap();
+    assert_eq!(wire::read_tlv_stream(&mut Reader::new(&bytes), &[]), Err(DecodeError::ShortRead));
+}
+
+#[test]
+fn test_open_and_accept_channel_match_ldk() {
+    let pubkey = |byte: u8| secp256k1pubkey_from_private_key(&[byte; 32]);
+    let shutdown_scriptpubkey = ScriptBuf::new_p2wpkh(&bitcoin::PublicKey::new(pubkey(9)).wpubkey_hash().unwrap());
+    let channel_type = ChannelTypeFeatures::only_static_remote_key();
+    let channel_type_bytes: Vec<u8> = channel_type.le_flags().iter().rev().cloned().collect();
+
+    let open_channel = OpenChannel {
+        chain_hash: ChainHash::using_genesis_block(Network::Regtest),
+        temporary_channel_id: [0x11; 32],
+        funding_satoshis: 5_000_000,
+        push_msat: 1_000_000,
+        dust_limit_satoshis: 546,
+        max_htlc_value_in_flight_msat: 5_000_000_000,
+        channel_reserve_satoshis: 50_000,
+        htlc_minimum_msat: 1,
+        feerate_per_kw: 253,
+        to_self_delay: 144,
+        max_accepted_htlcs: 483,
+        funding_pubkey: pubkey(1),
+        revocation_basepoint: pubkey(2),
+        payment_basepoint: pubkey(3),
+        delayed_payment_basepoint: pubkey(4),
+        htlc_basepoint: pubkey(5),
+        first_per_commitment_point: pubkey(6),
+        channel_flags: 1,
+        shutdown_scriptpubkey: Some(shutdown_scriptpubkey.clone()),
+        channel_type: Some(channel_type_bytes.clone()),
+    };
+
+    let ldk_open_channel = msgs::OpenChannel {
+        common_fields: msgs::CommonOpenChannelFields {
+            chain_hash: ChainHash::using_genesis_block(Network::Regtest),
+            temporary_channel_id: ChannelId([0x11; 32]),
+            funding_satoshis: 5_000_000,
+            dust_limit_satoshis: 546,
+            max_htlc_value_in_flight_msat: 5_000_000_000,
+            htlc_minimum_msat: 1,
+            commitment_feerate_sat_per_1000_weight: 253,
+            to_self_delay: 144,
+            max_accepted_htlcs: 483,
+            funding_pubkey: pubkey(1),
+            revocation_basepoint: pubkey(2),
+            payment_basepoint: pubkey(3),
+            delayed_payment_basepoint: pubkey(4),
+            htlc_basepoint: pubkey(5),
+            first_per_commitment_point: pubkey(6),
+            channel_flags: 1,
+            shutdown_scriptpubkey: Some(shutdown_scriptpubkey.clone()),
+            channel_type: Some(channel_type.clone()),
+        },
+        push_msat: 1_000_000,
+        channel_reserve_satoshis: 50_000,
+    };
+
+    assert_eq!(payload(&open_channel), ldk_open_channel.encode());
+    assert_eq!(OpenChannel::decode(&open_channel.encode()), Ok(open_channel.clone()));
+    let decoded: msgs::OpenChannel = Readable::read(&mut &payload(&open_channel)[..]).unwrap();
+    assert_eq!(decoded, ldk_open_channel);
+
+    let accept_channel = AcceptChannel {
+        temporary_channel_id: [0x11; 32],
+        dust_limit_satoshis: 546,
+        max_htlc_value_in_flight_msat: 5_000_000_000,
+        channel_reserve_satoshis: 50_000,
+        htlc_minimum_msat: 1,
+        minimum_depth: 3,
+        to_self_delay: 144,
+        max_accepted_htlcs: 483,
+        funding_pubkey: pubkey(1),
+        revocation_basepoint: pubkey(2),
+        payment_basepoint: pubkey(3),
+        delayed_payment_basepoint: pubkey(4),
+        htlc_basepoint: pubkey(5),
+        first_per_commitment_point: pubkey(6),
+        shutdown_scriptpubkey: None,
+        channel_type: Some(channel_type_bytes),
+    };
+
+    let ldk_accept_channel = msgs::AcceptChannel {
+        common_fields: msgs::CommonAcceptChannelFields {
+            temporary_channel_id: ChannelId([0x11; 32]),
+            dust_limit_satoshis: 546,
+            max_htlc_value_in_flight_msat: 5_000_000_000,
+            htlc_minimum_msat: 1,
+            minimum_depth: 3,
+            to_self_delay: 144,
+            max_accepted_htlcs: 483,
+            funding_pubkey: pubkey(1),
+            revocation_basepoint: pubkey(2),
+            payment_basepoint: pubkey(3),
+            delayed_payment_basepoint: pubkey(4),
+            htlc_basepoint: pubkey(5),
+            first_per_commitment_point: pubkey(6),
+            shutdown_scriptpubkey: None,
+            channel_type: Some(channel_type),
+        },
+        channel_reserve_satoshis: 50_000,
+    };
+
+    assert_eq!(payload(&accept_channel), ldk_accept_channel.encode());
+    assert_eq!(AcceptChannel::decode(&accept_channel.encode()), Ok(accept_channel));
+
+    // a message can't be decoded as a different type
+    assert_eq!(
+        AcceptChannel::decode(&open_channel.encode()),
+        Err(DecodeError::UnknownMessageType(wire::OPEN_CHANNEL_TYPE))
+    );
+}
+
+#[test]
+fn test_funding_messages_match_ldk() {
+    let funding_txid = "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
+        .parse::<Txid>()
+        .unwrap();
+
+    let funding_created = FundingCreated {
+        temporary_channel_id: [0x11; 32],
+        funding_txid,
+        funding_output_index: 1,
+        signature: test_signature(),
+    };
+    let ldk_funding_created = msgs::FundingCreated {
+        temporary_channel_id: ChannelId([0x11; 32]),
+        funding_txid,
+        funding_output_index: 1,
+        signature: test_signature(),
+    };
+    assert_eq!(payload(&funding_created), ldk_funding_created.encode());
+    assert_eq!(FundingCreated::decode(&funding_created.encode()), Ok(funding_created));
+
+    let funding_signed = FundingSigned {
+        channel_id: [0x22; 32],
+        signature: test_signature(),
+    };
+    let ldk_funding_signed = msgs::FundingSigned {
+        channel_id: ChannelId([0x22; 32]),
+        signature: test_signature(),
+    };
+    assert_eq!(payload(&funding_signed), ldk_funding_signed.encode());
+    assert_eq!(FundingSigned::decode(&funding_signed.encode()), Ok(funding_signed));
+
+    let channel_ready = ChannelReady {
+        channel_id: [0x22; 32],
+        next_per_commitment_point: secp256k1pubkey_from_private_key(&[0x07; 32]),
+        short_channel_id_alias: Some(0x0001_0000_0200_0003),
+    };
+    let ldk_channel_ready = msgs::ChannelReady {
+        channel_id: ChannelId([0x22; 32]),
+        next_per_commitment_point: secp256k1pubkey_from_private_key(&[0x07; 32]),
+        short_channel_id_alias: Some(0x0001_0000_0200_0003),
+    };
+    assert_eq!(payload(&channel_ready), ldk_channel_ready.encode());
+    assert_eq!(ChannelReady::decode(&channel_ready.encode()), Ok(channel_ready));
+}
+
+#[test]
+fn test_update_messages_match_ldk() {
+    let onion = test_onion_packet();
+
+    let update_add = UpdateAddHtlc {
+        channel_id: [0x22; 32],
+        htlc_id: 7,
+        amount_msat: 400_000_000,
+        payment_hash: [0x42; 32],
+        cltv_expiry: 500,
+        onion_routing_packet: onion.clone(),
+        blinding_point: Some(secp256k1pubkey_from_private_key(&[0x09; 32])),
+        skimmed_fee_msat: Some(1_000),
+    };
+    let ldk_update_add = msgs::UpdateAddHTLC {
+        channel_id: ChannelId([0x22; 32]),
+        htlc_id: 7,
+        amount_msat: 400_000_000,
+        payment_hash: lightning::ln::PaymentHash([0x42; 32]),
+        cltv_expiry: 500,
+        skimmed_fee_msat: Some(1_000),
+        onion_routing_packet: msgs::OnionPacket {
+            version: onion.version,
+            public_key: Ok(onion.public_key),
+            hop_data: onion.hop_data,
+            hmac: onion.hmac,
+        },
+        blinding_point: Some(secp256k1pubkey_from_private_key(&[0x09; 32])),
+    };
+    assert_eq!(payload(&update_add), ldk_update_add.encode());
+    assert_eq!(UpdateAddHtlc::decode(&update_add.encode()), Ok(update_add));
+
+    let update_fulfill = UpdateFulfillHtlc {
+        channel_id: [0x22; 32],
+        htlc_id: 7,
+        payment_preimage: [0x24; 32],
+    };
+    let ldk_update_fulfill = msgs::UpdateFulfillHTLC {
+        channel_id: ChannelId([0x22; 32]),
+        htlc_id: 7,
+        payment_preimage: lightning::ln::PaymentPreimage([0x24; 32]),
+    };
+    assert_eq!(payload(&update_fulfill), ldk_update_fulfill.encode());
+    assert_eq!(UpdateFulfillHtlc::decode(&update_fulfill.encode()), Ok(update_fulfill));
+
+    // LDK keeps the failure reason private, so check it reads and re-writes our encoding
+    let update_fail = UpdateFailHtlc {
+        channel_id: [0x22; 32],
+        htlc_id: 7,
+        reason: vec![0xab; 292],
+    };
+    let ldk_update_fail: msgs::UpdateFailHTLC = Readable::read(&mut &payload(&update_fail)[..]).unwrap();
+    assert_eq!(ldk_update_fail.channel_id, ChannelId([0x22; 32]));
+    assert_eq!(ldk_update_fail.htlc_id, 7);
+    assert_eq!(ldk_update_fail.encode(), payload(&update_fail));
+    assert_eq!(UpdateFailHtlc::decode(&update_fail.encode()), Ok(update_fail));
+}
+
+#[test]
+fn test_commitment_messages_match_ldk() {
+    let commitment_signed = CommitmentSigned {
+        channel_id: [0x22; 32],
+        signature: test_signature(),
+        htlc_signatures: vec![test_signature(), test_signature()],
+    };
+    let ldk_commitment_signed = msgs::CommitmentSigned {
+        channel_id: ChannelId([0x22; 32]),
+        signature: test_signature(),
+        htlc_signatures: vec![test_signature(), test_signature()],
+        batch: None,
+    };
+    assert_eq!(payload(&commitment_signed), ldk_commitment_signed.encode());
+    assert_eq!(CommitmentSigned::decode(&commitment_signed.encode()), Ok(commitment_signed));
+
+    let revoke_and_ack = RevokeAndAck {
+        channel_id: [0x22; 32],
+        per_commitment_secret: [0x55; 32],
+        next_per_commitment_point: secp256k1pubkey_from_private_key(&[0x07; 32]),
+    };
+    let ldk_revoke_and_ack = msgs::RevokeAndACK {
+        channel_id: ChannelId([0x22; 32]),
+        per_commitment_secret: [0x55; 32],
+        next_per_commitment_point: secp256k1pubkey_from_private_key(&[0x07; 32]),
+    };
+    assert_eq!(payload(&revoke_and_ack), ldk_revoke_and_ack.encode());
+    assert_eq!(RevokeAndAck::decode(&revoke_and_ack.encode()), Ok(revoke_and_ack));
+}
+
+#[test]
+fn test_channel_messages_survive_the_wire() {
+    let (mut alice, mut bob) = channel_pair();
+
+    let commitment_signed = CommitmentSigned::decode(&alice.sign_commitment().unwrap().encode()).unwrap();
+    bob.receive_commitment_signed(&commitment_signed).unwrap();
+    let commitment_signed = CommitmentSigned::decode(&bob.sign_commitment().unwrap().encode()).unwrap();
+    alice.receive_commitment_signed(&commitment_signed).unwrap();
+    alice.receive_channel_ready(bob.next_per_commitment_point());
+    bob.receive_channel_ready(alice.next_per_commitment_point());
+
+    let update_add = alice.add_htlc(200_000_000, [0x42; 32], 500, test_onion_packet()).unwrap();
+    bob.receive_update_add_htlc(&UpdateAddHtlc::decode(&update_add.encode()).unwrap()).unwrap();
+
+    let commitment_signed = CommitmentSigned::decode(&alice.sign_commitment().unwrap().encode()).unwrap();
+    assert_eq!(commitment_signed.htlc_signatures.len(), 1);
+    bob.receive_commitment_signed(&commitment_signed).unwrap();
+    let revoke_and_ack = RevokeAndAck::decode(&bob.revoke_and_ack().unwrap().encode()).unwrap();
+    alice.receive_revoke_and_ack(&revoke_and_ack).unwrap();
+
+    assert_eq!(bob.htlcs[0].onion_routing_packet, test_onion_packet());
+    assert_eq!(alice.counterparty_commitment().unwrap().tx, bob.holder_commitment().unwrap().tx);
+}
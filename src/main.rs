@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use clap::{Parser, Subcommand, ValueEnum};
-use pl_00_intro::interactive::{funding, refund, commit, htlc, htlc_timeout, htlc_demo, htlc_demo2, mempool};
+use pl_00_intro::interactive::{funding, refund, commit, htlc, htlc_timeout, htlc_demo, htlc_demo2, mempool, transport, invoice, onion, multihop, route, scid, node, script, cpfp};
+use pl_00_intro::interactive::script::ScriptCommand;
 use pl_00_intro::interactive::mempool::MempoolCommand;
 use sha2::{Sha256, Digest};
 use ripemd::{Ripemd160};
@@ -20,7 +21,10 @@ struct Cli {
 /// CLI Subcommands
 #[derive(Subcommand)]
 enum Commands {
-    Funding,
+    Funding {
+        #[arg(short = 'c', long, help = "Name to save the channel under", default_value = "default")]
+        channel: String,
+    },
     Mempool {
         #[arg(
             short = 'c',
@@ -36,21 +40,92 @@ enum Commands {
     },
     Commit {
         #[arg(short = 't', long, help = "Funding Tx ID")]
-        funding_txid: String,
+        funding_txid: Option<String>,
+        #[arg(short = 'c', long, help = "Channel name to look the funding Tx ID up by")]
+        channel: Option<String>,
     },
     Htlc {
         #[arg(short = 't', long, help = "Funding Tx ID")]
-        funding_txid: String,
+        funding_txid: Option<String>,
+        #[arg(short = 'c', long, help = "Channel name to look the funding Tx ID up by")]
+        channel: Option<String>,
+        #[arg(short = 'i', long, help = "BOLT 11 invoice to lock the HTLC to")]
+        invoice: Option<String>,
     },
     HtlcTimeout {
         #[arg(short = 't', long, help = "HTLC Tx ID")]
-        htlc_txid: String,
+        htlc_txid: Option<String>,
+        #[arg(short = 'c', long, help = "Channel name to look the last HTLC up by")]
+        channel: Option<String>,
+        #[arg(short = 'i', long, help = "BOLT 11 invoice the HTLC was locked to")]
+        invoice: Option<String>,
     },
     HtlcDemo,
+    Transport,
+    Invoice {
+        #[arg(short = 'a', long, help = "Amount in millisatoshis")]
+        amount_msat: Option<u64>,
+        #[arg(short = 'd', long, help = "Description", default_value = "Programming Lightning")]
+        description: String,
+    },
+    DecodeInvoice {
+        #[arg(short = 'i', long, help = "BOLT 11 invoice")]
+        invoice: String,
+    },
+    Onion,
+    Multihop,
+    Route {
+        #[arg(short = 'f', long, help = "Source node alias or id")]
+        from: String,
+        #[arg(short = 't', long, help = "Destination node alias or id")]
+        to: String,
+        #[arg(short = 'a', long, help = "Amount in millisatoshis")]
+        amount: u64,
+        #[arg(short = 'x', long, help = "Short channel id to avoid as 123x4x0, may be repeated")]
+        exclude: Vec<String>,
+        #[arg(short = 'g', long, help = "Network graph JSON", default_value = route::DEFAULT_GRAPH_PATH)]
+        graph: String,
+    },
+    Scid {
+        #[arg(short = 't', long, help = "Funding Tx ID")]
+        funding_txid: Option<String>,
+        #[arg(short = 'v', long, help = "Funding output index", default_value_t = 0)]
+        vout: u32,
+        #[arg(short = 's', long, help = "Short channel id to decode, as 123x4x0 or u64")]
+        scid: Option<String>,
+    },
+    Node {
+        #[arg(short = 'p', long, help = "Port to accept peers on", default_value_t = 9735)]
+        port: u16,
+        #[arg(short = 'd', long, help = "Where the node keeps its seed and channels, data/ldk-<port> by default")]
+        data_dir: Option<String>,
+        #[arg(short = 'c', long, help = "Peer to connect to, as pubkey@host:port")]
+        peer: Option<String>,
+        #[arg(short = 'o', long, help = "Open a channel of this many sats to the peer")]
+        open_channel: Option<u64>,
+    },
     HtlcDemo2 {
         #[arg(short = 't', long, help = "HTLC Tx ID")]
         txid: String,
     },
+    Script {
+        #[command(subcommand)]
+        command: ScriptCommand,
+    },
+    Cpfp {
+        #[arg(short = 't', long, help = "Parent transaction hex, such as a broadcast commitment")]
+        tx: String,
+        #[arg(short = 'v', long, help = "Parent output the child spends")]
+        vout: u32,
+        #[arg(short = 'a', long, help = "The output is our anchor, rather than a wallet P2WPKH output")]
+        anchor: bool,
+        #[arg(short = 'r', long, conflicts_with = "anchor", help = "The output is to_remote of a Commit or Htlc commitment, paying Bob")]
+        to_remote: bool,
+        #[arg(short = 'i', long, help = "Total value of the parent's inputs, to work out its fee")]
+        input_value: u64,
+        #[arg(short = 'f', long, help = "Target package feerate in sat/kW, the urgent sweep estimate by default")]
+        feerate: Option<u32>,
+    },
     Sha256 {
         #[arg(short = 'd', long, help = "Input string to hash")]
         input_string: String,
@@ -71,13 +146,23 @@ async fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Funding => funding::run().await,
+        Commands::Funding { channel } => funding::run(channel.clone()).await,
         Commands::Refund { funding_txid } => refund::run(funding_txid.clone()).await,
-        Commands::Commit { funding_txid } => commit::run(funding_txid.clone()).await,
-        Commands::Htlc { funding_txid } => htlc::run(funding_txid.clone()).await,
-        Commands::HtlcTimeout { htlc_txid } => htlc_timeout::run(htlc_txid.clone()).await,
+        Commands::Commit { funding_txid, channel } => commit::run(funding_txid.clone(), channel.clone()).await,
+        Commands::Htlc { funding_txid, channel, invoice } => htlc::run(funding_txid.clone(), channel.clone(), invoice.clone()).await,
+        Commands::HtlcTimeout { htlc_txid, channel, invoice } => htlc_timeout::run(htlc_txid.clone(), channel.clone(), invoice.clone()).await,
         Commands::HtlcDemo => htlc_demo::run().await,
+        Commands::Transport => transport::run().await,
+        Commands::Invoice { amount_msat, description } => invoice::run(*amount_msat, description.clone()),
+        Commands::DecodeInvoice { invoice } => invoice::decode(invoice.clone()),
+        Commands::Onion => onion::run(),
+        Commands::Multihop => multihop::run(),
+        Commands::Route { from, to, amount, exclude, graph } => route::run(from.clone(), to.clone(), *amount, exclude.clone(), graph.clone()),
+        Commands::Scid { funding_txid, vout, scid } => scid::run(funding_txid.clone(), *vout, scid.clone()).await,
+        Commands::Node { port, data_dir, peer, open_channel } => node::run(*port, data_dir.clone(), peer.clone(), *open_channel).await,
         Commands::HtlcDemo2 { txid } => htlc_demo2::run(txid.clone()).await,
+        Commands::Script { command } => script::run(command.clone()),
+        Commands::Cpfp { tx, vout, anchor, to_remote, input_value, feerate } => cpfp::run(tx.clone(), *vout, *anchor, *to_remote, *input_value, *feerate).await,
         Commands::Mempool { command_type } => mempool::run(command_type.clone()).await,
         Commands::Sha256 { input_string } => {
             let mut hasher = Sha256::new();